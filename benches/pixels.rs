@@ -0,0 +1,95 @@
+//! Times the SIMD pixel conversions against the scalar loops they replace,
+//! on 64 MiB of texel data, as one buffer and as typical small textures. Run with `cargo bench --bench pixels`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use convert_texture_o2r::pixels::{self, Expansion};
+
+const SIZE: usize = 64 << 20;
+const RUNS: u32 = 5;
+
+/// The fastest of `RUNS` runs of `f`.
+fn time(mut f: impl FnMut() -> usize) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, scalar: Duration, simd: Duration) {
+    let throughput = |duration: Duration| SIZE as f64 / duration.as_secs_f64() / (1 << 30) as f64;
+    println!(
+        "{:<24} scalar {:>8.2?} ({:.2} GiB/s), simd {:>8.2?} ({:.2} GiB/s), {:.2}x",
+        name,
+        scalar,
+        throughput(scalar),
+        simd,
+        throughput(simd),
+        scalar.as_secs_f64() / simd.as_secs_f64()
+    );
+}
+
+fn main() {
+    let data = (0..SIZE)
+        .map(|i| (i as u32).wrapping_mul(2_654_435_761) as u8)
+        .collect::<Vec<_>>();
+    let data = black_box(data.as_slice());
+
+    for expansion in [Expansion::Replicate, Expansion::Linear] {
+        let scalar = time(|| {
+            let mut dst = Vec::with_capacity(SIZE * 2);
+            for texel in data.chunks_exact(2) {
+                dst.extend(pixels::rgba5551(texel[0], texel[1], expansion));
+            }
+            dst.len()
+        });
+        let simd = time(|| {
+            let mut dst = Vec::new();
+            pixels::rgba5551_to_rgba8888(data, &mut dst, expansion);
+            dst.len()
+        });
+        report(&format!("rgba5551 {:?}", expansion), scalar, simd);
+    }
+
+    // Rows as wide as the whole input, so only the last one has a tail
+    let width = 4096;
+    let height = (SIZE / (width / 2)) as u32;
+    let scalar = time(|| {
+        let mut dst = Vec::with_capacity(SIZE * 2);
+        for byte in data {
+            dst.extend([byte >> 4, byte & 0x0F]);
+        }
+        dst.len()
+    });
+    let simd = time(|| pixels::unpack_4bpp(data, width as u32, height).len());
+    report("unpack 4bpp", scalar, simd);
+
+    // The sizes most CI4, I4 and IA4 textures come in, one call each
+    for (width, height) in [(32, 32), (64, 32)] {
+        let size = width as usize / 2 * height as usize;
+        let scalar = time(|| {
+            let mut length = 0;
+            for texture in data.chunks_exact(size) {
+                let mut dst = Vec::with_capacity(size * 2);
+                for byte in texture {
+                    dst.extend([byte >> 4, byte & 0x0F]);
+                }
+                length += dst.len();
+            }
+            length
+        });
+        let simd = time(|| {
+            data.chunks_exact(size)
+                .map(|texture| pixels::unpack_4bpp(texture, width, height).len())
+                .sum()
+        });
+        report(&format!("unpack 4bpp {}x{}", width, height), scalar, simd);
+    }
+}
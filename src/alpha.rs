@@ -0,0 +1,84 @@
+use image::ExtendedColorType;
+
+use crate::json::Json;
+
+/// How a texture uses its alpha channel, telling downstream tools whether it
+/// needs an opaque, alpha-tested or alpha-blended material.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaClass {
+    /// Every texel is fully opaque.
+    Opaque,
+    /// Texels are either fully opaque or fully transparent, fit for alpha
+    /// testing.
+    Binary,
+    /// Some texels are partly transparent and need blending.
+    Mixed,
+}
+
+impl AlphaClass {
+    fn name(&self) -> &'static str {
+        match self {
+            AlphaClass::Opaque => "opaque",
+            AlphaClass::Binary => "binary",
+            AlphaClass::Mixed => "mixed",
+        }
+    }
+}
+
+/// Number of texels of a texture by alpha. RGBA16 and IA textures with a
+/// 1-bit alpha decode it to 0 or 255 only, so they are never mixed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AlphaStats {
+    pub opaque: usize,
+    pub transparent: usize,
+    pub translucent: usize,
+}
+
+impl AlphaStats {
+    /// Alpha of the `pixels` decoded texels `data` in the layout `color`.
+    pub fn new(color: ExtendedColorType, data: &[u8], pixels: usize) -> Self {
+        let mut stats = AlphaStats::default();
+        let mut add = |alpha: u8| match alpha {
+            0xFF => stats.opaque += 1,
+            0 => stats.transparent += 1,
+            _ => stats.translucent += 1,
+        };
+        match color {
+            ExtendedColorType::Rgba8 => data.chunks_exact(4).for_each(|texel| add(texel[3])),
+            ExtendedColorType::La8 => data.chunks_exact(2).for_each(|texel| add(texel[1])),
+            // One bit per texel, standing for both intensity and alpha
+            _ => (0..pixels).for_each(|i| {
+                let bit = data.get(i / 8).map_or(0, |byte| byte >> (7 - i % 8) & 1);
+                add(bit * 0xFF)
+            }),
+        }
+        stats
+    }
+
+    pub fn class(&self) -> AlphaClass {
+        if self.translucent > 0 {
+            AlphaClass::Mixed
+        } else if self.transparent > 0 {
+            AlphaClass::Binary
+        } else {
+            AlphaClass::Opaque
+        }
+    }
+
+    pub fn from_json(json: &Json) -> Option<Self> {
+        let number = |key| json.get(key)?.as_f64().map(|value| value as usize);
+        Some(AlphaStats {
+            opaque: number("opaque")?,
+            transparent: number("transparent")?,
+            translucent: number("translucent")?,
+        })
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object()
+            .with("class", self.class().name())
+            .with("opaque", self.opaque)
+            .with("transparent", self.transparent)
+            .with("translucent", self.translucent)
+    }
+}
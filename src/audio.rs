@@ -0,0 +1,396 @@
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, OTRHeader, ResourceType, decoder::ResourceDecoder,
+    json::Json, log, reader::Reader,
+};
+
+/// Rate used for exported samples. The real playback rate depends on the
+/// tuning of the instrument referencing the sample, which lives in the sound font.
+pub const SAMPLE_RATE: u32 = 32000;
+
+/// Encoding of a sample's data, as `CODEC_*` in the audio library.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Adpcm,
+    S8,
+    S16InMemory,
+    SmallAdpcm,
+    Reverb,
+    S16,
+    Unknown(u8),
+}
+
+impl Codec {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Codec::Adpcm,
+            1 => Codec::S8,
+            2 => Codec::S16InMemory,
+            3 => Codec::SmallAdpcm,
+            4 => Codec::Reverb,
+            5 => Codec::S16,
+            value => Codec::Unknown(value),
+        }
+    }
+}
+
+/// An audio sample resource.
+pub struct Sample {
+    pub codec: Codec,
+    pub medium: u8,
+    pub data: Vec<u8>,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    pub loop_count: u32,
+    pub order: usize,
+    pub predictor_count: usize,
+    /// ADPCM codebook, `predictor_count` tables of `order` rows of 8 coefficients.
+    pub book: Vec<i16>,
+}
+
+pub fn parse_sample(data: &[u8]) -> Result<Sample, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let codec = Codec::from_u8(reader.u8()?);
+    let medium = reader.u8()?;
+    let _unk_bit26 = reader.u8()?;
+    let _unk_bit25 = reader.u8()?;
+    let size = reader.u32()? as usize;
+    let sample_data = reader.bytes(size)?.to_vec();
+
+    let loop_start = reader.u32()?;
+    let loop_end = reader.u32()?;
+    let loop_count = reader.u32()?;
+    let loop_state_count = reader.u32()?;
+    for _ in 0..loop_state_count {
+        reader.i16()?;
+    }
+
+    let order = reader.i32()?.max(0) as usize;
+    let predictor_count = reader.i32()?.max(0) as usize;
+    let book_size = reader.u32()?;
+    let book = (0..book_size)
+        .map(|_| reader.i16())
+        .collect::<Result<Vec<_>, _>>()?;
+    if book.len() < order * predictor_count * 8 {
+        return Err(format!(
+            "Codebook has {} coefficients, expected {}",
+            book.len(),
+            order * predictor_count * 8
+        ));
+    }
+
+    Ok(Sample {
+        codec,
+        medium,
+        data: sample_data,
+        loop_start,
+        loop_end,
+        loop_count,
+        order,
+        predictor_count,
+        book,
+    })
+}
+
+impl Sample {
+    /// Decodes the sample to 16-bit PCM, or `None` for codecs without a
+    /// decoder.
+    pub fn decode(&self) -> Option<Vec<i16>> {
+        match self.codec {
+            Codec::Adpcm => Some(decode_vadpcm(
+                &self.data,
+                &self.book,
+                self.order,
+                self.predictor_count,
+            )),
+            Codec::S8 => Some(
+                self.data
+                    .iter()
+                    .map(|byte| (*byte as i8 as i16) << 8)
+                    .collect(),
+            ),
+            Codec::S16 | Codec::S16InMemory => Some(
+                self.data
+                    .chunks_exact(2)
+                    .map(|pair| i16::from_be_bytes([pair[0], pair[1]]))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object()
+            .with("codec", format!("{:?}", self.codec))
+            .with("medium", self.medium as u32)
+            .with("size", self.data.len())
+            .with("sample_rate", SAMPLE_RATE)
+            .with(
+                "loop",
+                Json::object()
+                    .with("start", self.loop_start)
+                    .with("end", self.loop_end)
+                    .with("count", self.loop_count),
+            )
+            .with(
+                "book",
+                Json::object()
+                    .with("order", self.order)
+                    .with("predictors", self.predictor_count)
+                    .with(
+                        "coefficients",
+                        Json::Array(
+                            self.book
+                                .iter()
+                                .map(|value| Json::Number(*value as f64))
+                                .collect(),
+                        ),
+                    ),
+            )
+    }
+}
+
+/// Decodes VADPCM frames of 9 bytes, a scale/predictor header followed by
+/// 16 signed 4-bit residuals, into 16 samples each.
+fn decode_vadpcm(data: &[u8], book: &[i16], order: usize, predictor_count: usize) -> Vec<i16> {
+    let tables = expand_book(book, order, predictor_count);
+    let mut samples = Vec::with_capacity(data.len() / 9 * 16);
+    let mut previous = [0i32; 16];
+
+    for frame in data.chunks_exact(9) {
+        let scale = 1i32 << (frame[0] >> 4);
+        let Some(table) = tables.get((frame[0] & 0x0F) as usize) else {
+            break;
+        };
+        let residuals: Vec<i32> = frame[1..]
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0x0F])
+            .map(|nibble| {
+                (if nibble <= 7 {
+                    nibble as i32
+                } else {
+                    nibble as i32 - 16
+                }) * scale
+            })
+            .collect();
+
+        let mut output = [0i32; 16];
+        for half in 0..2 {
+            let mut input = vec![0i32; order + 8];
+            for i in 0..order {
+                input[i] = if half == 0 {
+                    previous[16 - order + i]
+                } else {
+                    output[8 - order + i]
+                };
+            }
+            input[order..].copy_from_slice(&residuals[half * 8..half * 8 + 8]);
+
+            for i in 0..8 {
+                let total: i32 = table[i].iter().zip(&input).map(|(a, b)| a * b).sum();
+                output[half * 8 + i] = total.div_euclid(1 << 11);
+            }
+        }
+
+        samples.extend(
+            output
+                .iter()
+                .map(|sample| (*sample).clamp(i16::MIN as i32, i16::MAX as i32) as i16),
+        );
+        previous = output;
+    }
+    samples
+}
+
+/// Expands each predictor of the codebook into 8 rows of `order + 8`
+/// coefficients, covering both the previous samples and the residuals of the
+/// current half frame.
+fn expand_book(book: &[i16], order: usize, predictor_count: usize) -> Vec<Vec<Vec<i32>>> {
+    (0..predictor_count)
+        .map(|predictor| {
+            let mut table = vec![vec![0i32; order + 8]; 8];
+            for j in 0..order {
+                for k in 0..8 {
+                    table[k][j] = book[predictor * order * 8 + j * 8 + k] as i32;
+                }
+            }
+            if order > 0 {
+                for k in 1..8 {
+                    table[k][order] = table[k - 1][order - 1];
+                }
+            }
+            table[0][order] = 1 << 11;
+            for k in 1..8 {
+                for j in k..8 {
+                    table[j][k + order] = table[j - k][order];
+                }
+            }
+            table
+        })
+        .collect()
+}
+
+/// Wraps mono 16-bit samples in a WAV container.
+pub fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// A music sequence resource, kept in its original binary form.
+pub struct Sequence {
+    pub data: Vec<u8>,
+    pub number: u8,
+    pub medium: u8,
+    pub cache_policy: u8,
+    pub fonts: Vec<u8>,
+}
+
+pub fn parse_sequence(data: &[u8]) -> Result<Sequence, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let size = reader.u32()? as usize;
+    let sequence_data = reader.bytes(size)?.to_vec();
+    let number = reader.u8()?;
+    let medium = reader.u8()?;
+    let cache_policy = reader.u8()?;
+    let font_count = reader.u32()?;
+    let fonts = (0..font_count)
+        .map(|_| reader.u8())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Sequence {
+        data: sequence_data,
+        number,
+        medium,
+        cache_policy,
+        fonts,
+    })
+}
+
+impl Sequence {
+    pub fn to_json(&self) -> Json {
+        Json::object()
+            .with("number", self.number as u32)
+            .with("medium", self.medium as u32)
+            .with("cache_policy", self.cache_policy as u32)
+            .with("size", self.data.len())
+            .with(
+                "fonts",
+                Json::Array(
+                    self.fonts
+                        .iter()
+                        .map(|font| Json::Number(*font as f64))
+                        .collect(),
+                ),
+            )
+    }
+}
+
+/// Header of a sound font (instrument bank) resource. Only the fixed part is
+/// decoded; instruments reference samples by path and are left to the port.
+pub fn parse_sound_font(data: &[u8]) -> Result<Json, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let index = reader.i32()?;
+    let medium = reader.u8()?;
+    let cache_policy = reader.u8()?;
+    let data1 = reader.u16()?;
+    let data2 = reader.u16()?;
+    let data3 = reader.u16()?;
+    let drums = reader.u32()?;
+    let instruments = reader.u32()?;
+    let sound_effects = reader.u32()?;
+    Ok(Json::object()
+        .with("index", index as f64)
+        .with("medium", medium as u32)
+        .with("cache_policy", cache_policy as u32)
+        .with(
+            "data",
+            Json::Array(vec![data1.into(), data2.into(), data3.into()]),
+        )
+        .with("drums", drums)
+        .with("instruments", instruments)
+        .with("sound_effects", sound_effects))
+}
+
+/// Exports samples to WAV and sequences and sound fonts to metadata.
+pub struct AudioDecoder;
+
+impl ResourceDecoder for AudioDecoder {
+    fn name(&self) -> &'static str {
+        "audio"
+    }
+
+    fn directory(&self) -> &'static str {
+        "audio"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[
+            (ResourceType::AudioSample, 0),
+            (ResourceType::AudioSequence, 0),
+            (ResourceType::AudioSoundFont, 0),
+        ]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let base = converter.output_base(self, name);
+
+        let outputs = match OTRHeader::parse(data).map(|header| header.type_id) {
+            Ok(ResourceType::AudioSample) => parse_sample(data).map(|sample| {
+                let mut outputs = vec![(
+                    base.clone() + ".json",
+                    (sample.to_json().pretty() + "\n").into_bytes(),
+                )];
+                match sample.decode() {
+                    Some(samples) => {
+                        outputs.push((base.clone() + ".wav", wav(&samples, SAMPLE_RATE)))
+                    }
+                    None => log::skip(format!(
+                        "No decoder for {:?} samples, {} exported as metadata only",
+                        sample.codec, name
+                    )),
+                }
+                outputs
+            }),
+            Ok(ResourceType::AudioSequence) => parse_sequence(data).map(|sequence| {
+                vec![
+                    (
+                        base.clone() + ".json",
+                        (sequence.to_json().pretty() + "\n").into_bytes(),
+                    ),
+                    (base.clone() + ".seq", sequence.data),
+                ]
+            }),
+            _ => parse_sound_font(data).map(|sound_font| {
+                vec![(
+                    base.clone() + ".json",
+                    (sound_font.pretty() + "\n").into_bytes(),
+                )]
+            }),
+        };
+
+        match outputs {
+            Ok(outputs) => {
+                for (path, contents) in outputs {
+                    log::progress(format!("Exporting audio: {}", path));
+                    converter.write(&path, contents);
+                }
+            }
+            Err(err) => log::error(format!("Failed to parse audio resource {}: {}", name, err)),
+        }
+    }
+}
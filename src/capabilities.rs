@@ -0,0 +1,121 @@
+use crate::{TextureType, decoder::DECODERS, json::Json};
+
+/// Archive formats the converter reads.
+const ARCHIVE_FORMATS: &[&str] = &["o2r"];
+/// Layers `--payload` can unwrap.
+const PAYLOAD_LAYERS: &[&str] = &["zlib", "mio0", "yay0", "yaz0", "xor"];
+/// Texture type ids of the OTR header the texture decoder converts, the TLUTs
+/// only being read for CI textures.
+const TEXTURE_TYPE_IDS: std::ops::RangeInclusive<u32> = 1..=10;
+
+/// Id, type and bits per texel of each of the `TEXTURE_TYPE_IDS`.
+fn texture_types() -> impl Iterator<Item = (u32, TextureType, u8)> {
+    TEXTURE_TYPE_IDS.filter_map(|id| {
+        let type_id = TextureType::from_u32(id)?;
+        let bits = type_id.bits_per_pixel()?;
+        Some((id, type_id, bits))
+    })
+}
+
+/// Cargo features this build was made with.
+fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "archive") {
+        features.push("archive");
+    }
+    if cfg!(feature = "yaml") {
+        features.push("yaml");
+    }
+    if cfg!(feature = "png") {
+        features.push("png");
+    }
+    if cfg!(feature = "exr") {
+        features.push("exr");
+    }
+    if cfg!(feature = "fuse") {
+        features.push("fuse");
+    }
+    features
+}
+
+/// Image formats textures can be written as with `--image-format`.
+fn image_formats() -> Vec<&'static str> {
+    let mut formats = vec!["png"];
+    if cfg!(feature = "exr") {
+        formats.push("exr");
+    }
+    formats
+}
+
+/// What this build supports, for tools to check before relying on it: the
+/// decoders with the resource types and header versions they read, the
+/// texture types decoded and the archive and output formats.
+pub fn to_json() -> Json {
+    let strings = |values: &[&str]| {
+        values
+            .iter()
+            .map(|value| Json::from(*value))
+            .collect::<Vec<_>>()
+    };
+    let decoders = DECODERS
+        .iter()
+        .map(|decoder| {
+            let resources = decoder
+                .handles()
+                .iter()
+                .map(|(type_id, version)| {
+                    Json::object()
+                        .with("type", format!("{:?}", type_id))
+                        .with("version", *version)
+                })
+                .collect::<Vec<_>>();
+            Json::object()
+                .with("name", decoder.name())
+                .with("resources", resources)
+        })
+        .collect::<Vec<_>>();
+    let texture_types = texture_types()
+        .map(|(id, type_id, bits)| {
+            Json::object()
+                .with("id", id)
+                .with("name", format!("{:?}", type_id))
+                .with("bits_per_pixel", bits as u32)
+        })
+        .collect::<Vec<_>>();
+    Json::object()
+        .with("version", env!("CARGO_PKG_VERSION"))
+        .with("features", strings(&features()))
+        .with("archive_formats", strings(ARCHIVE_FORMATS))
+        .with("payload_layers", strings(PAYLOAD_LAYERS))
+        .with("image_formats", strings(&image_formats()))
+        .with("texture_types", texture_types)
+        .with("decoders", decoders)
+}
+
+/// The capabilities of `to_json` as a table for people.
+pub fn report() -> String {
+    let mut report = format!("convert-texture-o2r {}\n", env!("CARGO_PKG_VERSION"));
+    report += &format!("Features: {}\n", features().join(", "));
+    report += &format!("Archive formats: {}\n", ARCHIVE_FORMATS.join(", "));
+    report += &format!("Payload layers: {}\n", PAYLOAD_LAYERS.join(", "));
+    report += &format!("Image formats: {}\n", image_formats().join(", "));
+    report += "Texture types:\n";
+    for (id, type_id, bits) in texture_types() {
+        report += &format!("  {:2} {:?}, {} bits per pixel\n", id, type_id, bits);
+    }
+    report += "Decoders:\n";
+    for decoder in DECODERS {
+        let resources = decoder
+            .handles()
+            .iter()
+            .map(|(type_id, version)| format!("{:?} v{}", type_id, version))
+            .collect::<Vec<_>>();
+        let resources = match resources.is_empty() {
+            // Picked by the config rather than the header
+            true => "entries the config points out".to_owned(),
+            false => resources.join(", "),
+        };
+        report += &format!("  {}: {}\n", decoder.name(), resources);
+    }
+    report
+}
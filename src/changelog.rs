@@ -0,0 +1,140 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{manifest::ManifestEntry, thumbnail};
+
+/// File the `--changelog` is written to in the output folder.
+pub const CHANGELOG_FILE: &str = "CHANGELOG.md";
+
+/// Width thumbnails are shown at, in pixels.
+const THUMBNAIL_WIDTH: u32 = 64;
+
+/// Textures added, removed or changed since a previous manifest.
+pub struct Changelog<'a> {
+    pub added: Vec<&'a ManifestEntry>,
+    pub removed: Vec<&'a ManifestEntry>,
+    /// Changed textures as `(previous, current)`.
+    pub changed: Vec<(&'a ManifestEntry, &'a ManifestEntry)>,
+}
+
+impl<'a> Changelog<'a> {
+    /// Compares textures by archive entry. Entries whose texels hash the same
+    /// are unchanged; without hashes, from older manifests, the format and
+    /// size are compared instead.
+    pub fn compare(previous: &'a [ManifestEntry], current: &'a [ManifestEntry]) -> Self {
+        let previous_entries = previous
+            .iter()
+            .map(|texture| (texture.entry.as_str(), texture))
+            .collect::<HashMap<_, _>>();
+        let current_entries = current
+            .iter()
+            .map(|texture| (texture.entry.as_str(), texture))
+            .collect::<HashMap<_, _>>();
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for texture in current {
+            match previous_entries.get(texture.entry.as_str()) {
+                None => added.push(texture),
+                Some(old) if is_changed(old, texture) => changed.push((*old, texture)),
+                Some(_) => {}
+            }
+        }
+        let mut removed = previous
+            .iter()
+            .filter(|texture| !current_entries.contains_key(texture.entry.as_str()))
+            .collect::<Vec<_>>();
+
+        added.sort_by(|a, b| a.entry.cmp(&b.entry));
+        removed.sort_by(|a, b| a.entry.cmp(&b.entry));
+        changed.sort_by(|(a, _), (b, _)| a.entry.cmp(&b.entry));
+        Changelog {
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Markdown release notes with thumbnails linking to the converted
+    /// textures, meant to sit next to them in the output folder. With
+    /// `thumbnails` the previews of the `thumbs` folder are shown instead of
+    /// the full textures.
+    pub fn markdown(&self, title: &str, thumbnails: bool) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# {}\n", title);
+        let _ = writeln!(
+            out,
+            "{} added, {} removed, {} changed.",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        );
+
+        if !self.added.is_empty() {
+            out.push_str("\n## Added\n\n| Texture | Preview | Format |\n| --- | --- | --- |\n");
+            for texture in &self.added {
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | {} |",
+                    texture.entry,
+                    preview(texture, thumbnails),
+                    details(texture)
+                );
+            }
+        }
+
+        if !self.changed.is_empty() {
+            out.push_str("\n## Changed\n\n| Texture | Preview | Format |\n| --- | --- | --- |\n");
+            for (previous, texture) in &self.changed {
+                let (old, new) = (details(previous), details(texture));
+                let format = if old == new {
+                    new
+                } else {
+                    format!("{} → {}", old, new)
+                };
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | {} |",
+                    texture.entry,
+                    preview(texture, thumbnails),
+                    format
+                );
+            }
+        }
+
+        if !self.removed.is_empty() {
+            out.push_str("\n## Removed\n\n| Texture | Format |\n| --- | --- |\n");
+            for texture in &self.removed {
+                let _ = writeln!(out, "| `{}` | {} |", texture.entry, details(texture));
+            }
+        }
+        out
+    }
+}
+
+fn is_changed(previous: &ManifestEntry, current: &ManifestEntry) -> bool {
+    match (previous.hash, current.hash) {
+        (Some(previous), Some(current)) => previous != current,
+        _ => details(previous) != details(current),
+    }
+}
+
+fn details(texture: &ManifestEntry) -> String {
+    format!("{} {}x{}", texture.format, texture.width, texture.height)
+}
+
+fn preview(texture: &ManifestEntry, thumbnails: bool) -> String {
+    let path = if thumbnails {
+        thumbnail::path(&texture.output)
+    } else {
+        texture.output.clone()
+    };
+    format!(
+        "<img src=\"{}\" width=\"{}\">",
+        path.replace(' ', "%20"),
+        THUMBNAIL_WIDTH.min(texture.width * 4)
+    )
+}
@@ -0,0 +1,181 @@
+use crate::{
+    Converter, EntryResult, OTRHeader, ResourceType, TextureFormat, TextureType,
+    alpha::AlphaStats,
+    classify,
+    config::Config,
+    crc64,
+    decoder::ResourceDecoder,
+    dilate, log,
+    manifest::ManifestEntry,
+    pack_rows,
+    pixels::{self, Expansion},
+    texture,
+};
+
+/// Bits of a 16-bit texel holding the palette index, the top 4 are unused.
+const INDEX_MASK: u16 = 0x0FFF;
+
+/// Colors of a 12-bit palette.
+pub const MAX_COLORS: usize = INDEX_MASK as usize + 1;
+
+/// Texels of an extended CI texture expanded to RGBA8888. Every texel is a
+/// big-endian 16-bit word whose low 12 bits index `tlut`, RGBA5551 colors.
+/// Indices past the end of the TLUT come out transparent, and the highest is
+/// returned when there are any.
+pub fn decode(
+    texture_format: &TextureFormat,
+    tlut: &[u8],
+    expansion: Expansion,
+) -> (Vec<u8>, Option<usize>) {
+    let pixels = texture_format.width as usize * texture_format.height as usize;
+    let mut data = Vec::with_capacity(pixels * 4);
+    let mut overflow = None;
+    for texel in texture_format.data.chunks_exact(2).take(pixels) {
+        let index = (u16::from_be_bytes([texel[0], texel[1]]) & INDEX_MASK) as usize;
+        match tlut.get(index * 2..index * 2 + 2) {
+            Some(color) => data.extend(pixels::rgba5551(color[0], color[1], expansion)),
+            None => {
+                overflow = overflow.max(Some(index));
+                data.extend([0; 4]);
+            }
+        }
+    }
+    (data, overflow)
+}
+
+/// Decodes the textures a `ci16` glob of the config marks as extended CI:
+/// 12-bit palette indices in 16-bit texels, with TLUTs of up to 4096 colors.
+/// Their headers give them a stock 16-bit type, which the texture decoder
+/// would read as colors, so every header version is claimed.
+pub struct Ci16Decoder;
+
+impl ResourceDecoder for Ci16Decoder {
+    fn name(&self) -> &'static str {
+        "ci16"
+    }
+
+    fn directory(&self) -> &'static str {
+        "textures"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[]
+    }
+
+    fn claims(&self, config: &Config, header: &OTRHeader, name: &str) -> bool {
+        header.type_id == ResourceType::Texture && config.is_ci16(name)
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let mut texture_format = match TextureFormat::parse(data) {
+            Ok(texture_format) => texture_format,
+            Err(err) => {
+                log::error(format!("{}: {}", name, err));
+                return;
+            }
+        };
+        // Every texel takes 2 bytes, half of the 4 `pixels` checks for
+        let expected_size = match texture_format.pixels() {
+            Ok(pixels) => pixels * 2,
+            Err(err) => {
+                log::error(format!("{}: {}", name, err));
+                return;
+            }
+        };
+        if let Some(stride) = TextureFormat::stride(data).filter(|stride| *stride > 0) {
+            let row_size = texture_format.width as usize * 2;
+            match pack_rows(
+                &texture_format.data,
+                row_size,
+                stride as usize,
+                texture_format.height,
+            ) {
+                Ok(texels) => texture_format.data = texels,
+                Err(err) => {
+                    log::error(format!("{}: {}", name, err));
+                    return;
+                }
+            }
+        }
+        if expected_size > texture_format.data.len() {
+            log::error(format!(
+                "Data size does not match expected size for {}: {} vs {}",
+                name,
+                texture_format.data.len(),
+                expected_size
+            ));
+            return;
+        }
+
+        let file_name = name.split('/').next_back().unwrap();
+        let Some(tlut) = converter.tluts.for_texture(name, &TextureType::RGBA16bpp) else {
+            log::skip(format!(
+                "Missing TLUT for extended CI texture {}",
+                file_name
+            ));
+            return;
+        };
+        let tlut = &tlut.data[..tlut.data.len().min(MAX_COLORS * 2)];
+        let (mut pixels, overflow) = decode(&texture_format, tlut, converter.options.expand);
+        if let Some(index) = overflow {
+            log::error(format!(
+                "Texture {} uses palette index {} but its TLUT only has {} entries",
+                name,
+                index,
+                tlut.len() / 2
+            ));
+        }
+
+        let options = converter.options;
+        let output = converter.output_name(self, name) + "." + options.image_format.extension();
+        let path = converter.folder_name.to_owned() + "/" + &output;
+        log::progress(format!("Processing extended CI texture: {}", path));
+        let (width, height) = (texture_format.width, texture_format.height);
+        if let Some(radius) = options.dilate_alpha {
+            dilate::dilate(
+                &mut pixels,
+                image::ExtendedColorType::Rgba8,
+                width,
+                height,
+                radius,
+            );
+        }
+        let encoded = texture::encode_image(
+            options.image_format,
+            &pixels,
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+        );
+        match encoded {
+            Ok(encoded) => converter.write(&path, encoded),
+            Err(err) => {
+                log::error(format!("Failed to encode {}: {}", name, err));
+                result.encode_failure = Some(err);
+                return;
+            }
+        }
+        result.converted = Some(ManifestEntry {
+            entry: name.to_owned(),
+            output,
+            format: "CI16".to_owned(),
+            width,
+            height,
+            hash: Some(crc64::checksum(&pixels)),
+            alpha: Some(AlphaStats::new(
+                image::ExtendedColorType::Rgba8,
+                &pixels,
+                width as usize * height as usize,
+            )),
+            pack_hash: None,
+            pack_name: None,
+            // Classified like the stock CI textures
+            category: options.classify.then(|| {
+                classify::classify(name, &TextureType::Palette8bpp, width, height)
+                    .name()
+                    .to_owned()
+            }),
+        });
+    }
+}
@@ -0,0 +1,85 @@
+use crate::TextureType;
+
+/// Guessed use of a texture, written to the manifest with `--classify` so
+/// pack authors can route each kind to its own upscaling settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Category {
+    Font,
+    Ui,
+    Skybox,
+    Terrain,
+    Other,
+}
+
+impl Category {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Font => "font",
+            Category::Ui => "ui",
+            Category::Skybox => "skybox",
+            Category::Terrain => "terrain",
+            Category::Other => "other",
+        }
+    }
+}
+
+/// Folders of the archive holding interface textures.
+const UI_FOLDERS: &[&str] = &[
+    "parameter_static",
+    "icon_item",
+    "interface",
+    "hud",
+    "menu",
+    "title_static",
+];
+
+/// Folders of the archive holding level geometry.
+const TERRAIN_FOLDERS: &[&str] = &["courses", "scenes", "levels"];
+
+/// Category of the texture `name` from its path, format and size, the first
+/// rule that matches winning:
+///
+/// - skyboxes are under a folder naming them, `vr_*` in OoT,
+/// - fonts are under a folder naming them, or 4-bit intensity glyphs of at
+///   most 16x16,
+/// - interface textures are under a known interface folder, or have a side
+///   that isn't a power of two, which the RDP can't wrap so they are only
+///   drawn as screen rectangles,
+/// - terrain is a power-of-two CI or RGBA16 texture of at least 32x32 under a
+///   level folder.
+pub fn classify(name: &str, type_id: &TextureType, width: u32, height: u32) -> Category {
+    let name = name.to_lowercase();
+    let folders = name.split('/').rev().skip(1).collect::<Vec<_>>();
+    let in_folder = |names: &[&str]| {
+        folders
+            .iter()
+            .any(|folder| names.iter().any(|name| folder.contains(name)))
+    };
+
+    if folders
+        .iter()
+        .any(|folder| folder.contains("skybox") || folder.starts_with("vr_"))
+    {
+        return Category::Skybox;
+    }
+    let glyph = matches!(
+        type_id,
+        TextureType::Grayscale4bpp | TextureType::GrayscaleAlpha4bpp
+    ) && width <= 16
+        && height <= 16;
+    if in_folder(&["font"]) || glyph {
+        return Category::Font;
+    }
+    if in_folder(UI_FOLDERS) || !width.is_power_of_two() || !height.is_power_of_two() {
+        return Category::Ui;
+    }
+    let tileable = matches!(
+        type_id,
+        TextureType::Palette4bpp | TextureType::Palette8bpp | TextureType::RGBA16bpp
+    ) && width >= 32
+        && height >= 32;
+    if tileable && in_folder(TERRAIN_FOLDERS) {
+        return Category::Terrain;
+    }
+    Category::Other
+}
@@ -0,0 +1,270 @@
+use std::fmt::Write;
+
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, ResourceType, decoder::ResourceDecoder, json::Json,
+    log, reader::Reader,
+};
+
+/// Mask of the vertex index in a polygon, the upper bits hold flags.
+const VERTEX_INDEX_MASK: u16 = 0x1FFF;
+
+pub struct Polygon {
+    pub surface_type: u16,
+    pub vertices: [u16; 3],
+    pub normal: [i16; 3],
+    pub distance: i16,
+}
+
+pub struct WaterBox {
+    pub x_min: i16,
+    pub y_surface: i16,
+    pub z_min: i16,
+    pub x_length: i16,
+    pub z_length: i16,
+    pub properties: u32,
+}
+
+/// A CollisionHeader resource.
+pub struct Collision {
+    pub min_bounds: [i16; 3],
+    pub max_bounds: [i16; 3],
+    pub vertices: Vec<[i16; 3]>,
+    pub polygons: Vec<Polygon>,
+    pub surface_types: Vec<[u32; 2]>,
+    pub water_boxes: Vec<WaterBox>,
+}
+
+pub fn parse(data: &[u8]) -> Result<Collision, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let min_bounds = [reader.i16()?, reader.i16()?, reader.i16()?];
+    let max_bounds = [reader.i16()?, reader.i16()?, reader.i16()?];
+
+    let vertex_count = reader.u32()?;
+    let vertices = (0..vertex_count)
+        .map(|_| Ok([reader.i16()?, reader.i16()?, reader.i16()?]))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let polygon_count = reader.u32()?;
+    let polygons = (0..polygon_count)
+        .map(|_| {
+            Ok(Polygon {
+                surface_type: reader.u16()?,
+                vertices: [reader.u16()?, reader.u16()?, reader.u16()?],
+                normal: [reader.i16()?, reader.i16()?, reader.i16()?],
+                distance: reader.i16()?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Stored as the second word first
+    let surface_type_count = reader.u32()?;
+    let surface_types = (0..surface_type_count)
+        .map(|_| {
+            let data1 = reader.u32()?;
+            Ok([reader.u32()?, data1])
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Camera data isn't exported, but sits between the surface types and the
+    // water boxes
+    let camera_data_count = reader.u32()?;
+    for _ in 0..camera_data_count {
+        let _setting = reader.u16()?;
+        let _count = reader.i16()?;
+        let _position_index = reader.i32()?;
+    }
+    let camera_position_count = reader.u32()?;
+    reader.bytes(camera_position_count as usize * 6)?;
+
+    let water_box_count = reader.u32()?;
+    let water_boxes = (0..water_box_count)
+        .map(|_| {
+            Ok(WaterBox {
+                x_min: reader.i16()?,
+                y_surface: reader.i16()?,
+                z_min: reader.i16()?,
+                x_length: reader.i16()?,
+                z_length: reader.i16()?,
+                properties: reader.u32()?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Collision {
+        min_bounds,
+        max_bounds,
+        vertices,
+        polygons,
+        surface_types,
+        water_boxes,
+    })
+}
+
+impl Collision {
+    /// Wavefront OBJ of the collision mesh, with the polygons grouped by
+    /// surface type.
+    pub fn obj(&self) -> String {
+        let mut obj = String::new();
+        for [x, y, z] in &self.vertices {
+            let _ = writeln!(obj, "v {} {} {}", x, y, z);
+        }
+
+        let mut order = (0..self.polygons.len()).collect::<Vec<usize>>();
+        order.sort_by_key(|i| self.polygons[*i].surface_type);
+        let mut group = None;
+        for i in order {
+            let polygon = &self.polygons[i];
+            if group != Some(polygon.surface_type) {
+                group = Some(polygon.surface_type);
+                let _ = writeln!(obj, "g surface_{}", polygon.surface_type);
+            }
+            let [a, b, c] = polygon
+                .vertices
+                .map(|vertex| (vertex & VERTEX_INDEX_MASK) as u32 + 1);
+            let _ = writeln!(obj, "f {} {} {}", a, b, c);
+        }
+        obj
+    }
+
+    /// Everything but the vertices, with the packed surface and water box
+    /// fields split out.
+    pub fn to_json(&self) -> Json {
+        let vec3 = |values: &[i16; 3]| {
+            Json::Array(
+                values
+                    .iter()
+                    .map(|value| Json::from(*value as f64))
+                    .collect(),
+            )
+        };
+
+        Json::object()
+            .with(
+                "bounds",
+                Json::object()
+                    .with("min", vec3(&self.min_bounds))
+                    .with("max", vec3(&self.max_bounds)),
+            )
+            .with("vertex_count", self.vertices.len())
+            .with(
+                "polygons",
+                Json::Array(
+                    self.polygons
+                        .iter()
+                        .map(|polygon| {
+                            Json::object()
+                                .with("surface_type", polygon.surface_type)
+                                .with(
+                                    "vertices",
+                                    Json::Array(
+                                        polygon
+                                            .vertices
+                                            .iter()
+                                            .map(|vertex| Json::from(vertex & VERTEX_INDEX_MASK))
+                                            .collect(),
+                                    ),
+                                )
+                                .with("flags", polygon.vertices[0] >> 13)
+                                .with("conveyor", polygon.vertices[1] >> 13 != 0)
+                                .with(
+                                    "normal",
+                                    Json::Array(
+                                        polygon
+                                            .normal
+                                            .iter()
+                                            .map(|value| Json::from(*value as f64 / 32767.0))
+                                            .collect(),
+                                    ),
+                                )
+                                .with("distance", polygon.distance as f64)
+                        })
+                        .collect(),
+                ),
+            )
+            .with(
+                "surface_types",
+                Json::Array(self.surface_types.iter().map(surface_type_json).collect()),
+            )
+            .with(
+                "water_boxes",
+                Json::Array(
+                    self.water_boxes
+                        .iter()
+                        .map(|water_box| {
+                            let properties = water_box.properties;
+                            Json::object()
+                                .with("x_min", water_box.x_min as f64)
+                                .with("y_surface", water_box.y_surface as f64)
+                                .with("z_min", water_box.z_min as f64)
+                                .with("x_length", water_box.x_length as f64)
+                                .with("z_length", water_box.z_length as f64)
+                                .with("camera", properties & 0xFF)
+                                .with("light", properties >> 8 & 0x1F)
+                                .with("room", properties >> 13 & 0x3F)
+                                .with("flag_19", properties >> 19 & 1 != 0)
+                        })
+                        .collect(),
+                ),
+            )
+    }
+}
+
+/// Splits the two words of a surface type into the fields of `SurfaceType`.
+fn surface_type_json(data: &[u32; 2]) -> Json {
+    let [data0, data1] = *data;
+    Json::object()
+        .with("data", Json::Array(vec![data0.into(), data1.into()]))
+        .with("camera", data0 & 0xFF)
+        .with("exit", data0 >> 8 & 0x1F)
+        .with("floor_type", data0 >> 13 & 0x1F)
+        .with("unk_18", data0 >> 18 & 0x7)
+        .with("wall_type", data0 >> 21 & 0x1F)
+        .with("floor_property", data0 >> 26 & 0xF)
+        .with("is_soft", data0 >> 30 & 1 != 0)
+        .with("is_horse_blocked", data0 >> 31 != 0)
+        .with("material", data1 & 0xF)
+        .with("floor_effect", data1 >> 4 & 0x3)
+        .with("light_setting", data1 >> 6 & 0x1F)
+        .with("echo", data1 >> 11 & 0x3F)
+        .with("can_hookshot", data1 >> 17 & 1 != 0)
+        .with("conveyor_speed", data1 >> 18 & 0x7)
+        .with("conveyor_direction", data1 >> 21 & 0x3F)
+        .with("unk_27", data1 >> 27 & 1 != 0)
+}
+
+/// Exports collision meshes to OBJ with their attributes as JSON.
+pub struct CollisionDecoder;
+
+impl ResourceDecoder for CollisionDecoder {
+    fn name(&self) -> &'static str {
+        "collision"
+    }
+
+    fn directory(&self) -> &'static str {
+        "collision"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::CollisionHeader, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let collision = match parse(data) {
+            Ok(collision) => collision,
+            Err(err) => {
+                log::error(format!("Failed to parse collision {}: {}", name, err));
+                return;
+            }
+        };
+
+        let base = converter.output_base(self, name);
+        log::progress(format!(
+            "Exporting collision with {} polygons: {}.obj",
+            collision.polygons.len(),
+            base
+        ));
+        converter.write(&(base.clone() + ".obj"), collision.obj());
+        converter.write(&(base + ".json"), collision.to_json().pretty() + "\n");
+    }
+}
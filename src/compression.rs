@@ -0,0 +1,170 @@
+/// Magic of Nintendo's MIO0 LZ compression, followed by the decompressed
+/// size and the offsets of the back-references and the literal bytes.
+pub const MIO0_MAGIC: &[u8; 4] = b"MIO0";
+/// Magic of Yay0, laid out as MIO0 with longer back-references.
+pub const YAY0_MAGIC: &[u8; 4] = b"Yay0";
+/// Magic of Yaz0, interleaving the layout bits with the data they describe.
+pub const YAZ0_MAGIC: &[u8; 4] = b"Yaz0";
+
+fn be16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "Truncated compressed data".to_owned())
+}
+
+fn be32(data: &[u8], offset: usize) -> Result<usize, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+        .ok_or_else(|| "Truncated compressed data".to_owned())
+}
+
+fn byte(data: &[u8], offset: usize) -> Result<u8, String> {
+    data.get(offset)
+        .copied()
+        .ok_or_else(|| "Truncated compressed data".to_owned())
+}
+
+/// Decompressed size the header of `data` gives, which mustn't be more than
+/// the `limit` bytes the data is expected to hold.
+fn size(data: &[u8], limit: usize) -> Result<usize, String> {
+    let size = be32(data, 4)?;
+    if size > limit {
+        return Err(format!(
+            "Decompressed size {} is more than the {} bytes expected",
+            size, limit
+        ));
+    }
+    Ok(size)
+}
+
+/// Whether the bit `bit` of `data`, counted from the most significant bit
+/// of its first byte, is set.
+fn bit(data: &[u8], bit: usize) -> Result<bool, String> {
+    Ok(byte(data, bit / 8)? << (bit % 8) & 0x80 != 0)
+}
+
+/// Copies `length` bytes from `distance` bytes back in `output`, the copy
+/// overlapping what it writes when the distance is shorter.
+fn copy_back(output: &mut Vec<u8>, distance: usize, length: usize) -> Result<(), String> {
+    if distance > output.len() {
+        return Err(format!(
+            "Back-reference {} bytes back with only {} decompressed",
+            distance,
+            output.len()
+        ));
+    }
+    for _ in 0..length {
+        output.push(output[output.len() - distance]);
+    }
+    Ok(())
+}
+
+/// Decompresses the MIO0 data `data`. A layout bit stream, most significant
+/// bit first, tells for each step whether the next byte is a literal or a
+/// back-reference of 3 to 18 bytes up to 4096 bytes back. Data decompressing
+/// to more than `limit` bytes is rejected.
+pub fn mio0(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if !data.starts_with(MIO0_MAGIC) {
+        return Err("Not MIO0 data".to_owned());
+    }
+    let size = size(data, limit)?;
+    let mut references = be32(data, 8)?;
+    let mut literals = be32(data, 12)?;
+    let mut layout = 16 * 8;
+    let mut output = Vec::new();
+    while output.len() < size {
+        layout += 1;
+        if bit(data, layout - 1)? {
+            output.push(byte(data, literals)?);
+            literals += 1;
+        } else {
+            let reference = be16(data, references)? as usize;
+            references += 2;
+            copy_back(&mut output, (reference & 0xFFF) + 1, (reference >> 12) + 3)?;
+        }
+    }
+    output.truncate(size);
+    Ok(output)
+}
+
+/// Decompresses the Yay0 data `data`. Laid out as MIO0, except a length
+/// nibble of 0 takes a byte from the literals for back-references of 18 to
+/// 273 bytes.
+pub fn yay0(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if !data.starts_with(YAY0_MAGIC) {
+        return Err("Not Yay0 data".to_owned());
+    }
+    let size = size(data, limit)?;
+    let mut references = be32(data, 8)?;
+    let mut literals = be32(data, 12)?;
+    let mut layout = 16 * 8;
+    let mut output = Vec::new();
+    while output.len() < size {
+        layout += 1;
+        if bit(data, layout - 1)? {
+            output.push(byte(data, literals)?);
+            literals += 1;
+            continue;
+        }
+        let reference = be16(data, references)? as usize;
+        references += 2;
+        let length = match reference >> 12 {
+            0 => {
+                literals += 1;
+                byte(data, literals - 1)? as usize + 0x12
+            }
+            length => length + 2,
+        };
+        copy_back(&mut output, (reference & 0xFFF) + 1, length)?;
+    }
+    output.truncate(size);
+    Ok(output)
+}
+
+/// Decompresses the Yaz0 data `data`. Each group of eight steps starts with
+/// its layout byte, back-references are encoded as in Yay0 but inline.
+pub fn yaz0(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if !data.starts_with(YAZ0_MAGIC) {
+        return Err("Not Yaz0 data".to_owned());
+    }
+    let size = size(data, limit)?;
+    let mut position = 16;
+    let mut output = Vec::new();
+    while output.len() < size {
+        let layout = position;
+        position += 1;
+        for step in 0..8 {
+            if output.len() >= size {
+                break;
+            }
+            if bit(data, layout * 8 + step)? {
+                output.push(byte(data, position)?);
+                position += 1;
+                continue;
+            }
+            let reference = be16(data, position)? as usize;
+            position += 2;
+            let length = match reference >> 12 {
+                0 => {
+                    position += 1;
+                    byte(data, position - 1)? as usize + 0x12
+                }
+                length => length + 2,
+            };
+            copy_back(&mut output, (reference & 0xFFF) + 1, length)?;
+        }
+    }
+    output.truncate(size);
+    Ok(output)
+}
+
+/// The data compressed in `data`, if it starts with the magic of a
+/// compression format, of at most `limit` bytes.
+pub fn decompress(data: &[u8], limit: usize) -> Option<Result<Vec<u8>, String>> {
+    match data.get(..4)? {
+        magic if magic == MIO0_MAGIC => Some(mio0(data, limit)),
+        magic if magic == YAY0_MAGIC => Some(yay0(data, limit)),
+        magic if magic == YAZ0_MAGIC => Some(yaz0(data, limit)),
+        _ => None,
+    }
+}
@@ -0,0 +1,292 @@
+use walkdir::WalkDir;
+use yaml_rust2::Yaml;
+
+use crate::names;
+
+/// Config file read when none is given.
+pub const DEFAULT_CONFIG_FILE: &str = "config.yml";
+
+/// Settings read from `config.yml`.
+pub struct Config {
+    /// Directory holding the decomp YAML asset definitions.
+    pub path: String,
+    /// Archive path prefixes to rewrite in output paths, as `(from, to)`.
+    pub path_map: Vec<(String, String)>,
+    /// Archive directory each segment of segmented addresses points into, as
+    /// `(segment, directory)`.
+    pub segments: Vec<(u8, String)>,
+    /// Images stored as a grid of tile entries `<path>_0`, `<path>_1`... in
+    /// row-major order, as `(path, columns)`.
+    pub tiled: Vec<(String, u32)>,
+    /// Globs of the archive paths of textures tagged I4 that hold IA4 texels.
+    pub i4_as_ia4: Vec<String>,
+    /// Globs of the archive paths of textures holding 12-bit palette indices
+    /// in 16-bit texels, decoded with their TLUT instead of as colors.
+    pub ci16: Vec<String>,
+    /// How `--skip-placeholders` tells filler textures apart.
+    pub placeholders: Placeholders,
+}
+
+/// How `--skip-placeholders` tells the filler textures of an archive apart,
+/// from the `placeholders` hash of the config.
+pub struct Placeholders {
+    /// Largest width and height of the textures skipped whatever they hold,
+    /// 1 by default.
+    pub max_size: u32,
+    /// Whether textures of a single color are skipped at any size, the
+    /// default.
+    pub solid: bool,
+    /// Globs of the archive paths of textures never skipped.
+    pub keep: Vec<String>,
+}
+
+impl Placeholders {
+    /// Whether the texture `name` of `width` by `height` texels is filler:
+    /// small enough, or with every decoded pixel of `pixels` the same. The
+    /// texels are compared once widened, as 4-bit and 1-bit textures pack
+    /// several in a byte.
+    pub fn matches(&self, name: &str, width: u32, height: u32, pixels: &[u8]) -> bool {
+        if self.keep.iter().any(|glob| names::glob_match(glob, name)) {
+            return false;
+        }
+        if width <= self.max_size && height <= self.max_size {
+            return true;
+        }
+        let pixel_size = pixels.len() / (width as usize * height as usize).max(1);
+        self.solid
+            && pixel_size > 0
+            && pixels
+                .chunks_exact(pixel_size)
+                .all(|pixel| pixel == &pixels[..pixel_size])
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> Self {
+        if !std::path::Path::new(path).exists() {
+            panic!("Configuration file '{}' not found.", path);
+        }
+
+        let config = yaml_rust2::YamlLoader::load_from_str(
+            &std::fs::read_to_string(path).expect("Failed to read config file"),
+        )
+        .expect("Failed to parse YAML config file");
+
+        let config = &config[0];
+
+        // The first game with an asset path, preferring one that exists when
+        // several are configured
+        let key_path = Yaml::String("path".to_owned());
+        let games = config
+            .as_hash()
+            .expect("Config is not a hash")
+            .values()
+            .filter_map(|value| {
+                value
+                    .as_hash()
+                    .filter(|hash_map| hash_map.contains_key(&key_path))
+            })
+            .collect::<Vec<_>>();
+        let game = games
+            .iter()
+            .find(|game| {
+                game.get(&key_path)
+                    .and_then(Yaml::as_str)
+                    .is_some_and(|path| std::path::Path::new(path).is_dir())
+            })
+            .or(games.first())
+            .copied();
+
+        let path = game
+            .map(|game| {
+                game.get(&key_path)
+                    .expect("Path key not found in config")
+                    .as_str()
+                    .expect("Path value is not a string")
+                    .to_owned()
+            })
+            .unwrap_or_default();
+
+        let path_map = match game.and_then(|game| game.get(&Yaml::String("path_map".to_owned()))) {
+            Some(path_map) => path_map
+                .as_hash()
+                .expect("path_map is not a hash")
+                .iter()
+                .map(|(from, to)| {
+                    (
+                        from.as_str()
+                            .expect("path_map key is not a string")
+                            .to_owned(),
+                        to.as_str()
+                            .expect("path_map value is not a string")
+                            .to_owned(),
+                    )
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let segments = match game.and_then(|game| game.get(&Yaml::String("segments".to_owned()))) {
+            Some(segments) => segments
+                .as_hash()
+                .expect("segments is not a hash")
+                .iter()
+                .map(|(segment, directory)| {
+                    (
+                        segment
+                            .as_i64()
+                            .and_then(|segment| u8::try_from(segment).ok())
+                            .filter(|segment| *segment < 0x10)
+                            .expect("segments key is not a segment number"),
+                        directory
+                            .as_str()
+                            .expect("segments value is not a string")
+                            .trim_end_matches('/')
+                            .to_owned(),
+                    )
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let tiled = match game.and_then(|game| game.get(&Yaml::String("tiled".to_owned()))) {
+            Some(tiled) => tiled
+                .as_hash()
+                .expect("tiled is not a hash")
+                .iter()
+                .map(|(path, columns)| {
+                    (
+                        path.as_str().expect("tiled key is not a string").to_owned(),
+                        columns
+                            .as_i64()
+                            .and_then(|columns| u32::try_from(columns).ok())
+                            .filter(|columns| *columns > 0)
+                            .expect("tiled value is not a column count"),
+                    )
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let i4_as_ia4 = match game.and_then(|game| game.get(&Yaml::String("i4_as_ia4".to_owned())))
+        {
+            Some(globs) => globs
+                .as_vec()
+                .expect("i4_as_ia4 is not a list")
+                .iter()
+                .map(|glob| {
+                    glob.as_str()
+                        .expect("i4_as_ia4 glob is not a string")
+                        .to_owned()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let ci16 = match game.and_then(|game| game.get(&Yaml::String("ci16".to_owned()))) {
+            Some(globs) => globs
+                .as_vec()
+                .expect("ci16 is not a list")
+                .iter()
+                .map(|glob| glob.as_str().expect("ci16 glob is not a string").to_owned())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let placeholders = game.and_then(|game| game.get(&Yaml::String("placeholders".to_owned())));
+        // Settings left out keep their default
+        let setting = |key: &str| {
+            placeholders
+                .map(|placeholders| &placeholders[key])
+                .filter(|value| !value.is_badvalue())
+        };
+        let placeholders = Placeholders {
+            max_size: match setting("max_size") {
+                None => 1,
+                Some(size) => size
+                    .as_i64()
+                    .and_then(|size| u32::try_from(size).ok())
+                    .expect("placeholders max_size is not a size"),
+            },
+            solid: match setting("solid") {
+                None => true,
+                Some(solid) => solid
+                    .as_bool()
+                    .expect("placeholders solid is not a boolean"),
+            },
+            keep: match setting("keep") {
+                None => Vec::new(),
+                Some(globs) => globs
+                    .as_vec()
+                    .expect("placeholders keep is not a list")
+                    .iter()
+                    .map(|glob| {
+                        glob.as_str()
+                            .expect("placeholders keep glob is not a string")
+                            .to_owned()
+                    })
+                    .collect(),
+            },
+        };
+
+        Config {
+            path,
+            path_map,
+            segments,
+            tiled,
+            i4_as_ia4,
+            ci16,
+            placeholders,
+        }
+    }
+
+    /// Number of YAML files under the asset path, or why it can't hold any
+    /// definitions.
+    pub fn yaml_file_count(&self) -> Result<usize, String> {
+        if self.path.is_empty() {
+            return Err("No game gives an asset path".to_owned());
+        }
+        let path = std::path::Path::new(&self.path);
+        if !path.exists() {
+            return Err(format!("Asset path '{}' does not exist", self.path));
+        }
+        if !path.is_dir() {
+            return Err(format!("Asset path '{}' is not a folder", self.path));
+        }
+        Ok(WalkDir::new(path)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .filter(|file| file.file_type().is_file())
+            .filter(|file| {
+                file.path()
+                    .extension()
+                    .is_some_and(|extension| extension == "yml" || extension == "yaml")
+            })
+            .count())
+    }
+
+    /// Whether the I4 texture `name` matches an `i4_as_ia4` glob and is
+    /// decoded as IA4.
+    pub fn treats_i4_as_ia4(&self, name: &str) -> bool {
+        self.i4_as_ia4
+            .iter()
+            .any(|glob| names::glob_match(glob, name))
+    }
+
+    /// Whether the texture `name` matches a `ci16` glob and holds 12-bit
+    /// palette indices.
+    pub fn is_ci16(&self, name: &str) -> bool {
+        self.ci16.iter().any(|glob| names::glob_match(glob, name))
+    }
+
+    /// Path of an archive entry in the output tree, with the longest matching
+    /// `path_map` prefix replaced.
+    pub fn map_path(&self, name: &str) -> String {
+        self.path_map
+            .iter()
+            .filter(|(from, _)| name.starts_with(from.as_str()))
+            .max_by_key(|(from, _)| from.len())
+            .map(|(from, to)| to.to_owned() + &name[from.len()..])
+            .unwrap_or_else(|| name.to_owned())
+    }
+}
@@ -0,0 +1,466 @@
+use std::{fmt::Write, str::FromStr};
+
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, ResourceType, decoder::ResourceDecoder, json::Json,
+    log, reader::Reader,
+};
+
+/// File format cutscenes are exported to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutsceneFormat {
+    Json,
+    /// The `CS_*` macros of the decomp, as found in its cutscene sources.
+    Macro,
+}
+
+impl CutsceneFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CutsceneFormat::Json => "json",
+            CutsceneFormat::Macro => "c",
+        }
+    }
+}
+
+impl FromStr for CutsceneFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(CutsceneFormat::Json),
+            "macro" => Ok(CutsceneFormat::Macro),
+            _ => Err(format!(
+                "Unknown cutscene format '{}', expected json or macro",
+                value
+            )),
+        }
+    }
+}
+
+const CS_CMD_CAM_EYE_SPLINE: u32 = 0x01;
+const CS_CMD_CAM_AT_SPLINE: u32 = 0x02;
+const CS_CMD_MISC: u32 = 0x03;
+const CS_CMD_LIGHT_SETTING: u32 = 0x04;
+const CS_CMD_CAM_EYE_SPLINE_REL_TO_PLAYER: u32 = 0x05;
+const CS_CMD_CAM_AT_SPLINE_REL_TO_PLAYER: u32 = 0x06;
+const CS_CMD_CAM_EYE: u32 = 0x07;
+const CS_CMD_CAM_AT: u32 = 0x08;
+const CS_CMD_RUMBLE_CONTROLLER: u32 = 0x09;
+const CS_CMD_PLAYER_CUE: u32 = 0x0A;
+const CS_CMD_TEXT: u32 = 0x13;
+const CS_CMD_TRANSITION: u32 = 0x2D;
+const CS_CMD_START_SEQ: u32 = 0x56;
+const CS_CMD_STOP_SEQ: u32 = 0x57;
+const CS_CMD_FADE_OUT_SEQ: u32 = 0x7C;
+const CS_CMD_TIME: u32 = 0x8C;
+const CS_CMD_DESTINATION: u32 = 0x3E8;
+const CS_CMD_END: u32 = 0xFFFFFFFF;
+
+/// `continueFlag` of the last point of a camera spline.
+const CS_CAM_STOP: u32 = 0xFF;
+/// Text id of `CS_TEXT_NONE`.
+const CS_TEXT_ID_NONE: u32 = 0xFFFF;
+
+enum Value {
+    Int(i64),
+    Float(f32),
+}
+
+/// A cutscene macro with its named arguments.
+struct Macro {
+    name: String,
+    args: Vec<(&'static str, Value)>,
+}
+
+impl Macro {
+    fn new(name: &str) -> Self {
+        Macro {
+            name: name.to_owned(),
+            args: Vec::new(),
+        }
+    }
+
+    fn int(mut self, name: &'static str, value: impl Into<i64>) -> Self {
+        self.args.push((name, Value::Int(value.into())));
+        self
+    }
+
+    fn float(mut self, name: &'static str, value: f32) -> Self {
+        self.args.push((name, Value::Float(value)));
+        self
+    }
+
+    fn to_json(&self) -> Json {
+        let mut json = Json::object().with(
+            "command",
+            self.name.to_lowercase().trim_start_matches("cs_"),
+        );
+        for (name, value) in &self.args {
+            json.insert(
+                name,
+                match value {
+                    Value::Int(value) => *value as f64,
+                    Value::Float(value) => *value as f64,
+                },
+            );
+        }
+        json
+    }
+
+    fn to_macro(&self) -> String {
+        let args = self
+            .args
+            .iter()
+            .map(|(_, value)| match value {
+                Value::Int(value) => value.to_string(),
+                Value::Float(value) => format!("{:?}f", value),
+            })
+            .collect::<Vec<_>>();
+        format!("{}({})", self.name, args.join(", "))
+    }
+}
+
+/// A command, and for list commands their entries.
+struct Command {
+    header: Macro,
+    entries: Vec<Macro>,
+}
+
+/// The decoded command stream of a Cutscene resource.
+pub struct Cutscene {
+    commands: Vec<Command>,
+    /// Why decoding stopped before `CS_END`, if it did.
+    pub error: Option<String>,
+}
+
+/// Decodes a Cutscene resource. Like the game, decoding stops at a command it
+/// doesn't know the size of; what was read up to there is kept.
+pub fn parse(data: &[u8]) -> Result<Cutscene, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let word_count = reader.u32()?;
+    let words = (0..word_count)
+        .map(|_| reader.u32())
+        .collect::<Result<Vec<u32>, String>>()?;
+
+    let mut words = Words {
+        words: &words,
+        position: 0,
+    };
+    let mut commands = Vec::new();
+    let error = parse_commands(&mut words, &mut commands).err();
+    Ok(Cutscene { commands, error })
+}
+
+struct Words<'a> {
+    words: &'a [u32],
+    position: usize,
+}
+
+impl Words<'_> {
+    fn next(&mut self) -> Result<u32, String> {
+        let word = *self
+            .words
+            .get(self.position)
+            .ok_or_else(|| format!("Cutscene truncated at word {}", self.position))?;
+        self.position += 1;
+        Ok(word)
+    }
+
+    /// The two halves of the next word, high first.
+    fn halves(&mut self) -> Result<(u16, u16), String> {
+        let word = self.next()?;
+        Ok(((word >> 16) as u16, word as u16))
+    }
+}
+
+fn parse_commands(words: &mut Words, commands: &mut Vec<Command>) -> Result<(), String> {
+    let total_entries = words.next()?;
+    let frame_count = words.next()?;
+    commands.push(Command {
+        header: Macro::new("CS_BEGIN_CUTSCENE")
+            .int("total_entries", total_entries)
+            .int("frame_count", frame_count),
+        entries: Vec::new(),
+    });
+
+    loop {
+        let id = words.next()?;
+        let command = match id {
+            CS_CMD_END => {
+                commands.push(Command {
+                    header: Macro::new("CS_END"),
+                    entries: Vec::new(),
+                });
+                return Ok(());
+            }
+            CS_CMD_CAM_EYE_SPLINE
+            | CS_CMD_CAM_AT_SPLINE
+            | CS_CMD_CAM_EYE_SPLINE_REL_TO_PLAYER
+            | CS_CMD_CAM_AT_SPLINE_REL_TO_PLAYER
+            | CS_CMD_CAM_EYE
+            | CS_CMD_CAM_AT => camera(words, id)?,
+            CS_CMD_TRANSITION | CS_CMD_DESTINATION => {
+                let _entries = words.next()?;
+                let (kind, start_frame) = words.halves()?;
+                let (end_frame, _) = words.halves()?;
+                let header = if id == CS_CMD_TRANSITION {
+                    Macro::new("CS_TRANSITION").int("type", kind)
+                } else {
+                    Macro::new("CS_DESTINATION").int("destination", kind)
+                };
+                Command {
+                    header: header
+                        .int("start_frame", start_frame)
+                        .int("end_frame", end_frame),
+                    entries: Vec::new(),
+                }
+            }
+            _ => list(words, id)?,
+        };
+        commands.push(command);
+    }
+}
+
+/// Camera splines, whose points run until one has the stop flag.
+fn camera(words: &mut Words, id: u32) -> Result<Command, String> {
+    let name = match id {
+        CS_CMD_CAM_EYE_SPLINE => "CS_CAM_EYE_SPLINE",
+        CS_CMD_CAM_AT_SPLINE => "CS_CAM_AT_SPLINE",
+        CS_CMD_CAM_EYE_SPLINE_REL_TO_PLAYER => "CS_CAM_EYE_SPLINE_REL_TO_PLAYER",
+        CS_CMD_CAM_AT_SPLINE_REL_TO_PLAYER => "CS_CAM_AT_SPLINE_REL_TO_PLAYER",
+        CS_CMD_CAM_EYE => "CS_CAM_EYE",
+        _ => "CS_CAM_AT",
+    };
+    let (_, start_frame) = words.halves()?;
+    let (end_frame, _) = words.halves()?;
+
+    let mut entries = Vec::new();
+    loop {
+        let flags = words.next()?;
+        let view_angle = f32::from_bits(words.next()?);
+        let (x, y) = words.halves()?;
+        let (z, unused) = words.halves()?;
+        entries.push(
+            Macro::new("CS_CAM_POINT")
+                .int("continue_flag", (flags >> 24) as u8 as i8)
+                .int("camera_roll", (flags >> 16) as u8 as i8)
+                .int("next_point_frame", flags as u16)
+                .float("view_angle", view_angle)
+                .int("x", x as i16)
+                .int("y", y as i16)
+                .int("z", z as i16)
+                .int("unused", unused as i16),
+        );
+        if flags >> 24 == CS_CAM_STOP {
+            break;
+        }
+    }
+
+    Ok(Command {
+        header: Macro::new(name)
+            .int("start_frame", start_frame)
+            .int("end_frame", end_frame),
+        entries,
+    })
+}
+
+/// Commands made of a count followed by fixed size entries. Everything not
+/// listed is an actor cue.
+fn list(words: &mut Words, id: u32) -> Result<Command, String> {
+    let count = words.next()?;
+    let name = match id {
+        CS_CMD_MISC => "CS_MISC",
+        CS_CMD_LIGHT_SETTING => "CS_LIGHT_SETTING",
+        CS_CMD_RUMBLE_CONTROLLER => "CS_RUMBLE_CONTROLLER",
+        CS_CMD_PLAYER_CUE => "CS_PLAYER_CUE",
+        CS_CMD_TEXT => "CS_TEXT",
+        CS_CMD_START_SEQ => "CS_START_SEQ",
+        CS_CMD_STOP_SEQ => "CS_STOP_SEQ",
+        CS_CMD_FADE_OUT_SEQ => "CS_FADE_OUT_SEQ",
+        CS_CMD_TIME => "CS_TIME",
+        _ => "CS_ACTOR_CUE",
+    };
+    // Ids with no known layout would otherwise be read as actor cues
+    if name == "CS_ACTOR_CUE" && !(0x0E..0x3E8).contains(&id) {
+        return Err(format!("Unknown cutscene command 0x{:X}", id));
+    }
+
+    let entries = (0..count)
+        .map(|_| match id {
+            CS_CMD_RUMBLE_CONTROLLER => {
+                let (unused0, start_frame) = words.halves()?;
+                let (end_frame, strength) = words.halves()?;
+                let (rates, unused) = words.halves()?;
+                Ok(Macro::new(name)
+                    .int("unused0", unused0)
+                    .int("start_frame", start_frame)
+                    .int("unused1", end_frame)
+                    .int("source_strength", (strength >> 8) as u8)
+                    .int("duration", strength as u8)
+                    .int("decrease_rate", (rates >> 8) as u8)
+                    .int("unused2", rates as u8)
+                    .int("unused3", unused))
+            }
+            CS_CMD_TEXT => {
+                let (text_id, start_frame) = words.halves()?;
+                let (end_frame, kind) = words.halves()?;
+                let (alt_text_id1, alt_text_id2) = words.halves()?;
+                Ok(if text_id as u32 == CS_TEXT_ID_NONE {
+                    Macro::new("CS_TEXT_NONE")
+                        .int("start_frame", start_frame)
+                        .int("end_frame", end_frame)
+                } else {
+                    Macro::new(name)
+                        .int("text_id", text_id)
+                        .int("start_frame", start_frame)
+                        .int("end_frame", end_frame)
+                        .int("type", kind)
+                        .int("alt_text_id1", alt_text_id1)
+                        .int("alt_text_id2", alt_text_id2)
+                })
+            }
+            CS_CMD_TIME => {
+                let (unused, start_frame) = words.halves()?;
+                let (end_frame, time) = words.halves()?;
+                let _ = words.next()?;
+                Ok(Macro::new(name)
+                    .int("unused", unused)
+                    .int("start_frame", start_frame)
+                    .int("end_frame", end_frame)
+                    .int("hour", (time >> 8) as u8)
+                    .int("minute", time as u8))
+            }
+            CS_CMD_MISC | CS_CMD_LIGHT_SETTING | CS_CMD_START_SEQ | CS_CMD_STOP_SEQ
+            | CS_CMD_FADE_OUT_SEQ => {
+                let (kind, start_frame) = words.halves()?;
+                let (end_frame, _) = words.halves()?;
+                for _ in 0..10 {
+                    words.next()?;
+                }
+                let kind_name = match id {
+                    CS_CMD_MISC => "misc_type",
+                    CS_CMD_LIGHT_SETTING => "light_setting",
+                    CS_CMD_FADE_OUT_SEQ => "seq_player",
+                    _ => "seq_id",
+                };
+                Ok(Macro::new(name)
+                    .int(kind_name, kind)
+                    .int("start_frame", start_frame)
+                    .int("end_frame", end_frame))
+            }
+            _ => {
+                let (cue_id, start_frame) = words.halves()?;
+                let (end_frame, rot_x) = words.halves()?;
+                let (rot_y, rot_z) = words.halves()?;
+                let mut cue = Macro::new(name)
+                    .int("cue_id", cue_id)
+                    .int("start_frame", start_frame)
+                    .int("end_frame", end_frame)
+                    .int("rot_x", rot_x as i16)
+                    .int("rot_y", rot_y as i16)
+                    .int("rot_z", rot_z as i16);
+                for axis in ["start_x", "start_y", "start_z", "end_x", "end_y", "end_z"] {
+                    cue = cue.int(axis, words.next()? as i32);
+                }
+                for _ in 0..3 {
+                    words.next()?;
+                }
+                Ok(cue)
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let header = match name {
+        "CS_ACTOR_CUE" => Macro::new("CS_ACTOR_CUE_LIST").int("cmd_type", id),
+        _ => Macro::new(&format!("{}_LIST", name)),
+    };
+    Ok(Command {
+        header: header.int("entries", count),
+        entries,
+    })
+}
+
+impl Cutscene {
+    pub fn export(&self, format: CutsceneFormat) -> String {
+        match format {
+            CutsceneFormat::Json => {
+                let commands = self
+                    .commands
+                    .iter()
+                    .map(|command| {
+                        let json = command.header.to_json();
+                        if command.entries.is_empty() {
+                            json
+                        } else {
+                            json.with(
+                                "entries",
+                                Json::Array(command.entries.iter().map(Macro::to_json).collect()),
+                            )
+                        }
+                    })
+                    .collect();
+                let mut json = Json::object().with("commands", Json::Array(commands));
+                if let Some(error) = &self.error {
+                    json.insert("error", error.as_str());
+                }
+                json.pretty() + "\n"
+            }
+            CutsceneFormat::Macro => {
+                let mut out = String::new();
+                for command in &self.commands {
+                    let _ = writeln!(out, "{},", command.header.to_macro());
+                    for entry in &command.entries {
+                        let _ = writeln!(out, "    {},", entry.to_macro());
+                    }
+                }
+                if let Some(error) = &self.error {
+                    let _ = writeln!(out, "// {}", error);
+                }
+                out
+            }
+        }
+    }
+}
+
+/// Exports cutscenes to the format picked with `--cutscene-format`.
+pub struct CutsceneDecoder;
+
+impl ResourceDecoder for CutsceneDecoder {
+    fn name(&self) -> &'static str {
+        "cutscene"
+    }
+
+    fn directory(&self) -> &'static str {
+        "cutscenes"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::Cutscene, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let cutscene = match parse(data) {
+            Ok(cutscene) => cutscene,
+            Err(err) => {
+                log::error(format!("Failed to parse cutscene {}: {}", name, err));
+                return;
+            }
+        };
+        if let Some(error) = &cutscene.error {
+            log::error(format!(
+                "Cutscene {} only partially decoded: {}",
+                name, error
+            ));
+        }
+
+        let format = converter.options.cutscene_format;
+        let path = format!(
+            "{}.{}",
+            converter.output_base(self, name),
+            format.extension()
+        );
+        log::progress(format!("Exporting cutscene: {}", path));
+        converter.write(&path, cutscene.export(format));
+    }
+}
@@ -0,0 +1,344 @@
+//! Decoding of whole texture entries the way the converter does it: padded
+//! rows packed, byte swaps and interleaved rows undone or detected, and the
+//! palette, TMEM and pack hash checks made along the way.
+
+use std::sync::Arc;
+
+use crate::{
+    DecodeError, DecodeOptions, OTR_HEADER_SIZE, OTRHeader, ResourceType, TextureFormat,
+    TextureType, decode_texels,
+    interleave::{self, Deinterleave},
+    pack_hash::PackHashes,
+    pack_rows,
+    palette::{self, PaletteOverflow, PaletteUsage},
+    pixels::{self, Expansion},
+    swap::{self, ByteSwap},
+};
+
+/// What the asset definitions of an archive say about its textures that
+/// their resources don't. Textures are named by their archive path, or by
+/// their file name where the definitions only know symbols.
+pub trait TextureDefinitions {
+    /// TLUT symbol and colors of the CI texture `name` of type `type_id`.
+    fn tlut(&self, name: &str, type_id: &TextureType) -> Option<(&str, Arc<TextureFormat>)>;
+
+    /// Row pitch, in texels, of the texture file `file_name` stored with pad
+    /// texels at the end of every row.
+    fn pitch(&self, _file_name: &str) -> Option<u32> {
+        None
+    }
+
+    /// Whether the I4 texture `name` holds IA4 texels.
+    fn i4_as_ia4(&self, _name: &str) -> bool {
+        false
+    }
+
+    /// TLUT entry the colors `tlut` gives the CI texture `name` start at.
+    fn tlut_start(&self, _name: &str, _type_id: &TextureType) -> usize {
+        0
+    }
+}
+
+/// Drops the pad texels at the end of each row of a texture stored `pitch`
+/// texels wide.
+fn strip_pitch(texture_format: &mut TextureFormat, pitch: u32) -> Result<(), String> {
+    if pitch < texture_format.width {
+        return Err(format!(
+            "Pitch {} is less than the width {}",
+            pitch, texture_format.width
+        ));
+    }
+    let row_size = texture_format.row_size(texture_format.width)?;
+    let pitch_size = texture_format.row_size(pitch)?;
+    texture_format.data = pack_rows(
+        &texture_format.data,
+        row_size,
+        pitch_size,
+        texture_format.height,
+    )?;
+    Ok(())
+}
+
+/// Decodes a texture after undoing `swap`, returning the byte order that was
+/// used. In auto mode the candidate giving the smoothest image wins.
+pub fn decode_with_swap(
+    texture_format: &TextureFormat,
+    tlut: Option<&TextureFormat>,
+    swap: ByteSwap,
+    expansion: Expansion,
+) -> Option<(ByteSwap, Vec<u8>)> {
+    match swap {
+        ByteSwap::Auto => {
+            let channels = texture_format
+                .type_id
+                .to_image_type()?
+                .bits_per_pixel()
+                .div_ceil(8) as usize;
+            ByteSwap::CANDIDATES
+                .iter()
+                .filter_map(|swap| {
+                    let data = swap.apply(&texture_format.data);
+                    Some((
+                        *swap,
+                        decode_texels(texture_format, &data, tlut, expansion)?,
+                    ))
+                })
+                .min_by(|(_, a), (_, b)| {
+                    let width = texture_format.width as usize;
+                    let a = swap::neighbor_entropy(a, width, channels, (1, 0));
+                    let b = swap::neighbor_entropy(b, width, channels, (1, 0));
+                    a.total_cmp(&b)
+                })
+        }
+        swap => {
+            let data = swap.apply(&texture_format.data);
+            Some((swap, decode_texels(texture_format, &data, tlut, expansion)?))
+        }
+    }
+}
+
+/// Decodes a texture `row_size` bytes a row after undoing the byte swap and
+/// the TMEM interleaving of odd rows `options` set, detecting them in auto
+/// mode. Returns the byte order used and whether the rows were
+/// deinterleaved.
+pub fn decode_ordered(
+    texture_format: &TextureFormat,
+    tlut: Option<&TextureFormat>,
+    row_size: usize,
+    options: &DecodeOptions,
+) -> Option<(ByteSwap, bool, Vec<u8>)> {
+    let DecodeOptions {
+        expansion,
+        swap,
+        deinterleave,
+        ..
+    } = *options;
+    let deinterleaved = || {
+        TextureFormat::new(
+            texture_format.type_id.clone(),
+            texture_format.width,
+            texture_format.height,
+            texture_format.size,
+            interleave::deinterleave(&texture_format.data, row_size),
+        )
+    };
+    if deinterleave == Deinterleave::On {
+        let (swap, data) = decode_with_swap(&deinterleaved(), tlut, swap, expansion)?;
+        return Some((swap, true, data));
+    }
+    let (swap, data) = decode_with_swap(texture_format, tlut, swap, expansion)?;
+    if deinterleave == Deinterleave::Auto
+        && let Some((_, candidate)) = decode_with_swap(&deinterleaved(), tlut, swap, expansion)
+    {
+        let channels = texture_format
+            .type_id
+            .to_image_type()?
+            .bits_per_pixel()
+            .div_ceil(8) as usize;
+        let width = texture_format.width as usize;
+        if swap::neighbor_entropy(&candidate, width, channels, (0, 1))
+            < swap::neighbor_entropy(&data, width, channels, (0, 1))
+        {
+            return Some((swap, true, candidate));
+        }
+    }
+    Some((swap, false, data))
+}
+
+/// A texture decoded to 8-bit channels, ready to be encoded.
+pub struct DecodedTexture {
+    pub type_id: TextureType,
+    pub width: u32,
+    pub height: u32,
+    pub format: image::ExtendedColorType,
+    pub swap: ByteSwap,
+    /// Whether the odd rows were swapped back from their TMEM layout.
+    pub deinterleaved: bool,
+    pub data: Vec<u8>,
+    /// Set when the texture uses indices past the end of its TLUT.
+    pub palette_overflow: Option<PaletteOverflow>,
+    /// Why the size of the TLUT doesn't fit the texture type, when it doesn't.
+    pub palette_mismatch: Option<String>,
+    /// Palette slots read by a CI texture.
+    pub palette_usage: Option<PaletteUsage>,
+    /// Set when the texels of an I4 texture look like IA4 ones.
+    pub ia4_suspect: bool,
+    /// Why the texture doesn't fit in TMEM, when it doesn't.
+    pub tmem_overflow: Option<String>,
+    /// Hashes of the texels and TLUT in the hi-res pack schemes.
+    pub pack_hashes: Option<PackHashes>,
+}
+
+/// Whether the texels of an I4 texture look like IA4 ones, the lowest bit of
+/// every texel being an alpha mask: it takes both values and stays the same
+/// across nearly all horizontal neighbors. Tiny textures are never flagged.
+fn looks_like_ia4(texture_format: &TextureFormat) -> bool {
+    let texels = pixels::unpack_4bpp(
+        &texture_format.data,
+        texture_format.width,
+        texture_format.height,
+    );
+    if texels.len() < 64 || texture_format.width < 2 {
+        return false;
+    }
+    let masked = texels.iter().filter(|texel| *texel & 1 == 1).count();
+    let share = masked as f64 / texels.len() as f64;
+    if !(0.05..=0.95).contains(&share) {
+        return false;
+    }
+    let (mut pairs, mut same) = (0, 0);
+    for row in texels.chunks_exact(texture_format.width as usize) {
+        for pair in row.windows(2) {
+            pairs += 1;
+            if pair[0] & 1 == pair[1] & 1 {
+                same += 1;
+            }
+        }
+    }
+    same as f64 / pairs as f64 >= 0.9
+}
+
+/// Makes the CI8 pixels `rgba` of the texels `indices` opaque when either of
+/// the two low bits of their TLUT color is set and transparent otherwise, the
+/// alpha the option-less tool gave them. Indices past the TLUT read `[1, 1]`
+/// as they did there.
+fn legacy_ci8_alpha(indices: &[u8], tlut: &TextureFormat, rgba: &mut [u8]) {
+    for (&index, pixel) in indices.iter().zip(rgba.chunks_exact_mut(4)) {
+        let color = tlut.data.chunks(2).nth(index as usize).unwrap_or(&[1, 1]);
+        pixel[3] = if color[1] & 0x03 != 0 { 0xFF } else { 0x00 };
+    }
+}
+
+/// Decodes the archive entry `name`, the resource `data`, with what
+/// `definitions` say about it. Entries that aren't textures to convert
+/// (other resource types, TLUTs) give `Ok(None)`.
+pub fn decode_entry(
+    name: &str,
+    data: &[u8],
+    options: &DecodeOptions,
+    definitions: &dyn TextureDefinitions,
+) -> Result<Option<DecodedTexture>, DecodeError> {
+    if data.len() < OTR_HEADER_SIZE {
+        return Err(DecodeError::Invalid(format!(
+            "File {} is too short to be a valid OTR file",
+            name
+        )));
+    }
+    let invalid = |err: String| DecodeError::Invalid(format!("{}: {}", name, err));
+    let otr_format = OTRHeader::parse(data).map_err(|err| invalid(err.to_string()))?;
+    if otr_format.type_id != ResourceType::Texture {
+        return Ok(None);
+    }
+    let mut texture_format = TextureFormat::parse(data).map_err(|err| invalid(err.to_string()))?;
+    let Some(format) = texture_format.type_id.to_image_type() else {
+        return Ok(None);
+    };
+
+    texture_format.pixels().map_err(invalid)?;
+    if options.compressed_texels {
+        texture_format.decompress().map_err(invalid)?;
+    }
+    // Rows are padded to a whole byte
+    let row_size = texture_format
+        .row_size(texture_format.width)
+        .map_err(invalid)?;
+
+    if let Some(stride) = TextureFormat::stride(data).filter(|stride| *stride > 0) {
+        texture_format.data = pack_rows(
+            &texture_format.data,
+            row_size,
+            stride as usize,
+            texture_format.height,
+        )
+        .map_err(invalid)?;
+    }
+    let file_name = name.split('/').next_back().unwrap();
+    if let Some(pitch) = definitions.pitch(file_name) {
+        strip_pitch(&mut texture_format, pitch).map_err(invalid)?;
+    }
+    let mut ia4_suspect = false;
+    if texture_format.type_id == TextureType::Grayscale4bpp {
+        if definitions.i4_as_ia4(name) {
+            texture_format.type_id = TextureType::GrayscaleAlpha4bpp;
+        } else {
+            ia4_suspect = looks_like_ia4(&texture_format);
+        }
+    }
+
+    let expected_size = texture_format.data_size().map_err(invalid)?;
+    if expected_size > texture_format.data.len() {
+        return Err(DecodeError::Invalid(format!(
+            "Data size does not match expected size for {}: {} vs {}",
+            name,
+            texture_format.data.len(),
+            expected_size
+        )));
+    }
+
+    let tlut = match texture_format.type_id {
+        TextureType::Palette4bpp | TextureType::Palette8bpp => Some(
+            definitions
+                .tlut(name, &texture_format.type_id)
+                .ok_or_else(|| DecodeError::MissingTlut(file_name.to_owned()))?,
+        ),
+        _ => None,
+    };
+    let tlut_symbol = tlut.as_ref().map(|(symbol, _)| *symbol);
+    let tlut = tlut.as_ref().map(|(_, tlut)| tlut.as_ref());
+
+    let tmem_overflow = texture_format.tmem_overflow();
+    let palette_overflow = tlut.and_then(|tlut| palette::check(&texture_format, tlut));
+    let palette_mismatch = tlut.and_then(|tlut| {
+        palette::pairing_mismatch(&texture_format.type_id, palette::entry_count(tlut))
+    });
+    let palette_usage = tlut_symbol.zip(tlut).map(|(symbol, tlut)| {
+        let start = definitions.tlut_start(name, &texture_format.type_id);
+        PaletteUsage::new(&texture_format, symbol, tlut, start)
+    });
+
+    // Hashed as the rows sit in RDRAM, once the byte order is known
+    let texels = texture_format.data.clone();
+
+    let (swap, deinterleaved, mut data) = decode_ordered(&texture_format, tlut, row_size, options)
+        .ok_or_else(|| {
+            DecodeError::Invalid(format!(
+                "Unknown or unsupported texture type: {:?}",
+                texture_format.type_id
+            ))
+        })?;
+    let rdram = TextureFormat::new(
+        texture_format.type_id.clone(),
+        texture_format.width,
+        texture_format.height,
+        texture_format.size,
+        swap.apply(&texels),
+    );
+    let pack_hashes = PackHashes::compute(&rdram, tlut);
+    if options.legacy_ci8_alpha
+        && texture_format.type_id == TextureType::Palette8bpp
+        && let Some(tlut) = tlut
+    {
+        if deinterleaved {
+            let indices = interleave::deinterleave(&rdram.data, row_size);
+            legacy_ci8_alpha(&indices, tlut, &mut data);
+        } else {
+            legacy_ci8_alpha(&rdram.data, tlut, &mut data);
+        }
+    }
+
+    Ok(Some(DecodedTexture {
+        type_id: texture_format.type_id,
+        width: texture_format.width,
+        height: texture_format.height,
+        format,
+        swap,
+        deinterleaved,
+        data,
+        palette_overflow,
+        palette_mismatch,
+        palette_usage,
+        ia4_suspect,
+        tmem_overflow,
+        pack_hashes,
+    }))
+}
@@ -0,0 +1,119 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    Converter, EntryResult, OTRHeader, ResourceType, audio::AudioDecoder, ci16::Ci16Decoder,
+    collision::CollisionDecoder, config::Config, cutscene::CutsceneDecoder, gltf::SkeletonDecoder,
+    light::LightDecoder, path::PathDecoder, relocation::DisplayListDecoder, scene::SceneDecoder,
+    text::TextDecoder, texture::TextureDecoder,
+};
+
+/// Exports one kind of resource. Decoders are picked by the type and version
+/// found in the resource header.
+pub trait ResourceDecoder: Sync {
+    /// Name selecting the decoder with `--types`.
+    fn name(&self) -> &'static str;
+
+    /// Folder the outputs go to with `--layout by-type`.
+    fn directory(&self) -> &'static str;
+
+    /// Resource types and versions the decoder understands.
+    fn handles(&self) -> &'static [(ResourceType, u32)];
+
+    /// Whether the decoder takes the entry `name` over from the one its type
+    /// and version pick, for custom variants of a stock resource the config
+    /// points out.
+    fn claims(&self, _config: &Config, _header: &OTRHeader, _name: &str) -> bool {
+        false
+    }
+
+    /// Writes the outputs for the entry `result.name`, recording in `result`
+    /// what the conversion summary needs to know.
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult);
+}
+
+/// Every decoder, listing one here is all it takes to register it.
+pub const DECODERS: &[&dyn ResourceDecoder] = &[
+    &TextureDecoder,
+    &TextDecoder,
+    &AudioDecoder,
+    &SkeletonDecoder,
+    &CollisionDecoder,
+    &SceneDecoder,
+    &CutsceneDecoder,
+    &PathDecoder,
+    &DisplayListDecoder,
+    &LightDecoder,
+    &Ci16Decoder,
+];
+
+/// Decoders keyed by the resource type and version they read.
+pub struct Registry {
+    decoders: HashMap<(ResourceType, u32), &'static dyn ResourceDecoder>,
+    /// Every registered decoder, asked first whether it claims an entry.
+    selected: Vec<&'static dyn ResourceDecoder>,
+    types: HashSet<ResourceType>,
+}
+
+impl Registry {
+    /// Registers every decoder, or only those named in `types`.
+    pub fn new(types: Option<&[String]>) -> Self {
+        if let Some(types) = types {
+            for name in types {
+                if !DECODERS.iter().any(|decoder| decoder.name() == name) {
+                    let names = DECODERS
+                        .iter()
+                        .map(|decoder| decoder.name())
+                        .collect::<Vec<_>>();
+                    panic!(
+                        "Unknown resource type '{}', expected one of {}",
+                        name,
+                        names.join(", ")
+                    );
+                }
+            }
+        }
+
+        let mut decoders = HashMap::new();
+        let mut selected = Vec::new();
+        for decoder in DECODERS {
+            if types.is_some_and(|types| !types.iter().any(|name| name == decoder.name())) {
+                continue;
+            }
+            selected.push(*decoder);
+            for key in decoder.handles() {
+                decoders.insert(*key, *decoder);
+            }
+        }
+        let types = decoders.keys().map(|(type_id, _)| *type_id).collect();
+        Registry {
+            decoders,
+            selected,
+            types,
+        }
+    }
+
+    /// Decoder for the entry `name` with `header`, `Ok(None)` when its type
+    /// isn't selected and an error when no decoder reads its version.
+    pub fn get(
+        &self,
+        config: &Config,
+        header: &OTRHeader,
+        name: &str,
+    ) -> Result<Option<&'static dyn ResourceDecoder>, String> {
+        if let Some(decoder) = self
+            .selected
+            .iter()
+            .find(|decoder| decoder.claims(config, header, name))
+        {
+            return Ok(Some(*decoder));
+        }
+        match self.decoders.get(&(header.type_id, header.version)) {
+            Some(decoder) => Ok(Some(*decoder)),
+            None if self.types.contains(&header.type_id) => Err(format!(
+                "Unsupported {:?} resource version {}",
+                header.type_id, header.version
+            )),
+            None => Ok(None),
+        }
+    }
+}
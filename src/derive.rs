@@ -0,0 +1,146 @@
+use std::str::FromStr;
+
+use image::ExtendedColorType;
+
+use crate::{
+    Converter, log,
+    stream::{self, ImageOutputFormat},
+};
+
+/// Slope given to the height differences found by the Sobel filter, higher
+/// values make bumpier normal maps.
+const NORMAL_STRENGTH: f32 = 2.0;
+
+/// Image derived from every texture with `--derive`, written next to it as a
+/// PNG named with the suffix of the kind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Derived {
+    /// Grayscale luma of the texture.
+    Height,
+    /// Tangent space normal map of the height, green pointing up as in
+    /// OpenGL.
+    Normal,
+}
+
+impl FromStr for Derived {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "height" => Ok(Derived::Height),
+            "normal" => Ok(Derived::Normal),
+            _ => Err(format!(
+                "Unknown derived image '{}', expected height or normal",
+                value
+            )),
+        }
+    }
+}
+
+impl Derived {
+    fn suffix(&self) -> &'static str {
+        match self {
+            Derived::Height => "height",
+            Derived::Normal => "normal",
+        }
+    }
+}
+
+/// Path of the `derived` image of the output `output`, relative to the
+/// output folder.
+pub fn path(output: &str, derived: Derived) -> String {
+    let output = std::path::Path::new(output);
+    let name = format!(
+        "{}_{}.png",
+        output.file_stem().unwrap().to_string_lossy(),
+        derived.suffix()
+    );
+    output
+        .with_file_name(name)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Luma of RGBA texels from 0 to 1, with the Rec. 709 weights.
+pub fn height_map(rgba: &[u8]) -> Vec<f32> {
+    rgba.chunks_exact(4)
+        .map(|texel| {
+            (0.2126 * texel[0] as f32 + 0.7152 * texel[1] as f32 + 0.0722 * texel[2] as f32) / 255.0
+        })
+        .collect()
+}
+
+/// Normal map of `heights` as RGB texels, from the slopes a Sobel filter finds.
+/// Textures usually tile, so the edges wrap around.
+pub fn normal_map(heights: &[f32], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let at = |x: usize, y: usize, dx: isize, dy: isize| {
+        let x = (x as isize + dx).rem_euclid(width as isize) as usize;
+        let y = (y as isize + dy).rem_euclid(height as isize) as usize;
+        heights[y * width + x]
+    };
+    let mut normals = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let slope_x = at(x, y, 1, -1) + 2.0 * at(x, y, 1, 0) + at(x, y, 1, 1)
+                - at(x, y, -1, -1)
+                - 2.0 * at(x, y, -1, 0)
+                - at(x, y, -1, 1);
+            let slope_y = at(x, y, -1, 1) + 2.0 * at(x, y, 0, 1) + at(x, y, 1, 1)
+                - at(x, y, -1, -1)
+                - 2.0 * at(x, y, 0, -1)
+                - at(x, y, 1, -1);
+            // Image rows go down, the green axis goes up
+            let normal = [-slope_x * NORMAL_STRENGTH, slope_y * NORMAL_STRENGTH, 1.0];
+            let length = normal.iter().map(|value| value * value).sum::<f32>().sqrt();
+            normals
+                .extend(normal.map(|value| ((value / length * 0.5 + 0.5) * 255.0).round() as u8));
+        }
+    }
+    normals
+}
+
+/// Writes the images `--derive` asks for of the decoded texels of the output
+/// `output`.
+pub fn write(
+    converter: &Converter,
+    output: &str,
+    color: ExtendedColorType,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) {
+    let Some(derived) = &converter.options.derive else {
+        return;
+    };
+    let heights = height_map(&stream::rgba8(
+        color,
+        data,
+        width as usize * height as usize,
+    ));
+    for derived in derived {
+        let (texels, color) = match derived {
+            Derived::Height => (
+                heights
+                    .iter()
+                    .map(|value| (value * 255.0).round() as u8)
+                    .collect(),
+                ExtendedColorType::L8,
+            ),
+            Derived::Normal => (normal_map(&heights, width, height), ExtendedColorType::Rgb8),
+        };
+        let path = format!("{}/{}", converter.folder_name, path(output, *derived));
+        let mut png = Vec::new();
+        match stream::write_image(
+            &mut png,
+            &texels,
+            width,
+            height,
+            color,
+            ImageOutputFormat::Png,
+        ) {
+            Ok(()) => converter.write(&path, png),
+            Err(err) => log::error(format!("Failed to encode {}: {}", path, err)),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use image::ExtendedColorType;
+
+/// Fills the color of fully transparent texels from their neighbors, one
+/// texel further from the opaque ones on each of the `radius` passes, so
+/// upscalers and bilinear filtering blend with nearby colors instead of the
+/// black the decoders leave there. Alpha is left untouched, and texels
+/// further than `radius` from any opaque texel keep their color.
+pub fn dilate(data: &mut [u8], color: ExtendedColorType, width: u32, height: u32, radius: u32) {
+    // Only the layouts with 8-bit channels and alpha last
+    let stride = match color {
+        ExtendedColorType::La8 => 2,
+        ExtendedColorType::Rgba8 => 4,
+        _ => return,
+    };
+    let (width, height) = (width as usize, height as usize);
+    if data.len() < width * height * stride {
+        return;
+    }
+    let mut filled = data
+        .chunks_exact(stride)
+        .take(width * height)
+        .map(|texel| texel[stride - 1] > 0)
+        .collect::<Vec<_>>();
+    for _ in 0..radius {
+        let mut updates = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if filled[y * width + x] {
+                    continue;
+                }
+                let mut sum = [0u32; 3];
+                let mut count = 0;
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                        let neighbor = ny * width + nx;
+                        if !filled[neighbor] {
+                            continue;
+                        }
+                        let texel = &data[neighbor * stride..];
+                        for (channel, sum) in sum.iter_mut().take(stride - 1).enumerate() {
+                            *sum += texel[channel] as u32;
+                        }
+                        count += 1;
+                    }
+                }
+                if count > 0 {
+                    updates.push((
+                        y * width + x,
+                        sum.map(|sum| ((sum + count / 2) / count) as u8),
+                    ));
+                }
+            }
+        }
+        if updates.is_empty() {
+            break;
+        }
+        for (texel, average) in updates {
+            data[texel * stride..texel * stride + stride - 1]
+                .copy_from_slice(&average[..stride - 1]);
+            filled[texel] = true;
+        }
+    }
+}
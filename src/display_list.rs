@@ -0,0 +1,435 @@
+use crate::{
+    OTR_HEADER_MAGIC, OTR_HEADER_SIZE, ResourceType, TEXTURE_STRIDE_VERSION, TextureType,
+    reader::Reader,
+};
+
+// F3DEX2 opcodes
+const G_NOOP: u8 = 0x00;
+const G_VTX: u8 = 0x01;
+const G_TRI1: u8 = 0x05;
+const G_TRI2: u8 = 0x06;
+const G_QUAD: u8 = 0x07;
+const G_TEXTURE: u8 = 0xD7;
+const G_GEOMETRYMODE: u8 = 0xD9;
+const G_SETOTHERMODE_H: u8 = 0xE3;
+const G_DL: u8 = 0xDE;
+const G_ENDDL: u8 = 0xDF;
+const G_LOADTLUT: u8 = 0xF0;
+const G_LOADBLOCK: u8 = 0xF3;
+const G_LOADTILE: u8 = 0xF4;
+const G_SETTILE: u8 = 0xF5;
+const G_SETPRIMCOLOR: u8 = 0xFA;
+const G_SETENVCOLOR: u8 = 0xFB;
+const G_SETCOMBINE: u8 = 0xFC;
+const G_SETTIMG: u8 = 0xFD;
+
+/// Geometry mode bit turning vertex colors into normals for lighting.
+pub const G_LIGHTING: u32 = 0x00020000;
+
+/// Shift of the two texture filter bits in the high word of the other modes.
+const G_MDSFT_TEXTFILT: u32 = 12;
+
+// Clamp and mirror bits of the s and t axes of a tile descriptor
+pub const G_TX_MIRROR: u8 = 0x1;
+pub const G_TX_CLAMP: u8 = 0x2;
+
+// LUS opcodes, replacing segmented addresses with references to other resources
+const G_SETTIMG_OTR_HASH: u8 = 0x20;
+const G_VTX_OTR_FILEPATH: u8 = 0x24;
+const G_SETTIMG_OTR_FILEPATH: u8 = 0x25;
+const G_DL_OTR_FILEPATH: u8 = 0x27;
+const G_DL_OTR_HASH: u8 = 0x31;
+const G_VTX_OTR_HASH: u8 = 0x32;
+const G_MARKER: u8 = 0x33;
+const G_BRANCH_Z_OTR: u8 = 0x35;
+const G_MTX_OTR: u8 = 0x36;
+
+/// Size of a `Vtx` in bytes.
+const VERTEX_SIZE: u32 = 16;
+
+/// How a command refers to another resource of the archive.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Reference {
+    Hash(u64),
+    Path(String),
+    /// Address in a segment set up at runtime, left by the packer.
+    Segmented(u32),
+}
+
+/// The display list commands that matter for geometry, its look and the
+/// resources a display list pulls in.
+pub enum Command {
+    /// Loads `count` vertices starting at `offset` of `source` into the
+    /// vertex buffer at `destination`.
+    Vertex {
+        source: Reference,
+        offset: u32,
+        count: u8,
+        destination: u8,
+    },
+    /// Triangles as indices into the vertex buffer.
+    Triangles(Vec<[u8; 3]>),
+    /// Calls another display list, or jumps to it for a branch.
+    Call { target: Reference, branch: bool },
+    /// Sets the texture image the next loads read from, with the texture type
+    /// its format and texel size stand for.
+    Texture {
+        image: Reference,
+        format: Option<TextureType>,
+    },
+    /// Scales texture coordinates by `s` and `t`, 0xFFFF standing for 1.
+    TextureScale { s: u16, t: u16 },
+    /// Clears then sets geometry mode bits.
+    GeometryMode { clear: u32, set: u32 },
+    /// Sets the primitive color as RGBA.
+    PrimitiveColor([u8; 4]),
+    /// Sets the environment color as RGBA.
+    EnvironmentColor([u8; 4]),
+    /// Sets the color combiner.
+    Combine(Combiner),
+    /// Places a tile descriptor at `tmem` in TMEM, with rows `line` apart,
+    /// both in 64-bit words. `cms` and `cmt` hold the `G_TX_MIRROR` and
+    /// `G_TX_CLAMP` bits of the s and t axes.
+    Tile {
+        tile: u8,
+        line: u16,
+        tmem: u16,
+        cms: u8,
+        cmt: u8,
+    },
+    /// Sets the texture filter of the other modes, point sampling or
+    /// filtering between texels.
+    TextureFilter { point: bool },
+    /// Copies texels of the texture image into TMEM where `tile` points.
+    Load { tile: u8, load: Load },
+    /// A whole texture resource, OTR header included, embedded `offset`
+    /// bytes into the display list where a command would be. Custom display
+    /// lists place their texels after the loads reading them this way.
+    InlineTexture { offset: u32, resource: Vec<u8> },
+}
+
+/// How a load command copies the texture image into TMEM.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Load {
+    /// `texels` texels in a row, `G_LOADBLOCK`.
+    Block { texels: u16 },
+    /// A rectangle of texels, `G_LOADTILE`.
+    Tile { width: u16, height: u16 },
+    /// `count` TLUT entries, `G_LOADTLUT`.
+    Tlut { count: u16 },
+}
+
+/// Inputs a color combiner mixes, as far as materials can reproduce them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CombinerInputs {
+    pub texel: bool,
+    pub shade: bool,
+    pub primitive: bool,
+    pub environment: bool,
+}
+
+impl CombinerInputs {
+    fn add(&mut self, input: u32) {
+        match input {
+            1 | 2 => self.texel = true,
+            3 => self.primitive = true,
+            4 => self.shade = true,
+            5 => self.environment = true,
+            _ => {}
+        }
+    }
+}
+
+/// Color combiner set by `G_SETCOMBINE`, computing `(a - b) * c + d` for the
+/// color and the alpha of both cycles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Combiner {
+    w0: u32,
+    w1: u32,
+}
+
+impl Combiner {
+    /// Inputs of the color of both cycles, as `a`, `b` and `c` fall out when
+    /// `c` is 0.
+    pub fn color_inputs(&self) -> CombinerInputs {
+        let (w0, w1) = (self.w0, self.w1);
+        let cycles = [
+            [
+                (w0 >> 20) & 0xF,
+                (w1 >> 28) & 0xF,
+                (w0 >> 15) & 0x1F,
+                (w1 >> 15) & 0x7,
+            ],
+            [
+                (w0 >> 5) & 0xF,
+                (w1 >> 24) & 0xF,
+                w0 & 0x1F,
+                (w1 >> 6) & 0x7,
+            ],
+        ];
+        let mut inputs = CombinerInputs::default();
+        for [a, b, c, d] in cycles {
+            // 16 and up are 0
+            if c < 16 {
+                inputs.add(a);
+                inputs.add(b);
+                // The alpha of the inputs from 8
+                inputs.add(match c {
+                    8 | 9 => 1,
+                    10 => 3,
+                    11 => 4,
+                    12 => 5,
+                    c => c,
+                });
+            }
+            inputs.add(d);
+        }
+        inputs
+    }
+
+    /// Inputs of the alpha of both cycles.
+    pub fn alpha_inputs(&self) -> CombinerInputs {
+        let (w0, w1) = (self.w0, self.w1);
+        let cycles = [
+            [
+                (w0 >> 12) & 0x7,
+                (w1 >> 12) & 0x7,
+                (w0 >> 9) & 0x7,
+                (w1 >> 9) & 0x7,
+            ],
+            [
+                (w1 >> 21) & 0x7,
+                (w1 >> 3) & 0x7,
+                (w1 >> 18) & 0x7,
+                w1 & 0x7,
+            ],
+        ];
+        let mut inputs = CombinerInputs::default();
+        for [a, b, c, d] in cycles {
+            // 7 is 0, 0 the level of detail fraction
+            if c != 7 {
+                inputs.add(a);
+                inputs.add(b);
+                inputs.add(c);
+            }
+            inputs.add(d);
+        }
+        inputs
+    }
+}
+
+/// A `Vtx` of a Vertex resource.
+pub struct Vertex {
+    pub position: [i16; 3],
+    /// Texture coordinates in 10.5 fixed point texels.
+    pub uv: [i16; 2],
+    /// Color, or normal and alpha when lighting is on.
+    pub color: [u8; 4],
+}
+
+/// Parses a DisplayList resource.
+pub fn parse_display_list(data: &[u8]) -> Result<Vec<Command>, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    reader.align(8);
+
+    let mut commands = Vec::new();
+    loop {
+        let start = reader.position;
+        let w0 = reader.u32()?;
+        let w1 = reader.u32()?;
+        match (w0 >> 24) as u8 {
+            G_ENDDL => break,
+            // The start of an OTR header reads as a G_NOOP
+            G_NOOP if w0 == 0 && w1 == ResourceType::Texture as u32 => {
+                if let Some(resource) = inline_texture(data, start) {
+                    reader.position = start + resource.len();
+                    reader.align(8);
+                    commands.push(Command::InlineTexture {
+                        offset: start as u32,
+                        resource: resource.to_vec(),
+                    });
+                }
+            }
+            G_TRI1 => commands.push(Command::Triangles(vec![triangle(w0)])),
+            G_TRI2 | G_QUAD => commands.push(Command::Triangles(vec![triangle(w0), triangle(w1)])),
+            opcode @ (G_VTX_OTR_HASH | G_VTX_OTR_FILEPATH) => {
+                let source = reference(&mut reader, opcode == G_VTX_OTR_HASH)?;
+                let count = (w0 >> 12) as u8;
+                commands.push(Command::Vertex {
+                    source,
+                    offset: w1,
+                    count,
+                    destination: ((w0 >> 1) as u8 & 0x7F).wrapping_sub(count),
+                });
+            }
+            opcode @ (G_DL_OTR_HASH | G_DL_OTR_FILEPATH) => {
+                let target = reference(&mut reader, opcode == G_DL_OTR_HASH)?;
+                commands.push(Command::Call {
+                    target,
+                    branch: (w0 >> 16) as u8 & 1 != 0,
+                });
+            }
+            G_VTX => {
+                let count = (w0 >> 12) as u8;
+                commands.push(Command::Vertex {
+                    source: Reference::Segmented(w1),
+                    offset: 0,
+                    count,
+                    destination: ((w0 >> 1) as u8 & 0x7F).wrapping_sub(count),
+                });
+            }
+            G_DL => commands.push(Command::Call {
+                target: Reference::Segmented(w1),
+                branch: (w0 >> 16) as u8 & 1 != 0,
+            }),
+            G_SETTIMG => commands.push(Command::Texture {
+                image: Reference::Segmented(w1),
+                format: image_format(w0),
+            }),
+            opcode @ (G_SETTIMG_OTR_HASH | G_SETTIMG_OTR_FILEPATH) => {
+                let image = reference(&mut reader, opcode == G_SETTIMG_OTR_HASH)?;
+                commands.push(Command::Texture {
+                    image,
+                    format: image_format(w0),
+                });
+            }
+            G_TEXTURE => commands.push(Command::TextureScale {
+                s: (w1 >> 16) as u16,
+                t: w1 as u16,
+            }),
+            G_GEOMETRYMODE => commands.push(Command::GeometryMode {
+                clear: !w0 & 0x00FFFFFF,
+                set: w1,
+            }),
+            G_SETPRIMCOLOR => commands.push(Command::PrimitiveColor(w1.to_be_bytes())),
+            G_SETENVCOLOR => commands.push(Command::EnvironmentColor(w1.to_be_bytes())),
+            G_SETCOMBINE => commands.push(Command::Combine(Combiner { w0, w1 })),
+            G_SETTILE => commands.push(Command::Tile {
+                tile: (w1 >> 24) as u8 & 0x07,
+                line: (w0 >> 9) as u16 & 0x1FF,
+                tmem: w0 as u16 & 0x1FF,
+                cms: (w1 >> 8) as u8 & 0x03,
+                cmt: (w1 >> 18) as u8 & 0x03,
+            }),
+            G_SETOTHERMODE_H => {
+                // F3DEX2 stores the bits kept, 32 - shift - length, and the
+                // length minus one
+                let length = (w0 & 0xFF) + 1;
+                let shift = 32u32.checked_sub((w0 >> 8 & 0xFF) + length);
+                if let Some(shift) = shift
+                    && shift <= G_MDSFT_TEXTFILT
+                    && shift + length >= G_MDSFT_TEXTFILT + 2
+                {
+                    commands.push(Command::TextureFilter {
+                        point: w1 >> G_MDSFT_TEXTFILT & 0x03 == 0,
+                    });
+                }
+            }
+            G_LOADBLOCK => commands.push(Command::Load {
+                tile: (w1 >> 24) as u8 & 0x07,
+                load: Load::Block {
+                    texels: ((w1 >> 12) as u16 & 0xFFF) + 1,
+                },
+            }),
+            G_LOADTILE => {
+                // Corners in 10.2 fixed point
+                let (uls, ult) = ((w0 >> 14) & 0x3FF, (w0 >> 2) & 0x3FF);
+                let (lrs, lrt) = ((w1 >> 14) & 0x3FF, (w1 >> 2) & 0x3FF);
+                commands.push(Command::Load {
+                    tile: (w1 >> 24) as u8 & 0x07,
+                    load: Load::Tile {
+                        width: lrs.saturating_sub(uls) as u16 + 1,
+                        height: lrt.saturating_sub(ult) as u16 + 1,
+                    },
+                });
+            }
+            G_LOADTLUT => commands.push(Command::Load {
+                tile: (w1 >> 24) as u8 & 0x07,
+                load: Load::Tlut {
+                    count: ((w1 >> 14) as u16 & 0x3FF) + 1,
+                },
+            }),
+            G_MARKER | G_BRANCH_Z_OTR | G_MTX_OTR => {
+                reader.bytes(8)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(commands)
+}
+
+/// The texture resource embedded at `offset` of the display list `data`,
+/// when an OTR header with a known texture type starts there.
+fn inline_texture(data: &[u8], offset: usize) -> Option<&[u8]> {
+    let mut reader = Reader::new(data, offset + 8);
+    let version = reader.u32().ok()?;
+    let magic = reader.bytes(8).ok()?;
+    if u64::from_le_bytes(magic.try_into().unwrap()) != OTR_HEADER_MAGIC {
+        return None;
+    }
+    reader.position = offset + OTR_HEADER_SIZE;
+    if !(1..=11).contains(&reader.u32().ok()?) {
+        return None;
+    }
+    // Width and height, then the stride in the resources that have one
+    reader.bytes(8).ok()?;
+    if version == TEXTURE_STRIDE_VERSION {
+        reader.u32().ok()?;
+    }
+    let size = reader.u32().ok()? as usize;
+    data.get(offset..reader.position.checked_add(size)?)
+}
+
+/// Texture type of the format and texel size fields of a `G_SETTIMG` word.
+fn image_format(word: u32) -> Option<TextureType> {
+    TextureType::from_fmt_siz((word >> 21) as u8 & 0x07, (word >> 19) as u8 & 0x03)
+}
+
+fn triangle(word: u32) -> [u8; 3] {
+    [
+        (word >> 16) as u8 / 2,
+        (word >> 8) as u8 / 2,
+        word as u8 / 2,
+    ]
+}
+
+/// Reads the reference following a LUS command: a hash in the next command
+/// slot, or a NUL terminated path padded to the next command.
+fn reference(reader: &mut Reader, hash: bool) -> Result<Reference, String> {
+    if hash {
+        let high = reader.u32()? as u64;
+        let low = reader.u32()? as u64;
+        Ok(Reference::Hash(high << 32 | low))
+    } else {
+        let path = reader.c_string()?;
+        reader.align(8);
+        Ok(Reference::Path(
+            crate::skeleton::resource_path(&path).to_owned(),
+        ))
+    }
+}
+
+/// The `Vtx` of a Vertex resource.
+pub fn parse_vertices(data: &[u8]) -> Result<Vec<Vertex>, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let count = reader.u32()?;
+    (0..count)
+        .map(|_| {
+            let position = [reader.i16()?, reader.i16()?, reader.i16()?];
+            // flag
+            reader.bytes(2)?;
+            let uv = [reader.i16()?, reader.i16()?];
+            let color = reader.bytes(4)?;
+            Ok(Vertex {
+                position,
+                uv,
+                color: [color[0], color[1], color[2], color[3]],
+            })
+        })
+        .collect()
+}
+
+/// Index of the vertex `offset` bytes into a Vertex resource.
+pub fn vertex_index(offset: u32) -> usize {
+    (offset / VERTEX_SIZE) as usize
+}
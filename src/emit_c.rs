@@ -0,0 +1,193 @@
+use std::{fmt::Write, str::FromStr};
+
+use crate::{
+    Converter, DecodedTexture, TextureFormat, TextureType, encode, log, torch::torch_format,
+};
+
+/// Texel data `--emit-c` writes as C arrays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitC {
+    /// The payload as stored in the archive, padding and byte order included.
+    Raw,
+    /// The decoded texels encoded back to the texture's format, so byte
+    /// swapped, interleaved and strided payloads come out as the decomp
+    /// stores them. CI textures whose TLUT repeats a color use the first
+    /// index of that color.
+    Encoded,
+}
+
+impl FromStr for EmitC {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "raw" => Ok(EmitC::Raw),
+            "encoded" => Ok(EmitC::Encoded),
+            _ => Err(format!(
+                "Unknown C array data '{}', expected raw or encoded",
+                value
+            )),
+        }
+    }
+}
+
+/// Format part of the decomp's file names, `ci4` in `gFooTex.ci4.inc.c`.
+fn format_suffix(type_id: &TextureType) -> Option<String> {
+    match type_id {
+        TextureType::TLUT => Some("tlut.rgba16".to_owned()),
+        _ => torch_format(type_id).map(str::to_lowercase),
+    }
+}
+
+/// C identifier for the archive entry `name`, its file name with the
+/// characters C doesn't allow replaced by underscores.
+pub fn symbol(name: &str) -> String {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    let mut symbol = file_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if !symbol.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        symbol.insert(0, '_');
+    }
+    symbol
+}
+
+/// `data` as the big-endian `u64` words of a decomp `.inc.c` file, four per
+/// line, the last one padded with zeros.
+pub fn words(data: &[u8]) -> String {
+    let mut text = String::new();
+    for line in data.chunks(32) {
+        let words = line
+            .chunks(8)
+            .map(|word| {
+                let mut bytes = [0; 8];
+                bytes[..word.len()].copy_from_slice(word);
+                format!("0x{:016X},", u64::from_be_bytes(bytes))
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(text, "{}", words.join(" "));
+    }
+    text
+}
+
+/// Header declaring the array `symbol` of a `width` by `height` texture
+/// taking `size` bytes.
+pub fn header(symbol: &str, width: u32, height: u32, size: usize) -> String {
+    let guard = symbol.to_uppercase() + "_H";
+    format!(
+        concat!(
+            "#ifndef {0}\n",
+            "#define {0}\n",
+            "\n",
+            "#define {1}_WIDTH {2}\n",
+            "#define {1}_HEIGHT {3}\n",
+            "extern u64 {1}[{4}];\n",
+            "\n",
+            "#endif\n",
+        ),
+        guard,
+        symbol,
+        width,
+        height,
+        size.div_ceil(8)
+    )
+}
+
+/// Writes `<base>.<format>.inc.c` and `<base>.h` for the texels `data` of the
+/// archive entry `name`.
+fn write(
+    converter: &Converter,
+    base: &str,
+    name: &str,
+    type_id: &TextureType,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) {
+    let Some(suffix) = format_suffix(type_id) else {
+        return;
+    };
+    converter.write(&format!("{}.{}.inc.c", base, suffix), words(data));
+    converter.write(
+        &format!("{}.h", base),
+        header(&symbol(name), width, height, data.len()),
+    );
+}
+
+/// Writes the C files of the decoded texture entry `name` with `--emit-c`.
+/// `data` is the whole resource.
+pub fn write_texture(
+    converter: &Converter,
+    base: &str,
+    name: &str,
+    data: &[u8],
+    texture: &DecodedTexture,
+) {
+    let Some(emit) = converter.options.emit_c else {
+        return;
+    };
+    let texels = match emit {
+        EmitC::Raw => match TextureFormat::parse(data) {
+            Ok(texture_format) => texture_format.data,
+            Err(err) => {
+                log::error(format!("Failed to read the texels of {}: {}", name, err));
+                return;
+            }
+        },
+        EmitC::Encoded => {
+            let tlut = converter.tluts.for_texture(name, &texture.type_id);
+            let texture_format = TextureFormat::new(
+                texture.type_id.clone(),
+                texture.width,
+                texture.height,
+                0,
+                Vec::new(),
+            );
+            match encode::encode_texture(
+                &texture_format,
+                &texture.data,
+                tlut.as_deref(),
+                converter.options.expand,
+            ) {
+                Ok(texels) => texels,
+                Err(err) => {
+                    log::error(format!("Failed to encode {} as a C array: {}", name, err));
+                    return;
+                }
+            }
+        }
+    };
+    write(
+        converter,
+        base,
+        name,
+        &texture.type_id,
+        texture.width,
+        texture.height,
+        &texels,
+    );
+}
+
+/// Writes the C files of the TLUT entry `name` with `--emit-c`. TLUTs are
+/// written as stored whatever data is asked for, they are never decoded.
+pub fn write_tlut(converter: &Converter, base: &str, name: &str, data: &[u8]) {
+    if converter.options.emit_c.is_none() {
+        return;
+    }
+    let Ok(texture_format) = TextureFormat::parse(data) else {
+        return;
+    };
+    if texture_format.type_id != TextureType::TLUT {
+        return;
+    }
+    write(
+        converter,
+        base,
+        name,
+        &texture_format.type_id,
+        texture_format.width,
+        texture_format.height,
+        &texture_format.data,
+    );
+}
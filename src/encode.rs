@@ -0,0 +1,287 @@
+use std::{collections::HashMap, fs};
+
+use crate::{
+    OTR_HEADER_MAGIC, OTR_HEADER_SIZE, ResourceType, TextureFormat, TextureType, decode_texels,
+    pixels::{self, Expansion},
+    torch::torch_format,
+};
+
+/// Converts pixels laid out as `decode_texels` produces them back to the
+/// texel data of `texture_format.type_id`.
+///
+/// The reverse mapping is built by running the decoder over every possible
+/// texel, so encoding is exact: it fails on the first pixel the format can't
+/// represent instead of picking the nearest color.
+pub fn encode_texture(
+    texture_format: &TextureFormat,
+    pixels: &[u8],
+    tlut: Option<&TextureFormat>,
+    expansion: Expansion,
+) -> Result<Vec<u8>, String> {
+    let type_id = &texture_format.type_id;
+    let pixel_count = texture_format.width as usize * texture_format.height as usize;
+    let channels = type_id
+        .to_image_type()
+        .ok_or_else(|| format!("Encoding {:?} textures is not supported", type_id))?
+        .bits_per_pixel()
+        .div_ceil(8) as usize;
+    if pixels.len() != pixel_count * channels {
+        return Err(format!(
+            "Expected {} bytes of pixel data for a {}x{} {:?} texture, got {}",
+            pixel_count * channels,
+            texture_format.width,
+            texture_format.height,
+            type_id,
+            pixels.len()
+        ));
+    }
+
+    if *type_id == TextureType::RGBA32bpp {
+        return Ok(pixels.to_vec());
+    }
+
+    let codes = texel_codes(type_id, tlut, expansion)?;
+    let mut texels = Vec::with_capacity(pixel_count);
+    for (i, pixel) in pixels.chunks(channels).enumerate() {
+        let Some(code) = codes.get(pixel) else {
+            return Err(format!(
+                "Pixel ({}, {}) {:?} can't be represented as {:?}",
+                i as u32 % texture_format.width,
+                i as u32 / texture_format.width,
+                pixel,
+                type_id
+            ));
+        };
+        texels.push(*code);
+    }
+    pack_texels(type_id, texture_format.width, &texels)
+}
+
+/// Packs texel codes, one per pixel in pixel order, into the texel data of a
+/// `width` pixels wide texture of type `type_id`.
+pub fn pack_texels(type_id: &TextureType, width: u32, texels: &[u32]) -> Result<Vec<u8>, String> {
+    let height = texels.len().div_ceil(width.max(1) as usize) as u32;
+    let mut data = Vec::with_capacity(type_id.data_size(width, height)?);
+    match type_id.bits_per_pixel() {
+        // Rows start on a byte boundary, see `pixels::unpack_1bpp`
+        Some(1) => {
+            for row in texels.chunks(width as usize) {
+                for byte in row.chunks(8) {
+                    data.push(
+                        byte.iter()
+                            .enumerate()
+                            .fold(0, |packed, (i, bit)| packed | (*bit as u8) << (7 - i)),
+                    );
+                }
+            }
+        }
+        // Rows start on a byte boundary, see `pixels::unpack_4bpp`
+        Some(4) => {
+            for row in texels.chunks(width as usize) {
+                for pair in row.chunks(2) {
+                    data.push((pair[0] << 4) as u8 | *pair.get(1).unwrap_or(&0) as u8);
+                }
+            }
+        }
+        Some(8) => data.extend(texels.iter().map(|code| *code as u8)),
+        Some(16) => {
+            for code in texels {
+                data.extend_from_slice(&(*code as u16).to_be_bytes());
+            }
+        }
+        _ => return Err(format!("Encoding {:?} textures is not supported", type_id)),
+    }
+    Ok(data)
+}
+
+/// Maps every pixel value the decoder can produce for `type_id` to the texel
+/// producing it. When several texels decode to the same pixel the lowest wins.
+pub fn texel_codes(
+    type_id: &TextureType,
+    tlut: Option<&TextureFormat>,
+    expansion: Expansion,
+) -> Result<HashMap<Vec<u8>, u32>, String> {
+    let bits = type_id
+        .bits_per_pixel()
+        .ok_or_else(|| format!("Encoding {:?} textures is not supported", type_id))?;
+    let count = match (type_id, tlut) {
+        (TextureType::Palette4bpp | TextureType::Palette8bpp, Some(tlut)) => {
+            (tlut.data.len() as u32 / 2).min(1 << bits)
+        }
+        (TextureType::Palette4bpp | TextureType::Palette8bpp, None) => {
+            return Err(format!(
+                "A TLUT is required to encode {:?} textures",
+                type_id
+            ));
+        }
+        (_, _) if matches!(bits, 1 | 4 | 8 | 16) => 1 << bits,
+        _ => return Err(format!("Encoding {:?} textures is not supported", type_id)),
+    };
+
+    let mut codes = HashMap::new();
+    for code in 0..count {
+        let data = match bits {
+            // A single texel is the high bit or nibble of the first byte
+            1 => vec![(code << 7) as u8],
+            4 => vec![(code << 4) as u8],
+            8 => vec![code as u8],
+            _ => (code as u16).to_be_bytes().to_vec(),
+        };
+        let probe = TextureFormat::new(type_id.clone(), 1, 1, data.len() as u32, data);
+        if let Some(pixel) = decode_texels(&probe, &probe.data, tlut, expansion) {
+            codes.entry(pixel).or_insert(code);
+        }
+    }
+    Ok(codes)
+}
+
+/// Texture type of the Torch format name `name`, such as `rgba16` or `ci8`,
+/// in any case. TLUTs are written along with the CI textures using them.
+pub fn parse_format(name: &str) -> Result<TextureType, String> {
+    (1..=10)
+        .filter_map(TextureType::from_u32)
+        .find(|type_id| torch_format(type_id).is_some_and(|format| format.eq_ignore_ascii_case(name)))
+        .ok_or_else(|| {
+            format!(
+                "Unknown texture format '{}', expected rgba32, rgba16, ci4, ci8, i4, i8, ia1, ia4, ia8 or ia16",
+                name
+            )
+        })
+}
+
+/// The value closest to `value` of those a `bits` wide channel widens to.
+fn snap(value: u8, bits: u32, expansion: Expansion) -> u8 {
+    if bits == 8 {
+        return value;
+    }
+    (0..1u8 << bits)
+        .map(|level| pixels::expand(level, bits, expansion))
+        .min_by_key(|expanded| expanded.abs_diff(value))
+        .unwrap()
+}
+
+/// Rounds `pixels`, laid out as `decode_texels` produces them for
+/// `type_id`, to the nearest values the format holds so `encode_texture`
+/// finds a texel for each. Intensity formats take alpha from intensity,
+/// and the transparent pixels of CI textures all become transparent black
+/// to share one palette entry.
+pub fn quantize(type_id: &TextureType, pixels: &mut [u8], expansion: Expansion) {
+    let (channels, bits): (usize, &[u32]) = match type_id {
+        TextureType::RGBA16bpp | TextureType::Palette4bpp | TextureType::Palette8bpp => {
+            (4, &[5, 5, 5, 1])
+        }
+        TextureType::Grayscale4bpp => (2, &[4, 4]),
+        TextureType::Grayscale8bpp => (2, &[8, 8]),
+        TextureType::GrayscaleAlpha1bpp => (2, &[1, 1]),
+        TextureType::GrayscaleAlpha4bpp => (2, &[3, 1]),
+        TextureType::GrayscaleAlpha8bpp => (2, &[4, 4]),
+        _ => return,
+    };
+    let intensity = matches!(
+        type_id,
+        TextureType::Grayscale4bpp | TextureType::Grayscale8bpp | TextureType::GrayscaleAlpha1bpp
+    );
+    let palette = matches!(type_id, TextureType::Palette4bpp | TextureType::Palette8bpp);
+    for pixel in pixels.chunks_exact_mut(channels) {
+        if intensity {
+            pixel[1] = pixel[0];
+        }
+        for (value, bits) in pixel.iter_mut().zip(bits) {
+            *value = snap(*value, *bits, expansion);
+        }
+        if palette && pixel[3] == 0 {
+            pixel.fill(0);
+        }
+    }
+}
+
+/// A texture resource of `type_id` holding `texels`, with the header the
+/// known packers write.
+pub fn texture_resource(type_id: &TextureType, width: u32, height: u32, texels: &[u8]) -> Vec<u8> {
+    let mut resource = vec![0; OTR_HEADER_SIZE];
+    resource[4..8].copy_from_slice(&(ResourceType::Texture as u32).to_le_bytes());
+    resource[12..20].copy_from_slice(&OTR_HEADER_MAGIC.to_le_bytes());
+    for field in [type_id.clone() as u32, width, height, texels.len() as u32] {
+        resource.extend_from_slice(&field.to_le_bytes());
+    }
+    resource.extend_from_slice(texels);
+    resource
+}
+
+/// Encodes `image` as a new texture resource of `type_id`, rounding its
+/// colors to the nearest the format holds. CI textures get a TLUT of the
+/// colors they use, 16 a row, returned as a second resource.
+pub fn encode_image(
+    image: &image::DynamicImage,
+    type_id: &TextureType,
+    expansion: Expansion,
+) -> Result<(Vec<u8>, Option<Vec<u8>>), String> {
+    let (width, height) = (image.width(), image.height());
+    let mut pixels = match type_id.to_image_type() {
+        Some(image::ExtendedColorType::La8) => image.to_luma_alpha8().into_raw(),
+        Some(_) => image.to_rgba8().into_raw(),
+        None => return Err(format!("Encoding {:?} textures is not supported", type_id)),
+    };
+    quantize(type_id, &mut pixels, expansion);
+
+    let tlut = match type_id {
+        TextureType::Palette4bpp | TextureType::Palette8bpp => {
+            let capacity = match type_id {
+                TextureType::Palette4bpp => 16,
+                _ => 256,
+            };
+            let mut colors = Vec::new();
+            for pixel in pixels.chunks_exact(4) {
+                if !colors.contains(&pixel) {
+                    colors.push(pixel);
+                }
+            }
+            if colors.len() > capacity {
+                return Err(format!(
+                    "The image has {} colors once rounded to RGBA5551, {:?} textures hold {}",
+                    colors.len(),
+                    type_id,
+                    capacity
+                ));
+            }
+            let codes = texel_codes(&TextureType::RGBA16bpp, None, expansion)?;
+            let mut data = vec![0; capacity * 2];
+            for (entry, color) in data.chunks_exact_mut(2).zip(&colors) {
+                entry.copy_from_slice(&(codes[*color] as u16).to_be_bytes());
+            }
+            let rows = capacity as u32 / 16;
+            Some(TextureFormat::new(
+                TextureType::TLUT,
+                16,
+                rows,
+                data.len() as u32,
+                data,
+            ))
+        }
+        _ => None,
+    };
+
+    let texture_format = TextureFormat::new(type_id.clone(), width, height, 0, Vec::new());
+    let texels = encode_texture(&texture_format, &pixels, tlut.as_ref(), expansion)?;
+    Ok((
+        texture_resource(type_id, width, height, &texels),
+        tlut.map(|tlut| texture_resource(&tlut.type_id, tlut.width, tlut.height, &tlut.data)),
+    ))
+}
+
+/// Encodes the image `image` as a texture resource of `type_id` written to
+/// `output`, for textures authored without an archive to replace. The TLUT
+/// of a CI texture is written next to it, with `_tlut` appended.
+pub fn run(image: &str, type_id: &TextureType, output: &str, expansion: Expansion) {
+    let decoded =
+        image::open(image).unwrap_or_else(|err| panic!("Failed to read {}: {}", image, err));
+    let (resource, tlut) =
+        encode_image(&decoded, type_id, expansion).unwrap_or_else(|err| panic!("{}", err));
+    fs::write(output, resource).unwrap_or_else(|err| panic!("Failed to write {}: {}", output, err));
+    println!("Encoded {} as {:?} to {}", image, type_id, output);
+    if let Some(tlut) = tlut {
+        let path = format!("{}_tlut", output);
+        fs::write(&path, tlut).unwrap_or_else(|err| panic!("Failed to write {}: {}", path, err));
+        println!("Wrote its TLUT to {}", path);
+    }
+}
@@ -0,0 +1,260 @@
+use std::str::FromStr;
+
+use crate::display_list::{Command, G_TX_CLAMP, G_TX_MIRROR, Load, Reference};
+
+/// Game engine to generate import settings for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    Godot,
+    Unity,
+    Unreal,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "godot" => Ok(Engine::Godot),
+            "unity" => Ok(Engine::Unity),
+            "unreal" => Ok(Engine::Unreal),
+            _ => Err(format!(
+                "Unknown engine '{}', expected godot, unity or unreal",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Point,
+    Bilinear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    /// Wrap mode of the clamp and mirror bits of a tile descriptor axis. A
+    /// texture both mirrored and clamped is mirrored up to its edges.
+    fn from_bits(bits: u8) -> Self {
+        if bits & G_TX_MIRROR != 0 {
+            Wrap::Mirror
+        } else if bits & G_TX_CLAMP != 0 {
+            Wrap::Clamp
+        } else {
+            Wrap::Repeat
+        }
+    }
+}
+
+/// How a display list samples a texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub filter: Filter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+}
+
+impl Default for Sampling {
+    /// The RDP defaults of bilinear filtering with wrapping on both axes.
+    fn default() -> Self {
+        Sampling {
+            filter: Filter::Bilinear,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+        }
+    }
+}
+
+/// The texture images the display list `commands` loads, with how they are
+/// sampled: the filter set when they are drawn and the wrap modes of the
+/// render tile, or of the tile they are loaded through when the render tile
+/// isn't set after the load. Display lists it calls aren't followed.
+pub fn samplings(commands: &[Command]) -> Vec<(&Reference, Sampling)> {
+    let mut tiles = [(Wrap::Repeat, Wrap::Repeat); 8];
+    let mut filter = Filter::Bilinear;
+    let mut image = None;
+    // Textures loaded since the last draw, waiting for its filter
+    let mut loaded: Vec<(&Reference, (Wrap, Wrap))> = Vec::new();
+    let mut samplings = Vec::new();
+    let draw = |loaded: &mut Vec<_>, samplings: &mut Vec<_>, filter| {
+        samplings.extend(loaded.drain(..).map(|(image, (wrap_s, wrap_t))| {
+            (
+                image,
+                Sampling {
+                    filter,
+                    wrap_s,
+                    wrap_t,
+                },
+            )
+        }));
+    };
+    for command in commands {
+        match command {
+            Command::Texture {
+                image: reference, ..
+            } => image = Some(reference),
+            Command::Tile { tile, cms, cmt, .. } => {
+                let wrap = (Wrap::from_bits(*cms), Wrap::from_bits(*cmt));
+                tiles[*tile as usize] = wrap;
+                // G_TX_RENDERTILE, set up after the load for drawing
+                if *tile == 0
+                    && let Some(last) = loaded.last_mut()
+                {
+                    last.1 = wrap;
+                }
+            }
+            Command::Load {
+                load: Load::Tlut { .. },
+                ..
+            } => {}
+            Command::Load { tile, .. } => {
+                if let Some(image) = image {
+                    loaded.push((image, tiles[*tile as usize]));
+                }
+            }
+            Command::TextureFilter { point } => {
+                filter = if *point {
+                    Filter::Point
+                } else {
+                    Filter::Bilinear
+                };
+            }
+            Command::Triangles(_) => draw(&mut loaded, &mut samplings, filter),
+            _ => {}
+        }
+    }
+    draw(&mut loaded, &mut samplings, filter);
+    samplings
+}
+
+/// Import settings of a texture.
+pub struct TextureSettings {
+    pub filter: Filter,
+    pub srgb: bool,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+}
+
+impl TextureSettings {
+    /// Settings of a texture sampled as `sampling`. Intensity formats are
+    /// mostly used as masks, so they are kept linear.
+    pub fn new(grayscale: bool, sampling: Sampling) -> Self {
+        TextureSettings {
+            filter: sampling.filter,
+            srgb: !grayscale,
+            wrap_s: sampling.wrap_s,
+            wrap_t: sampling.wrap_t,
+        }
+    }
+}
+
+/// Path and contents of the import descriptor for the image `output`, which
+/// goes next to it. Both paths are relative to the output folder, taken as
+/// the root of the engine project.
+pub fn sidecar(engine: Engine, output: &str, settings: &TextureSettings) -> (String, String) {
+    match engine {
+        Engine::Godot => (output.to_owned() + ".import", godot(output, settings)),
+        Engine::Unity => (output.to_owned() + ".meta", unity(output, settings)),
+        Engine::Unreal => (output.to_owned() + ".json", unreal(settings)),
+    }
+}
+
+fn godot(output: &str, settings: &TextureSettings) -> String {
+    let repeat = match (settings.wrap_s, settings.wrap_t) {
+        (Wrap::Clamp, Wrap::Clamp) => 0,
+        (Wrap::Mirror, _) | (_, Wrap::Mirror) => 2,
+        _ => 1,
+    };
+    format!(
+        "[remap]\n\
+         \n\
+         importer=\"texture\"\n\
+         type=\"StreamTexture\"\n\
+         \n\
+         [deps]\n\
+         \n\
+         source_file=\"res://{}\"\n\
+         \n\
+         [params]\n\
+         \n\
+         flags/repeat={}\n\
+         flags/filter={}\n\
+         flags/mipmaps=false\n\
+         flags/srgb={}\n",
+        output,
+        repeat,
+        settings.filter == Filter::Bilinear,
+        if settings.srgb { 1 } else { 0 },
+    )
+}
+
+fn unity(output: &str, settings: &TextureSettings) -> String {
+    let wrap = |wrap: Wrap| match wrap {
+        Wrap::Repeat => 0,
+        Wrap::Clamp => 1,
+        Wrap::Mirror => 2,
+    };
+    format!(
+        "fileFormatVersion: 2\n\
+         guid: {}\n\
+         TextureImporter:\n\
+         \x20 serializedVersion: 11\n\
+         \x20 mipmaps:\n\
+         \x20   sRGBTexture: {}\n\
+         \x20   enableMipMap: 0\n\
+         \x20 textureSettings:\n\
+         \x20   serializedVersion: 2\n\
+         \x20   filterMode: {}\n\
+         \x20   wrapU: {}\n\
+         \x20   wrapV: {}\n\
+         \x20 textureType: 0\n",
+        unity_guid(output),
+        if settings.srgb { 1 } else { 0 },
+        match settings.filter {
+            Filter::Point => 0,
+            Filter::Bilinear => 1,
+        },
+        wrap(settings.wrap_s),
+        wrap(settings.wrap_t),
+    )
+}
+
+fn unreal(settings: &TextureSettings) -> String {
+    let address = |wrap: Wrap| match wrap {
+        Wrap::Repeat => "TA_Wrap",
+        Wrap::Clamp => "TA_Clamp",
+        Wrap::Mirror => "TA_Mirror",
+    };
+    format!(
+        "{{\n  \"SRGB\": {},\n  \"Filter\": \"{}\",\n  \"AddressX\": \"{}\",\n  \"AddressY\": \"{}\",\n  \"MipGenSettings\": \"TMGS_NoMipmaps\"\n}}\n",
+        settings.srgb,
+        match settings.filter {
+            Filter::Point => "TF_Nearest",
+            Filter::Bilinear => "TF_Bilinear",
+        },
+        address(settings.wrap_s),
+        address(settings.wrap_t),
+    )
+}
+
+/// Unity identifies assets by GUID; derive it from the output path so
+/// re-running the conversion keeps references in the project valid.
+fn unity_guid(output: &str) -> String {
+    let fnv1a = |seed: u64| {
+        output.bytes().fold(seed, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    };
+    format!(
+        "{:016x}{:016x}",
+        fnv1a(0xcbf29ce484222325),
+        fnv1a(0x84222325cbf29ce4)
+    )
+}
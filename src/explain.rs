@@ -0,0 +1,263 @@
+use std::{collections::HashSet, fs::File};
+
+use crate::{
+    OTR_HEADER_SIZE, OTRHeader, ResourceType, TextureFormat, TextureType, asset_definitions,
+    config::Config, crc64, decode_entry, decoder::Registry, load_pitches, load_tlut_config, names,
+    options::Options, palette, read_entry, symbols::SymbolResolver, tiles, tlut::Tluts,
+};
+
+/// Prints why the archive entry `entry` is or isn't converted, going through
+/// the same selection, config lookups and validation as a conversion without
+/// writing anything.
+pub fn run(options: &Options, entry: &str) {
+    let mut zip =
+        zip::ZipArchive::new(File::open(&options.zip_file).expect("Failed to open zip file"))
+            .expect("Failed to read zip file");
+    let file_names = zip
+        .file_names()
+        .map(|name| name.to_owned())
+        .collect::<Vec<String>>();
+    let names = file_names
+        .iter()
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+
+    println!("Entry: {}", entry);
+    if !names.contains(entry) {
+        println!("  Not in {}", options.zip_file);
+        let similar = file_names
+            .iter()
+            .filter(|name| name.to_lowercase() == entry.to_lowercase() || name.ends_with(entry))
+            .collect::<Vec<_>>();
+        for name in similar {
+            println!("  Did you mean {}?", name);
+        }
+        println!("Decision: not converted, the entry doesn't exist");
+        return;
+    }
+
+    let config = Config::load(&options.config);
+    println!("Config: {}", options.config);
+    match config
+        .path_map
+        .iter()
+        .filter(|(from, _)| entry.starts_with(from.as_str()))
+        .max_by_key(|(from, _)| from.len())
+    {
+        Some((from, to)) => println!("  path_map: '{}' -> '{}'", from, to),
+        None => println!("  path_map: no prefix matches"),
+    }
+    println!("  Output path: {}", names::nfc(&config.map_path(entry)));
+
+    println!("Selection:");
+    if options.symbol_names.is_some() || options.ids.is_some() {
+        let resolver =
+            SymbolResolver::new(&config, options.yaml_dialect, options.symbols.as_deref());
+        let by_symbol = options.symbol_names.iter().flatten().any(|symbol| {
+            let resolved = resolver.resolve(symbol, &names);
+            match &resolved {
+                Ok(path) => println!("  --symbol {} resolves to {}", symbol, path),
+                Err(err) => println!("  --symbol {}: {}", symbol, err),
+            }
+            resolved.is_ok_and(|path| path == entry)
+        });
+        let id = crc64::crc64(entry);
+        let by_id = options.ids.iter().flatten().any(|selected| *selected == id);
+        if options.ids.is_some() {
+            println!(
+                "  Resource id {:016X} is {}selected by --id",
+                id,
+                if by_id { "" } else { "not " }
+            );
+        }
+        if !by_symbol && !by_id {
+            println!("Decision: not converted, no --symbol or --id selects the entry");
+            return;
+        }
+    } else {
+        println!("  No --symbol or --id filter, every entry is selected");
+    }
+    if let Some((path, _)) = config.tiled.iter().find(|(path, _)| {
+        tiles::tile_names(path, &names)
+            .iter()
+            .any(|tile| tile == entry)
+    }) {
+        println!("  Tile of {} in the tiled config", path);
+        println!("Decision: converted as part of the stitched image {}", path);
+        return;
+    }
+
+    let data = read_entry(&mut zip, entry, &options.payload).unwrap_or_default();
+    let header = match OTRHeader::parse(&data) {
+        Ok(header) if data.len() >= OTR_HEADER_SIZE => header,
+        _ => {
+            println!(
+                "Decision: skipped, {} bytes is too short for an OTR resource",
+                data.len()
+            );
+            return;
+        }
+    };
+    println!("Resource: {:?} version {}", header.type_id, header.version);
+    if let Some(mismatch) = options.header_filter.mismatch(&header) {
+        println!("Decision: not converted, {}", mismatch);
+        return;
+    }
+    let decoder = match Registry::new(options.types.as_deref()).get(&config, &header, entry) {
+        Ok(Some(decoder)) => decoder,
+        Ok(None) => {
+            match &options.types {
+                Some(types) => println!(
+                    "Decision: skipped, --types {} doesn't include a decoder for it",
+                    types.join(",")
+                ),
+                None => println!("Decision: skipped, no decoder reads {:?}", header.type_id),
+            }
+            return;
+        }
+        Err(err) => {
+            println!("Decision: skipped, {}", err);
+            return;
+        }
+    };
+    println!("  Decoder: {}", decoder.name());
+    if header.type_id != ResourceType::Texture {
+        println!("Decision: converted by the {} decoder", decoder.name());
+        return;
+    }
+
+    let texture_format = match TextureFormat::parse(&data) {
+        Ok(texture_format) => texture_format,
+        Err(err) => {
+            println!("Decision: skipped, {}", err);
+            return;
+        }
+    };
+    println!(
+        "Texture: {:?} {}x{}, {} bytes of texels",
+        texture_format.type_id,
+        texture_format.width,
+        texture_format.height,
+        texture_format.data.len()
+    );
+    if matches!(
+        texture_format.type_id,
+        TextureType::Error | TextureType::TLUT
+    ) {
+        println!(
+            "Decision: skipped, {:?} textures aren't converted",
+            texture_format.type_id
+        );
+        return;
+    }
+
+    let definitions = asset_definitions(&config, options.yaml_dialect);
+    println!(
+        "  {} asset definitions read from {}",
+        definitions.len(),
+        config.path
+    );
+    let file_name = entry.rsplit('/').next().unwrap();
+    let pitches = load_pitches(&definitions);
+    match pitches.get(file_name) {
+        Some(pitch) => println!("  pitch: {} bytes per row", pitch),
+        None => println!("  pitch: none, rows are packed"),
+    }
+    let expected_size = match pitches.get(file_name) {
+        Some(pitch) => Ok(*pitch as usize * texture_format.height as usize),
+        None => texture_format.data_size(),
+    };
+    match expected_size {
+        Ok(size) => println!(
+            "  Size: {} bytes expected, {} found",
+            size,
+            texture_format.data.len()
+        ),
+        Err(err) => println!("  Size: {}", err),
+    }
+
+    let tluts = Tluts::open(options, &file_names, load_tlut_config(&definitions));
+    if matches!(
+        texture_format.type_id,
+        TextureType::Palette4bpp | TextureType::Palette8bpp
+    ) {
+        match tluts.texture_tlut(file_name) {
+            Some(texture_tlut) => {
+                println!(
+                    "  tlut: {}, palette_index {}",
+                    texture_tlut.symbol, texture_tlut.palette_index
+                );
+                match tluts.path(&texture_tlut.symbol, entry) {
+                    Some(path) => match tluts.get(path) {
+                        Some(tlut) => {
+                            println!(
+                                "  TLUT entry {}: {} colors",
+                                path,
+                                palette::entry_count(&tlut)
+                            )
+                        }
+                        None => println!("  TLUT entry {} is not a texture", path),
+                    },
+                    None => println!(
+                        "  No archive entry is named after or mentions {}",
+                        texture_tlut.symbol
+                    ),
+                }
+            }
+            None => println!("  tlut: no definition named {} gives one", file_name),
+        }
+    }
+
+    let i4_as_ia4 = texture_format.type_id == TextureType::Grayscale4bpp
+        && (options.treat_i4_as_ia4 || config.treats_i4_as_ia4(entry));
+    if i4_as_ia4 {
+        println!("  Decoded as IA4, the entry is listed to treat I4 as IA4");
+    }
+
+    match decode_entry(
+        entry,
+        &data,
+        &options.decode_options(),
+        &tluts,
+        &pitches,
+        i4_as_ia4,
+    ) {
+        Ok(Some(texture)) => {
+            if texture.ia4_suspect {
+                println!("  The texels look like IA4, see --treat-i4-as-ia4");
+            }
+            if let Some(overflow) = &texture.tmem_overflow {
+                println!("  Can't fit in TMEM: {}", overflow);
+            }
+            if let Some(mismatch) = &texture.palette_mismatch {
+                println!("  Suspicious TLUT: {}", mismatch);
+            }
+            if let Some(pack_hashes) = &texture.pack_hashes {
+                println!("  Rice hash: {}", pack_hashes.rice.key());
+                println!(
+                    "  GlideN64 hash: {}, checksum {:016X}",
+                    pack_hashes.gliden64.key(),
+                    pack_hashes.gliden64.checksum64()
+                );
+            }
+            if let Some(overflow) = &texture.palette_overflow {
+                println!(
+                    "  Palette index {} is past the {} TLUT entries",
+                    overflow.max_index, overflow.entries
+                );
+                if options.strict {
+                    println!("Decision: skipped, --strict rejects palette overflows");
+                    return;
+                }
+            }
+            println!(
+                "Decision: converted to a {}x{} {}",
+                texture.width,
+                texture.height,
+                options.image_format.extension()
+            );
+        }
+        Ok(None) => println!("Decision: skipped, not a texture to convert"),
+        Err(err) => println!("Decision: skipped, {}", err),
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fs::File,
+    os::unix::fs::MetadataExt,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use fuser::{
+    FUSE_ROOT_ID, FileAttr, FileType, MountOption, ReplyAttr, ReplyData, ReplyDirectory,
+    ReplyEntry, ReplyOpen, Request, consts::FOPEN_KEEP_CACHE,
+};
+
+use crate::{
+    TextureQuery, config::Config, log, open_query, options::Options, texture, texture::ImageFormat,
+    tlut::Tluts,
+};
+
+/// How long the kernel caches names and attributes for, the textures never
+/// change.
+const TIMEOUT: Duration = Duration::from_secs(3600);
+
+// Same values on Linux and macOS
+const ENOENT: i32 = 2;
+const EIO: i32 = 5;
+const ENOTDIR: i32 = 20;
+
+/// Name the mount shows up as in the mount table.
+const FS_NAME: &str = "convert-texture-o2r";
+
+/// A file or directory of the mounted tree, its inode being its index plus
+/// one.
+struct Node {
+    parent: u64,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Directory(BTreeMap<String, u64>),
+    /// The PNG of the texture entry.
+    Texture(String),
+}
+
+/// The textures of an archive as PNG files, decoded and encoded when first
+/// looked at.
+struct Filesystem {
+    query: TextureQuery,
+    nodes: Vec<Node>,
+    uid: u32,
+    gid: u32,
+    /// Sizes of the PNGs encoded so far, `None` when the texture failed to
+    /// decode.
+    sizes: HashMap<u64, Option<u64>>,
+    /// Encoded PNGs, dropped together once they take more than the budget.
+    encoded: HashMap<u64, Arc<Vec<u8>>>,
+    encoded_size: u64,
+    budget: u64,
+}
+
+impl Filesystem {
+    /// The files of the textures `names`, owned by `uid` and `gid`.
+    fn new(query: TextureQuery, names: &[String], budget: u64, uid: u32, gid: u32) -> Self {
+        let mut filesystem = Filesystem {
+            query,
+            nodes: vec![Node {
+                parent: FUSE_ROOT_ID,
+                kind: NodeKind::Directory(BTreeMap::new()),
+            }],
+            uid,
+            gid,
+            sizes: HashMap::new(),
+            encoded: HashMap::new(),
+            encoded_size: 0,
+            budget,
+        };
+        for name in names {
+            filesystem.add(name);
+        }
+        filesystem
+    }
+
+    /// Adds `name.png` for the texture entry `name`, with its directories.
+    fn add(&mut self, name: &str) {
+        let mut parent = FUSE_ROOT_ID;
+        let mut components = name
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .peekable();
+        while let Some(component) = components.next() {
+            let last = components.peek().is_none();
+            let file_name = match last {
+                true => format!("{}.png", component),
+                false => component.to_owned(),
+            };
+            let next = self.nodes.len() as u64 + 1;
+            let NodeKind::Directory(children) = &mut self.nodes[parent as usize - 1].kind else {
+                // A texture named like the folder of another one
+                return;
+            };
+            let inode = *children.entry(file_name).or_insert(next);
+            if inode == next {
+                self.nodes.push(Node {
+                    parent,
+                    kind: match last {
+                        true => NodeKind::Texture(name.to_owned()),
+                        false => NodeKind::Directory(BTreeMap::new()),
+                    },
+                });
+            }
+            parent = inode;
+        }
+    }
+
+    fn node(&self, inode: u64) -> Option<&Node> {
+        self.nodes.get((inode as usize).checked_sub(1)?)
+    }
+
+    /// The PNG of the texture file `inode`.
+    fn png(&mut self, inode: u64) -> Option<Arc<Vec<u8>>> {
+        if let Some(png) = self.encoded.get(&inode) {
+            return Some(png.clone());
+        }
+        if self.sizes.get(&inode) == Some(&None) {
+            return None;
+        }
+        let Some(NodeKind::Texture(name)) = self.node(inode).map(|node| &node.kind) else {
+            return None;
+        };
+        let name = name.clone();
+        let png = self.query.decode(&name).and_then(|texture| {
+            texture::encode_image(
+                ImageFormat::Png,
+                &texture.data,
+                texture.width,
+                texture.height,
+                texture.format,
+            )
+        });
+        let png = match png {
+            Ok(png) => Arc::new(png),
+            Err(err) => {
+                log::error(format!("Failed to convert {}: {}", name, err));
+                self.sizes.insert(inode, None);
+                return None;
+            }
+        };
+        self.sizes.insert(inode, Some(png.len() as u64));
+        if self.encoded_size + png.len() as u64 > self.budget {
+            self.encoded.clear();
+            self.encoded_size = 0;
+        }
+        self.encoded_size += png.len() as u64;
+        self.encoded.insert(inode, png.clone());
+        Some(png)
+    }
+
+    /// Attributes of `inode`, encoding its PNG to know its size.
+    fn attr(&mut self, inode: u64) -> Option<FileAttr> {
+        let (kind, perm, nlink, size) = match &self.node(inode)?.kind {
+            NodeKind::Directory(_) => (FileType::Directory, 0o555, 2, 0),
+            NodeKind::Texture(_) => {
+                let size = match self.sizes.get(&inode) {
+                    Some(size) => size.unwrap_or(0),
+                    None => self.png(inode).map_or(0, |png| png.len() as u64),
+                };
+                (FileType::RegularFile, 0o444, 1, size)
+            }
+        };
+        Some(FileAttr {
+            ino: inode,
+            size,
+            blocks: size.div_ceil(512),
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind,
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+}
+
+impl fuser::Filesystem for Filesystem {
+    fn lookup(&mut self, _request: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let child = match self.node(parent).map(|node| &node.kind) {
+            Some(NodeKind::Directory(children)) => {
+                children.get(name.to_string_lossy().as_ref()).copied()
+            }
+            Some(NodeKind::Texture(_)) => return reply.error(ENOTDIR),
+            None => None,
+        };
+        match child.and_then(|child| self.attr(child)) {
+            Some(attr) => reply.entry(&TIMEOUT, &attr, 0),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getattr(
+        &mut self,
+        _request: &Request<'_>,
+        inode: u64,
+        _handle: Option<u64>,
+        reply: ReplyAttr,
+    ) {
+        match self.attr(inode) {
+            Some(attr) => reply.attr(&TIMEOUT, &attr),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn open(&mut self, _request: &Request<'_>, inode: u64, _flags: i32, reply: ReplyOpen) {
+        match self.png(inode) {
+            // Keeps the page cache of the file across opens
+            Some(_) => reply.opened(0, FOPEN_KEEP_CACHE),
+            None => reply.error(EIO),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _request: &Request<'_>,
+        inode: u64,
+        _handle: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.png(inode) {
+            Some(png) => {
+                let start = (offset.max(0) as usize).min(png.len());
+                reply.data(&png[start..(start + size as usize).min(png.len())]);
+            }
+            None => reply.error(EIO),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _request: &Request<'_>,
+        inode: u64,
+        _handle: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(node) = self.node(inode) else {
+            return reply.error(ENOENT);
+        };
+        let NodeKind::Directory(children) = &node.kind else {
+            return reply.error(ENOTDIR);
+        };
+        let entries = [(".", inode), ("..", node.parent)]
+            .into_iter()
+            .chain(children.iter().map(|(name, child)| (name.as_str(), *child)));
+        for (index, (name, child)) in entries.enumerate().skip(offset.max(0) as usize) {
+            let kind = match self.nodes[child as usize - 1].kind {
+                NodeKind::Directory(_) => FileType::Directory,
+                NodeKind::Texture(_) => FileType::RegularFile,
+            };
+            // The offset of an entry is where listing resumes after it
+            if reply.add(child, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Mounts the textures of the archive read-only on `mountpoint` as PNG files,
+/// each texture entry `foo` showing up as `foo.png`. Textures are decoded the
+/// first time their file is looked at, and the PNGs kept up to
+/// `--cache-budget`. Serves requests until the mount is unmounted with
+/// `fusermount -u` or `umount`.
+pub fn mount(
+    options: &Options,
+    mountpoint: &str,
+    zip: zip::ZipArchive<File>,
+    file_names: &[String],
+    tluts: Tluts,
+    pitches: HashMap<String, u32>,
+    config: Config,
+) {
+    let mut query = open_query(options, zip, tluts, pitches, config);
+    let textures = file_names
+        .iter()
+        .filter(|name| {
+            query
+                .metadata(name)
+                .texture
+                .is_some_and(|(format, _, _)| format != "TLUT" && format != "Error")
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    // The files belong to whoever owns the mountpoint, usually who mounts it
+    let owner = std::fs::metadata(mountpoint)
+        .unwrap_or_else(|err| panic!("Failed to mount {}: {}", mountpoint, err));
+    let filesystem = Filesystem::new(
+        query,
+        &textures,
+        options.cache_budget,
+        owner.uid(),
+        owner.gid(),
+    );
+    let mount_options = [
+        MountOption::RO,
+        MountOption::NoSuid,
+        MountOption::NoDev,
+        MountOption::FSName(FS_NAME.to_owned()),
+        MountOption::Subtype(FS_NAME.to_owned()),
+    ];
+    let session = fuser::spawn_mount2(filesystem, mountpoint, &mount_options)
+        .unwrap_or_else(|err| panic!("Failed to mount {}: {}", mountpoint, err));
+    println!("{} textures mounted on {}", textures.len(), mountpoint);
+    session.join();
+}
@@ -0,0 +1,52 @@
+use std::str::FromStr;
+
+/// Game whose archive conventions are followed, given with `--game`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Game {
+    /// TLUTs are looked up in the whole archive, the default.
+    Generic,
+    /// Spaghetti Kart, the Mario Kart 64 port. The textures of a course
+    /// share the palettes of its segment, named the same in every course,
+    /// and some archives keep course texels MIO0 compressed as in the ROM.
+    SpaghettiKart,
+}
+
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "generic" => Ok(Game::Generic),
+            "spaghetti-kart" => Ok(Game::SpaghettiKart),
+            _ => Err(format!(
+                "Unknown game '{}', expected generic or spaghetti-kart",
+                value
+            )),
+        }
+    }
+}
+
+/// Folder holding the courses of Spaghetti Kart archives, one subfolder
+/// each.
+const COURSES_FOLDER: &str = "courses";
+
+impl Game {
+    /// Folder the TLUTs of the texture `name` are looked up in before the
+    /// rest of the archive.
+    pub fn tlut_scope(self, name: &str) -> Option<&str> {
+        match self {
+            Game::Generic => None,
+            Game::SpaghettiKart => {
+                let course = name.strip_prefix(COURSES_FOLDER)?.strip_prefix('/')?;
+                let (course, _) = course.split_once('/')?;
+                Some(&name[..COURSES_FOLDER.len() + 1 + course.len()])
+            }
+        }
+    }
+
+    /// Whether archives of the game can hold compressed texels. Raw texels
+    /// can start with the same magic, so other games read them as stored.
+    pub fn compressed_texels(self) -> bool {
+        self == Game::SpaghettiKart
+    }
+}
@@ -0,0 +1,884 @@
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    io::{Read, Seek},
+};
+
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, OTRHeader, ResourceType, TextureFormat, TextureType,
+    crc64::crc64,
+    decoder::ResourceDecoder,
+    display_list::{self, Combiner, CombinerInputs, Command, G_LIGHTING, Reference, Vertex},
+    json::Json,
+    log,
+    payload::PayloadTransform,
+    read_entry,
+    skeleton::{self, Animation, Limb, NO_LIMB, Skeleton},
+    texture::TextureDecoder,
+};
+
+/// Frame rate animations are played back at.
+const FRAMES_PER_SECOND: f32 = 20.0;
+/// Size of the RSP vertex buffer.
+const VERTEX_BUFFER_SIZE: usize = 64;
+/// How deep display lists calling each other are followed.
+const MAX_CALL_DEPTH: usize = 16;
+
+// glTF accessor component types
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+// glTF buffer view targets
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Geometry mode bit culling back faces.
+const G_CULL_BACK: u32 = 0x00000400;
+
+/// Archive access for the resources a skeleton pulls in, resolving the
+/// hashed references display lists use.
+pub struct Resources<R> {
+    zip: zip::ZipArchive<R>,
+    payload: PayloadTransform,
+    names: HashMap<u64, String>,
+    vertices: HashMap<String, Vec<Vertex>>,
+    /// Width and height of the textures read, none for TLUTs.
+    texture_sizes: HashMap<String, Option<(u32, u32)>>,
+}
+
+impl<R: Read + Seek> Resources<R> {
+    pub fn new(zip: zip::ZipArchive<R>, payload: PayloadTransform, file_names: &[String]) -> Self {
+        Resources {
+            zip,
+            payload,
+            names: file_names
+                .iter()
+                .map(|name| (crc64(name), name.to_owned()))
+                .collect(),
+            vertices: HashMap::new(),
+            texture_sizes: HashMap::new(),
+        }
+    }
+
+    fn read(&mut self, path: &str, type_id: ResourceType) -> Result<Vec<u8>, String> {
+        let data = read_entry(&mut self.zip, path, &self.payload)
+            .ok_or_else(|| format!("{} not found", path))?;
+        if data.len() < OTR_HEADER_SIZE
+            || !OTRHeader::parse(&data).is_ok_and(|header| header.type_id == type_id)
+        {
+            return Err(format!("{} is not a {:?} resource", path, type_id));
+        }
+        Ok(data)
+    }
+
+    fn resolve(&self, reference: &Reference) -> Result<String, String> {
+        match reference {
+            Reference::Path(path) => Ok(path.to_owned()),
+            Reference::Hash(hash) => self
+                .names
+                .get(hash)
+                .cloned()
+                .ok_or_else(|| format!("No resource with id {:016x}", hash)),
+            Reference::Segmented(address) => Err(format!(
+                "Segmented address {:08x} can't be followed",
+                address
+            )),
+        }
+    }
+
+    fn vertices(&mut self, path: &str) -> Result<&[Vertex], String> {
+        if !self.vertices.contains_key(path) {
+            let vertices = display_list::parse_vertices(&self.read(path, ResourceType::Vertex)?)?;
+            self.vertices.insert(path.to_owned(), vertices);
+        }
+        Ok(&self.vertices[path])
+    }
+
+    /// Width and height of the texture at `path`. TLUTs are loaded through
+    /// the texture image too and have none.
+    fn texture_size(&mut self, path: &str) -> Option<(u32, u32)> {
+        if !self.texture_sizes.contains_key(path) {
+            let size = self
+                .read(path, ResourceType::Texture)
+                .ok()
+                .and_then(|data| TextureFormat::parse(&data).ok())
+                .filter(|texture| texture.type_id != TextureType::TLUT)
+                .map(|texture| (texture.width, texture.height));
+            self.texture_sizes.insert(path.to_owned(), size);
+        }
+        self.texture_sizes[path]
+    }
+
+    /// Normal animations of the archive directory `directory` driving
+    /// `limb_count` limbs.
+    pub fn animations(
+        &mut self,
+        file_names: &[String],
+        directory: &str,
+        limb_count: usize,
+    ) -> Vec<(String, Animation)> {
+        file_names
+            .iter()
+            .filter(|name| name.rsplit_once('/').map(|(parent, _)| parent) == Some(directory))
+            .filter_map(|name| {
+                let data = self.read(name, ResourceType::Animation).ok()?;
+                let animation = skeleton::parse_animation(&data).ok()??;
+                (animation.joint_indices.len() == limb_count + 1)
+                    .then(|| (name.to_owned(), animation))
+            })
+            .collect()
+    }
+}
+
+/// Approximation of the combiner and geometry mode a triangle is drawn with,
+/// as a glTF material.
+#[derive(Clone, PartialEq)]
+struct Material {
+    /// Archive path of the texture the color samples.
+    texture: Option<String>,
+    /// Primitive or environment color the combiner mixes in, white without.
+    color: [u8; 4],
+    /// Lighting is off, the vertex colors are colors rather than normals.
+    unlit: bool,
+    /// The color is multiplied by the vertex colors.
+    vertex_colors: bool,
+    /// The alpha comes from the texture, alpha tested like most cutouts.
+    alpha_mask: bool,
+    double_sided: bool,
+}
+
+impl Material {
+    fn to_json(&self, texture: Option<usize>) -> Json {
+        let mut pbr = Json::object()
+            .with(
+                "baseColorFactor",
+                floats_json(&self.color.map(|value| value as f32 / 255.0)),
+            )
+            .with("metallicFactor", 0.0)
+            .with("roughnessFactor", 1.0);
+        if let Some(texture) = texture {
+            pbr.insert("baseColorTexture", Json::object().with("index", texture));
+        }
+        let mut material = Json::object().with("pbrMetallicRoughness", pbr);
+        if self.alpha_mask {
+            material.insert("alphaMode", "MASK");
+            material.insert("alphaCutoff", 0.5);
+        }
+        if self.double_sided {
+            material.insert("doubleSided", true);
+        }
+        if self.unlit {
+            material.insert(
+                "extensions",
+                Json::object().with("KHR_materials_unlit", Json::object()),
+            );
+        }
+        material
+    }
+}
+
+/// RDP state display lists set up for the triangles they draw, carried over
+/// to the display lists they call.
+struct RenderState {
+    /// Archive path, width and height of the texture image.
+    texture: Option<(String, u32, u32)>,
+    texture_scale: [f32; 2],
+    geometry_mode: u32,
+    primitive: [u8; 4],
+    environment: [u8; 4],
+    combiner: Option<Combiner>,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        RenderState {
+            texture: None,
+            texture_scale: [1.0, 1.0],
+            geometry_mode: G_LIGHTING | G_CULL_BACK,
+            primitive: [0xFF; 4],
+            environment: [0xFF; 4],
+            combiner: None,
+        }
+    }
+}
+
+impl RenderState {
+    fn lighting(&self) -> bool {
+        self.geometry_mode & G_LIGHTING != 0
+    }
+
+    /// Texture coordinates of `vertex` from 0 to 1 over the texture.
+    fn uv(&self, vertex: &Vertex) -> [f32; 2] {
+        match &self.texture {
+            Some((_, width, height)) => [
+                vertex.uv[0] as f32 / 32.0 * self.texture_scale[0] / *width as f32,
+                vertex.uv[1] as f32 / 32.0 * self.texture_scale[1] / *height as f32,
+            ],
+            None => [0.0, 0.0],
+        }
+    }
+
+    /// Material of the triangles drawn now. Without a combiner set by the
+    /// display lists, the texture is taken to be shaded as the setup display
+    /// lists of the games mostly do.
+    fn material(&self) -> Material {
+        let (color, alpha) = match &self.combiner {
+            Some(combiner) => (combiner.color_inputs(), combiner.alpha_inputs()),
+            None => {
+                let inputs = CombinerInputs {
+                    texel: true,
+                    shade: true,
+                    ..Default::default()
+                };
+                (inputs, inputs)
+            }
+        };
+        let texture = self
+            .texture
+            .as_ref()
+            .filter(|_| color.texel)
+            .map(|(path, _, _)| path.clone());
+        let source = |inputs: CombinerInputs| {
+            if inputs.primitive {
+                self.primitive
+            } else if inputs.environment {
+                self.environment
+            } else {
+                [0xFF; 4]
+            }
+        };
+        let [red, green, blue, _] = source(color);
+        Material {
+            alpha_mask: alpha.texel && texture.is_some(),
+            texture,
+            color: [red, green, blue, source(alpha)[3]],
+            unlit: !self.lighting(),
+            vertex_colors: color.shade && !self.lighting(),
+            double_sided: self.geometry_mode & G_CULL_BACK == 0,
+        }
+    }
+}
+
+/// Skinned mesh being assembled from the limb display lists.
+#[derive(Default)]
+struct Mesh {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    joints: Vec<u16>,
+    materials: Vec<Material>,
+    /// Triangle indices drawn with each material, as `(material, indices)`.
+    primitives: Vec<(usize, Vec<u32>)>,
+}
+
+impl Mesh {
+    /// Appends the geometry drawn by the display list at `path`. Vertices are
+    /// in limb space and get moved to the bind pose position of `joint`.
+    fn add_display_list<R: Read + Seek>(
+        &mut self,
+        resources: &mut Resources<R>,
+        path: &str,
+        joint: u16,
+        origin: [f32; 3],
+        state: &mut RenderState,
+        depth: usize,
+    ) -> Result<(), String> {
+        if depth > MAX_CALL_DEPTH {
+            return Err(format!("Display lists nested too deep at {}", path));
+        }
+        let commands =
+            display_list::parse_display_list(&resources.read(path, ResourceType::DisplayList)?)?;
+
+        let mut buffer = [None; VERTEX_BUFFER_SIZE];
+        for command in commands {
+            match command {
+                Command::Vertex {
+                    source,
+                    offset,
+                    count,
+                    destination,
+                } => {
+                    let source = resources.resolve(&source)?;
+                    let vertices = resources.vertices(&source)?;
+                    let start = display_list::vertex_index(offset);
+                    for i in 0..count as usize {
+                        let (Some(vertex), Some(slot)) = (
+                            vertices.get(start + i),
+                            buffer.get_mut(destination as usize + i),
+                        ) else {
+                            return Err(format!("Vertex load out of range in {}", path));
+                        };
+                        *slot = Some(self.positions.len() as u32);
+                        self.positions.push([
+                            vertex.position[0] as f32 + origin[0],
+                            vertex.position[1] as f32 + origin[1],
+                            vertex.position[2] as f32 + origin[2],
+                        ]);
+                        self.uvs.push(state.uv(vertex));
+                        self.colors.push(match state.lighting() {
+                            true => [1.0; 4],
+                            false => vertex.color.map(|value| value as f32 / 255.0),
+                        });
+                        self.joints.push(joint);
+                    }
+                }
+                Command::Triangles(triangles) => {
+                    let indices = self.primitive(state.material());
+                    for triangle in triangles {
+                        let vertices =
+                            triangle.map(|index| buffer.get(index as usize).copied().flatten());
+                        let [Some(a), Some(b), Some(c)] = vertices else {
+                            return Err(format!("Triangle uses an unloaded vertex in {}", path));
+                        };
+                        indices.extend([a, b, c]);
+                    }
+                }
+                Command::Texture { image, .. } => match resources.resolve(&image) {
+                    Ok(image) => {
+                        if let Some((width, height)) = resources.texture_size(&image) {
+                            state.texture = Some((image, width, height));
+                        }
+                    }
+                    // Set at runtime
+                    Err(_) => state.texture = None,
+                },
+                Command::TextureScale { s, t } => {
+                    state.texture_scale = [s as f32 / 65536.0, t as f32 / 65536.0];
+                }
+                Command::GeometryMode { clear, set } => {
+                    state.geometry_mode = state.geometry_mode & !clear | set;
+                }
+                Command::PrimitiveColor(color) => state.primitive = color,
+                Command::EnvironmentColor(color) => state.environment = color,
+                Command::Combine(combiner) => state.combiner = Some(combiner),
+                // Only where the texels sit in TMEM and how they are sampled
+                Command::Tile { .. }
+                | Command::TextureFilter { .. }
+                | Command::Load { .. }
+                | Command::InlineTexture { .. } => {}
+                // Set at runtime, usually to swap materials like eye textures
+                Command::Call {
+                    target: Reference::Segmented(_),
+                    ..
+                } => {}
+                Command::Call { target, branch } => {
+                    let target = resources.resolve(&target)?;
+                    self.add_display_list(resources, &target, joint, origin, state, depth + 1)?;
+                    if branch {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Indices of the triangles drawn with `material`.
+    fn primitive(&mut self, material: Material) -> &mut Vec<u32> {
+        let material = match self.materials.iter().position(|known| *known == material) {
+            Some(index) => index,
+            None => {
+                self.materials.push(material);
+                self.materials.len() - 1
+            }
+        };
+        let index = match self
+            .primitives
+            .iter()
+            .position(|(known, _)| *known == material)
+        {
+            Some(index) => index,
+            None => {
+                self.primitives.push((material, Vec::new()));
+                self.primitives.len() - 1
+            }
+        };
+        &mut self.primitives[index].1
+    }
+}
+
+/// Binary buffer of the glTF file along with the views and accessors into it.
+#[derive(Default)]
+struct Buffer {
+    data: Vec<u8>,
+    views: Vec<Json>,
+    accessors: Vec<Json>,
+}
+
+impl Buffer {
+    /// Adds an accessor over `values`, returning its index.
+    fn accessor<T: Copy>(
+        &mut self,
+        values: &[T],
+        component_type: u32,
+        element_type: &str,
+        target: Option<u32>,
+        to_bytes: impl Fn(T) -> Vec<u8>,
+    ) -> usize {
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+        let offset = self.data.len();
+        for value in values {
+            self.data.extend(to_bytes(*value));
+        }
+
+        let mut view = Json::object()
+            .with("buffer", 0u32)
+            .with("byteOffset", offset)
+            .with("byteLength", self.data.len() - offset);
+        if let Some(target) = target {
+            view.insert("target", target);
+        }
+        self.views.push(view);
+
+        let components = match element_type {
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT4" => 16,
+            _ => 1,
+        };
+        self.accessors.push(
+            Json::object()
+                .with("bufferView", self.views.len() - 1)
+                .with("componentType", component_type)
+                .with("count", values.len() / components)
+                .with("type", element_type),
+        );
+        self.accessors.len() - 1
+    }
+
+    fn floats(&mut self, values: &[f32], element_type: &str, target: Option<u32>) -> usize {
+        self.accessor(values, FLOAT, element_type, target, |value| {
+            value.to_le_bytes().to_vec()
+        })
+    }
+
+    /// Sets the `min` and `max` of a float accessor, which glTF requires for
+    /// positions and animation inputs.
+    fn bounds(&mut self, accessor: usize, values: &[f32], components: usize) {
+        let mut min = vec![f32::MAX; components];
+        let mut max = vec![f32::MIN; components];
+        for element in values.chunks(components) {
+            for (i, value) in element.iter().enumerate() {
+                min[i] = min[i].min(*value);
+                max[i] = max[i].max(*value);
+            }
+        }
+        self.accessors[accessor].insert("min", floats_json(&min));
+        self.accessors[accessor].insert("max", floats_json(&max));
+    }
+}
+
+/// Builds a glTF scene for `skeleton`: one node per limb, a mesh skinned to
+/// them with every vertex following its limb, and a track per animation.
+/// Materials approximate the combiner settings of the display lists, with
+/// `texture_uri` giving the URI of the image exported for a texture.
+/// Returns the glTF JSON, referencing `buffer_uri`, and the binary buffer.
+pub fn export<R: Read + Seek>(
+    resources: &mut Resources<R>,
+    skeleton: &Skeleton,
+    animations: &[(String, Animation)],
+    buffer_uri: &str,
+    texture_uri: &dyn Fn(&str) -> String,
+) -> Result<(Json, Vec<u8>), String> {
+    let limbs = skeleton
+        .limbs
+        .iter()
+        .map(|path| {
+            let data = resources.read(path, ResourceType::SkeletonLimb)?;
+            skeleton::parse_limb(&data).map_err(|err| format!("{}: {}", path, err))
+        })
+        .collect::<Result<Vec<Limb>, String>>()?;
+    if limbs.is_empty() {
+        return Err("Skeleton has no limbs".to_owned());
+    }
+
+    let mut children = vec![Vec::new(); limbs.len()];
+    let mut parents = vec![None; limbs.len()];
+    for (i, limb) in limbs.iter().enumerate() {
+        let mut child = limb.child;
+        while child != NO_LIMB {
+            let Some(next) = limbs.get(child as usize) else {
+                return Err(format!("Limb {} has an invalid child {}", i, child));
+            };
+            if child == 0 || parents[child as usize].is_some() {
+                return Err(format!("Limb {} is linked more than once", child));
+            }
+            parents[child as usize] = Some(i);
+            children[i].push(child as usize);
+            child = next.sibling;
+        }
+    }
+
+    // Bind pose has no rotation, so positions are summed translations
+    let mut origins = vec![[0.0f32; 3]; limbs.len()];
+    let mut order = vec![0];
+    while let Some(i) = order.pop() {
+        for &child in &children[i] {
+            let translation = limbs[child].translation;
+            origins[child] = [0, 1, 2].map(|axis| origins[i][axis] + translation[axis]);
+            order.push(child);
+        }
+    }
+
+    let mut mesh = Mesh::default();
+    // Limbs are drawn one after the other, each keeping the state the
+    // previous one left
+    let mut state = RenderState::default();
+    for (i, limb) in limbs.iter().enumerate() {
+        if let Some(display_list) = &limb.display_list
+            && let Err(err) =
+                mesh.add_display_list(resources, display_list, i as u16, origins[i], &mut state, 0)
+        {
+            log::error(format!(
+                "Skipping geometry of {}: {}",
+                skeleton.limbs[i], err
+            ));
+        }
+    }
+
+    let mut buffer = Buffer::default();
+    let mut nodes = limbs
+        .iter()
+        .enumerate()
+        .map(|(i, limb)| {
+            let mut node = Json::object()
+                .with(
+                    "name",
+                    skeleton.limbs[i].rsplit('/').next().unwrap_or_default(),
+                )
+                .with("translation", floats_json(&limb.translation));
+            if !children[i].is_empty() {
+                node.insert(
+                    "children",
+                    Json::Array(children[i].iter().map(|child| Json::from(*child)).collect()),
+                );
+            }
+            node
+        })
+        .collect::<Vec<Json>>();
+    let mut scene_nodes = vec![Json::from(0usize)];
+
+    let mut meshes = Vec::new();
+    let mut skins = Vec::new();
+    let mut materials = Vec::new();
+    let mut texture_paths: Vec<&str> = Vec::new();
+    if !mesh.primitives.is_empty() {
+        let positions = mesh.positions.concat();
+        let position_accessor = buffer.floats(&positions, "VEC3", Some(ARRAY_BUFFER));
+        buffer.bounds(position_accessor, &positions, 3);
+        let uv_accessor = buffer.floats(&mesh.uvs.concat(), "VEC2", Some(ARRAY_BUFFER));
+        let color_accessor = buffer.floats(&mesh.colors.concat(), "VEC4", Some(ARRAY_BUFFER));
+        let joints = mesh
+            .joints
+            .iter()
+            .flat_map(|joint| [*joint, 0, 0, 0])
+            .collect::<Vec<u16>>();
+        let joint_accessor = buffer.accessor(
+            &joints,
+            UNSIGNED_SHORT,
+            "VEC4",
+            Some(ARRAY_BUFFER),
+            |value| value.to_le_bytes().to_vec(),
+        );
+        let weights = mesh
+            .joints
+            .iter()
+            .flat_map(|_| [1.0, 0.0, 0.0, 0.0])
+            .collect::<Vec<f32>>();
+        let weight_accessor = buffer.floats(&weights, "VEC4", Some(ARRAY_BUFFER));
+        let primitives = mesh
+            .primitives
+            .iter()
+            .map(|(material, indices)| {
+                let index_accessor = buffer.accessor(
+                    indices,
+                    UNSIGNED_INT,
+                    "SCALAR",
+                    Some(ELEMENT_ARRAY_BUFFER),
+                    |value| value.to_le_bytes().to_vec(),
+                );
+                let mut attributes = Json::object()
+                    .with("POSITION", position_accessor)
+                    .with("TEXCOORD_0", uv_accessor)
+                    .with("JOINTS_0", joint_accessor)
+                    .with("WEIGHTS_0", weight_accessor);
+                if mesh.materials[*material].vertex_colors {
+                    attributes.insert("COLOR_0", color_accessor);
+                }
+                Json::object()
+                    .with("attributes", attributes)
+                    .with("indices", index_accessor)
+                    .with("material", *material)
+            })
+            .collect();
+        for material in &mesh.materials {
+            let texture = material.texture.as_deref().map(|path| {
+                match texture_paths.iter().position(|known| *known == path) {
+                    Some(index) => index,
+                    None => {
+                        texture_paths.push(path);
+                        texture_paths.len() - 1
+                    }
+                }
+            });
+            materials.push(material.to_json(texture));
+        }
+
+        let inverse_bind_matrices = origins
+            .iter()
+            .flat_map(|origin| {
+                let mut matrix = [0.0; 16];
+                for i in 0..4 {
+                    matrix[i * 5] = 1.0;
+                }
+                matrix[12] = -origin[0];
+                matrix[13] = -origin[1];
+                matrix[14] = -origin[2];
+                matrix
+            })
+            .collect::<Vec<f32>>();
+        let matrix_accessor = buffer.floats(&inverse_bind_matrices, "MAT4", None);
+
+        meshes.push(Json::object().with("primitives", Json::Array(primitives)));
+        skins.push(
+            Json::object()
+                .with("inverseBindMatrices", matrix_accessor)
+                .with("skeleton", 0u32)
+                .with(
+                    "joints",
+                    Json::Array((0..limbs.len()).map(Json::from).collect()),
+                ),
+        );
+        nodes.push(Json::object().with("mesh", 0u32).with("skin", 0u32));
+        scene_nodes.push(Json::from(nodes.len() - 1));
+    }
+
+    let animations = animations
+        .iter()
+        .map(|(name, animation)| export_animation(&mut buffer, name, animation))
+        .collect::<Vec<Json>>();
+
+    let mut gltf = Json::object()
+        .with("asset", Json::object().with("version", "2.0"))
+        .with("scene", 0u32)
+        .with(
+            "scenes",
+            Json::Array(vec![Json::object().with("nodes", Json::Array(scene_nodes))]),
+        )
+        .with("nodes", Json::Array(nodes));
+    if !meshes.is_empty() {
+        gltf.insert("meshes", Json::Array(meshes));
+        gltf.insert("skins", Json::Array(skins));
+        gltf.insert("materials", Json::Array(materials));
+    }
+    if !texture_paths.is_empty() {
+        gltf.insert(
+            "textures",
+            Json::Array(
+                (0..texture_paths.len())
+                    .map(|index| Json::object().with("source", index))
+                    .collect(),
+            ),
+        );
+        gltf.insert(
+            "images",
+            Json::Array(
+                texture_paths
+                    .iter()
+                    .map(|path| Json::object().with("uri", texture_uri(path)))
+                    .collect(),
+            ),
+        );
+    }
+    if mesh.materials.iter().any(|material| material.unlit) {
+        gltf.insert(
+            "extensionsUsed",
+            Json::Array(vec![Json::from("KHR_materials_unlit")]),
+        );
+    }
+    if !animations.is_empty() {
+        gltf.insert("animations", Json::Array(animations));
+    }
+    gltf.insert("accessors", Json::Array(buffer.accessors));
+    gltf.insert("bufferViews", Json::Array(buffer.views));
+    gltf.insert(
+        "buffers",
+        Json::Array(vec![
+            Json::object()
+                .with("uri", buffer_uri)
+                .with("byteLength", buffer.data.len()),
+        ]),
+    );
+    Ok((gltf, buffer.data))
+}
+
+/// Animation track moving the root limb and rotating every limb, frame by
+/// frame with linear interpolation in between.
+fn export_animation(buffer: &mut Buffer, name: &str, animation: &Animation) -> Json {
+    let frames = animation.frame_count.max(1);
+    let times = (0..frames)
+        .map(|frame| frame as f32 / FRAMES_PER_SECOND)
+        .collect::<Vec<f32>>();
+    let input = buffer.floats(&times, "SCALAR", None);
+    buffer.bounds(input, &times, 1);
+
+    let mut samplers = Vec::new();
+    let mut channels = Vec::new();
+    for (joint, indices) in animation.joint_indices.iter().enumerate() {
+        // The first joint is the root translation, the others limb rotations
+        let (node, path, values) = if joint == 0 {
+            let values = (0..frames)
+                .flat_map(|frame| indices.map(|index| animation.value(index, frame) as f32))
+                .collect::<Vec<f32>>();
+            (0, "translation", buffer.floats(&values, "VEC3", None))
+        } else {
+            let values = (0..frames)
+                .flat_map(|frame| rotation(indices.map(|index| animation.value(index, frame))))
+                .collect::<Vec<f32>>();
+            (joint - 1, "rotation", buffer.floats(&values, "VEC4", None))
+        };
+        samplers.push(
+            Json::object()
+                .with("input", input)
+                .with("output", values)
+                .with("interpolation", "LINEAR"),
+        );
+        channels.push(Json::object().with("sampler", samplers.len() - 1).with(
+            "target",
+            Json::object().with("node", node).with("path", path),
+        ));
+    }
+
+    Json::object()
+        .with("name", name.rsplit('/').next().unwrap_or_default())
+        .with("samplers", Json::Array(samplers))
+        .with("channels", Json::Array(channels))
+}
+
+/// Quaternion of a limb rotation, in binary angles applied Z then Y then X
+/// like `Matrix_TranslateRotateZYX`.
+fn rotation(angles: [i16; 3]) -> [f32; 4] {
+    let [x, y, z] = angles.map(|angle| angle as f32 * PI / 32768.0 / 2.0);
+    let qx = [x.sin(), 0.0, 0.0, x.cos()];
+    let qy = [0.0, y.sin(), 0.0, y.cos()];
+    let qz = [0.0, 0.0, z.sin(), z.cos()];
+    multiply(multiply(qz, qy), qx)
+}
+
+fn multiply(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [
+        a[3] * b[0] + a[0] * b[3] + a[1] * b[2] - a[2] * b[1],
+        a[3] * b[1] - a[0] * b[2] + a[1] * b[3] + a[2] * b[0],
+        a[3] * b[2] + a[0] * b[1] - a[1] * b[0] + a[2] * b[3],
+        a[3] * b[3] - a[0] * b[0] - a[1] * b[1] - a[2] * b[2],
+    ]
+}
+
+/// URI of the output `path` from the output `from`, both relative to the
+/// output folder.
+fn relative_uri(from: &str, path: &str) -> String {
+    let from = from.split('/').collect::<Vec<_>>();
+    let folder = &from[..from.len() - 1];
+    let path = path.split('/').collect::<Vec<_>>();
+    let common = folder.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    let relative = std::iter::repeat_n("..", folder.len() - common)
+        .chain(path[common..].iter().copied())
+        .collect::<Vec<_>>()
+        .join("/");
+    relative
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn floats_json(values: &[f32]) -> Json {
+    Json::Array(
+        values
+            .iter()
+            .map(|value| Json::from(*value as f64))
+            .collect(),
+    )
+}
+
+/// Exports skeletons, with the geometry and animations found for them, to
+/// glTF.
+pub struct SkeletonDecoder;
+
+impl ResourceDecoder for SkeletonDecoder {
+    fn name(&self) -> &'static str {
+        "skeleton"
+    }
+
+    fn directory(&self) -> &'static str {
+        "skeletons"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::Skeleton, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let skeleton = match skeleton::parse_skeleton(data) {
+            Ok(skeleton) => skeleton,
+            Err(err) => {
+                log::error(format!("Failed to parse skeleton {}: {}", name, err));
+                return;
+            }
+        };
+
+        // Skeletons are built from other resources, read through a separate
+        // handle so the workers' readers aren't shared
+        let zip = zip::ZipArchive::new(
+            std::fs::File::open(&converter.options.zip_file).expect("Failed to open zip file"),
+        )
+        .expect("Failed to read zip file");
+        let mut resources =
+            Resources::new(zip, converter.options.payload.clone(), converter.file_names);
+        let directory = name
+            .rsplit_once('/')
+            .map(|(directory, _)| directory)
+            .unwrap_or_default();
+        let animations =
+            resources.animations(converter.file_names, directory, skeleton.limbs.len());
+
+        let base = converter.output_base(self, name);
+        let buffer_path = base.clone() + ".bin";
+        let buffer_uri = buffer_path.rsplit('/').next().unwrap();
+        let output = converter.output_name(self, name);
+        let texture_uri = |texture: &str| {
+            let image = converter.output_name(&TextureDecoder, texture)
+                + "."
+                + converter.options.image_format.extension();
+            relative_uri(&output, &image)
+        };
+        let (gltf, buffer) = match export(
+            &mut resources,
+            &skeleton,
+            &animations,
+            buffer_uri,
+            &texture_uri,
+        ) {
+            Ok(exported) => exported,
+            Err(err) => {
+                log::error(format!("Failed to export skeleton {}: {}", name, err));
+                return;
+            }
+        };
+
+        let path = base + ".gltf";
+        log::progress(format!(
+            "Exporting skeleton with {} animations: {}",
+            animations.len(),
+            path
+        ));
+        converter.write(&path, gltf.pretty());
+        converter.write(&buffer_path, buffer);
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use crate::{options::Options, pipeline};
+
+/// Bytes searched for by `grep`: hex digits after a `hex:` prefix, spaces
+/// allowed between bytes, or else the UTF-8 encoding of the pattern.
+pub fn parse_pattern(pattern: &str) -> Result<Vec<u8>, String> {
+    let Some(hex) = pattern.strip_prefix("hex:") else {
+        return Ok(pattern.as_bytes().to_vec());
+    };
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return Err(format!(
+            "Invalid hex pattern '{}', expected whole bytes",
+            hex
+        ));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let byte = pair.iter().collect::<String>();
+            u8::from_str_radix(&byte, 16)
+                .map_err(|_| format!("Invalid hex byte '{}' in pattern", byte))
+        })
+        .collect()
+}
+
+/// Offsets of every occurrence of `pattern` in `data`, overlapping ones
+/// included.
+fn find_all(data: &[u8], pattern: &[u8]) -> Vec<usize> {
+    data.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| *window == pattern)
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+/// Prints the offsets of `pattern` in every decompressed entry of the
+/// archive, headers included, reading entries on the pipeline threads.
+pub fn run(options: &Options, pattern: &[u8]) {
+    let zip = zip::ZipArchive::new(
+        std::fs::File::open(&options.zip_file).expect("Failed to open zip file"),
+    )
+    .expect("Failed to read zip file");
+    let names = zip
+        .file_names()
+        .map(|name| name.to_owned())
+        .collect::<Vec<String>>();
+    drop(zip);
+
+    let mut matches = BTreeMap::new();
+    pipeline::run(
+        &options.zip_file,
+        names,
+        options.io_threads,
+        options.io_profile.read_ahead(),
+        options.threads,
+        options.read_retries,
+        &options.payload,
+        |name, data| (name, find_all(&data, pattern)),
+        |(name, offsets)| {
+            if !offsets.is_empty() {
+                matches.insert(name, offsets);
+            }
+        },
+    );
+
+    for (name, offsets) in &matches {
+        let offsets = offsets
+            .iter()
+            .map(|offset| format!("0x{:X}", offset))
+            .collect::<Vec<_>>();
+        println!("{}: {}", name, offsets.join(", "));
+    }
+    println!(
+        "{} matches in {} entries",
+        matches.values().map(Vec::len).sum::<usize>(),
+        matches.len()
+    );
+}
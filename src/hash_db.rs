@@ -0,0 +1,60 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use walkdir::WalkDir;
+
+use crate::pack_hash::PackHash;
+
+/// Names of the textures of a community hi-res pack by their hash, given with
+/// `--hash-db` as the pack folder itself or a text file listing its file
+/// names one per line, such as the output of `find`. Files not named in the
+/// Rice scheme are ignored.
+pub struct HashDb {
+    names: HashMap<PackHash, String>,
+}
+
+impl HashDb {
+    pub fn load(path: &str) -> Self {
+        let mut names = if Path::new(path).is_dir() {
+            WalkDir::new(path)
+                .into_iter()
+                .filter_map(|file| file.ok())
+                .filter(|file| file.file_type().is_file())
+                .map(|file| {
+                    let name = file.path().strip_prefix(path).unwrap();
+                    name.to_string_lossy().replace('\\', "/")
+                })
+                .collect::<Vec<_>>()
+        } else {
+            fs::read_to_string(path)
+                .unwrap_or_else(|err| panic!("Failed to read hash database {}: {}", path, err))
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+        names.sort();
+
+        // Packs often hold several images of a texture, `_all` and `_rgb`
+        // variants, the first in order names it
+        let mut db = HashDb {
+            names: HashMap::new(),
+        };
+        for name in names {
+            if let Some(hash) = PackHash::from_file_name(&name) {
+                db.names.entry(hash).or_insert(name);
+            }
+        }
+        db
+    }
+
+    /// Number of textures with a name.
+    pub fn count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Pack file of the texture hashed to `hash`.
+    pub fn name(&self, hash: &PackHash) -> Option<&str> {
+        self.names.get(hash).map(String::as_str)
+    }
+}
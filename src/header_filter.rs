@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use crate::OTRHeader;
+
+/// Byte order flag of the OTR header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl FromStr for ByteOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "le" | "little" => Ok(ByteOrder::Little),
+            "be" | "big" => Ok(ByteOrder::Big),
+            _ => Err(format!("Unknown byte order '{}', expected le or be", value)),
+        }
+    }
+}
+
+impl ByteOrder {
+    /// Value of the byte order field, 0 for little endian and 1 for big
+    /// endian.
+    fn flag(&self) -> i8 {
+        match self {
+            ByteOrder::Little => 0,
+            ByteOrder::Big => 1,
+        }
+    }
+}
+
+/// Selection of the entries by the fields of their OTR header, with
+/// `--only-custom`, `--header-version` and `--byte-order`. Every entry
+/// matches the default filter.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderFilter {
+    pub only_custom: bool,
+    pub version: Option<u32>,
+    pub byte_order: Option<ByteOrder>,
+}
+
+impl HeaderFilter {
+    pub fn matches(&self, header: &OTRHeader) -> bool {
+        self.mismatch(header).is_none()
+    }
+
+    /// Why `header` doesn't match, when it doesn't.
+    pub fn mismatch(&self, header: &OTRHeader) -> Option<String> {
+        if self.only_custom && !header.is_custom {
+            return Some("--only-custom rejects entries without the custom flag".to_owned());
+        }
+        if let Some(version) = self.version.filter(|version| header.version != *version) {
+            return Some(format!(
+                "--header-version {} rejects version {}",
+                version, header.version
+            ));
+        }
+        if let Some(byte_order) = self
+            .byte_order
+            .filter(|byte_order| header.byte_order != byte_order.flag())
+        {
+            return Some(format!(
+                "--byte-order {} rejects byte order {}",
+                match byte_order {
+                    ByteOrder::Little => "le",
+                    ByteOrder::Big => "be",
+                },
+                header.byte_order
+            ));
+        }
+        None
+    }
+}
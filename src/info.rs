@@ -0,0 +1,147 @@
+use std::fs::File;
+
+use crate::{
+    OTR_HEADER_SIZE, OTRHeader, ResourceType, TEXTURE_STRIDE_VERSION, crc64::crc64,
+    options::Options, patch::Patcher, read_entry,
+};
+
+/// Bytes per hexdump line.
+const LINE_SIZE: usize = 16;
+
+/// A labeled field of a resource, `size` bytes at `offset`.
+struct Field {
+    offset: usize,
+    size: usize,
+    label: String,
+}
+
+/// Little-endian `u32` at `offset` of `data`.
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Fields of the header of the resource `data`, followed by an empty field
+/// where the payload starts. Only the OTR header and the texture fields are
+/// known, the payload of other resources starts after the OTR header.
+fn fields(data: &[u8]) -> Vec<Field> {
+    let field = |offset, size, label: String| Field {
+        offset,
+        size,
+        label,
+    };
+    if data.len() < OTR_HEADER_SIZE {
+        return Vec::new();
+    }
+    let Ok(header) = OTRHeader::parse(data) else {
+        return Vec::new();
+    };
+    let mut fields = vec![
+        field(0x00, 1, format!("byte_order={}", header.byte_order)),
+        field(0x01, 1, format!("is_custom={}", header.is_custom)),
+        field(0x02, 2, "reserved".to_owned()),
+        field(0x04, 4, format!("type={:?}", header.type_id)),
+        field(0x08, 4, format!("version={}", header.version)),
+        field(0x0C, 8, format!("id={:016X}", header.id)),
+        field(0x14, OTR_HEADER_SIZE - 0x14, "reserved".to_owned()),
+    ];
+    let mut payload = OTR_HEADER_SIZE;
+    if header.type_id == ResourceType::Texture && data.len() >= OTR_HEADER_SIZE + 16 {
+        let mut names = vec!["texture_type", "width", "height"];
+        if header.version == TEXTURE_STRIDE_VERSION {
+            names.push("stride");
+        }
+        names.push("size");
+        for name in names {
+            if payload + 4 > data.len() {
+                break;
+            }
+            fields.push(field(
+                payload,
+                4,
+                format!("{}={}", name, u32_at(data, payload)),
+            ));
+            payload += 4;
+        }
+    }
+    fields.push(field(payload, 0, "payload".to_owned()));
+    fields
+}
+
+/// Hexdump of `data` with the fields starting on each line labeled after it
+/// and a `|` before the first byte of the payload.
+pub fn hexdump(data: &[u8]) -> String {
+    let fields = fields(data);
+    let payload = fields.last().map_or(0, |field| field.offset);
+    let mut dump = String::new();
+    for (line, bytes) in data.chunks(LINE_SIZE).enumerate() {
+        let start = line * LINE_SIZE;
+        dump += &format!("{:08x} ", start);
+        for i in 0..LINE_SIZE {
+            let separator = if start + i == payload { '|' } else { ' ' };
+            match bytes.get(i) {
+                Some(byte) => dump += &format!("{}{:02x}", separator, byte),
+                None => dump += "   ",
+            }
+        }
+        let text = bytes
+            .iter()
+            .map(|byte| match byte {
+                0x20..=0x7E => *byte as char,
+                _ => '.',
+            })
+            .collect::<String>();
+        dump += &format!("  {:16}", text);
+
+        let labels = fields
+            .iter()
+            .filter(|field| (start..start + LINE_SIZE).contains(&field.offset))
+            .map(|field| match field.size {
+                0 => format!("{:02x} {}", field.offset, field.label),
+                size => format!(
+                    "{:02x}..{:02x} {}",
+                    field.offset,
+                    field.offset + size,
+                    field.label
+                ),
+            })
+            .collect::<Vec<_>>();
+        if !labels.is_empty() {
+            dump += &format!("  ; {}", labels.join(", "));
+        }
+        dump = dump.trim_end().to_owned() + "\n";
+    }
+    dump
+}
+
+/// Prints the header fields of the archive entry `entry`, the hi-res pack
+/// hashes of textures and with `hex` an annotated hexdump of the resource.
+pub fn run(options: &Options, entry: &str, hex: bool) {
+    let mut zip =
+        zip::ZipArchive::new(File::open(&options.zip_file).expect("Failed to open zip file"))
+            .expect("Failed to read zip file");
+    let data = read_entry(&mut zip, entry, &options.payload)
+        .unwrap_or_else(|| panic!("Entry {} not found in {}", entry, options.zip_file));
+
+    println!("Entry: {}", entry);
+    // What display lists and other resources reference the entry by
+    println!("Resource id: {:016X}", crc64(entry));
+    println!("Size: {} bytes", data.len());
+    for field in fields(&data) {
+        if field.size > 0 && field.label != "reserved" {
+            println!("  {}", field.label.replacen('=', ": ", 1));
+        }
+    }
+    if let Some((_, texture)) = Patcher::open(options).decode(entry)
+        && let Some(pack_hashes) = texture.pack_hashes
+    {
+        println!("  Rice hash: {}", pack_hashes.rice.key());
+        println!(
+            "  GlideN64 hash: {}, checksum {:016X}",
+            pack_hashes.gliden64.key(),
+            pack_hashes.gliden64.checksum64()
+        );
+    }
+    if hex {
+        print!("{}", hexdump(&data));
+    }
+}
@@ -0,0 +1,47 @@
+use std::str::FromStr;
+
+/// Whether texel rows are stored the way TMEM holds them, with the 32-bit
+/// words of every odd row swapped in pairs.
+///
+/// The RDP swaps those words when loading a texture so odd rows read right
+/// with interleaved addressing. Some exporters dump TMEM as is, which shows
+/// as every other row shifted sideways.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Deinterleave {
+    #[default]
+    Off,
+    On,
+    /// Undo the swap when it makes the rows line up better.
+    Auto,
+}
+
+impl FromStr for Deinterleave {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(Deinterleave::Off),
+            "on" => Ok(Deinterleave::On),
+            "auto" => Ok(Deinterleave::Auto),
+            _ => Err(format!(
+                "Unknown deinterleave mode '{}', expected off, on or auto",
+                value
+            )),
+        }
+    }
+}
+
+/// Swaps back the 32-bit word pairs of the odd rows of `data`, rows being
+/// `row_size` bytes. A trailing partial pair is left as is.
+pub fn deinterleave(data: &[u8], row_size: usize) -> Vec<u8> {
+    let mut data = data.to_vec();
+    if row_size == 0 {
+        return data;
+    }
+    for row in data.chunks_mut(row_size).skip(1).step_by(2) {
+        for pair in row.chunks_exact_mut(8) {
+            pair.rotate_left(4);
+        }
+    }
+    data
+}
@@ -0,0 +1,146 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    str::FromStr,
+};
+
+/// Storage the archive is read from, setting how much is read at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IoProfile {
+    /// Local SSD, where small reads are cheap.
+    Nvme,
+    /// Spinning disk, read in large chunks by a single reader to keep the
+    /// head moving forward.
+    Hdd,
+    /// SMB or NFS share, where every read is a round trip and only large
+    /// reads keep the link busy.
+    Network,
+}
+
+impl FromStr for IoProfile {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "nvme" => Ok(IoProfile::Nvme),
+            "hdd" => Ok(IoProfile::Hdd),
+            "network" => Ok(IoProfile::Network),
+            _ => Err(format!(
+                "Unknown IO profile '{}', expected nvme, hdd or network",
+                value
+            )),
+        }
+    }
+}
+
+impl IoProfile {
+    /// Bytes read from the archive at once, the rest of the chunk serving
+    /// the next reads of the entries stored after it.
+    pub fn read_ahead(&self) -> usize {
+        match self {
+            IoProfile::Nvme => 64 * 1024,
+            IoProfile::Hdd => 1024 * 1024,
+            IoProfile::Network => 4 * 1024 * 1024,
+        }
+    }
+
+    /// Readers when `--threads-io` isn't given, out of `threads`.
+    pub fn io_threads(&self, threads: usize) -> usize {
+        match self {
+            IoProfile::Hdd => 1,
+            IoProfile::Nvme | IoProfile::Network => threads,
+        }
+    }
+}
+
+/// Reader serving reads and seeks from a chunk of `size` bytes read ahead of
+/// them. The zip reader seeks to every local header and reads entries in
+/// small pieces, which a `BufReader` would turn into a read of the file each
+/// since it drops its buffer on seeks.
+pub struct ReadAhead<R> {
+    inner: R,
+    size: usize,
+    buffer: Vec<u8>,
+    /// Offset in `inner` of the first byte of `buffer`.
+    start: u64,
+    position: u64,
+    /// Where `inner` is, when known.
+    inner_position: Option<u64>,
+}
+
+impl<R: Read + Seek> ReadAhead<R> {
+    pub fn new(inner: R, size: usize) -> Self {
+        ReadAhead {
+            inner,
+            size,
+            buffer: Vec::new(),
+            start: 0,
+            position: 0,
+            inner_position: None,
+        }
+    }
+
+    fn seek_inner(&mut self) -> io::Result<()> {
+        if self.inner_position != Some(self.position) {
+            self.inner.seek(SeekFrom::Start(self.position))?;
+            self.inner_position = Some(self.position);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for ReadAhead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let end = self.start + self.buffer.len() as u64;
+        if !(self.start..end).contains(&self.position) {
+            self.seek_inner()?;
+            // Reads larger than a chunk gain nothing from going through it
+            if buf.len() >= self.size {
+                let read = self.inner.read(buf)?;
+                self.position += read as u64;
+                self.inner_position = Some(self.position);
+                return Ok(read);
+            }
+            self.buffer.resize(self.size, 0);
+            let mut filled = 0;
+            while filled < self.size {
+                match self.inner.read(&mut self.buffer[filled..]) {
+                    Ok(0) => break,
+                    Ok(read) => filled += read,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => {
+                        self.buffer.clear();
+                        self.inner_position = None;
+                        return Err(err);
+                    }
+                }
+            }
+            self.buffer.truncate(filled);
+            self.start = self.position;
+            self.inner_position = Some(self.position + filled as u64);
+        }
+        let offset = (self.position - self.start) as usize;
+        let available = &self.buffer[offset.min(self.buffer.len())..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for ReadAhead<R> {
+    fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+        self.position = match from {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(offset) => self
+                .position
+                .checked_add_signed(offset)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?,
+            SeekFrom::End(_) => {
+                let position = self.inner.seek(from)?;
+                self.inner_position = Some(position);
+                position
+            }
+        };
+        Ok(self.position)
+    }
+}
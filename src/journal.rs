@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Seek, Write},
+    path::Path,
+};
+
+use crate::{json::Json, manifest::ManifestEntry};
+
+/// Journal of the entries a conversion finished, in the output folder.
+pub const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// Size and CRC of an archive entry as given by the zip directory, enough to
+/// tell whether an entry changed without decompressing it.
+#[derive(Clone, Copy, PartialEq)]
+pub struct EntryStamp {
+    pub size: u64,
+    pub crc: u32,
+}
+
+/// Stamps of every entry of the archive.
+pub fn stamps<R: Read + Seek>(zip: &mut zip::ZipArchive<R>) -> HashMap<String, EntryStamp> {
+    (0..zip.len())
+        .filter_map(|i| {
+            let file = zip.by_index_raw(i).ok()?;
+            let stamp = EntryStamp {
+                size: file.size(),
+                crc: file.crc32(),
+            };
+            Some((file.name().to_owned(), stamp))
+        })
+        .collect()
+}
+
+/// A finished entry read back from the journal.
+pub struct JournalRecord {
+    pub stamp: EntryStamp,
+    /// Manifest entry of the texture the entry was converted to.
+    pub converted: Option<ManifestEntry>,
+    /// Whether the entry was skipped as a placeholder.
+    pub placeholder: bool,
+}
+
+/// Append-only record of finished entries, one JSON object per line, so an
+/// interrupted conversion can be resumed with `--resume`.
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    pub fn open(folder: &str) -> io::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Path::new(folder).join(JOURNAL_FILE))?;
+        Ok(Journal { file })
+    }
+
+    /// Records `entry` as finished. Each record is written out right away so
+    /// it survives the process being killed.
+    pub fn record(
+        &mut self,
+        entry: &str,
+        stamp: EntryStamp,
+        converted: Option<&ManifestEntry>,
+        placeholder: bool,
+    ) -> io::Result<()> {
+        let record = Json::object()
+            .with("entry", entry)
+            .with("size", stamp.size)
+            .with("crc", stamp.crc)
+            .with("converted", converted.map(ManifestEntry::to_json));
+        let record = if placeholder {
+            record.with("placeholder", true)
+        } else {
+            record
+        };
+        writeln!(self.file, "{}", record)
+    }
+
+    /// Rewrites the journal in `folder` with its records sorted, so it doesn't
+    /// depend on the order the workers finished in.
+    pub fn sort(folder: &str) -> io::Result<()> {
+        let path = Path::new(folder).join(JOURNAL_FILE);
+        let text = fs::read_to_string(&path)?;
+        let mut lines = text.lines().collect::<Vec<_>>();
+        lines.sort();
+        fs::write(&path, lines.join("\n") + "\n")
+    }
+
+    /// Reads the journal of a previous run in `folder`. Lines that don't parse,
+    /// such as one cut short by the interruption, are ignored.
+    pub fn load(folder: &str) -> HashMap<String, JournalRecord> {
+        let Ok(text) = fs::read_to_string(Path::new(folder).join(JOURNAL_FILE)) else {
+            return HashMap::new();
+        };
+        text.lines()
+            .filter_map(|line| {
+                let json = Json::parse(line).ok()?;
+                let entry = json.get("entry")?.as_str()?.to_owned();
+                let stamp = EntryStamp {
+                    size: json.get("size")?.as_f64()? as u64,
+                    crc: json.get("crc")?.as_f64()? as u32,
+                };
+                let converted = json.get("converted").and_then(ManifestEntry::from_json);
+                let placeholder = matches!(json.get("placeholder"), Some(Json::Bool(true)));
+                Some((
+                    entry,
+                    JournalRecord {
+                        stamp,
+                        converted,
+                        placeholder,
+                    },
+                ))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
+
+use crate::json::Json;
+
+/// File the localized variants are grouped in with `--language`, in the
+/// output folder.
+pub const LANGUAGE_REPORT_FILE: &str = "languages.json";
+
+/// Language of a localized texture, given by the suffix ports add to its
+/// entry name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Japanese,
+}
+
+const LANGUAGES: [Language; 4] = [
+    Language::English,
+    Language::German,
+    Language::French,
+    Language::Japanese,
+];
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        LANGUAGES
+            .into_iter()
+            .find(|language| language.suffix() == value)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown language '{}', expected eng, ger, fra or jpn",
+                    value
+                )
+            })
+    }
+}
+
+impl Language {
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Language::English => "eng",
+            Language::German => "ger",
+            Language::French => "fra",
+            Language::Japanese => "jpn",
+        }
+    }
+}
+
+/// Entry `name` without its language suffix, and the language, for
+/// localized textures like `textures/title_static/gTitleCopyrightTex_ger`.
+pub fn split(name: &str) -> Option<(&str, Language)> {
+    let (base, suffix) = name.rsplit_once('_')?;
+    Some((base, suffix.parse().ok()?))
+}
+
+/// Whether the entry `name` is a texture localized to `language`.
+pub fn is_localized_to(name: &str, language: Language) -> bool {
+    split(name).is_some_and(|(_, suffix)| suffix == language)
+}
+
+/// Localized variants of the archive entries, by the entry name without
+/// its language suffix.
+pub struct LanguageGroups {
+    groups: BTreeMap<String, BTreeSet<Language>>,
+}
+
+impl LanguageGroups {
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut groups: BTreeMap<String, BTreeSet<Language>> = BTreeMap::new();
+        for (base, language) in names.into_iter().filter_map(split) {
+            groups.entry(base.to_owned()).or_default().insert(language);
+        }
+        LanguageGroups { groups }
+    }
+
+    /// Localized textures with a variant in another language but none in
+    /// `language`.
+    pub fn missing(&self, language: Language) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|(_, languages)| !languages.contains(&language))
+            .map(|(base, _)| base.as_str())
+            .collect()
+    }
+
+    pub fn to_json(&self, language: Language) -> Json {
+        let groups = self
+            .groups
+            .iter()
+            .fold(Json::object(), |json, (base, languages)| {
+                json.with(
+                    base,
+                    languages
+                        .iter()
+                        .map(|language| Json::from(language.suffix()))
+                        .collect::<Vec<_>>(),
+                )
+            });
+        Json::object()
+            .with("language", language.suffix())
+            .with("groups", groups)
+            .with(
+                "missing",
+                self.missing(language)
+                    .into_iter()
+                    .map(Json::from)
+                    .collect::<Vec<_>>(),
+            )
+    }
+}
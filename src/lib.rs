@@ -0,0 +1,673 @@
+//! Decoding of the texture resources of LUS O2R archives, shared by the
+//! converter and usable by other tools without the archive and YAML support.
+//!
+//! `decode_texture` turns a whole resource into an RGBA image. The header
+//! types, `decode::decode_entry` with the checks the converter reports and
+//! the per-format decoders under it are public for finer control. With the
+//! `png` feature `stream::decode_to_writer` encodes a resource straight to
+//! any writer.
+
+use std::{fmt, sync::Arc};
+
+use image::RgbaImage;
+
+pub mod compression;
+pub mod crc64;
+pub mod decode;
+pub mod interleave;
+pub mod json;
+pub mod pack_hash;
+pub mod palette;
+pub mod payload;
+pub mod pixels;
+#[cfg(feature = "png")]
+pub mod stream;
+pub mod swap;
+#[cfg(feature = "archive")]
+pub mod texture_query;
+
+use decode::{DecodedTexture, TextureDefinitions};
+use interleave::Deinterleave;
+use pixels::Expansion;
+use swap::ByteSwap;
+
+// libultra image formats (G_IM_FMT_*) and texel sizes (G_IM_SIZ_*)
+pub const G_IM_FMT_RGBA: u8 = 0;
+pub const G_IM_FMT_CI: u8 = 2;
+pub const G_IM_FMT_IA: u8 = 3;
+pub const G_IM_FMT_I: u8 = 4;
+pub const G_IM_SIZ_4B: u8 = 0;
+pub const G_IM_SIZ_8B: u8 = 1;
+pub const G_IM_SIZ_16B: u8 = 2;
+pub const G_IM_SIZ_32B: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureType {
+    Error,
+    RGBA32bpp,
+    RGBA16bpp,
+    Palette4bpp,
+    Palette8bpp,
+    Grayscale4bpp,
+    Grayscale8bpp,
+    GrayscaleAlpha4bpp,
+    GrayscaleAlpha8bpp,
+    GrayscaleAlpha16bpp,
+    GrayscaleAlpha1bpp,
+    TLUT,
+}
+
+impl TextureType {
+    /// Texture type of the id in texture headers, none for ids past `TLUT`.
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(TextureType::Error),
+            1 => Some(TextureType::RGBA32bpp),
+            2 => Some(TextureType::RGBA16bpp),
+            3 => Some(TextureType::Palette4bpp),
+            4 => Some(TextureType::Palette8bpp),
+            5 => Some(TextureType::Grayscale4bpp),
+            6 => Some(TextureType::Grayscale8bpp),
+            7 => Some(TextureType::GrayscaleAlpha4bpp),
+            8 => Some(TextureType::GrayscaleAlpha8bpp),
+            9 => Some(TextureType::GrayscaleAlpha16bpp),
+            10 => Some(TextureType::GrayscaleAlpha1bpp),
+            11 => Some(TextureType::TLUT),
+            _ => None,
+        }
+    }
+
+    /// Texture type of the RDP image format `fmt` and texel size `siz`, as
+    /// given to `gsDPSetTextureImage` and `gsDPSetTile`. YUV and the
+    /// combinations the RDP can't sample have none.
+    pub fn from_fmt_siz(fmt: u8, siz: u8) -> Option<Self> {
+        match (fmt, siz) {
+            (G_IM_FMT_RGBA, G_IM_SIZ_32B) => Some(TextureType::RGBA32bpp),
+            (G_IM_FMT_RGBA, G_IM_SIZ_16B) => Some(TextureType::RGBA16bpp),
+            (G_IM_FMT_CI, G_IM_SIZ_4B) => Some(TextureType::Palette4bpp),
+            (G_IM_FMT_CI, G_IM_SIZ_8B) => Some(TextureType::Palette8bpp),
+            (G_IM_FMT_I, G_IM_SIZ_4B) => Some(TextureType::Grayscale4bpp),
+            (G_IM_FMT_I, G_IM_SIZ_8B) => Some(TextureType::Grayscale8bpp),
+            (G_IM_FMT_IA, G_IM_SIZ_4B) => Some(TextureType::GrayscaleAlpha4bpp),
+            (G_IM_FMT_IA, G_IM_SIZ_8B) => Some(TextureType::GrayscaleAlpha8bpp),
+            (G_IM_FMT_IA, G_IM_SIZ_16B) => Some(TextureType::GrayscaleAlpha16bpp),
+            _ => None,
+        }
+    }
+
+    /// RDP image format and texel size of the texture type, the reverse of
+    /// `from_fmt_siz`. TLUTs are loaded as RGBA16 images; the 1-bit
+    /// intensity textures of the ports have no RDP equivalent.
+    pub fn to_fmt_siz(&self) -> Option<(u8, u8)> {
+        match self {
+            TextureType::RGBA32bpp => Some((G_IM_FMT_RGBA, G_IM_SIZ_32B)),
+            TextureType::RGBA16bpp | TextureType::TLUT => Some((G_IM_FMT_RGBA, G_IM_SIZ_16B)),
+            TextureType::Palette4bpp => Some((G_IM_FMT_CI, G_IM_SIZ_4B)),
+            TextureType::Palette8bpp => Some((G_IM_FMT_CI, G_IM_SIZ_8B)),
+            TextureType::Grayscale4bpp => Some((G_IM_FMT_I, G_IM_SIZ_4B)),
+            TextureType::Grayscale8bpp => Some((G_IM_FMT_I, G_IM_SIZ_8B)),
+            TextureType::GrayscaleAlpha4bpp => Some((G_IM_FMT_IA, G_IM_SIZ_4B)),
+            TextureType::GrayscaleAlpha8bpp => Some((G_IM_FMT_IA, G_IM_SIZ_8B)),
+            TextureType::GrayscaleAlpha16bpp => Some((G_IM_FMT_IA, G_IM_SIZ_16B)),
+            TextureType::GrayscaleAlpha1bpp | TextureType::Error => None,
+        }
+    }
+
+    /// Layout of the decoded pixels, none for the types holding no image.
+    pub fn to_image_type(&self) -> Option<image::ExtendedColorType> {
+        match self {
+            TextureType::RGBA32bpp => Some(image::ExtendedColorType::Rgba8),
+            TextureType::RGBA16bpp => Some(image::ExtendedColorType::Rgba8),
+            TextureType::Palette4bpp => Some(image::ExtendedColorType::Rgba8),
+            TextureType::Palette8bpp => Some(image::ExtendedColorType::Rgba8),
+            TextureType::Grayscale4bpp => Some(image::ExtendedColorType::La8),
+            TextureType::Grayscale8bpp => Some(image::ExtendedColorType::La8),
+            TextureType::GrayscaleAlpha4bpp => Some(image::ExtendedColorType::La8),
+            TextureType::GrayscaleAlpha8bpp => Some(image::ExtendedColorType::La8),
+            TextureType::GrayscaleAlpha16bpp => Some(image::ExtendedColorType::La8),
+            TextureType::GrayscaleAlpha1bpp => Some(image::ExtendedColorType::La8),
+            TextureType::TLUT | TextureType::Error => None,
+        }
+    }
+
+    pub fn is_grayscale(&self) -> bool {
+        matches!(
+            self,
+            TextureType::Grayscale4bpp
+                | TextureType::Grayscale8bpp
+                | TextureType::GrayscaleAlpha4bpp
+                | TextureType::GrayscaleAlpha8bpp
+                | TextureType::GrayscaleAlpha16bpp
+                | TextureType::GrayscaleAlpha1bpp
+        )
+    }
+
+    /// Bits of a texel, none for the types holding no image.
+    pub fn bits_per_pixel(&self) -> Option<u8> {
+        match self {
+            TextureType::RGBA32bpp => Some(32),
+            TextureType::RGBA16bpp => Some(16),
+            TextureType::Palette4bpp => Some(4),
+            TextureType::Palette8bpp => Some(8),
+            TextureType::Grayscale4bpp => Some(4),
+            TextureType::Grayscale8bpp => Some(8),
+            TextureType::GrayscaleAlpha4bpp => Some(4),
+            TextureType::GrayscaleAlpha8bpp => Some(8),
+            TextureType::GrayscaleAlpha16bpp => Some(16),
+            TextureType::GrayscaleAlpha1bpp => Some(1),
+            TextureType::TLUT | TextureType::Error => None,
+        }
+    }
+
+    /// Bytes of a row of `width` texels. Rows of 4-bit and 1-bit textures
+    /// start on a byte boundary, so odd widths leave the last byte partly
+    /// unused.
+    pub fn row_size(&self, width: u32) -> Result<usize, String> {
+        let bits = self
+            .bits_per_pixel()
+            .ok_or_else(|| format!("{:?} textures don't hold an image", self))?;
+        usize::try_from((bits as u64 * width as u64).div_ceil(8))
+            .map_err(|_| format!("Rows of {} texels are too large to decode", width))
+    }
+
+    /// Exact bytes of texel data of a `width`x`height` texture, `height` rows
+    /// of `row_size`. Validating the data size and decoding both go through
+    /// it, so they agree on where rows start.
+    pub fn data_size(&self, width: u32, height: u32) -> Result<usize, String> {
+        let row_size = self.row_size(width)?;
+        row_size.checked_mul(height as usize).ok_or_else(|| {
+            format!(
+                "{} rows of {} bytes are too large to decode",
+                height, row_size
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceType {
+    None = 0x00000000,
+
+    DisplayList = 0x4F444C54,     // ODLT
+    Light = 0x46669697,           // LGTS
+    Matrix = 0x4F4D5458,          // OMTX
+    Texture = 0x4F544558,         // OTEX
+    Vertex = 0x4F565458,          // OVTX
+    Text = 0x4F545854,            // OTXT
+    AudioSample = 0x4F534D50,     // OSMP
+    AudioSequence = 0x4F534551,   // OSEQ
+    AudioSoundFont = 0x4F534654,  // OSFT
+    Animation = 0x4F414E4D,       // OANM
+    Skeleton = 0x4F534B4C,        // OSKL
+    SkeletonLimb = 0x4F534C42,    // OSLB
+    CollisionHeader = 0x4F434F4C, // OCOL
+    Scene = 0x4F524F4D,           // OROM
+    Cutscene = 0x4F435554,        // OCUT
+    Path = 0x4F505448,            // OPTH
+}
+
+pub const OTR_HEADER_SIZE: usize = 64;
+/// Version of texture resources with a row stride after the height.
+pub const TEXTURE_STRIDE_VERSION: u32 = 2;
+/// Value packers write in place of the resource id.
+pub const OTR_HEADER_MAGIC: u64 = 0xDEADBEEFDEADBEEF;
+
+pub struct OTRHeader {
+    pub byte_order: i8,
+    pub is_custom: bool,
+    pub type_id: ResourceType,
+    pub version: u32,
+    pub id: u64,
+}
+
+impl OTRHeader {
+    pub fn new(
+        byte_order: i8,
+        is_custom: bool,
+        type_id: ResourceType,
+        version: u32,
+        id: u64,
+    ) -> Self {
+        OTRHeader {
+            byte_order,
+            is_custom,
+            type_id,
+            version,
+            id,
+        }
+    }
+
+    /// Header fields of the resource `data`. Only the first 20 bytes are
+    /// read, unknown resource types are `ResourceType::None`.
+    pub fn parse(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.len() < 20 {
+            return Err(DecodeError::Truncated(data.len()));
+        }
+        let byte_order = data[0] as i8;
+        let is_custom = data[1] != 0;
+        let type_id = match u32::from_le_bytes([data[4], data[5], data[6], data[7]]) {
+            0x00000000 => ResourceType::None,
+            0x4F444C54 => ResourceType::DisplayList, // ODLT
+            0x46669697 => ResourceType::Light,       // LGTS
+            0x4F4D5458 => ResourceType::Matrix,      // OMTX
+            0x4F544558 => ResourceType::Texture,     // OTEX
+            0x4F565458 => ResourceType::Vertex,      // OVTX
+            0x4F545854 => ResourceType::Text,        // OTXT
+            0x4F534D50 => ResourceType::AudioSample, // OSMP
+            0x4F534551 => ResourceType::AudioSequence, // OSEQ
+            0x4F534654 => ResourceType::AudioSoundFont, // OSFT
+            0x4F414E4D => ResourceType::Animation,   // OANM
+            0x4F534B4C => ResourceType::Skeleton,    // OSKL
+            0x4F534C42 => ResourceType::SkeletonLimb, // OSLB
+            0x4F434F4C => ResourceType::CollisionHeader, // OCOL
+            0x4F524F4D => ResourceType::Scene,       // OROM
+            0x4F435554 => ResourceType::Cutscene,    // OCUT
+            0x4F505448 => ResourceType::Path,        // OPTH
+            _ => ResourceType::None,
+        };
+        let version = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
+        let id = u64::from_le_bytes([
+            data[12], data[13], data[14], data[15], data[16], data[17], data[18], data[19],
+        ]);
+        Ok(OTRHeader::new(byte_order, is_custom, type_id, version, id))
+    }
+
+    /// Differences between the header of `data` and what the known packers
+    /// write: a byte order of 0 or 1, zeroed reserved bytes and the magic in
+    /// the id field.
+    pub fn deviations(data: &[u8]) -> Result<Vec<String>, DecodeError> {
+        if data.len() < OTR_HEADER_SIZE {
+            return Err(DecodeError::Truncated(data.len()));
+        }
+        let header = OTRHeader::parse(data)?;
+        let mut deviations = Vec::new();
+        if !matches!(header.byte_order, 0 | 1) {
+            deviations.push(format!("unknown byte order {}", header.byte_order));
+        }
+        if data[2..4] != [0, 0] {
+            deviations.push(format!(
+                "reserved bytes 0x02..0x04 are {:02x?}",
+                &data[2..4]
+            ));
+        }
+        if header.id != OTR_HEADER_MAGIC {
+            deviations.push(format!(
+                "id is {:016X} instead of {:016X}",
+                header.id, OTR_HEADER_MAGIC
+            ));
+        }
+        if let Some(offset) = data[20..OTR_HEADER_SIZE].iter().position(|byte| *byte != 0) {
+            deviations.push(format!("reserved byte 0x{:02x} is not zero", offset + 20));
+        }
+        Ok(deviations)
+    }
+}
+
+pub struct TextureFormat {
+    pub type_id: TextureType,
+    pub width: u32,
+    pub height: u32,
+    pub size: u32,
+    pub data: Vec<u8>,
+}
+
+impl TextureFormat {
+    pub fn new(type_id: TextureType, width: u32, height: u32, size: u32, data: Vec<u8>) -> Self {
+        TextureFormat {
+            type_id,
+            width,
+            height,
+            size,
+            data,
+        }
+    }
+
+    /// Texture header and texels of the texture resource `data`, whose
+    /// resource type isn't checked. Texels are read as stored, see
+    /// `decompress` for the compressed ones.
+    pub fn parse(data: &[u8]) -> Result<Self, DecodeError> {
+        let header = OTRHeader::parse(data)?;
+        // The stride comes before the size in the resources that have one
+        let offset = match header.version {
+            TEXTURE_STRIDE_VERSION => OTR_HEADER_SIZE + 16,
+            _ => OTR_HEADER_SIZE + 12,
+        };
+        if data.len() < offset + 4 {
+            return Err(DecodeError::Truncated(data.len()));
+        }
+        let field = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let type_id = field(OTR_HEADER_SIZE);
+        let type_id = TextureType::from_u32(type_id).ok_or(DecodeError::UnknownType(type_id))?;
+
+        Ok(TextureFormat::new(
+            type_id,
+            field(OTR_HEADER_SIZE + 4),
+            field(OTR_HEADER_SIZE + 8),
+            field(offset),
+            data[offset + 4..].to_vec(),
+        ))
+    }
+
+    /// Decompresses texels starting with the magic of MIO0, Yay0 or Yaz0, as
+    /// some archives store them. They mustn't decompress to more than the
+    /// `data_size` of the texture.
+    pub fn decompress(&mut self) -> Result<(), String> {
+        let limit = self.data_size()?;
+        if let Some(texels) = compression::decompress(&self.data, limit) {
+            self.data = texels.map_err(|err| format!("Failed to decompress texels: {}", err))?;
+        }
+        Ok(())
+    }
+
+    /// Bytes from the start of a row to the next, given by the header of
+    /// texture resources of `TEXTURE_STRIDE_VERSION`. 0 means packed rows.
+    pub fn stride(data: &[u8]) -> Option<u32> {
+        if OTRHeader::parse(data).ok()?.version != TEXTURE_STRIDE_VERSION {
+            return None;
+        }
+        let stride = data.get(OTR_HEADER_SIZE + 12..OTR_HEADER_SIZE + 16)?;
+        Some(u32::from_le_bytes(stride.try_into().unwrap()))
+    }
+
+    /// Number of texels. Corrupt headers can give dimensions too large to
+    /// decode, which overflow far sooner on 32-bit targets, so the decoded
+    /// size of up to 4 bytes a texel must fit in memory.
+    pub fn pixels(&self) -> Result<usize, String> {
+        (self.width as u64)
+            .checked_mul(self.height as u64)
+            .filter(|pixels| {
+                pixels
+                    .checked_mul(4)
+                    .is_some_and(|size| usize::try_from(size).is_ok())
+            })
+            .map(|pixels| pixels as usize)
+            .ok_or_else(|| {
+                format!(
+                    "Texture size {}x{} is too large to decode",
+                    self.width, self.height
+                )
+            })
+    }
+
+    /// Bytes of a row of `width` texels, padded to a whole byte.
+    pub fn row_size(&self, width: u32) -> Result<usize, String> {
+        self.type_id.row_size(width)
+    }
+
+    /// Bytes of texel data the texture takes, see `TextureType::data_size`.
+    pub fn data_size(&self) -> Result<usize, String> {
+        self.type_id.data_size(self.width, self.height)
+    }
+
+    /// Why the texture couldn't be loaded into the 4 KiB of TMEM at once, when
+    /// it couldn't: rows take whole 64-bit lines, RGBA32 texels are split
+    /// across both halves, and CI textures leave the upper half to their
+    /// TLUT. Such a texture usually has a wrong format or size in its header.
+    pub fn tmem_overflow(&self) -> Option<String> {
+        let (bits, capacity) = match self.type_id {
+            TextureType::RGBA32bpp => (16, 2048),
+            TextureType::Palette4bpp | TextureType::Palette8bpp => {
+                (self.type_id.bits_per_pixel()? as u64, 2048)
+            }
+            TextureType::GrayscaleAlpha1bpp | TextureType::TLUT | TextureType::Error => {
+                return None;
+            }
+            _ => (self.type_id.bits_per_pixel()? as u64, 4096),
+        };
+        let line_size = (bits * self.width as u64).div_ceil(64) * 8;
+        let size = line_size * self.height as u64;
+        (size > capacity).then(|| {
+            format!(
+                "{}x{} {:?} takes {} bytes of TMEM but only {} are available",
+                self.width, self.height, self.type_id, size, capacity
+            )
+        })
+    }
+}
+
+/// Copies `height` rows of `row_size` bytes, starting every `stride` bytes of
+/// `data`, into a buffer without padding between rows.
+pub fn pack_rows(
+    data: &[u8],
+    row_size: usize,
+    stride: usize,
+    height: u32,
+) -> Result<Vec<u8>, String> {
+    if stride < row_size {
+        return Err(format!(
+            "Stride of {} bytes is less than the row size of {} bytes",
+            stride, row_size
+        ));
+    }
+    // The padding of the last row may be left out
+    let needed = stride
+        .checked_mul(height as usize)
+        .ok_or_else(|| {
+            format!(
+                "{} rows of {} bytes are too large to decode",
+                height, stride
+            )
+        })?
+        .saturating_sub(stride - row_size);
+    if needed > data.len() {
+        return Err(format!(
+            "Data size {} is too small for {} rows of {} bytes",
+            data.len(),
+            height,
+            stride
+        ));
+    }
+    Ok(data
+        .chunks(stride)
+        .take(height as usize)
+        .flat_map(|row| &row[..row_size])
+        .copied()
+        .collect())
+}
+
+/// Decodes the texels `data` of `texture_format` to pixels laid out as its
+/// `TextureType::to_image_type`, CI textures with the colors of `tlut`.
+/// Channels narrower than 8 bits are widened as `expansion` says. Texels past
+/// the size of the texture are left out, and `None` is returned when `data`
+/// is shorter than that.
+pub fn decode_texels(
+    texture_format: &TextureFormat,
+    data: &[u8],
+    tlut: Option<&TextureFormat>,
+    expansion: Expansion,
+) -> Option<Vec<u8>> {
+    let pixels = texture_format.pixels().ok()?;
+    let data = data.get(..texture_format.data_size().ok()?)?;
+    match texture_format.type_id {
+        TextureType::RGBA32bpp => Some(data.to_vec()),
+        TextureType::RGBA16bpp => {
+            let mut new_data = Vec::with_capacity(pixels * 4);
+            pixels::rgba5551_to_rgba8888(&data[..pixels * 2], &mut new_data, expansion);
+            Some(new_data)
+        }
+        TextureType::Palette4bpp => {
+            let tlut = tlut?;
+            let mut new_data = Vec::with_capacity(pixels * 4);
+            for index in pixels::unpack_4bpp(data, texture_format.width, texture_format.height) {
+                let color = tlut.data.chunks(2).nth(index as usize).unwrap_or(&[1, 1]);
+                new_data.extend(pixels::rgba5551(color[0], color[1], expansion));
+            }
+            Some(new_data)
+        }
+        TextureType::Palette8bpp => {
+            let tlut = tlut?;
+            let mut new_data = Vec::with_capacity(pixels * 4);
+            for &index in data.iter().take(pixels) {
+                let color = tlut.data.chunks(2).nth(index as usize).unwrap_or(&[1, 1]);
+                new_data.extend(pixels::rgba5551(color[0], color[1], expansion));
+            }
+            Some(new_data)
+        }
+        TextureType::Grayscale4bpp => {
+            let mut new_data = Vec::with_capacity(pixels * 2);
+            for bits in pixels::unpack_4bpp(data, texture_format.width, texture_format.height) {
+                new_data.push(pixels::expand(bits, 4, expansion));
+                new_data.push(pixels::expand(bits, 4, expansion));
+            }
+            Some(new_data)
+        }
+        TextureType::Grayscale8bpp => {
+            let mut new_data = Vec::with_capacity(pixels * 2);
+            for &bits in data.iter().take(pixels) {
+                new_data.push(bits); // Grayscale
+                new_data.push(bits); // Alpha
+            }
+            Some(new_data)
+        }
+        TextureType::GrayscaleAlpha4bpp => {
+            let mut new_data = Vec::with_capacity(pixels * 2);
+            for bits in pixels::unpack_4bpp(data, texture_format.width, texture_format.height) {
+                new_data.push(pixels::expand((bits >> 1) & 0x07, 3, expansion));
+                new_data.push(if (bits & 0x01) != 0 { 0xFF } else { 0x00 });
+            }
+            Some(new_data)
+        }
+        TextureType::GrayscaleAlpha8bpp => {
+            let mut new_data = Vec::with_capacity(pixels * 2);
+            for &bits in data.iter().take(pixels) {
+                new_data.push(pixels::expand((bits & 0xF0) >> 4, 4, expansion)); // Grayscale
+                new_data.push(pixels::expand(bits & 0x0F, 4, expansion)); // Alpha
+            }
+            Some(new_data)
+        }
+        TextureType::GrayscaleAlpha16bpp => Some(data.to_vec()),
+        // The bit stands for both intensity and alpha
+        TextureType::GrayscaleAlpha1bpp => Some(
+            pixels::unpack_1bpp(data, texture_format.width, texture_format.height)
+                .into_iter()
+                .flat_map(|bit| [bit * 0xFF; 2])
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Why a texture resource couldn't be decoded by `decode_texture`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The resource, of the given size, is shorter than its headers.
+    Truncated(usize),
+    /// The resource isn't a texture.
+    NotATexture(ResourceType),
+    /// The texture type id is none the decoders know.
+    UnknownType(u32),
+    /// The texture type holds no image, such as a TLUT.
+    Unsupported(TextureType),
+    /// The CI texture of the given file name was decoded without its TLUT.
+    MissingTlut(String),
+    /// The texels don't match the header.
+    Invalid(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated(size) => {
+                write!(f, "Resource of {} bytes is too short for its headers", size)
+            }
+            DecodeError::NotATexture(type_id) => {
+                write!(f, "Resource is a {:?}, not a texture", type_id)
+            }
+            DecodeError::UnknownType(type_id) => write!(f, "Unknown texture type ID {}", type_id),
+            DecodeError::Unsupported(type_id) => {
+                write!(f, "{:?} textures don't hold an image", type_id)
+            }
+            DecodeError::MissingTlut(file_name) => {
+                write!(f, "Texture TLUT not found for {}", file_name)
+            }
+            DecodeError::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<DecodeError> for String {
+    fn from(err: DecodeError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Settings of the decoders, the same for every texture of a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecodeOptions {
+    /// How channels narrower than 8 bits are widened, `Expansion::Replicate`
+    /// by default.
+    pub expansion: Expansion,
+    /// Byte order the texels are stored in, `ByteSwap::Auto` to detect it
+    /// for each texture.
+    pub swap: ByteSwap,
+    /// Whether odd rows are stored with their TMEM word swap.
+    pub deinterleave: Deinterleave,
+    /// Whether texels starting with the magic of a compression format are
+    /// decompressed, see `TextureFormat::decompress`. Off by default, raw
+    /// texels can start with the same bytes.
+    pub compressed_texels: bool,
+    /// Whether CI8 pixels are opaque when either of the two low bits of
+    /// their color is set, as the option-less tool had it, rather than only
+    /// for the RGBA5551 alpha bit.
+    pub legacy_ci8_alpha: bool,
+}
+
+/// Definitions giving every CI texture the same TLUT, for the resources
+/// decoded on their own.
+struct SingleTlut(Option<Arc<TextureFormat>>);
+
+impl TextureDefinitions for SingleTlut {
+    fn tlut(&self, _name: &str, _type_id: &TextureType) -> Option<(&str, Arc<TextureFormat>)> {
+        Some(("tlut", self.0.clone()?))
+    }
+}
+
+/// Texture header and texels of the texture resource `data`, checking its
+/// resource type.
+fn parse_texture(data: &[u8]) -> Result<TextureFormat, DecodeError> {
+    let header = OTRHeader::parse(data)?;
+    if header.type_id != ResourceType::Texture {
+        return Err(DecodeError::NotATexture(header.type_id));
+    }
+    TextureFormat::parse(data)
+}
+
+/// Decodes the texture resource `data` to an RGBA image with `options`.
+/// Intensity textures are widened to gray RGBA. CI textures are decoded with
+/// `decode_texture_with_tlut`.
+pub fn decode_texture(data: &[u8], options: &DecodeOptions) -> Result<RgbaImage, DecodeError> {
+    decode_texture_with_tlut(data, None, options)
+}
+
+/// Decodes the texture resource `data` to an RGBA image as `decode_texture`
+/// does, CI textures with the colors of the TLUT resource `tlut`.
+pub fn decode_texture_with_tlut(
+    data: &[u8],
+    tlut: Option<&[u8]>,
+    options: &DecodeOptions,
+) -> Result<RgbaImage, DecodeError> {
+    let texture_format = parse_texture(data)?;
+    if texture_format.type_id.to_image_type().is_none() {
+        return Err(DecodeError::Unsupported(texture_format.type_id));
+    }
+    let tlut = tlut.map(parse_texture).transpose()?.map(Arc::new);
+    let DecodedTexture {
+        width,
+        height,
+        format,
+        data: texels,
+        ..
+    } = decode::decode_entry("texture", data, options, &SingleTlut(tlut))?
+        .ok_or(DecodeError::Unsupported(texture_format.type_id))?;
+    let rgba = match format {
+        image::ExtendedColorType::La8 => texels
+            .chunks_exact(2)
+            .flat_map(|texel| [texel[0], texel[0], texel[0], texel[1]])
+            .collect(),
+        _ => texels,
+    };
+    RgbaImage::from_raw(width, height, rgba)
+        .ok_or_else(|| DecodeError::Invalid("Texels don't fill the image".to_owned()))
+}
@@ -0,0 +1,145 @@
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, ResourceType,
+    decoder::ResourceDecoder,
+    json::Json,
+    log,
+    reader::Reader,
+    stream::{self, ImageOutputFormat},
+};
+
+/// Side in pixels of the square a color gets in a swatch sheet.
+const SWATCH_SIZE: u32 = 16;
+
+/// Directional light of a Light resource.
+pub struct DirectionalLight {
+    pub color: [u8; 3],
+    pub direction: [i8; 3],
+}
+
+/// Light set of a Light resource: the `Ambient` and `Light` structs of a
+/// `Lights1` or `Lights2`, with their copied colors and padding.
+pub struct Lights {
+    pub ambient: [u8; 3],
+    pub lights: Vec<DirectionalLight>,
+}
+
+impl Lights {
+    pub fn to_json(&self) -> Json {
+        let color = |color: &[u8; 3]| {
+            color
+                .iter()
+                .map(|value| Json::from(*value as u32))
+                .collect::<Vec<_>>()
+        };
+        Json::object().with("ambient", color(&self.ambient)).with(
+            "lights",
+            self.lights
+                .iter()
+                .map(|light| {
+                    Json::object().with("color", color(&light.color)).with(
+                        "direction",
+                        light
+                            .direction
+                            .iter()
+                            .map(|value| Json::from(*value as f64))
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Ambient color, then the color of each light.
+    pub fn colors(&self) -> Vec<[u8; 3]> {
+        std::iter::once(self.ambient)
+            .chain(self.lights.iter().map(|light| light.color))
+            .collect()
+    }
+}
+
+/// Reads the ambient light, 8 bytes, and as many 16-byte directional lights
+/// as follow it.
+pub fn parse(data: &[u8]) -> Result<Lights, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let color = |reader: &mut Reader| -> Result<[u8; 3], String> {
+        let bytes = reader.bytes(8)?;
+        Ok([bytes[0], bytes[1], bytes[2]])
+    };
+    let ambient = color(&mut reader)?;
+    let mut lights = Vec::new();
+    while data.len().saturating_sub(reader.position) >= 16 {
+        let color = color(&mut reader)?;
+        let direction = reader.bytes(4)?;
+        reader.bytes(4)?;
+        lights.push(DirectionalLight {
+            color,
+            direction: [direction[0] as i8, direction[1] as i8, direction[2] as i8],
+        });
+    }
+    Ok(Lights { ambient, lights })
+}
+
+/// PNG of a row of color squares for each of `rows`, so the colors of a light
+/// set can be seen at a glance. Shorter rows are padded with transparency.
+pub fn swatches(rows: &[Vec<[u8; 3]>]) -> Vec<u8> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0) as u32;
+    let width = columns * SWATCH_SIZE;
+    let height = rows.len() as u32 * SWATCH_SIZE;
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    for (y, x, color) in rows.iter().enumerate().flat_map(|(y, row)| {
+        row.iter()
+            .enumerate()
+            .map(move |(x, color)| (y as u32, x as u32, color))
+    }) {
+        for row in y * SWATCH_SIZE..(y + 1) * SWATCH_SIZE {
+            let start = (row * width + x * SWATCH_SIZE) as usize * 4;
+            for pixel in pixels[start..start + SWATCH_SIZE as usize * 4].chunks_mut(4) {
+                pixel.copy_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+        }
+    }
+    let mut encoded = Vec::new();
+    stream::write_image(
+        &mut encoded,
+        &pixels,
+        width,
+        height,
+        image::ExtendedColorType::Rgba8,
+        ImageOutputFormat::Png,
+    )
+    .unwrap();
+    encoded
+}
+
+/// Exports light sets to JSON, with a swatch sheet of their colors.
+pub struct LightDecoder;
+
+impl ResourceDecoder for LightDecoder {
+    fn name(&self) -> &'static str {
+        "light"
+    }
+
+    fn directory(&self) -> &'static str {
+        "lights"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::Light, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let lights = match parse(data) {
+            Ok(lights) => lights,
+            Err(err) => {
+                log::error(format!("Failed to parse light {}: {}", name, err));
+                return;
+            }
+        };
+
+        let base = converter.output_base(self, name);
+        log::progress(format!("Exporting light set: {}.json", base));
+        converter.write(&(base.clone() + ".json"), lights.to_json().pretty() + "\n");
+        converter.write(&(base + "_swatches.png"), swatches(&[lights.colors()]));
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    fs::File,
+    io::{LineWriter, Write},
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::options::Options;
+
+/// Kind of message printed while converting, each routed on its own with
+/// `--log`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Category {
+    /// What is exported where.
+    Progress,
+    /// Entries left out for lack of information or support: CI textures
+    /// without a TLUT, unknown resource versions, unexpected headers.
+    Skip,
+    /// Malformed entries and outputs that couldn't be written.
+    Error,
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "progress" => Ok(Category::Progress),
+            "skip" => Ok(Category::Skip),
+            "error" => Ok(Category::Error),
+            _ => Err(format!(
+                "Unknown log category '{}', expected progress, skip or error",
+                value
+            )),
+        }
+    }
+}
+
+/// Where the messages of a category go. Errors are printed to stderr, the
+/// other categories to stdout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Console,
+    File,
+    Both,
+    Off,
+}
+
+impl Target {
+    fn console(self) -> bool {
+        matches!(self, Target::Console | Target::Both)
+    }
+
+    fn file(self) -> bool {
+        matches!(self, Target::File | Target::Both)
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "console" => Ok(Target::Console),
+            "file" => Ok(Target::File),
+            "both" => Ok(Target::Both),
+            "off" => Ok(Target::Off),
+            _ => Err(format!(
+                "Unknown log target '{}', expected console, file, both or off",
+                value
+            )),
+        }
+    }
+}
+
+/// Parses the value of `--log`, comma separated `category=target` pairs:
+/// `skip=file,progress=off`.
+pub fn parse_routes(value: &str) -> Result<Vec<(Category, Target)>, String> {
+    value
+        .split(',')
+        .map(|route| {
+            let (category, target) = route
+                .split_once('=')
+                .ok_or_else(|| format!("Expected category=target in '{}'", route))?;
+            Ok((category.trim().parse()?, target.trim().parse()?))
+        })
+        .collect()
+}
+
+struct Logger {
+    progress: Target,
+    skip: Target,
+    error: Target,
+    file: Option<Mutex<LineWriter<File>>>,
+}
+
+impl Logger {
+    fn target(&self, category: Category) -> Target {
+        match category {
+            Category::Progress => self.progress,
+            Category::Skip => self.skip,
+            Category::Error => self.error,
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Sets up the routing of the options. With a `--log-file`, skips go only
+/// to the file and errors to both; `--quiet-skip` keeps skips off the
+/// console. Routes given with `--log` take precedence.
+pub fn init(options: &Options) {
+    let file = options.log_file.as_ref().map(|path| {
+        let file = File::create(path)
+            .unwrap_or_else(|err| panic!("Failed to create log file {}: {}", path, err));
+        Mutex::new(LineWriter::new(file))
+    });
+    let mut logger = Logger {
+        progress: Target::Console,
+        skip: if file.is_some() {
+            Target::File
+        } else {
+            Target::Console
+        },
+        error: if file.is_some() {
+            Target::Both
+        } else {
+            Target::Console
+        },
+        file,
+    };
+    if options.quiet_skip {
+        logger.skip = match logger.skip {
+            Target::Both | Target::File => Target::File,
+            Target::Console | Target::Off => Target::Off,
+        };
+    }
+    for (category, target) in &options.log_routes {
+        if target.file() && logger.file.is_none() {
+            panic!("Logging {:?} messages to a file needs --log-file", category);
+        }
+        match category {
+            Category::Progress => logger.progress = *target,
+            Category::Skip => logger.skip = *target,
+            Category::Error => logger.error = *target,
+        }
+    }
+    let _ = LOGGER.set(logger);
+}
+
+thread_local! {
+    /// Messages held back by `capture` on this thread.
+    static CAPTURED: RefCell<Option<Vec<(Category, String)>>> = const { RefCell::new(None) };
+}
+
+/// Messages held back while converting an entry, written with `flush` once
+/// the entries before it are done so that the log doesn't depend on the
+/// order the workers finish in.
+#[derive(Debug, Default)]
+pub struct Captured(Vec<(Category, String)>);
+
+impl Captured {
+    /// Appends the messages of `other`, held back later on.
+    pub fn extend(&mut self, other: Captured) {
+        self.0.extend(other.0);
+    }
+
+    /// Writes the messages where their categories are routed.
+    pub fn flush(self) {
+        for (category, message) in self.0 {
+            write(category, message);
+        }
+    }
+}
+
+/// Runs `f`, holding back the messages it writes on this thread rather than
+/// printing them.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Captured) {
+    let outer = CAPTURED.replace(Some(Vec::new()));
+    let result = f();
+    let captured = CAPTURED.replace(outer).unwrap_or_default();
+    (result, Captured(captured))
+}
+
+/// Prints `message` where its category is routed, or holds it back inside
+/// `capture`. Before `init`, as in the subcommands, everything goes to the
+/// console.
+pub fn write(category: Category, message: impl fmt::Display) {
+    let captured = CAPTURED.with_borrow_mut(|captured| {
+        captured
+            .as_mut()
+            .map(|captured| captured.push((category, message.to_string())))
+            .is_some()
+    });
+    if captured {
+        return;
+    }
+    let logger = LOGGER.get();
+    let target = logger.map_or(Target::Console, |logger| logger.target(category));
+    if target.console() {
+        match category {
+            Category::Error => eprintln!("{}", message),
+            _ => println!("{}", message),
+        }
+    }
+    if target.file()
+        && let Some(file) = logger.and_then(|logger| logger.file.as_ref())
+    {
+        let _ = writeln!(file.lock().unwrap(), "[{:?}] {}", category, message);
+    }
+}
+
+pub fn progress(message: impl fmt::Display) {
+    write(Category::Progress, message);
+}
+
+pub fn skip(message: impl fmt::Display) {
+    write(Category::Skip, message);
+}
+
+pub fn error(message: impl fmt::Display) {
+    write(Category::Error, message);
+}
@@ -1,567 +1,1298 @@
+use changelog::Changelog;
+use config::Config;
+use convert_texture_o2r::{
+    DecodeError, DecodeOptions, OTR_HEADER_MAGIC, OTR_HEADER_SIZE, OTRHeader, ResourceType,
+    TEXTURE_STRIDE_VERSION, TextureFormat, TextureType, crc64,
+    decode::{self, DecodedTexture, TextureDefinitions},
+    decode_texels, interleave, json, pack_hash, pack_rows, palette, payload, pixels, stream, swap,
+    texture_query,
+};
+use decoder::{Registry, ResourceDecoder};
+use display_list::Reference;
+use engine_meta::Sampling;
+use hash_db::HashDb;
+use journal::Journal;
+use language::LanguageGroups;
+use manifest::{Manifest, ManifestEntry};
+use metadata::ArchiveMetadata;
+use options::{Command, Layout, Options};
+use payload::PayloadTransform;
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
     env, fs,
-    io::Read,
+    io::{Read, Seek},
+    sync::{Arc, Mutex, OnceLock},
+    time::Instant,
 };
+use symbols::SymbolResolver;
+use tar::TarWriter;
+use tlut::{TextureTlut, Tluts};
 use walkdir::WalkDir;
+use yaml_dialect::YamlDialect;
 use zip::{self};
 
-fn scale_3_8(value: u8) -> u8 {
-    // Scale a 3-bit value to 8 bits
-    (value as u16 * 255 / 7) as u8
+mod alpha;
+mod audio;
+mod capabilities;
+mod changelog;
+mod ci16;
+mod classify;
+mod collision;
+mod config;
+mod cutscene;
+mod decoder;
+mod derive;
+mod dilate;
+mod display_list;
+mod emit_c;
+mod encode;
+mod engine_meta;
+mod explain;
+#[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+mod fuse;
+mod game;
+mod gltf;
+mod grep;
+mod hash_db;
+mod header_filter;
+mod info;
+mod io_profile;
+mod journal;
+mod language;
+mod light;
+mod log;
+mod manifest;
+mod memory;
+mod merge;
+mod metadata;
+mod multicall;
+mod names;
+mod options;
+mod patch;
+mod path;
+mod pipe;
+mod pipeline;
+mod post_process;
+mod profile;
+mod prune;
+mod quarantine;
+mod query;
+mod reader;
+mod reencode;
+mod relocation;
+mod replace;
+mod reproducible;
+mod rpc;
+mod scene;
+mod schema;
+mod sha256;
+mod skeleton;
+mod socket;
+mod symbols;
+mod tar;
+mod text;
+mod texture;
+mod thumbnail;
+mod tiles;
+mod tlut;
+mod tmem;
+mod torch;
+mod transform;
+mod yaml_dialect;
+
+/// Header of the texture resource `data` up to its size field, for packed
+/// texels to be written after it. A stride is kept as 0.
+fn texture_header(data: &[u8]) -> Vec<u8> {
+    let mut header = data[..OTR_HEADER_SIZE + 12].to_vec();
+    if TextureFormat::stride(data).is_some() {
+        header.extend_from_slice(&0u32.to_le_bytes());
+    }
+    header
 }
 
-fn scale_4_8(value: u8) -> u8 {
-    // Scale a 4-bit value to 8 bits
-    (value as u16 * 255 / 15) as u8
+/// Asset definitions of the decomp YAML files pointed to by the config, laid
+/// out as `dialect`, as `(symbol, definition)`.
+fn asset_definitions(config: &Config, dialect: YamlDialect) -> Vec<(String, yaml_rust2::Yaml)> {
+    WalkDir::new(&config.path)
+        .into_iter()
+        .filter_map(|file| file.ok())
+        .filter(|file| file.file_type().is_file())
+        .map(|file| {
+            file.path()
+                .to_str()
+                .expect("Failed to convert path to string")
+                .to_owned()
+        })
+        .filter(|file| file.ends_with(".yml") || file.ends_with(".yaml"))
+        .filter_map(|file_path| {
+            yaml_rust2::YamlLoader::load_from_str(&std::fs::read_to_string(file_path).ok()?).ok()
+        })
+        .flatten()
+        .flat_map(|document| dialect.definitions(document))
+        .collect()
 }
 
-fn scale_5_8(value: u8) -> u8 {
-    // Scale a 5-bit value to 8 bits
-    (value as u16 * 255 / 31) as u8
+/// Returns the TLUT used by each texture of the asset definitions.
+fn load_tlut_config(definitions: &[(String, yaml_rust2::Yaml)]) -> HashMap<String, TextureTlut> {
+    let mut texture_tlut: HashMap<String, TextureTlut> = HashMap::new();
+
+    definitions
+        .iter()
+        .filter_map(|(key, value)| {
+            let object = value.as_hash()?;
+            let tlut_str = yaml_dialect::tlut_symbol(object)?;
+            let palette_index = object
+                .get(&yaml_rust2::Yaml::String("palette_index".to_owned()))
+                .and_then(|index| index.as_i64())
+                .unwrap_or(0);
+            Some((key, tlut_str.to_owned(), palette_index))
+        })
+        .for_each(|(key, tlut_str, palette_index)| {
+            let palette_index = u8::try_from(palette_index)
+                .ok()
+                .filter(|index| *index < 16)
+                .unwrap_or_else(|| panic!("Invalid palette_index {} for {}", palette_index, key));
+            texture_tlut.insert(
+                key.to_owned(),
+                TextureTlut {
+                    symbol: tlut_str,
+                    palette_index,
+                },
+            );
+        });
+
+    texture_tlut
 }
 
+/// Returns the row pitch, in texels, of the textures whose definition sets
+/// `pitch` or `line_width`. Some fonts keep pad texels at the end of every
+/// row to fill whole TMEM lines.
+fn load_pitches(definitions: &[(String, yaml_rust2::Yaml)]) -> HashMap<String, u32> {
+    definitions
+        .iter()
+        .filter_map(|(key, value)| {
+            let object = value.as_hash()?;
+            let pitch = ["pitch", "line_width"]
+                .iter()
+                .find_map(|name| object.get(&yaml_rust2::Yaml::String(name.to_string())))?;
+            let pitch = pitch
+                .as_i64()
+                .and_then(|pitch| u32::try_from(pitch).ok())
+                .filter(|pitch| *pitch > 0)
+                .unwrap_or_else(|| panic!("Invalid pitch {:?} for {}", pitch, key));
+            Some((key.to_owned(), pitch))
+        })
+        .collect()
+}
 
-#[derive(Debug, PartialEq)]
-enum TextureType {
-    Error,
-    RGBA32bpp,
-    RGBA16bpp,
-    Palette4bpp,
-    Palette8bpp,
-    Grayscale4bpp,
-    Grayscale8bpp,
-    GrayscaleAlpha4bpp,
-    GrayscaleAlpha8bpp,
-    GrayscaleAlpha16bpp,
-    GrayscaleAlpha1bpp,
-    TLUT,
+/// What the asset definitions say about the textures decoded by
+/// `decode_entry`.
+struct EntryDefinitions<'a> {
+    tluts: &'a Tluts,
+    pitches: &'a HashMap<String, u32>,
+    /// Whether the entry is an I4 texture decoded as IA4.
+    i4_as_ia4: bool,
 }
 
-impl TextureType {
-    fn from_u32(value: u32) -> Self {
-        match value {
-            0 => TextureType::Error,
-            1 => TextureType::RGBA32bpp,
-            2 => TextureType::RGBA16bpp,
-            3 => TextureType::Palette4bpp,
-            4 => TextureType::Palette8bpp,
-            5 => TextureType::Grayscale4bpp,
-            6 => TextureType::Grayscale8bpp,
-            7 => TextureType::GrayscaleAlpha4bpp,
-            8 => TextureType::GrayscaleAlpha8bpp,
-            9 => TextureType::GrayscaleAlpha16bpp,
-            10 => TextureType::GrayscaleAlpha1bpp,
-            11 => TextureType::TLUT,
-            _ => panic!("Unknown texture type ID"),
-        }
+impl TextureDefinitions for EntryDefinitions<'_> {
+    fn tlut(&self, name: &str, type_id: &TextureType) -> Option<(&str, Arc<TextureFormat>)> {
+        self.tluts.definition(name, type_id)
     }
 
-    fn to_image_type(&self) -> image::ExtendedColorType {
-        match self {
-            TextureType::RGBA32bpp => image::ExtendedColorType::Rgba8,
-            TextureType::RGBA16bpp => image::ExtendedColorType::Rgba8,
-            TextureType::Palette4bpp => image::ExtendedColorType::Rgba8,
-            TextureType::Palette8bpp => image::ExtendedColorType::Rgba8,
-            TextureType::Grayscale4bpp => image::ExtendedColorType::La8,
-            TextureType::Grayscale8bpp => image::ExtendedColorType::La8,
-            TextureType::GrayscaleAlpha4bpp => image::ExtendedColorType::La8,
-            TextureType::GrayscaleAlpha8bpp => image::ExtendedColorType::La8,
-            TextureType::GrayscaleAlpha16bpp => image::ExtendedColorType::La8,
-            TextureType::GrayscaleAlpha1bpp => image::ExtendedColorType::La1,
-            _ => panic!("Unsupported texture type for conversion to image type"),
-        }
+    fn pitch(&self, file_name: &str) -> Option<u32> {
+        self.pitches.get(file_name).copied()
     }
 
-    fn bits_per_pixel(&self) -> u8 {
-        match self {
-            TextureType::RGBA32bpp => 32,
-            TextureType::RGBA16bpp => 16,
-            TextureType::Palette4bpp => 4,
-            TextureType::Palette8bpp => 8,
-            TextureType::Grayscale4bpp => 4,
-            TextureType::Grayscale8bpp => 8,
-            TextureType::GrayscaleAlpha4bpp => 4,
-            TextureType::GrayscaleAlpha8bpp => 8,
-            TextureType::GrayscaleAlpha16bpp => 16,
-            TextureType::GrayscaleAlpha1bpp => 1,
-            _ => panic!("Unsupported texture type for bits per pixel"),
-        }
+    fn i4_as_ia4(&self, _name: &str) -> bool {
+        self.i4_as_ia4
+    }
+
+    fn tlut_start(&self, name: &str, type_id: &TextureType) -> usize {
+        self.tluts.start(name, type_id)
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum ResourceType {
-    None = 0x00000000,
+/// What the asset definitions say about the textures of an archive, owned
+/// by the `TextureQuery` looking them up.
+struct ArchiveDefinitions {
+    tluts: Tluts,
+    pitches: HashMap<String, u32>,
+    config: Config,
+    /// Every I4 texture is decoded as IA4, with `--treat-i4-as-ia4`.
+    treat_i4_as_ia4: bool,
+}
+
+impl TextureDefinitions for ArchiveDefinitions {
+    fn tlut(&self, name: &str, type_id: &TextureType) -> Option<(&str, Arc<TextureFormat>)> {
+        self.tluts.definition(name, type_id)
+    }
+
+    fn pitch(&self, file_name: &str) -> Option<u32> {
+        self.pitches.get(file_name).copied()
+    }
+
+    fn i4_as_ia4(&self, name: &str) -> bool {
+        self.treat_i4_as_ia4 || self.config.treats_i4_as_ia4(name)
+    }
 
-    DisplayList = 0x4F444C54, // ODLT
-    Light = 0x46669697,       // LGTS
-    Matrix = 0x4F4D5458,      // OMTX
-    Texture = 0x4F544558,     // OTEX
-    Vertex = 0x4F565458,      // OVTX
+    fn tlut_start(&self, name: &str, type_id: &TextureType) -> usize {
+        self.tluts.start(name, type_id)
+    }
 }
 
-const OTR_HEADER_SIZE: usize = 64;
+type TextureQuery = texture_query::TextureQuery<fs::File, ArchiveDefinitions>;
 
-struct OTRHeader {
-    byte_order: i8,
-    is_custom: bool,
-    type_id: ResourceType,
-    version: u32,
-    id: u64,
+/// Texture lookups over the archive `zip`, decoded as `options` say.
+fn open_query(
+    options: &Options,
+    zip: zip::ZipArchive<fs::File>,
+    tluts: Tluts,
+    pitches: HashMap<String, u32>,
+    config: Config,
+) -> TextureQuery {
+    let definitions = ArchiveDefinitions {
+        tluts,
+        pitches,
+        config,
+        treat_i4_as_ia4: options.treat_i4_as_ia4,
+    };
+    TextureQuery::new(
+        zip,
+        definitions,
+        options.decode_options(),
+        options.payload.clone(),
+        options.cache_budget,
+    )
 }
 
-impl OTRHeader {
-    fn new(byte_order: i8, is_custom: bool, type_id: ResourceType, version: u32, id: u64) -> Self {
-        OTRHeader {
-            byte_order,
-            is_custom,
-            type_id,
-            version,
-            id,
-        }
-    }
+/// Decodes the archive entry `name` with the TLUTs and pitches of the asset
+/// definitions. Entries that aren't textures to convert (other resource
+/// types, TLUTs) give `Ok(None)`.
+fn decode_entry(
+    name: &str,
+    data: &[u8],
+    options: &DecodeOptions,
+    tluts: &Tluts,
+    pitches: &HashMap<String, u32>,
+    i4_as_ia4: bool,
+) -> Result<Option<DecodedTexture>, DecodeError> {
+    let definitions = EntryDefinitions {
+        tluts,
+        pitches,
+        i4_as_ia4,
+    };
+    decode::decode_entry(name, data, options, &definitions)
+}
 
-    fn parse(data: &[u8]) -> Self {
-        if data.len() < 20 {
-            panic!("Data too short to parse OTR header");
+/// The resource of the archive entry `name`, its payload unwrapped with
+/// `payload`.
+fn read_entry<R: Read + Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    name: &str,
+    payload: &PayloadTransform,
+) -> Option<Vec<u8>> {
+    let mut file = zip.by_name(name).ok()?;
+    let mut data = Vec::new();
+    let _ = file.read_to_end(&mut data);
+    Some(unwrap_payload(name, data, payload))
+}
+
+/// The resource in the payload `data` of the entry `name`. Payloads that
+/// can't be unwrapped are logged and returned as they are.
+fn unwrap_payload(name: &str, data: Vec<u8>, payload: &PayloadTransform) -> Vec<u8> {
+    match payload.unwrap(&data) {
+        Ok(Cow::Owned(resource)) => resource,
+        Ok(Cow::Borrowed(_)) => data,
+        Err(err) => {
+            log::error(format!("Failed to unwrap the payload of {}: {}", name, err));
+            data
         }
-        let byte_order = data[0] as i8;
-        let is_custom = data[1] != 0;
-        let type_id = match u32::from_le_bytes([data[4], data[5], data[6], data[7]]) {
-            0x00000000 => ResourceType::None,
-            0x4F444C54 => ResourceType::DisplayList, // ODLT
-            0x46669697 => ResourceType::Light,       // LGTS
-            0x4F4D5458 => ResourceType::Matrix,      // OMTX
-            0x4F544558 => ResourceType::Texture,     // OTEX
-            0x4F565458 => ResourceType::Vertex,      // OVTX
-            _ => ResourceType::None,
-        };
-        let version = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
-        let id = u64::from_le_bytes([
-            data[12], data[13], data[14], data[15], data[16], data[17], data[18], data[19],
-        ]);
-        OTRHeader::new(byte_order, is_custom, type_id, version, id)
     }
 }
 
-struct TextureFormat {
-    type_id: TextureType,
-    width: u32,
-    height: u32,
-    size: u32,
-    data: Vec<u8>,
+/// Like `read_entry`, but a read that fails partway, as a checksum mismatch
+/// or an I/O error, is an error rather than giving the data read so far.
+fn try_read_entry<R: Read + Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    name: &str,
+    payload: &PayloadTransform,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut file = match zip.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(|err| err.to_string())?;
+    Ok(Some(unwrap_payload(name, data, payload)))
+}
+
+/// What happened to an archive entry during conversion.
+struct EntryResult {
+    name: String,
+    converted: Option<ManifestEntry>,
+    palette_overflow: Option<palette::PaletteOverflow>,
+    palette_usage: Option<palette::PaletteUsage>,
+    /// Why the decoded texels couldn't be encoded to an image.
+    encode_failure: Option<String>,
+    /// Skipped as a filler texture with `--skip-placeholders`.
+    placeholder: bool,
+    timings: profile::Timings,
+}
+
+/// Everything needed to convert archive entries, shared by the workers.
+struct Converter<'a> {
+    options: &'a Options,
+    config: &'a Config,
+    tluts: &'a Tluts,
+    /// Row pitch of textures stored with padded rows.
+    pitches: &'a HashMap<String, u32>,
+    folder_name: &'a str,
+    file_names: &'a [String],
+    registry: &'a Registry,
+    /// Output paths of entries renamed so they don't collide with another
+    /// entry on case-insensitive file systems.
+    renames: &'a HashMap<String, String>,
+    /// Archive entries by resource id, built on first use.
+    resource_ids: OnceLock<HashMap<u64, String>>,
+    /// Asset definitions, loaded on first use.
+    symbols: OnceLock<SymbolResolver>,
+    /// How the display lists sample each texture, read on first use with
+    /// `--engine-meta`.
+    samplings: OnceLock<HashMap<String, Sampling>>,
+    /// Files written so far, relative to the output folder.
+    written: Mutex<Vec<String>>,
+    /// Hi-res pack names the textures are matched against.
+    hash_db: Option<&'a HashDb>,
+    /// Stream outputs are appended to instead of the output folder, with
+    /// `--output -`.
+    tar: Option<Mutex<TarWriter<fs::File>>>,
 }
 
-impl TextureFormat {
-    fn new(type_id: TextureType, width: u32, height: u32, size: u32, data: Vec<u8>) -> Self {
-        TextureFormat {
-            type_id,
-            width,
-            height,
-            size,
-            data,
+impl Converter<'_> {
+    fn convert(&self, name: String, data: Vec<u8>) -> EntryResult {
+        let mut result = EntryResult {
+            name,
+            converted: None,
+            palette_overflow: None,
+            palette_usage: None,
+            encode_failure: None,
+            placeholder: false,
+            timings: profile::Timings::default(),
+        };
+        let header = match OTRHeader::parse(&data) {
+            Ok(header) if data.len() >= OTR_HEADER_SIZE => header,
+            _ => {
+                log::error(format!(
+                    "File {} is too short to be a valid OTR file",
+                    result.name
+                ));
+                return result;
+            }
+        };
+
+        if self.options.strict
+            && let Ok(deviations) = OTRHeader::deviations(&data)
+            && !deviations.is_empty()
+        {
+            log::skip(format!(
+                "Skipping {}, unexpected header: {}",
+                result.name,
+                deviations.join(", ")
+            ));
+            return result;
+        }
+
+        if !self.options.header_filter.matches(&header) {
+            return result;
+        }
+        if let Some(query) = &self.options.query {
+            let selected = header.type_id == ResourceType::Texture
+                && TextureFormat::parse(&data).is_ok_and(|texture| query.matches(&texture));
+            if !selected {
+                return result;
+            }
         }
-    }
 
-    fn parse(data: &[u8]) -> Self {
-        if data.len() < 24 {
-            panic!("Data too short to parse texture format");
+        match self.registry.get(self.config, &header, &result.name) {
+            Ok(Some(decoder)) => {
+                let start = Instant::now();
+                decoder.decode(self, &data, &mut result);
+                result.timings.finish(start.elapsed());
+            }
+            Ok(None) => {}
+            Err(err) => log::skip(format!("Skipping {}: {}", result.name, err)),
         }
-        let type_id = match u32::from_le_bytes([
-            data[OTR_HEADER_SIZE],
-            data[OTR_HEADER_SIZE + 1],
-            data[OTR_HEADER_SIZE + 2],
-            data[OTR_HEADER_SIZE + 3],
-        ]) {
-            0 => TextureType::Error,
-            1 => TextureType::RGBA32bpp,
-            2 => TextureType::RGBA16bpp,
-            3 => TextureType::Palette4bpp,
-            4 => TextureType::Palette8bpp,
-            5 => TextureType::Grayscale4bpp,
-            6 => TextureType::Grayscale8bpp,
-            7 => TextureType::GrayscaleAlpha4bpp,
-            8 => TextureType::GrayscaleAlpha8bpp,
-            9 => TextureType::GrayscaleAlpha16bpp,
-            10 => TextureType::GrayscaleAlpha1bpp,
-            11 => TextureType::TLUT,
-            _ => panic!("Unknown texture type ID"),
+        result
+    }
+
+    /// Whether the I4 texture `name` is decoded as IA4, with
+    /// `--treat-i4-as-ia4` or an `i4_as_ia4` glob of the config.
+    fn i4_as_ia4(&self, name: &str) -> bool {
+        self.options.treat_i4_as_ia4 || self.config.treats_i4_as_ia4(name)
+    }
+
+    fn resource_ids(&self) -> &HashMap<u64, String> {
+        self.resource_ids.get_or_init(|| {
+            self.file_names
+                .iter()
+                .map(|name| (crc64::crc64(name), name.to_owned()))
+                .collect()
+        })
+    }
+
+    /// Archive entry with the resource id `hash`, the CRC-64 of its path.
+    fn resource_name(&self, hash: u64) -> Option<&str> {
+        self.resource_ids().get(&hash).map(String::as_str)
+    }
+
+    fn has_resource(&self, name: &str) -> bool {
+        self.resource_ids().contains_key(&crc64::crc64(name))
+    }
+
+    /// How the display lists of the archive sample the texture `name`, the
+    /// first one loading it deciding.
+    fn sampling(&self, name: &str) -> Option<Sampling> {
+        let samplings = self.samplings.get_or_init(|| {
+            // Read through a separate handle so the workers' readers aren't
+            // shared
+            let mut zip = zip::ZipArchive::new(
+                fs::File::open(&self.options.zip_file).expect("Failed to open zip file"),
+            )
+            .expect("Failed to read zip file");
+            let mut samplings = HashMap::new();
+            for entry in self.file_names {
+                let Some(data) = read_entry(&mut zip, entry, &self.options.payload) else {
+                    continue;
+                };
+                let header = OTRHeader::parse(&data);
+                if data.len() < OTR_HEADER_SIZE
+                    || !header.is_ok_and(|header| header.type_id == ResourceType::DisplayList)
+                {
+                    continue;
+                }
+                let Ok(commands) = display_list::parse_display_list(&data) else {
+                    continue;
+                };
+                for (image, sampling) in engine_meta::samplings(&commands) {
+                    let image = match image {
+                        Reference::Path(path) => Some(path.as_str()),
+                        Reference::Hash(hash) => self.resource_name(*hash),
+                        Reference::Segmented(_) => None,
+                    };
+                    if let Some(image) = image {
+                        samplings.entry(image.to_owned()).or_insert(sampling);
+                    }
+                }
+            }
+            samplings
+        });
+        samplings.get(name).copied()
+    }
+
+    fn symbols(&self) -> &SymbolResolver {
+        self.symbols.get_or_init(|| {
+            SymbolResolver::new(
+                self.config,
+                self.options.yaml_dialect,
+                self.options.symbols.as_deref(),
+            )
+        })
+    }
+
+    /// Output path of the archive entry `name` relative to the output folder,
+    /// without an extension.
+    fn output_name(&self, decoder: &dyn ResourceDecoder, name: &str) -> String {
+        let path = match self.renames.get(name) {
+            Some(path) => path.clone(),
+            None => names::sanitize(&names::nfc(&self.config.map_path(name))),
         };
-        let width = u32::from_le_bytes([
-            data[OTR_HEADER_SIZE + 4],
-            data[OTR_HEADER_SIZE + 5],
-            data[OTR_HEADER_SIZE + 6],
-            data[OTR_HEADER_SIZE + 7],
-        ]);
-        let height = u32::from_le_bytes([
-            data[OTR_HEADER_SIZE + 8],
-            data[OTR_HEADER_SIZE + 9],
-            data[OTR_HEADER_SIZE + 10],
-            data[OTR_HEADER_SIZE + 11],
-        ]);
-        let size = u32::from_le_bytes([
-            data[OTR_HEADER_SIZE + 12],
-            data[OTR_HEADER_SIZE + 13],
-            data[OTR_HEADER_SIZE + 14],
-            data[OTR_HEADER_SIZE + 15],
-        ]);
-        let texture_data = data[OTR_HEADER_SIZE + 16..].to_vec();
-
-        TextureFormat::new(type_id, width, height, size, texture_data)
+        match self.options.layout {
+            Layout::ByPath => path,
+            Layout::ByType => format!("{}/{}", decoder.directory(), path),
+            Layout::Flat => path.replace('/', "_"),
+        }
+    }
+
+    /// Output path of the archive entry `name`, without an extension.
+    fn output_base(&self, decoder: &dyn ResourceDecoder, name: &str) -> String {
+        format!("{}/{}", self.folder_name, self.output_name(decoder, name))
     }
-}
 
-fn convert_texture(data: Vec<u8>) {
-    let otr_format = OTRHeader::parse(&data);
-    let texture_format = TextureFormat::parse(&data);
+    /// Writes an output file, creating its directory. Failures are reported
+    /// and don't stop the conversion.
+    fn write(&self, path: &str, contents: impl AsRef<[u8]>) {
+        let contained = path
+            .strip_prefix(self.folder_name)
+            .and_then(|path| path.strip_prefix('/'))
+            .is_some_and(names::is_contained);
+        if !contained {
+            log::error(format!(
+                "Refusing to write {} outside of {}",
+                path, self.folder_name
+            ));
+            return;
+        }
+        let relative = &path[self.folder_name.len() + 1..];
+        let written = match &self.tar {
+            Some(tar) => tar.lock().unwrap().append(relative, contents.as_ref()),
+            None => {
+                let _ = fs::create_dir_all(std::path::Path::new(path).parent().unwrap());
+                fs::write(path, contents)
+            }
+        };
+        match written {
+            Ok(()) => self.record(relative),
+            Err(err) => log::error(format!("Failed to write {}: {}", path, err)),
+        }
+    }
 
-    println!("byte_order: {}", otr_format.byte_order);
-    println!("is_custom: {}", otr_format.is_custom);
-    println!("version: {}", otr_format.version);
-    println!("id: {}", otr_format.id);
+    /// Records the file `path` of the output folder as an output of this run,
+    /// so it isn't pruned.
+    fn record(&self, path: &str) {
+        self.written.lock().unwrap().push(path.to_owned());
+    }
 
-    println!("type_id: {:?}", texture_format.type_id);
-    println!("width: {}", texture_format.width);
-    println!("height: {}", texture_format.height);
-    println!("size: {}", texture_format.size);
+    /// Files written by this run, relative to the output folder, sorted.
+    fn written(&self) -> Vec<String> {
+        let mut written = self.written.lock().unwrap().clone();
+        written.sort();
+        written.dedup();
+        written
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    println!("{:?}", args);
-    let zip_file = args
-        .get(1)
-        .expect("Please provide a zip file path as the first argument.");
-    let mut zip =
-        zip::ZipArchive::new(std::fs::File::open(zip_file).expect("Failed to open zip file"))
-            .expect("Failed to read zip file");
-    println!("Number of files in zip: {}", zip.len());
-
-    let config_file = "config.yml";
-    if !std::path::Path::new(config_file).exists() {
-        panic!("Configuration file '{}' not found.", config_file);
+    let options = Options::parse(&args);
+    if options.stdin {
+        pipe::run(&options);
+        return;
     }
-
-    let config = yaml_rust2::YamlLoader::load_from_str(
-        &std::fs::read_to_string(config_file).expect("Failed to read config file"),
+    if let Command::Replace {
+        entry,
+        image,
+        output,
+    } = &options.command
+    {
+        replace::run(&options, entry, image, output);
+        return;
+    }
+    if let Command::ReencodeCi {
+        entry,
+        image,
+        output,
+    } = &options.command
+    {
+        reencode::run(&options, entry, image, output);
+        return;
+    }
+    if let Command::GenerateYaml { output } = &options.command {
+        torch::generate(&options, output);
+        return;
+    }
+    if let Command::Grep { pattern } = &options.command {
+        grep::run(&options, pattern);
+        return;
+    }
+    if let Command::Explain { entry } = &options.command {
+        explain::run(&options, entry);
+        return;
+    }
+    if let Command::Info { entry, hex } = &options.command {
+        info::run(&options, entry, *hex);
+        return;
+    }
+    if let Command::Patch {
+        folder,
+        output,
+        watch,
+    } = &options.command
+    {
+        patch::run(&options, folder, output, *watch);
+        return;
+    }
+    if let Command::Encode {
+        image,
+        format,
+        output,
+    } = &options.command
+    {
+        encode::run(image, format, output, options.expand);
+        return;
+    }
+    if let Command::Merge {
+        output,
+        patches,
+        resolve,
+    } = &options.command
+    {
+        merge::run(&options, output, patches, *resolve);
+        return;
+    }
+    if let Command::Transform { script, output } = &options.command {
+        transform::run(&options, script, output);
+        return;
+    }
+    if let Command::Schema { output } = &options.command {
+        schema::write(output);
+        return;
+    }
+    if let Command::InstallAliases { dir } = &options.command {
+        multicall::install(dir);
+        return;
+    }
+    if let Command::VersionInfo { json } = options.command {
+        if json {
+            println!("{}", capabilities::to_json().pretty());
+        } else {
+            print!("{}", capabilities::report());
+        }
+        return;
+    }
+    // Taken before anything is printed, messages go to stderr from then on
+    let tar = (options.output == "-").then(|| {
+        let stdout =
+            tar::take_stdout().unwrap_or_else(|err| panic!("Failed to stream to stdout: {}", err));
+        let mtime = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        Mutex::new(TarWriter::new(stdout, mtime))
+    });
+    log::init(&options);
+    if !options.serve_rpc {
+        println!("{:?}", args);
+    }
+    let mut zip = zip::ZipArchive::new(
+        std::fs::File::open(&options.zip_file).expect("Failed to open zip file"),
     )
-    .expect("Failed to parse YAML config file");
-
-    let mut tlut_texture: HashSet<String> = HashSet::new();
-    let mut texture_tlut: HashMap<String, String> = HashMap::new();
-    let mut texture_palette: HashMap<String, TextureFormat> = HashMap::new();
-
-    let config = &config[0];
-
-    // get the first element of the hashmap
-    let mut path = "";
-    let key_path = yaml_rust2::Yaml::String("path".to_owned());
-    for (_, value) in config.as_hash().expect("Config is not a hash") {
-        let hash_map = value.as_hash().unwrap();
-        if hash_map.contains_key(&key_path) {
-            path = hash_map
-                .get(&key_path)
-                .expect("Path key not found in config")
-                .as_str()
-                .expect("Path value is not a string");
-            break;
+    .expect("Failed to read zip file");
+    let metadata = ArchiveMetadata::read(&mut zip, &options.payload);
+    if !options.serve_rpc {
+        println!("Number of files in zip: {}", zip.len());
+        if let Some(port_version) = metadata.port_version_string() {
+            println!("Port version: {}", port_version);
+        }
+        if !metadata.game_versions.is_empty() {
+            let crcs = metadata
+                .game_versions
+                .iter()
+                .map(|crc| format!("{:08x}", crc))
+                .collect::<Vec<_>>();
+            println!("Game versions: {}", crcs.join(", "));
+        }
+        if let Some(comment) = &metadata.comment {
+            println!("Archive comment: {}", comment);
         }
     }
+    if let Some(required) = &options.require_port_version {
+        metadata
+            .check_port_version(required)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
 
-    WalkDir::new(path)
-        .into_iter()
-        .filter_map(|file| file.ok())
-        .filter(|file| file.file_type().is_file())
-        .map(|file| {
-            file.path()
-                .to_str()
-                .expect("Failed to convert path to string")
-                .to_owned()
-        })
-        .filter(|file| file.ends_with(".yml") || file.ends_with(".yaml"))
-        .filter_map(|file_path| {
-            yaml_rust2::YamlLoader::load_from_str(&std::fs::read_to_string(file_path).ok()?).ok()
-        })
-        .flat_map(std::convert::identity)
-        .filter_map(|yaml| yaml.into_hash())
-        .flat_map(std::convert::identity)
-        .filter_map(|(key, value)| {
-            let object = value.as_hash()?;
-            let mut tlut = { object.get(&yaml_rust2::Yaml::String("tlut".to_owned())) };
-            if tlut.is_none() {
-                tlut = object.get(&yaml_rust2::Yaml::String("tlut_symbol".to_owned()));
+    let config = Config::load(&options.config);
+    // Without definitions every CI texture is skipped for lack of a TLUT
+    let asset_problem = match config.yaml_file_count() {
+        Ok(0) => Some(format!(
+            "No YAML files found under the asset path '{}'",
+            config.path
+        )),
+        Ok(count) => {
+            if !options.serve_rpc {
+                println!("{} YAML files found under {}", count, config.path);
             }
-            let tlut = tlut?;
-            let tlut_str = tlut.as_str()?;
-            Some((key, tlut_str.to_owned()))
-        })
-        .for_each(|(key, tlut_str)| {
-            tlut_texture.insert(tlut_str.to_owned());
-            texture_tlut.insert(
-                key.as_str().expect("Key is not a string").to_owned(),
-                tlut_str.to_owned(),
-            );
-        });
-
-    let file_names = zip
+            None
+        }
+        Err(err) => Some(err),
+    };
+    if let Some(problem) = asset_problem {
+        if options.strict {
+            panic!("{} in {}", problem, options.config);
+        }
+        log::error(format!(
+            "{} in {}, CI textures will be skipped without the TLUTs of the asset definitions",
+            problem, options.config
+        ));
+    }
+    // Sorted so the entries are processed, and TLUTs looked up by name, the
+    // same way whatever order the central directory lists them in
+    let mut file_names = zip
         .file_names()
         .map(|name| name.to_owned())
         .collect::<Vec<String>>();
+    file_names.sort();
 
-    for path in file_names.clone().into_iter().filter(|path| {
-        tlut_texture
-            .iter()
-            .filter(|tlut| path.contains(*tlut))
-            .count()
-            > 0
-    }) {
-        let Some(mut file) = zip.by_name(&path).ok() else {
-            continue;
-        };
-        let mut data = Vec::new();
-        let _ = file.read_to_end(&mut data);
-        texture_palette.insert(file.name().to_owned(), TextureFormat::parse(&data));
+    let definitions = asset_definitions(&config, options.yaml_dialect);
+    let tluts = Tluts::open(&options, &file_names, load_tlut_config(&definitions));
+    let pitches = load_pitches(&definitions);
+
+    #[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+    if let Command::Mount { mountpoint } = &options.command {
+        fuse::mount(
+            &options,
+            mountpoint,
+            zip,
+            &file_names,
+            tluts,
+            pitches,
+            config,
+        );
+        return;
     }
 
-    let folder_name = "assets";
-    fs::remove_dir_all(folder_name).ok();
-    fs::create_dir_all(folder_name).expect("Failed to create folder");
+    if options.serve_rpc {
+        rpc::serve(&options, zip, metadata, file_names, tluts, pitches, config);
+        return;
+    }
 
-    println!("{:?} TLUT textures found", texture_tlut);
+    // Read before the output folder is cleared, it may hold the manifest
+    let previous_textures = options.changelog.as_ref().map(|path| {
+        Manifest::load_textures(path)
+            .unwrap_or_else(|err| panic!("Failed to read manifest {}: {}", path, err))
+    });
 
-    for path in file_names {
-        let Some(mut file) = zip.by_name(&path).ok() else {
-            continue;
-        };
-        let mut data = Vec::new();
-        let _ = file.read_to_end(&mut data);
-        if data.len() < OTR_HEADER_SIZE {
-            println!("File {} is too short to be a valid OTR file", file.name());
-            continue;
-        }
-        let otr_format = OTRHeader::parse(&data);
-        if otr_format.type_id != ResourceType::Texture {
-            continue;
+    let retried = options.retry_failed.as_ref().map(|path| {
+        quarantine::load(path)
+            .unwrap_or_else(|err| panic!("Failed to read failed list {}: {}", path, err))
+            .into_iter()
+            .collect::<HashSet<_>>()
+    });
+
+    let folder_name = options.output.as_str();
+    let streamed = tar.is_some();
+    if options.clear_output && !streamed {
+        match fs::remove_dir_all(folder_name) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                panic!(
+                    "Failed to clear the output folder '{}': {}",
+                    folder_name, err
+                )
+            }
+            _ => {}
         }
-        let texture_format = TextureFormat::parse(&data);
-        let name = file.name().to_owned();
-        if !(otr_format.type_id == ResourceType::Texture
-            && texture_format.type_id != TextureType::Error
-            && texture_format.type_id != TextureType::TLUT)
-        {
-            continue;
+    }
+    // Only clear folders a previous run wrote to, the output path may come
+    // from the environment
+    let output_path = std::path::Path::new(folder_name);
+    let previous_run = !streamed
+        && (output_path.join(manifest::MANIFEST_FILE).exists()
+            || output_path.join(journal::JOURNAL_FILE).exists());
+    // Outputs of the previous run, pruned at the end unless this run writes
+    // them again
+    let mut previous_files = Vec::new();
+    if previous_run {
+        if !options.resume {
+            let _ = fs::remove_file(output_path.join(journal::JOURNAL_FILE));
+            let manifest_path = format!("{}/{}", folder_name, manifest::MANIFEST_FILE);
+            match Manifest::load_files(&manifest_path) {
+                Ok(files) => previous_files = files,
+                // An interrupted run leaves no manifest telling its outputs
+                // apart from other files
+                Err(_) if !options.keep_stale => {
+                    fs::remove_dir_all(folder_name).ok();
+                }
+                Err(_) => {}
+            }
         }
+    } else if !streamed
+        && output_path
+            .read_dir()
+            .is_ok_and(|mut entries| entries.next().is_some())
+    {
+        log::error(format!(
+            "Output folder '{}' is not empty and has no {}, pass --clear-output to empty it first",
+            folder_name,
+            manifest::MANIFEST_FILE
+        ));
+        std::process::exit(1);
+    }
+    if !streamed {
+        fs::create_dir_all(folder_name).expect("Failed to create folder");
+    }
+    let mut finished = if options.resume {
+        Journal::load(folder_name)
+    } else {
+        HashMap::new()
+    };
+    let stamps = journal::stamps(&mut zip);
+    // Legacy mode writes no journal or manifest, as the old tool
+    let mut journal = (!streamed && !options.legacy)
+        .then(|| Journal::open(folder_name).expect("Failed to open journal"));
 
-        let path = folder_name.to_owned() + "/" + &name + ".png";
-        let file_name = name.split('/').last().unwrap();
+    println!("{} TLUT textures found", tluts.len());
 
-        println!("Processing texture: {}", path);
+    let selected_names = if options.symbol_names.is_some() || options.ids.is_some() {
+        let resolver =
+            SymbolResolver::new(&config, options.yaml_dialect, options.symbols.as_deref());
+        let names = file_names
+            .iter()
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+        let symbols = options.symbol_names.iter().flatten().map(|symbol| {
+            let path = resolver
+                .resolve(symbol, &names)
+                .unwrap_or_else(|err| panic!("{}", err));
+            println!("Symbol {} resolved to {}", symbol, path);
+            path
+        });
+        let ids = options.ids.iter().flatten().map(|id| {
+            let path = file_names
+                .iter()
+                .find(|name| crc64::crc64(name) == *id)
+                .unwrap_or_else(|| panic!("No entry has the resource id {:016X}", id));
+            println!("Id {:016X} resolved to {}", id, path);
+            path.to_owned()
+        });
+        symbols.chain(ids).collect()
+    } else {
+        file_names.clone()
+    };
 
-        let _ = fs::create_dir_all(std::path::Path::new(&path).parent().unwrap());
+    // Tiles are only written stitched together, for the images with a
+    // selected tile
+    let names = file_names
+        .iter()
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+    let tiled = config
+        .tiled
+        .iter()
+        .map(|(path, columns)| (path.as_str(), *columns, tiles::tile_names(path, &names)))
+        .filter(|(_, _, tiles)| tiles.iter().any(|tile| selected_names.contains(tile)))
+        .filter(|(path, _, _)| {
+            options
+                .language
+                .is_none_or(|language| language::is_localized_to(path, language))
+        })
+        .collect::<Vec<_>>();
+    let tile_names = tiled
+        .iter()
+        .flat_map(|(_, _, tiles)| tiles.iter().map(String::as_str))
+        .collect::<HashSet<_>>();
+    // Localization teams only extract the variants of their language
+    let selected_names = selected_names
+        .into_iter()
+        .filter(|name| !tile_names.contains(name.as_str()))
+        .filter(|name| {
+            options
+                .language
+                .is_none_or(|language| language::is_localized_to(name, language))
+        })
+        // Only the quarantined entries are converted again, the others are
+        // taken from the journal
+        .filter(|name| {
+            retried
+                .as_ref()
+                .is_none_or(|retried| retried.contains(name) || finished.contains_key(name))
+        })
+        .collect::<Vec<_>>();
 
-        let format = texture_format.type_id.to_image_type();
-        let mut data = texture_format.data;
+    let mapped_path = |name: &str| names::sanitize(&names::nfc(&config.map_path(name)));
 
-        println!("size: {}", texture_format.size);
+    // Hostile names could otherwise write anywhere the user can
+    let escaping = selected_names
+        .iter()
+        .filter(|name| !names::is_contained(&names::nfc(&config.map_path(name))))
+        .collect::<Vec<_>>();
+    if !escaping.is_empty() {
+        println!(
+            "{} entries have paths leaving the output folder:",
+            escaping.len()
+        );
+        for name in &escaping {
+            println!("  {} is written to {}", name, mapped_path(name));
+        }
+        if options.strict {
+            panic!("Entries with unsafe paths, run without --strict to write them sanitized");
+        }
+    }
 
-        if (((texture_format.type_id.bits_per_pixel() as u32 * texture_format.width * texture_format.height)/8) as usize)
-            > data.len()
-        {
-            println!(
-                "Data size does not match expected size for {}: {} vs {}",
-                file.name(),
-                data.len(),
-                ((format.bits_per_pixel() as u32 * texture_format.width * texture_format.height)/8) as usize
-            );
-            continue;
+    // Entries only differing by case or Unicode normalization would
+    // overwrite each other on macOS and Windows
+    let collisions = names::collisions(&selected_names, |name| match options.layout {
+        Layout::Flat => mapped_path(name).replace('/', "_"),
+        _ => mapped_path(name),
+    });
+    if !collisions.is_empty() {
+        println!(
+            "{} groups of entries have output paths only differing by case or Unicode normalization:",
+            collisions.len()
+        );
+        for group in &collisions {
+            println!("  {}", group.join(", "));
         }
+        if options.strict {
+            panic!("Output path collisions, rename the entries or run without --strict");
+        }
+    }
+    let renames = names::disambiguate(&collisions, mapped_path);
+    for (name, path) in renames.iter().collect::<BTreeMap<_, _>>() {
+        println!("Writing {} to {} to avoid a collision", name, path);
+    }
+
+    let registry = Registry::new(options.types.as_deref());
+    let hash_db = options.hash_db.as_deref().map(|path| {
+        let hash_db = HashDb::load(path);
+        println!(
+            "{} textures named in the hash database {}",
+            hash_db.count(),
+            path
+        );
+        hash_db
+    });
+    let mut manifest = Manifest::new(&options.zip_file, &config.path_map);
 
-        match texture_format.type_id {
-            TextureType::RGBA32bpp => {
-                println!("Converting RGBA32bpp texture");
+    // Entries finished by the interrupted run are skipped if the archive entry
+    // is unchanged and its texture is still there
+    let (skipped, selected_names): (Vec<_>, Vec<_>) =
+        selected_names.into_iter().partition(|name| {
+            finished.get(name).is_some_and(|record| {
+                stamps.get(name) == Some(&record.stamp)
+                    && record
+                        .converted
+                        .as_ref()
+                        .is_none_or(|entry| output_path.join(&entry.output).exists())
+            })
+        });
+    if options.resume {
+        println!("Resuming, {} entries already converted", skipped.len());
+    }
+    for name in skipped {
+        if let Some(record) = finished.remove(&name) {
+            if record.placeholder {
+                manifest.placeholders.push(name);
             }
-            TextureType::RGBA16bpp => {
-                println!("Converting RGBA16bpp texture");
-                let mut new_data = Vec::with_capacity(
-                    (texture_format.height * texture_format.width * 4)
-                        .try_into()
-                        .unwrap(),
+            manifest.textures.extend(record.converted);
+        }
+    }
+    let mut palette_overflows = Vec::new();
+    let mut encode_failures = Vec::new();
+    let mut palette_report = palette::PaletteReport::default();
+    let mut profile = profile::Profile::default();
+
+    let converter = Converter {
+        options: &options,
+        config: &config,
+        tluts: &tluts,
+        pitches: &pitches,
+        folder_name,
+        file_names: &file_names,
+        registry: &registry,
+        renames: &renames,
+        resource_ids: OnceLock::new(),
+        symbols: OnceLock::new(),
+        samplings: OnceLock::new(),
+        written: Mutex::new(Vec::new()),
+        hash_db: hash_db.as_ref(),
+        tar,
+    };
+
+    let largest = selected_names
+        .iter()
+        .filter_map(|name| stamps.get(name))
+        .map(|stamp| stamp.size)
+        .max()
+        .unwrap_or(0);
+    let (threads, io_threads) = match options.memory_limit {
+        Some(limit) => {
+            let (threads, io_threads) = memory::fit_threads(
+                limit,
+                largest,
+                options.threads,
+                options.io_threads,
+                options.image_format,
+            );
+            if threads < options.threads || io_threads < options.io_threads {
+                let requested = memory::predict(
+                    largest,
+                    options.threads,
+                    options.io_threads,
+                    options.image_format,
                 );
-                for i in 0..(texture_format.height * texture_format.width) as usize {
-                    new_data.push(scale_5_8((data[i * 2] & 0xF8) >> 3)); // R
-                    new_data.push(scale_5_8(((data[i * 2] & 0x07) << 2) | ((data[i * 2 + 1] & 0xc0) >> 6))); // G
-                    new_data.push(scale_5_8((data[i * 2 + 1] & 0x3E) >> 1)); // B
-                    new_data.push(if (data[i * 2 + 1] & 0x01) != 0 { 0xFF } else { 0x00 }); // A
-                }
-                data = new_data;
-            }
-            TextureType::Palette4bpp => {
-                println!("Converting Palette4bpp texture");
-                let mut new_data = Vec::with_capacity(
-                    (texture_format.height * texture_format.width * 4)
-                        .try_into()
-                        .unwrap(),
+                println!(
+                    "Predicted memory use of {} is over the limit of {}, decoding with {} workers and {} readers",
+                    memory::format_size(requested),
+                    memory::format_size(limit),
+                    threads,
+                    io_threads
                 );
-                if !texture_tlut.contains_key(file_name) {
-                    println!("Texture TLUT not found for {}", file_name);
-                    continue;
-                }
-                let tlut = texture_tlut.get(file_name).unwrap();
-                let Some(tlut) = texture_palette
-                        .iter().find(|(name, _)| name.contains(tlut)) else {
-                    println!("Texture TLUT not found for {}", file_name);
-                    continue;
-                };
-                for i in 0..(texture_format.height * texture_format.width) as usize {
-                    let index = data[i / 2] >> if i % 2 == 0 { 4 } else { 0 } & 0x0F;
-                    let color = tlut
-                        .1
-                        .data
-                        .chunks(2)
-                        .nth(index as usize)
-                        .unwrap_or(&[1, 1]);
-                    let r = scale_5_8((color[0] & 0xF8) >> 3);
-                    let g = scale_5_8(((color[0] & 0x07) << 2) | ((color[1] & 0xc0) >> 6));
-                    let b = scale_5_8((color[1] & 0x3E) >> 1);
-                    let a = if (color[1] & 0x01) != 0 { 0xFF } else { 0x00 };
-                    new_data.push(r); // R
-                    new_data.push(g); // G
-                    new_data.push(b); // B
-                    new_data.push(a); // A
-                }
-                data = new_data;
             }
-            TextureType::Palette8bpp => {
-                println!("Converting Palette8bpp texture");
-                let mut new_data = Vec::with_capacity(
-                    (texture_format.height * texture_format.width * 4)
-                        .try_into()
-                        .unwrap(),
-                );
-                if !texture_tlut.contains_key(file_name) {
-                    println!("Texture TLUT not found for {}", file_name);
-                    continue;
-                }
-
-                let tlut = texture_tlut.get(file_name).unwrap();
-                let Some(tlut) = texture_palette
-                        .iter().find(|(name, _)| name.contains(tlut)) else {
-                    println!("Texture TLUT not found for {}", file_name);
-                    continue;
-                };
+            // Entries are decoded whole, so nothing else brings it down
+            let predicted = memory::predict(largest, threads, io_threads, options.image_format);
+            if predicted > limit {
+                log::error(format!(
+                    "Predicted memory use of {} is still over the limit of {} with a single worker and reader, raise --memory-limit to convert {}",
+                    memory::format_size(predicted),
+                    memory::format_size(limit),
+                    options.zip_file
+                ));
+                std::process::exit(1);
+            }
+            (threads, io_threads)
+        }
+        None => (options.threads, options.io_threads),
+    };
 
-                for i in 0..(texture_format.height * texture_format.width) as usize {
-                    let index = data[i] as usize;
-                    let color = tlut
-                        .1
-                        .data
-                        .chunks(2)
-                        .nth(index as usize)
-                        .unwrap_or(&[1, 1]);
-                    let r = scale_5_8((color[0] & 0xF8) >> 3);
-                    let g = scale_5_8(((color[0] & 0x07) << 2) | ((color[1] & 0xc0) >> 6));
-                    let b = scale_5_8((color[1] & 0x3E) >> 1);
-                    let a = if (color[1] & 0x03) != 0 {
-                        0xFF
-                    } else {
-                        0x00
-                    };
-                    new_data.push(r); // R
-                    new_data.push(g); // G
-                    new_data.push(b); // B
-                    new_data.push(a); // A
-                }
-                data = new_data;
+    memory::reset_peak();
+    let read_failures = pipeline::run(
+        &options.zip_file,
+        selected_names,
+        io_threads,
+        options.io_profile.read_ahead(),
+        threads,
+        options.read_retries,
+        &options.payload,
+        |name, data| converter.convert(name, data),
+        |result| {
+            if let Some(journal) = &mut journal
+                && let Some(stamp) = stamps.get(&result.name)
+                && let Err(err) = journal.record(
+                    &result.name,
+                    *stamp,
+                    result.converted.as_ref(),
+                    result.placeholder,
+                )
+            {
+                log::error(format!(
+                    "Failed to record {} in the journal: {}",
+                    result.name, err
+                ));
             }
-            TextureType::Grayscale4bpp => {
-                println!("Converting Grayscale4bpp texture");
-                let mut new_data = Vec::with_capacity(
-                    (texture_format.height * texture_format.width)
-                        .try_into()
-                        .unwrap(),
-                );
-                for i in 0..(texture_format.height * texture_format.width) as usize {
-                    let mut bits = data[i / 2];
-                    if i % 2 != 0 {
-                        bits &= 0xF;
-                    } else {
-                        bits >>= 4;
-                    }
-                    new_data.push(scale_4_8(bits));
-                    new_data.push(scale_4_8(bits));
-                }
-                data = new_data;
+            if let Some(overflow) = result.palette_overflow {
+                palette_overflows.push((result.name.clone(), overflow));
             }
-            TextureType::Grayscale8bpp => {
-                println!("Converting Grayscale8bpp texture");
-                let mut new_data = Vec::with_capacity(
-                    (texture_format.height * texture_format.width * 2)
-                        .try_into()
-                        .unwrap(),
-                );
-                for i in 0..(texture_format.height * texture_format.width) as usize {
-                    let bits = data[i];
-                    new_data.push(bits); // Grayscale
-                    new_data.push(bits); // Alpha
-                }
-                data = new_data;
+            if let Some(failure) = result.encode_failure {
+                encode_failures.push((result.name.clone(), failure));
             }
-            TextureType::GrayscaleAlpha4bpp => {
-                println!("Converting GrayscaleAlpha4bpp texture");
-                let mut new_data = Vec::with_capacity(
-                    (texture_format.height * texture_format.width * 2)
-                        .try_into()
-                        .unwrap(),
-                );
-                for i in 0..(texture_format.height * texture_format.width) as usize {
-                    let mut bits = data[i / 2];
-                    if i % 2 != 0 {
-                        bits &= 0xF;
-                    } else {
-                        bits >>= 4;
-                    }
-                    new_data.push(scale_3_8((bits >> 1) & 0x07));
-                    new_data.push(if (bits & 0x01) != 0 { 0xFF } else { 0x00 });
-                }
-                data = new_data;
+            if options.profile.is_some() && result.converted.is_some() {
+                profile.add(result.name.clone(), result.timings);
             }
-            TextureType::GrayscaleAlpha8bpp => {
-                println!("Converting GrayscaleAlpha8bpp texture");
-                let mut new_data = Vec::with_capacity(
-                    (texture_format.height * texture_format.width * 2)
-                        .try_into()
-                        .unwrap(),
-                );
-                for i in 0..(texture_format.height * texture_format.width) as usize {
-                    let bits = data[i];
-                    new_data.push(scale_4_8((bits & 0xF0) >> 4)); // Grayscale
-                    new_data.push(scale_4_8(bits & 0x0F)); // Alpha
-                }
-                data = new_data;
+            if result.placeholder {
+                manifest.placeholders.push(result.name.clone());
             }
-            TextureType::GrayscaleAlpha16bpp => {
-                println!("Converting GrayscaleAlpha16bpp texture");
+            if let Some(usage) = result.palette_usage {
+                palette_report.add(result.name, usage);
             }
-            TextureType::GrayscaleAlpha1bpp => {
-                println!("Converting GrayscaleAlpha1bpp texture");
+            if let Some(entry) = result.converted {
+                manifest.textures.push(entry);
             }
-            _ => {
-                println!(
-                    "Unknown or unsupported texture type: {:?}",
-                    texture_format.type_id
-                );
-                continue;
+        },
+    );
+
+    for (path, columns, tiles) in &tiled {
+        match tiles::stitch(&converter, &mut zip, path, *columns, tiles) {
+            Ok((entry, layout)) => {
+                manifest.textures.push(entry);
+                manifest.tiled.push(layout);
+            }
+            Err(err) => log::error(format!("Failed to stitch {}: {}", path, err)),
+        }
+    }
+
+    if options.report_memory {
+        println!(
+            "Peak memory of the decode pipeline: {} (predicted {})",
+            memory::format_size(memory::peak() as u64),
+            memory::format_size(memory::predict(
+                largest,
+                threads,
+                io_threads,
+                options.image_format
+            ))
+        );
+    }
+
+    if let Some(slowest) = options.profile {
+        print!("{}", profile.report(slowest));
+    }
+
+    // Resumed entries, stitched tiles and entries selected by symbol come
+    // out of order
+    manifest.textures.sort_by(|a, b| a.entry.cmp(&b.entry));
+    manifest.tiled.sort_by(|a, b| a.entry.cmp(&b.entry));
+    manifest.placeholders.sort();
+    if options.index_csv {
+        converter.write(
+            &format!("{}/{}", folder_name, manifest::INDEX_FILE),
+            manifest.to_csv(),
+        );
+    }
+
+    if let Some(post_process) = &options.post_process {
+        let outputs = manifest
+            .textures
+            .iter()
+            .map(|entry| entry.output.clone())
+            .collect::<Vec<_>>();
+        println!("Post-processing {} textures", outputs.len());
+        let failures = post_process.run(folder_name, &outputs, options.post_process_jobs);
+        for failure in &failures {
+            log::error(format!(
+                "Failed to post-process {}: {}",
+                failure.output, failure.error
+            ));
+        }
+        for output in &outputs {
+            if !failures.iter().any(|failure| failure.output == *output) {
+                converter.record(&format!("{}/{}", post_process::POST_PROCESS_FOLDER, output));
             }
         }
+        let processed = outputs.len() - failures.len();
+        let path = format!("{}/{}", folder_name, post_process::POST_PROCESS_REPORT_FILE);
+        println!(
+            "{} of {} textures post-processed, see {}",
+            processed,
+            outputs.len(),
+            path
+        );
+        converter.write(
+            &path,
+            post_process.report(processed, &failures).pretty() + "\n",
+        );
+    }
+
+    if options.palette_report {
+        let path = format!("{}/{}", folder_name, palette::PALETTE_REPORT_FILE);
+        println!(
+            "{} palette slots unused by any texture, see {}",
+            palette_report.wasted_slots(),
+            path
+        );
+        converter.write(&path, palette_report.to_json().pretty() + "\n");
+    }
+
+    if let Some(language) = options.language {
+        let groups = LanguageGroups::new(file_names.iter().map(String::as_str));
+        let path = format!("{}/{}", folder_name, language::LANGUAGE_REPORT_FILE);
+        println!(
+            "{} localized textures have no {} variant, see {}",
+            groups.missing(language).len(),
+            language.suffix(),
+            path
+        );
+        converter.write(&path, groups.to_json(language).pretty() + "\n");
+    }
+
+    if let Some(previous_textures) = &previous_textures {
+        let changelog = Changelog::compare(previous_textures, &manifest.textures);
+        let path = format!("{}/{}", folder_name, changelog::CHANGELOG_FILE);
+        if changelog.is_empty() {
+            println!("No texture changes since the previous manifest");
+        } else {
+            println!(
+                "{} textures added, {} removed and {} changed, see {}",
+                changelog.added.len(),
+                changelog.removed.len(),
+                changelog.changed.len(),
+                path
+            );
+        }
+        let archive = std::path::Path::new(&options.zip_file)
+            .file_name()
+            .map_or(options.zip_file.clone(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        let markdown = changelog.markdown(
+            &format!("Texture changes in {}", archive),
+            options.thumbnails.is_some(),
+        );
+        converter.write(&path, markdown);
+    }
+
+    let failed_path = format!("{}/{}", folder_name, quarantine::FAILED_FILE);
+    if !read_failures.is_empty() {
+        converter.write(
+            &failed_path,
+            quarantine::to_json(&read_failures).pretty() + "\n",
+        );
+    } else if !streamed {
+        // Left by the run whose failures this one retried
+        let _ = fs::remove_file(&failed_path);
+    }
 
-        image::save_buffer(
-            path,
-            &data,
-            texture_format.width,
-            texture_format.height,
-            format,
+    manifest.files = converter.written();
+    manifest
+        .files
+        .extend(manifest.textures.iter().map(|entry| entry.output.clone()));
+    manifest.files.sort();
+    manifest.files.dedup();
+    if let Some(tar) = converter.tar {
+        let mut tar = tar.into_inner().unwrap();
+        tar.append(
+            manifest::MANIFEST_FILE,
+            (manifest.to_json().pretty() + "\n").as_bytes(),
         )
-        .unwrap();
+        .expect("Failed to stream the manifest");
+        tar.finish().expect("Failed to stream the outputs");
+    } else if !options.legacy {
+        manifest
+            .write(folder_name)
+            .expect("Failed to write manifest");
+        if !options.keep_stale {
+            let pruned = prune::prune(folder_name, &previous_files, &manifest.files);
+            if pruned > 0 {
+                println!("Removed {} stale outputs of the previous run", pruned);
+            }
+        }
+
+        drop(journal);
+        if let Err(err) = Journal::sort(folder_name) {
+            log::error(format!("Failed to sort the journal: {}", err));
+        }
+    }
+    if options.reproducible {
+        match reproducible::seal(folder_name) {
+            Ok(hash) => println!(
+                "Output tree hash: {}, see {}/{}",
+                hash,
+                folder_name,
+                reproducible::SUMS_FILE
+            ),
+            Err(err) => log::error(format!("Failed to checksum the output: {}", err)),
+        }
+    }
+
+    if !encode_failures.is_empty() {
+        encode_failures.sort();
+        println!(
+            "{} textures couldn't be encoded and were left out:",
+            encode_failures.len()
+        );
+        for (name, failure) in &encode_failures {
+            println!("  {}: {}", name, failure);
+        }
+    }
+
+    if !palette_overflows.is_empty() {
+        palette_overflows.sort_by(|(a_name, a), (b_name, b)| {
+            b.missing()
+                .cmp(&a.missing())
+                .then_with(|| a_name.cmp(b_name))
+        });
+        println!(
+            "{} textures use palette indices past the end of their TLUT, worst offenders:",
+            palette_overflows.len()
+        );
+        for (name, overflow) in palette_overflows.iter().take(10) {
+            println!(
+                "  {}: index {} with {} TLUT entries",
+                name, overflow.max_index, overflow.entries
+            );
+        }
+    }
+    if !read_failures.is_empty() {
+        println!(
+            "{} entries couldn't be read and were quarantined to {}, convert them again with --retry-failed {}",
+            read_failures.len(),
+            failed_path,
+            failed_path
+        );
+    }
+    if options.strict
+        && !(palette_overflows.is_empty() && encode_failures.is_empty() && read_failures.is_empty())
+    {
+        std::process::exit(1);
     }
 }
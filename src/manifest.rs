@@ -0,0 +1,261 @@
+use std::{fs, io};
+
+use crate::{alpha::AlphaStats, json::Json};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// Spreadsheet-friendly listing of the textures, written with `--index-csv`.
+pub const INDEX_FILE: &str = "index.csv";
+
+/// A texture written to the output folder.
+pub struct ManifestEntry {
+    /// Path of the resource in the archive.
+    pub entry: String,
+    /// Path of the image relative to the output folder.
+    pub output: String,
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    /// CRC-64 of the decoded texels, missing from older manifests.
+    pub hash: Option<u64>,
+    /// Texels by alpha, missing from older manifests.
+    pub alpha: Option<AlphaStats>,
+    /// Rice hash fields of the texture, `<crc>#<fmt>#<siz>[#<palette crc>]`,
+    /// kept when matching against a hi-res pack with `--hash-db`.
+    pub pack_hash: Option<String>,
+    /// File of the hi-res pack with the same hash.
+    pub pack_name: Option<String>,
+    /// Guessed use of the texture, with `--classify`.
+    pub category: Option<String>,
+}
+
+impl ManifestEntry {
+    pub fn from_json(json: &Json) -> Option<Self> {
+        let string = |key| json.get(key)?.as_str().map(str::to_owned);
+        let number = |key| json.get(key)?.as_f64().map(|value| value as u32);
+        Some(ManifestEntry {
+            entry: string("entry")?,
+            output: string("output")?,
+            format: string("format")?,
+            width: number("width")?,
+            height: number("height")?,
+            hash: json
+                .get("hash")
+                .and_then(Json::as_str)
+                .and_then(|hash| u64::from_str_radix(hash, 16).ok()),
+            alpha: json.get("alpha").and_then(AlphaStats::from_json),
+            pack_hash: string("pack_hash"),
+            pack_name: string("pack_name"),
+            category: string("category"),
+        })
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut json = Json::object()
+            .with("entry", self.entry.as_str())
+            .with("output", self.output.as_str())
+            .with("format", self.format.as_str())
+            .with("width", self.width)
+            .with("height", self.height)
+            .with("hash", self.hash.map(|hash| format!("{:016x}", hash)))
+            .with("alpha", self.alpha.as_ref().map(AlphaStats::to_json));
+        if let Some(pack_hash) = &self.pack_hash {
+            json.insert("pack_hash", pack_hash.as_str());
+            json.insert("pack_name", self.pack_name.as_deref());
+        }
+        if let Some(category) = &self.category {
+            json.insert("category", category.as_str());
+        }
+        json
+    }
+}
+
+/// An image stitched together from a grid of tile entries.
+pub struct TiledTexture {
+    /// Path of the image in the archive, without the tile suffix.
+    pub entry: String,
+    /// Path of the image relative to the output folder.
+    pub output: String,
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Tile entries in row-major order.
+    pub tiles: Vec<String>,
+}
+
+impl TiledTexture {
+    pub fn to_json(&self) -> Json {
+        Json::object()
+            .with("entry", self.entry.as_str())
+            .with("output", self.output.as_str())
+            .with("columns", self.columns)
+            .with("rows", self.rows)
+            .with("tile_width", self.tile_width)
+            .with("tile_height", self.tile_height)
+            .with(
+                "tiles",
+                Json::Array(
+                    self.tiles
+                        .iter()
+                        .map(|tile| Json::from(tile.as_str()))
+                        .collect(),
+                ),
+            )
+    }
+}
+
+/// Record of a conversion run, written next to the converted textures so the
+/// output tree can be mapped back to the archive.
+pub struct Manifest {
+    pub archive: String,
+    pub path_map: Vec<(String, String)>,
+    pub textures: Vec<ManifestEntry>,
+    /// Layout of the textures stitched from tiles.
+    pub tiled: Vec<TiledTexture>,
+    /// Filler textures skipped with `--skip-placeholders`.
+    pub placeholders: Vec<String>,
+    /// Every file the run wrote to the output folder besides the manifest
+    /// and the journal, so the next run can prune the ones it doesn't write
+    /// again.
+    pub files: Vec<String>,
+}
+
+impl Manifest {
+    pub fn new(archive: &str, path_map: &[(String, String)]) -> Self {
+        Manifest {
+            archive: archive.to_owned(),
+            path_map: path_map.to_vec(),
+            textures: Vec::new(),
+            tiled: Vec::new(),
+            placeholders: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let json = Json::object()
+            .with("archive", self.archive.as_str())
+            .with(
+                "path_map",
+                Json::Object(
+                    self.path_map
+                        .iter()
+                        .map(|(from, to)| (from.to_owned(), Json::from(to.as_str())))
+                        .collect(),
+                ),
+            )
+            .with(
+                "textures",
+                Json::Array(self.textures.iter().map(ManifestEntry::to_json).collect()),
+            );
+        let json = if self.tiled.is_empty() {
+            json
+        } else {
+            json.with(
+                "tiled",
+                Json::Array(self.tiled.iter().map(TiledTexture::to_json).collect()),
+            )
+        };
+        let json = if self.placeholders.is_empty() {
+            json
+        } else {
+            json.with(
+                "placeholders",
+                Json::Array(
+                    self.placeholders
+                        .iter()
+                        .map(|entry| Json::from(entry.as_str()))
+                        .collect(),
+                ),
+            )
+        };
+        json.with(
+            "files",
+            Json::Array(
+                self.files
+                    .iter()
+                    .map(|file| Json::from(file.as_str()))
+                    .collect(),
+            ),
+        )
+    }
+
+    /// The textures as CSV, one row per texture sorted by output path, with an
+    /// empty `replaced` column for tracking replacement status. Hashes get a
+    /// `0x` prefix so spreadsheets keep them as text.
+    pub fn to_csv(&self) -> String {
+        let mut textures = self.textures.iter().collect::<Vec<_>>();
+        textures.sort_by(|a, b| a.output.cmp(&b.output));
+
+        let mut csv = String::from("path,entry,format,size,hash,replaced\n");
+        for texture in textures {
+            let hash = texture
+                .hash
+                .map(|hash| format!("0x{:016x}", hash))
+                .unwrap_or_default();
+            let row = [
+                csv_field(&texture.output),
+                csv_field(&texture.entry),
+                csv_field(&texture.format),
+                format!("{}x{}", texture.width, texture.height),
+                hash,
+                String::new(),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Textures listed in the manifest at `path`.
+    pub fn load_textures(path: &str) -> Result<Vec<ManifestEntry>, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let json = Json::parse(&text)?;
+        match json.get("textures") {
+            Some(Json::Array(textures)) => textures
+                .iter()
+                .map(|texture| {
+                    ManifestEntry::from_json(texture)
+                        .ok_or_else(|| format!("Invalid texture entry {}", texture))
+                })
+                .collect(),
+            _ => Err("No textures list".to_owned()),
+        }
+    }
+
+    /// Files written by the run of the manifest at `path`. Manifests from
+    /// before the list was kept only give the texture outputs.
+    pub fn load_files(path: &str) -> Result<Vec<String>, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let json = Json::parse(&text)?;
+        if let Some(Json::Array(files)) = json.get("files") {
+            return Ok(files
+                .iter()
+                .filter_map(|file| file.as_str().map(str::to_owned))
+                .collect());
+        }
+        match json.get("textures") {
+            Some(Json::Array(textures)) => Ok(textures
+                .iter()
+                .filter_map(|texture| texture.get("output")?.as_str().map(str::to_owned))
+                .collect()),
+            _ => Err("No files or textures list".to_owned()),
+        }
+    }
+
+    pub fn write(&self, folder: &str) -> io::Result<()> {
+        fs::write(
+            folder.to_owned() + "/" + MANIFEST_FILE,
+            self.to_json().pretty() + "\n",
+        )
+    }
+}
+
+/// Quotes a CSV field holding a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
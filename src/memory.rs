@@ -0,0 +1,119 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::texture::ImageFormat;
+
+/// System allocator keeping track of the bytes in use and their peak, for
+/// `--report-memory`.
+struct CountingAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+impl CountingAllocator {
+    fn add(size: usize) {
+        let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn remove(size: usize) {
+        CURRENT.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            Self::add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            Self::add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        Self::remove(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            Self::remove(layout.size());
+            Self::add(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Most bytes allocated at once since the last `reset_peak`.
+pub fn peak() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+/// Starts measuring the peak from the current usage.
+pub fn reset_peak() {
+    PEAK.store(CURRENT.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+pub fn format_size(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Predicted peak memory of decoding an archive entry of `size` bytes. 4-bit
+/// texels grow 8 times when expanded to RGBA, and the encoder and byte order
+/// detection keep copies around. EXR output stores 4 floats per pixel. The
+/// size is the one the archive declares, so it may be anything.
+fn decode_cost(size: u64, image_format: ImageFormat) -> u64 {
+    match image_format {
+        ImageFormat::Png => size.saturating_mul(16),
+        #[cfg(feature = "exr")]
+        ImageFormat::Exr => size.saturating_mul(64),
+    }
+}
+
+/// Predicted peak memory of the pipeline with `threads` decode workers and
+/// `io_threads` readers when the largest entry is `largest` bytes. Every
+/// reader and up to two queued entries per worker hold raw entries.
+pub fn predict(largest: u64, threads: usize, io_threads: usize, image_format: ImageFormat) -> u64 {
+    let raw_entries = (io_threads + threads * 2) as u64;
+    raw_entries
+        .saturating_mul(largest)
+        .saturating_add((threads as u64).saturating_mul(decode_cost(largest, image_format)))
+}
+
+/// Decode workers and readers keeping the predicted peak under `limit`
+/// bytes, giving up parallelism first. Down to a single worker and reader,
+/// entries are streamed through the pipeline one at a time, and the
+/// prediction may still be over the limit.
+pub fn fit_threads(
+    limit: u64,
+    largest: u64,
+    threads: usize,
+    io_threads: usize,
+    image_format: ImageFormat,
+) -> (usize, usize) {
+    let (mut threads, mut io_threads) = (threads, io_threads);
+    while predict(largest, threads, io_threads, image_format) > limit
+        && (threads > 1 || io_threads > 1)
+    {
+        if io_threads >= threads && io_threads > 1 {
+            io_threads -= 1;
+        } else {
+            threads -= 1;
+        }
+    }
+    (threads, io_threads)
+}
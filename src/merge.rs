@@ -0,0 +1,241 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+
+use crate::{
+    options::Options,
+    patch::Patcher,
+    read_entry,
+    texture::{self, ImageFormat},
+};
+
+/// How `merge` picks the version kept of an entry several patches change,
+/// given with `--resolve`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolve {
+    /// The patch given last, loaded over the others as in a mod list. The
+    /// default.
+    Priority,
+    /// The version modified last, the patch given last on ties.
+    Newest,
+    /// Ask on stdin, with previews of the textures written next to the
+    /// output.
+    Ask,
+}
+
+impl FromStr for Resolve {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "priority" => Ok(Resolve::Priority),
+            "newest" => Ok(Resolve::Newest),
+            "ask" => Ok(Resolve::Ask),
+            _ => Err(format!(
+                "Unknown conflict resolution '{}', expected priority, newest or ask",
+                value
+            )),
+        }
+    }
+}
+
+/// A version of an entry, found in one of the patches.
+struct Version {
+    /// Index of the patch in the merge order.
+    patch: usize,
+    /// Index of the entry in the patch.
+    index: usize,
+    /// Modification time, from the year down to the second.
+    modified: [u16; 6],
+    crc: u32,
+    size: u64,
+}
+
+/// Modification time of a zip entry, from the year down to the second.
+fn modified(file: &zip::read::ZipFile<File>) -> [u16; 6] {
+    file.last_modified().map_or([0; 6], |time| {
+        [
+            time.year(),
+            time.month() as u16,
+            time.day() as u16,
+            time.hour() as u16,
+            time.minute() as u16,
+            time.second() as u16,
+        ]
+    })
+}
+
+fn format_time(time: &[u16; 6]) -> String {
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        time[0], time[1], time[2], time[3], time[4], time[5]
+    )
+}
+
+/// Combines the patch archives `patches`, given in load order, into the
+/// archive `output`. Entries changed by several patches with different
+/// contents are conflicts, settled by `resolve`.
+pub fn run(options: &Options, output: &str, patches: &[String], resolve: Resolve) {
+    let mut archives = patches
+        .iter()
+        .map(|path| {
+            File::open(path)
+                .map_err(|err| err.to_string())
+                .and_then(|file| zip::ZipArchive::new(file).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| panic!("Failed to read patch {}: {}", path, err))
+        })
+        .collect::<Vec<_>>();
+
+    let mut versions = BTreeMap::<String, Vec<Version>>::new();
+    for (patch, zip) in archives.iter_mut().enumerate() {
+        for index in 0..zip.len() {
+            let file = zip.by_index_raw(index).expect("Failed to read zip entry");
+            if file.is_dir() {
+                continue;
+            }
+            let versions = versions.entry(file.name().to_owned()).or_default();
+            // Patches built from the same edit don't conflict
+            versions.retain(|version| (version.crc, version.size) != (file.crc32(), file.size()));
+            versions.push(Version {
+                patch,
+                index,
+                modified: modified(&file),
+                crc: file.crc32(),
+                size: file.size(),
+            });
+        }
+    }
+
+    let previews = format!("{}.previews", output);
+    let patcher = (resolve == Resolve::Ask).then(|| Patcher::open(options));
+    let mut conflicts = 0;
+    let mut kept = Vec::new();
+    for (name, versions) in &versions {
+        let winner = match resolve {
+            _ if versions.len() == 1 => 0,
+            Resolve::Priority => versions.len() - 1,
+            Resolve::Newest => {
+                // The last of the newest, as the patches are in load order
+                let newest = versions
+                    .iter()
+                    .map(|version| version.modified)
+                    .max()
+                    .unwrap();
+                versions
+                    .iter()
+                    .rposition(|version| version.modified == newest)
+                    .unwrap()
+            }
+            Resolve::Ask => ask(
+                name,
+                versions,
+                patches,
+                &mut archives,
+                patcher.as_ref().unwrap(),
+                &previews,
+            ),
+        };
+        if versions.len() > 1 {
+            conflicts += 1;
+            let others = versions
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != winner)
+                .map(|(_, version)| patches[version.patch].as_str())
+                .collect::<Vec<_>>();
+            println!(
+                "  {}: {} over {}",
+                name,
+                patches[versions[winner].patch],
+                others.join(", ")
+            );
+        }
+        kept.push(&versions[winner]);
+    }
+    let _ = fs::remove_dir_all(&previews);
+
+    // Copied without recompressing, keeping the modification times newer
+    // merges compare
+    let partial = format!("{}.partial", output);
+    let mut writer =
+        zip::ZipWriter::new(File::create(&partial).expect("Failed to create output archive"));
+    for version in &kept {
+        let file = archives[version.patch]
+            .by_index_raw(version.index)
+            .expect("Failed to read zip entry");
+        writer
+            .raw_copy_file(file)
+            .expect("Failed to write zip entry");
+    }
+    writer.finish().expect("Failed to write output archive");
+    fs::rename(&partial, output)
+        .unwrap_or_else(|err| panic!("Failed to write {}: {}", output, err));
+    println!(
+        "Merged {} entries of {} patches into {}, {} conflicts",
+        kept.len(),
+        patches.len(),
+        output,
+        conflicts
+    );
+}
+
+/// Asks which of the `versions` of the entry `name` to keep, writing a
+/// preview of each texture to the folder `previews`. An empty answer or the
+/// end of stdin keeps the version of the patch given last.
+fn ask(
+    name: &str,
+    versions: &[Version],
+    patches: &[String],
+    archives: &mut [zip::ZipArchive<File>],
+    patcher: &Patcher,
+    previews: &str,
+) -> usize {
+    println!("{} is changed by {} patches:", name, versions.len());
+    for (i, version) in versions.iter().enumerate() {
+        let preview = read_entry(&mut archives[version.patch], name, &patcher.options.payload)
+            .and_then(|data| patcher.decode_resource(name, &data))
+            .and_then(|texture| {
+                let image = texture::encode_image(
+                    ImageFormat::Png,
+                    &texture.data,
+                    texture.width,
+                    texture.height,
+                    texture.format,
+                )
+                .ok()?;
+                let path = format!("{}/{}.{}.png", previews, name.replace('/', "_"), i + 1);
+                fs::create_dir_all(previews).ok()?;
+                fs::write(&path, image).ok()?;
+                Some(format!(", preview {}", path))
+            })
+            .unwrap_or_default();
+        println!(
+            "  {}: {}, modified {}{}",
+            i + 1,
+            patches[version.patch],
+            format_time(&version.modified),
+            preview
+        );
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("Keep which version? [{}] ", versions.len());
+        io::stdout().flush().expect("Failed to write the prompt");
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+            println!();
+            return versions.len() - 1;
+        }
+        match answer.trim() {
+            "" => return versions.len() - 1,
+            answer => match answer.parse::<usize>() {
+                Ok(choice) if (1..=versions.len()).contains(&choice) => return choice - 1,
+                _ => println!("Expected a number from 1 to {}", versions.len()),
+            },
+        }
+    }
+}
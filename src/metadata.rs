@@ -0,0 +1,102 @@
+use std::io::{Read, Seek};
+
+use crate::{json::Json, payload::PayloadTransform, read_entry};
+
+/// Entry holding the version of the port that wrote the archive.
+const PORT_VERSION_ENTRY: &str = "portVersion";
+/// Entry holding the CRCs of the game ROMs the archive was extracted from.
+const GAME_VERSION_ENTRY: &str = "version";
+
+/// Archive-level information, read from the version entries and the zip
+/// comment.
+pub struct ArchiveMetadata {
+    pub comment: Option<String>,
+    pub port_version: Option<[u16; 3]>,
+    pub game_versions: Vec<u32>,
+}
+
+impl ArchiveMetadata {
+    pub fn read<R: Read + Seek>(zip: &mut zip::ZipArchive<R>, payload: &PayloadTransform) -> Self {
+        let comment = String::from_utf8_lossy(zip.comment()).trim().to_owned();
+        let port_version = read_entry(zip, PORT_VERSION_ENTRY, payload)
+            .and_then(|data| versioned_words(&data, 2))
+            .and_then(|words| match words[..] {
+                [major, minor, patch, ..] => Some([major as u16, minor as u16, patch as u16]),
+                _ => None,
+            });
+        let game_versions = read_entry(zip, GAME_VERSION_ENTRY, payload)
+            .and_then(|data| versioned_words(&data, 4))
+            .unwrap_or_default();
+
+        ArchiveMetadata {
+            comment: (!comment.is_empty()).then_some(comment),
+            port_version,
+            game_versions,
+        }
+    }
+
+    pub fn port_version_string(&self) -> Option<String> {
+        self.port_version
+            .map(|[major, minor, patch]| format!("{}.{}.{}", major, minor, patch))
+    }
+
+    /// Checks the port version against `required`, which may give only the
+    /// leading components (`3` or `3.0` accept `3.0.2`).
+    pub fn check_port_version(&self, required: &str) -> Result<(), String> {
+        let required_parts = required
+            .split('.')
+            .map(|part| part.parse::<u16>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|parts| (1..=3).contains(&parts.len()))
+            .ok_or_else(|| format!("Invalid port version '{}'", required))?;
+        match self.port_version {
+            Some(version) if version.starts_with(&required_parts) => Ok(()),
+            Some(_) => Err(format!(
+                "Archive was written by port version {}, {} is required",
+                self.port_version_string().unwrap(),
+                required
+            )),
+            None => Err(format!(
+                "Archive has no port version, {} is required",
+                required
+            )),
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object()
+            .with("comment", self.comment.as_deref())
+            .with("port_version", self.port_version_string())
+            .with(
+                "game_versions",
+                Json::Array(
+                    self.game_versions
+                        .iter()
+                        .map(|crc| format!("{:08x}", crc).into())
+                        .collect(),
+                ),
+            )
+    }
+}
+
+/// Reads the words of `size` bytes following the byte order flag that starts
+/// the version entries, 0 for little endian and 1 for big endian.
+fn versioned_words(data: &[u8], size: usize) -> Option<Vec<u32>> {
+    let (&byte_order, words) = data.split_first()?;
+    Some(
+        words
+            .chunks_exact(size)
+            .map(|word| {
+                let mut bytes = word.to_vec();
+                if byte_order != 0 {
+                    bytes.reverse();
+                }
+                bytes
+                    .iter()
+                    .rev()
+                    .fold(0u32, |value, byte| value << 8 | *byte as u32)
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,64 @@
+use std::{env, fs, path::Path};
+
+/// Programs the binary stands in for when called by another name, as
+/// BusyBox does, with the arguments each name adds. Linked to the binary
+/// with `install-aliases` for shell pipelines and Makefiles.
+pub const ALIASES: &[(&str, &[&str])] = &[
+    ("o2r-extract", &[]),
+    ("o2r-replace", &["replace"]),
+    ("o2r-reencode-ci", &["reencode-ci"]),
+    ("o2r-generate-yaml", &["generate-yaml"]),
+    ("o2r-explain", &["explain"]),
+    ("o2r-grep", &["grep"]),
+    ("o2r-info", &["info"]),
+    ("o2r-patch", &["patch"]),
+    ("o2r-merge", &["merge"]),
+    ("o2r-encode", &["encode"]),
+    ("o2r-transform", &["transform"]),
+    ("o2r-schema", &["schema"]),
+    ("o2r-mount", &["mount"]),
+    ("o2r-serve", &["--serve-rpc"]),
+    ("o2r-decode", &["--stdin", "--stdout"]),
+];
+
+/// `args` with the arguments of the alias the binary was called by inserted
+/// after the program name, unchanged when called by its own name.
+pub fn expand(args: &[String]) -> Vec<String> {
+    let program = args
+        .first()
+        .and_then(|program| Path::new(program).file_stem())
+        .map(|name| name.to_string_lossy());
+    let mut expanded = args.to_vec();
+    if let Some((_, arguments)) = ALIASES
+        .iter()
+        .find(|(alias, _)| Some(*alias) == program.as_deref())
+    {
+        expanded.splice(1..1, arguments.iter().map(|argument| argument.to_string()));
+    }
+    expanded
+}
+
+/// Links every alias to the running binary in the folder `dir`, replacing
+/// links left by an earlier install. Symbolic links survive the binary being
+/// reinstalled, Windows gets hard links that don't need extra privileges.
+pub fn install(dir: &str) {
+    let binary = env::current_exe().expect("Failed to find the running binary");
+    let extension = binary
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    fs::create_dir_all(dir).unwrap_or_else(|err| panic!("Failed to create {}: {}", dir, err));
+    for (alias, _) in ALIASES {
+        let link = Path::new(dir).join(format!("{}{}", alias, extension));
+        if fs::symlink_metadata(&link).is_ok() {
+            fs::remove_file(&link)
+                .unwrap_or_else(|err| panic!("Failed to replace {}: {}", link.display(), err));
+        }
+        #[cfg(unix)]
+        let linked = std::os::unix::fs::symlink(&binary, &link);
+        #[cfg(not(unix))]
+        let linked = fs::hard_link(&binary, &link);
+        linked.unwrap_or_else(|err| panic!("Failed to link {}: {}", link.display(), err));
+    }
+    println!("Linked {} aliases in {}", ALIASES.len(), dir);
+}
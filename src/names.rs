@@ -0,0 +1,92 @@
+use std::collections::{BTreeMap, HashMap};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// `name` in Unicode NFC, as macOS file names and archives packed there
+/// come decomposed.
+pub fn nfc(name: &str) -> String {
+    name.nfc().collect()
+}
+
+/// Output paths, as given by `output_path`, that case-insensitive or
+/// normalization-insensitive file systems such as the macOS default would
+/// store as the same file. Each group lists the entries writing to it.
+pub fn collisions(names: &[String], output_path: impl Fn(&str) -> String) -> Vec<Vec<String>> {
+    let mut groups = BTreeMap::<String, Vec<String>>::new();
+    for name in names {
+        groups
+            .entry(nfc(&output_path(name)).to_lowercase())
+            .or_default()
+            .push(name.clone());
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect()
+}
+
+/// Renames giving every entry of `groups` but the first its own output path,
+/// by appending `~2`, `~3`... to the path of the entry.
+pub fn disambiguate(
+    groups: &[Vec<String>],
+    output_path: impl Fn(&str) -> String,
+) -> HashMap<String, String> {
+    groups
+        .iter()
+        .flat_map(|group| {
+            group
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, name)| (name.clone(), format!("{}~{}", output_path(name), i + 1)))
+        })
+        .collect()
+}
+
+/// Relative output path for the archive path `path` that stays inside the
+/// output folder. Archives come from anywhere, so `..`, `.` and empty
+/// components, leading slashes and drive letters are dropped rather than
+/// trusted; backslashes count as separators like on Windows.
+pub fn sanitize(path: &str) -> String {
+    let components = path
+        .split(['/', '\\'])
+        .filter(|component| !matches!(*component, "" | "." | ".."))
+        .map(|component| component.replace(':', "_"))
+        .collect::<Vec<_>>();
+    if components.is_empty() {
+        return "_".to_owned();
+    }
+    components.join("/")
+}
+
+/// Whether `path` is a relative path that doesn't leave the folder it is
+/// relative to.
+pub fn is_contained(path: &str) -> bool {
+    !path.is_empty() && sanitize(path) == path
+}
+
+/// Whether the archive path `path` matches `pattern`, where `*` stands for
+/// any characters but `/`, `**` for any characters and `?` for one
+/// character but `/`.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[u8], path: &[u8]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+            [b'*', rest @ ..] => (0..=path.len())
+                .take_while(|i| !path[..*i].contains(&b'/'))
+                .any(|i| matches(rest, &path[i..])),
+            [b'?', rest @ ..] => path
+                .split_first()
+                .is_some_and(|(c, path)| *c != b'/' && matches(rest, path)),
+            [c, rest @ ..] => path
+                .split_first()
+                .is_some_and(|(first, path)| first == c && matches(rest, path)),
+        }
+    }
+    matches(pattern.as_bytes(), path.as_bytes())
+}
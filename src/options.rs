@@ -0,0 +1,1044 @@
+use std::{collections::HashSet, env, str::FromStr};
+
+use crate::config::DEFAULT_CONFIG_FILE;
+use crate::cutscene::CutsceneFormat;
+use crate::derive::Derived;
+use crate::emit_c::EmitC;
+use crate::encode;
+use crate::engine_meta::Engine;
+use crate::game::Game;
+use crate::grep;
+use crate::header_filter::HeaderFilter;
+use crate::interleave::Deinterleave;
+use crate::io_profile::IoProfile;
+use crate::language::Language;
+use crate::log::{self, Category, Target};
+use crate::merge;
+use crate::multicall;
+use crate::payload::PayloadTransform;
+use crate::pixels::Expansion;
+use crate::post_process::PostProcess;
+use crate::profile;
+use crate::query::Query;
+use crate::swap::ByteSwap;
+use crate::text::TextFormat;
+use crate::texture::ImageFormat;
+use crate::texture_query::DEFAULT_CACHE_BUDGET;
+use crate::yaml_dialect::YamlDialect;
+use crate::{DecodeOptions, TextureType};
+
+/// Operation selected by the first positional argument.
+pub enum Command {
+    /// Convert every texture of the archive, the default.
+    Convert,
+    /// Re-encode a PNG into an entry of the archive and write the result to `output`.
+    Replace {
+        entry: String,
+        image: String,
+        output: String,
+    },
+    /// Turn an edited PNG of the CI texture `entry` back into indices into its
+    /// TLUT, adding the new colors to it, and write the result to `output`.
+    ReencodeCi {
+        entry: String,
+        image: String,
+        output: String,
+    },
+    /// Write Torch asset YAML describing the textures of the archive to `output`.
+    GenerateYaml { output: String },
+    /// Print why `entry` is or isn't converted.
+    Explain { entry: String },
+    /// Print the header fields of `entry`, with an annotated hexdump of it
+    /// when `hex` is set.
+    Info { entry: String, hex: bool },
+    /// Print where the entries of the archive contain `pattern`.
+    Grep { pattern: Vec<u8> },
+    /// Write the textures of the images in `folder` that differ from the
+    /// archive to the patch archive `output`, rebuilding it whenever an image
+    /// is saved when `watch` is set.
+    Patch {
+        folder: String,
+        output: String,
+        watch: bool,
+    },
+    /// Encode `image` as a new texture resource of `format` written to
+    /// `output`, the TLUT of CI textures next to it.
+    Encode {
+        image: String,
+        format: TextureType,
+        output: String,
+    },
+    /// Combine the patch archives `patches`, given in load order, into the
+    /// archive `output`, settling the entries several of them change with
+    /// `resolve`.
+    Merge {
+        output: String,
+        patches: Vec<String>,
+        resolve: merge::Resolve,
+    },
+    /// Extract the textures to a temporary folder, run `script` on it and
+    /// write the textures it changed to the patch archive `output`.
+    Transform { script: String, output: String },
+    /// Write the JSON Schemas of the manifest and the config to `output`.
+    Schema { output: String },
+    /// Mount the textures of the archive as PNG files on `mountpoint`.
+    #[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+    Mount { mountpoint: String },
+    /// Print what this build supports, as JSON when `json` is set.
+    VersionInfo { json: bool },
+    /// Link the `o2r-*` aliases of the binary in the folder `dir`.
+    InstallAliases { dir: String },
+}
+
+/// Prefix of the environment variables standing in for options, the option
+/// name in upper case with dashes as underscores: `CTO2R_THREADS_IO` for
+/// `--threads-io`. Options given on the command line take precedence, and
+/// replace the lists of `--base`, `--symbol` and `--id` set there.
+const ENV_PREFIX: &str = "CTO2R_";
+
+/// Options taking a value that can be set from the environment.
+const ENV_VALUE_OPTIONS: &[&str] = &[
+    "--config",
+    "--output",
+    "--swap",
+    "--engine-meta",
+    "--threads",
+    "--threads-io",
+    "--read-retries",
+    "--io-profile",
+    "--retry-failed",
+    "--text-format",
+    "--image-format",
+    "--cutscene-format",
+    "--types",
+    "--layout",
+    "--require-port-version",
+    "--symbols",
+    "--symbol",
+    "--id",
+    "--yaml-dialect",
+    "--expand",
+    "--game",
+    "--header-version",
+    "--byte-order",
+    "--changelog",
+    "--hash-db",
+    "--base",
+    "--language",
+    "--memory-limit",
+    "--cache-budget",
+    "--serve-socket",
+    "--thumbnails",
+    "--derive",
+    "--dilate-alpha",
+    "--post-process",
+    "--post-process-jobs",
+    "--emit-c",
+    "--where",
+    "--deinterleave",
+    "--payload",
+    "--log-file",
+    "--log",
+];
+
+/// Switches that can be turned on from the environment.
+const ENV_FLAG_OPTIONS: &[&str] = &[
+    "--strict",
+    "--only-custom",
+    "--treat-i4-as-ia4",
+    "--path-svg",
+    "--tmem-svg",
+    "--palette-report",
+    "--index-csv",
+    "--resume",
+    "--keep-stale",
+    "--reproducible",
+    "--classify",
+    "--report-memory",
+    "--profile",
+    "--quiet-skip",
+    "--skip-placeholders",
+    "--compressed-texels",
+];
+
+/// How outputs are arranged in the output folder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// Mirror the archive paths, the default.
+    ByPath,
+    /// Mirror the archive paths under one folder per kind of resource.
+    ByType,
+    /// Everything in the output folder, with the path folded into the name.
+    Flat,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "by-path" => Ok(Layout::ByPath),
+            "by-type" => Ok(Layout::ByType),
+            "flat" => Ok(Layout::Flat),
+            _ => Err(format!(
+                "Unknown layout '{}', expected by-path, by-type or flat",
+                value
+            )),
+        }
+    }
+}
+
+/// Command line options.
+pub struct Options {
+    pub command: Command,
+    pub zip_file: String,
+    pub config: String,
+    /// Folder outputs are written to, `-` to stream them to stdout as a tar.
+    pub output: String,
+    pub swap: ByteSwap,
+    /// Undo the TMEM word swap of odd texture rows.
+    pub deinterleave: Deinterleave,
+    pub engine_meta: Option<Engine>,
+    pub serve_rpc: bool,
+    /// Convert the one resource read from stdin, writing the image to stdout,
+    /// instead of an archive.
+    pub stdin: bool,
+    /// TLUT resource file of the CI texture read from stdin.
+    pub tlut: Option<String>,
+    /// Treat suspicious data as errors instead of warnings.
+    pub strict: bool,
+    /// Decode every texture tagged I4 as IA4.
+    pub treat_i4_as_ia4: bool,
+    /// Number of decode workers.
+    pub threads: usize,
+    /// Number of concurrent archive readers, lower it on spinning disks.
+    pub io_threads: usize,
+    /// Storage the archive is on, setting the read-ahead of the readers and
+    /// their default number.
+    pub io_profile: IoProfile,
+    /// Times a failed entry read is retried before the entry is quarantined.
+    pub read_retries: usize,
+    /// List of quarantined entries of a previous run, the only ones to
+    /// convert, resuming it.
+    pub retry_failed: Option<String>,
+    /// Format textures are exported to.
+    pub image_format: ImageFormat,
+    /// Format text resources are exported to.
+    pub text_format: TextFormat,
+    /// Format cutscene resources are exported to.
+    pub cutscene_format: CutsceneFormat,
+    /// Also plot path resources to SVG.
+    pub path_svg: bool,
+    /// Also diagram the TMEM loads of display lists to SVG.
+    pub tmem_svg: bool,
+    /// Report the palette entries CI textures use.
+    pub palette_report: bool,
+    /// Also list the textures in a CSV file for spreadsheets.
+    pub index_csv: bool,
+    /// Skip the filler textures the config's `placeholders` settings
+    /// match, listing them in the manifest.
+    pub skip_placeholders: bool,
+    /// Continue an interrupted conversion from its journal.
+    pub resume: bool,
+    /// Leave the outputs of the previous run that this one doesn't write
+    /// again instead of pruning them.
+    pub keep_stale: bool,
+    /// Empty the output folder before converting, whatever wrote its files.
+    /// Only taken from the command line, as the folder may come from the
+    /// environment.
+    pub clear_output: bool,
+    /// Write an output tree that only depends on the archive, with fixed
+    /// timestamps and SHA-256 checksums of every file.
+    pub reproducible: bool,
+    /// Tag each texture of the manifest with a guessed category.
+    pub classify: bool,
+    /// Also write previews of textures no larger than this many pixels a
+    /// side to the `thumbs` folder.
+    pub thumbnails: Option<u32>,
+    /// Images derived from every texture for material authoring, written
+    /// next to it.
+    pub derive: Option<Vec<Derived>>,
+    /// Fill the color of transparent texels up to this many texels away from
+    /// opaque ones, so upscalers and filtering don't pull in black halos.
+    pub dilate_alpha: Option<u32>,
+    /// Command run on every converted texture, writing under the `processed`
+    /// folder.
+    pub post_process: Option<PostProcess>,
+    /// Number of post-process commands running at once.
+    pub post_process_jobs: usize,
+    /// Log the peak memory use of the decode pipeline.
+    pub report_memory: bool,
+    /// Also write the texels of textures and TLUTs as decomp style C arrays.
+    pub emit_c: Option<EmitC>,
+    /// Time the stages of converting each entry and report the given number
+    /// of slowest entries.
+    pub profile: Option<usize>,
+    /// Keep messages about skipped entries off the console.
+    pub quiet_skip: bool,
+    /// File the messages routed to a file are written to.
+    pub log_file: Option<String>,
+    /// Where the messages of each category go, overriding the defaults.
+    pub log_routes: Vec<(Category, Target)>,
+    /// Cap in bytes on the memory of the decode pipeline, it gets less
+    /// parallel when the predicted use is over it and the run stops if a
+    /// single worker is still predicted to go over.
+    pub memory_limit: Option<u64>,
+    /// Bytes of decoded textures the RPC server keeps cached.
+    pub cache_budget: u64,
+    /// Unix socket or Windows named pipe the RPC server listens on instead of
+    /// stdin.
+    pub serve_socket: Option<String>,
+    /// How entry payloads are unwrapped before their header is parsed.
+    pub payload: PayloadTransform,
+    /// Manifest of a previous release to write a changelog against.
+    pub changelog: Option<String>,
+    /// Hi-res texture pack, or list of its file names, to match the textures
+    /// against by their Rice hash.
+    pub hash_db: Option<String>,
+    /// Archives the converted one is patched over, bottom first, searched for
+    /// the TLUTs it lacks.
+    pub base_archives: Vec<String>,
+    /// Only convert the textures localized to this language, and report the
+    /// localized textures lacking a variant in it.
+    pub language: Option<Language>,
+    /// Only convert the textures matching this query, and no other resources.
+    pub query: Option<Query>,
+    /// Only convert the entries whose OTR header matches.
+    pub header_filter: HeaderFilter,
+    /// Decoders to run, all of them when not given.
+    pub types: Option<Vec<String>>,
+    pub layout: Layout,
+    /// Refuse archives written by another port version.
+    pub require_port_version: Option<String>,
+    /// Decomp symbol file (`symbols.txt` or linker map) used to find the
+    /// symbols requested with `--symbol`.
+    pub symbols: Option<String>,
+    /// C symbol names of the only entries to convert, all of them when not
+    /// given.
+    pub symbol_names: Option<Vec<String>>,
+    /// Resource ids, the CRC-64 of their path, of the only entries to
+    /// convert along with the symbols.
+    pub ids: Option<Vec<u64>>,
+    /// How the decomp YAML files lay out asset definitions.
+    pub yaml_dialect: YamlDialect,
+    /// How color channels narrower than 8 bits are widened.
+    pub expand: Expansion,
+    /// Game whose archive conventions are followed.
+    pub game: Game,
+    /// Decompress the texels stored MIO0, Yay0 or Yaz0 compressed, which
+    /// games keeping them so turn on.
+    pub compressed_texels: bool,
+    /// Set by `legacy <archive>`: no manifest or journal is written and CI8
+    /// alpha is taken the way the option-less tool did.
+    pub legacy: bool,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Self {
+        let args = multicall::expand(args);
+        let args = args.as_slice();
+        // `legacy <archive>` converts the way the tool did before it took any
+        // option, for scripts written against it: config.yml from the working
+        // directory, textures only, channels widened linearly, to `assets`
+        // emptied first, with no manifest or journal. The environment is
+        // ignored.
+        if args.get(1).map(String::as_str) == Some("legacy") {
+            let [program, _, zip_file] = args else {
+                panic!("Usage: legacy <archive>, legacy mode takes no options");
+            };
+            let args = [
+                program.to_owned(),
+                zip_file.to_owned(),
+                "--types=texture".to_owned(),
+                "--expand=linear".to_owned(),
+                "--clear-output".to_owned(),
+            ];
+            return Self {
+                legacy: true,
+                ..Self::parse_args(&args, Vec::new())
+            };
+        }
+        Self::parse_args(args, env_args())
+    }
+
+    /// Options of the command line `args`, after the options `env_args` set
+    /// from the environment.
+    fn parse_args(args: &[String], env_args: Vec<String>) -> Self {
+        let mut positional = Vec::new();
+        let mut config = DEFAULT_CONFIG_FILE.to_owned();
+        let mut output = "assets".to_owned();
+        let mut swap = ByteSwap::None;
+        let mut deinterleave = Deinterleave::Off;
+        let mut engine_meta = None;
+        let mut serve_rpc = false;
+        let mut stdin = false;
+        let mut stdout = false;
+        let mut tlut = None;
+        let mut strict = false;
+        let mut treat_i4_as_ia4 = false;
+        let mut threads = None;
+        let mut io_threads = None;
+        let mut read_retries = 2;
+        let mut retry_failed = None;
+        let mut io_profile = IoProfile::Nvme;
+        let mut image_format = ImageFormat::Png;
+        let mut text_format = TextFormat::Json;
+        let mut cutscene_format = CutsceneFormat::Json;
+        let mut path_svg = false;
+        let mut tmem_svg = false;
+        let mut palette_report = false;
+        let mut index_csv = false;
+        let mut skip_placeholders = false;
+        let mut resume = false;
+        let mut keep_stale = false;
+        let mut clear_output = false;
+        let mut reproducible = false;
+        let mut classify = false;
+        let mut thumbnails = None;
+        let mut derive = None;
+        let mut dilate_alpha = None;
+        let mut post_process = None;
+        let mut post_process_jobs = 1;
+        let mut report_memory = false;
+        let mut emit_c = None;
+        let mut profile = None;
+        let mut quiet_skip = false;
+        let mut log_file = None;
+        let mut log_routes = Vec::new();
+        let mut memory_limit = None;
+        let mut cache_budget = DEFAULT_CACHE_BUDGET;
+        let mut serve_socket = None;
+        let mut payload = PayloadTransform::Auto;
+        let mut changelog = None;
+        let mut hash_db = None;
+        let mut base_archives = Vec::new();
+        let mut language = None;
+        let mut query = None;
+        let mut header_filter = HeaderFilter::default();
+        let mut types = None;
+        let mut layout = Layout::ByPath;
+        let mut require_port_version = None;
+        let mut symbols = None;
+        let mut symbol_names = None;
+        let mut ids = None;
+        let mut yaml_dialect = YamlDialect::Flat;
+        let mut expand = Expansion::default();
+        let mut game = Game::Generic;
+        let mut compressed_texels = false;
+        let mut exec = None;
+        let mut hex = false;
+        let mut watch = false;
+        let mut resolve = None;
+        let mut version_info = None;
+
+        // Environment options come first so the command line overrides them.
+        // They are all `--name=value`, one argument each, and the lists they
+        // set are replaced rather than extended by the command line.
+        let mut env_remaining = env_args.len();
+        let mut replaced_lists = HashSet::new();
+        let mut args = env_args.iter().chain(args.iter().skip(1));
+        while let Some(arg) = args.next() {
+            let from_env = env_remaining > 0;
+            env_remaining = env_remaining.saturating_sub(1);
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+            match name {
+                "--config" => config = value(name, inline_value, &mut args).to_owned(),
+                "--output" => output = value(name, inline_value, &mut args).to_owned(),
+                "--swap" => {
+                    swap = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--deinterleave" => {
+                    deinterleave = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--engine-meta" => {
+                    engine_meta = Some(
+                        value(name, inline_value, &mut args)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--serve-rpc" => serve_rpc = true,
+                "--serve-socket" => {
+                    serve_socket = Some(value(name, inline_value, &mut args).to_owned());
+                    serve_rpc = true;
+                }
+                "--stdin" => stdin = true,
+                "--stdout" => stdout = true,
+                "--tlut" => tlut = Some(value(name, inline_value, &mut args).to_owned()),
+                "--strict" => strict = true,
+                "--treat-i4-as-ia4" => treat_i4_as_ia4 = true,
+                "--path-svg" => path_svg = true,
+                "--tmem-svg" => tmem_svg = true,
+                "--palette-report" => palette_report = true,
+                "--index-csv" => index_csv = true,
+                "--skip-placeholders" => skip_placeholders = true,
+                "--resume" => resume = true,
+                "--keep-stale" => keep_stale = true,
+                "--clear-output" => clear_output = true,
+                "--reproducible" => reproducible = true,
+                "--compressed-texels" => compressed_texels = true,
+                "--classify" => classify = true,
+                "--report-memory" => report_memory = true,
+                "--profile" => {
+                    profile = Some(
+                        inline_value.map_or(profile::DEFAULT_SLOWEST, |value| count(name, value)),
+                    );
+                }
+                "--quiet-skip" => quiet_skip = true,
+                "--log-file" => log_file = Some(value(name, inline_value, &mut args).to_owned()),
+                "--log" => log_routes.extend(
+                    log::parse_routes(value(name, inline_value, &mut args))
+                        .unwrap_or_else(|err| panic!("{}", err)),
+                ),
+                "--thumbnails" => {
+                    thumbnails = Some(count(name, value(name, inline_value, &mut args)));
+                }
+                "--dilate-alpha" => {
+                    dilate_alpha = Some(count(name, value(name, inline_value, &mut args)));
+                }
+                "--derive" => {
+                    derive = Some(
+                        value(name, inline_value, &mut args)
+                            .split(',')
+                            .map(|derived| derived.trim().parse())
+                            .collect::<Result<_, _>>()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--emit-c" => {
+                    emit_c = Some(
+                        value(name, inline_value, &mut args)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--post-process" => {
+                    post_process = Some(
+                        value(name, inline_value, &mut args)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--post-process-jobs" => {
+                    post_process_jobs = count(name, value(name, inline_value, &mut args));
+                }
+                "--memory-limit" => {
+                    memory_limit = Some(mebibytes(name, value(name, inline_value, &mut args)));
+                }
+                "--payload" => {
+                    payload = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--cache-budget" => {
+                    cache_budget = mebibytes(name, value(name, inline_value, &mut args));
+                }
+                "--changelog" => changelog = Some(value(name, inline_value, &mut args).to_owned()),
+                "--hash-db" => hash_db = Some(value(name, inline_value, &mut args).to_owned()),
+                "--base" => {
+                    if !from_env && replaced_lists.insert(name) {
+                        base_archives.clear();
+                    }
+                    base_archives.push(value(name, inline_value, &mut args).to_owned());
+                }
+                "--language" => {
+                    language = Some(
+                        value(name, inline_value, &mut args)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--threads" => threads = Some(count(name, value(name, inline_value, &mut args))),
+                "--image-format" => {
+                    image_format = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--text-format" => {
+                    text_format = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--cutscene-format" => {
+                    cutscene_format = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--where" => {
+                    query = Some(
+                        value(name, inline_value, &mut args)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--only-custom" => header_filter.only_custom = true,
+                "--header-version" => {
+                    let version = value(name, inline_value, &mut args);
+                    header_filter.version = Some(version.parse().unwrap_or_else(|_| {
+                        panic!("Invalid value '{}' for option '{}'", version, name)
+                    }));
+                }
+                "--byte-order" => {
+                    header_filter.byte_order = Some(
+                        value(name, inline_value, &mut args)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--types" => {
+                    types = Some(
+                        value(name, inline_value, &mut args)
+                            .split(',')
+                            .map(|name| name.trim().to_owned())
+                            .collect(),
+                    );
+                }
+                "--layout" => {
+                    layout = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--require-port-version" => {
+                    require_port_version = Some(value(name, inline_value, &mut args).to_owned());
+                }
+                "--symbols" => symbols = Some(value(name, inline_value, &mut args).to_owned()),
+                "--symbol" => {
+                    if !from_env && replaced_lists.insert(name) {
+                        symbol_names = None;
+                    }
+                    symbol_names.get_or_insert_with(Vec::new).extend(
+                        value(name, inline_value, &mut args)
+                            .split(',')
+                            .map(|name| name.trim().to_owned()),
+                    );
+                }
+                "--id" => {
+                    if !from_env && replaced_lists.insert(name) {
+                        ids = None;
+                    }
+                    ids.get_or_insert_with(Vec::new).extend(
+                        value(name, inline_value, &mut args)
+                            .split(',')
+                            .map(|id| resource_id(id.trim())),
+                    );
+                }
+                "--yaml-dialect" => {
+                    yaml_dialect = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--expand" => {
+                    expand = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--game" => {
+                    game = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                "--hex" => hex = true,
+                "--watch" => watch = true,
+                "--resolve" => {
+                    resolve = Some(
+                        value(name, inline_value, &mut args)
+                            .parse()
+                            .unwrap_or_else(|err| panic!("{}", err)),
+                    );
+                }
+                "--version-info" => {
+                    version_info = Some(match inline_value {
+                        None => false,
+                        Some("json") => true,
+                        Some(value) => panic!(
+                            "Invalid value '{}' for option '{}', expected json",
+                            value, name
+                        ),
+                    });
+                }
+                "--exec" => exec = Some(value(name, inline_value, &mut args).to_owned()),
+                "--threads-io" => {
+                    io_threads = Some(count(name, value(name, inline_value, &mut args)))
+                }
+                "--read-retries" => {
+                    let retries = value(name, inline_value, &mut args);
+                    read_retries = retries.parse().unwrap_or_else(|_| {
+                        panic!("Invalid value '{}' for option '{}'", retries, name)
+                    });
+                }
+                "--retry-failed" => {
+                    retry_failed = Some(value(name, inline_value, &mut args).to_owned());
+                }
+                "--io-profile" => {
+                    io_profile = value(name, inline_value, &mut args)
+                        .parse()
+                        .unwrap_or_else(|err| panic!("{}", err));
+                }
+                _ if name.starts_with("--") => panic!("Unknown option '{}'", name),
+                _ => positional.push(arg.to_owned()),
+            }
+        }
+
+        let mut positional = positional.into_iter().peekable();
+        let subcommand = match positional.peek().map(String::as_str) {
+            Some(
+                "replace" | "reencode-ci" | "generate-yaml" | "explain" | "grep" | "info" | "patch"
+                | "merge" | "encode" | "transform" | "schema" | "mount" | "install-aliases",
+            ) => positional.next(),
+            _ => None,
+        };
+        // The image read from stdin can only go to stdout for now
+        match (stdin, stdout) {
+            (true, false) => panic!("--stdin writes the image to stdout, pass --stdout too"),
+            (false, true) => panic!("--stdout only writes the resource read with --stdin"),
+            _ => {}
+        }
+        // The schemas, capabilities and aliases don't depend on an archive
+        let zip_file = if stdin
+            || version_info.is_some()
+            || matches!(
+                subcommand.as_deref(),
+                Some("schema" | "encode" | "install-aliases")
+            ) {
+            String::new()
+        } else {
+            positional
+                .next()
+                .or_else(|| env::var(format!("{}ARCHIVE", ENV_PREFIX)).ok())
+                .expect("Please provide a zip file path as the first argument.")
+        };
+        let command = match subcommand.as_deref() {
+            _ if version_info.is_some() => Command::VersionInfo {
+                json: version_info == Some(true),
+            },
+            Some("replace") => {
+                let usage = "Usage: replace <archive> <entry> <png> [output]";
+                let entry = positional.next().expect(usage);
+                let image = positional.next().expect(usage);
+                let output = positional.next().unwrap_or_else(|| {
+                    std::path::Path::new(&zip_file)
+                        .with_extension("replaced.o2r")
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                Command::Replace {
+                    entry,
+                    image,
+                    output,
+                }
+            }
+            Some("reencode-ci") => {
+                let usage = "Usage: reencode-ci <archive> <entry> <png> [output]";
+                let entry = positional.next().expect(usage);
+                let image = positional.next().expect(usage);
+                let output = positional.next().unwrap_or_else(|| {
+                    std::path::Path::new(&zip_file)
+                        .with_extension("reencoded.o2r")
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                Command::ReencodeCi {
+                    entry,
+                    image,
+                    output,
+                }
+            }
+            Some("generate-yaml") => Command::GenerateYaml {
+                output: positional.next().unwrap_or_else(|| "yaml".to_owned()),
+            },
+            Some("explain") => Command::Explain {
+                entry: positional.next().expect("Usage: explain <archive> <entry>"),
+            },
+            Some("grep") => {
+                let pattern = positional
+                    .next()
+                    .expect("Usage: grep <archive> <text or hex:bytes>");
+                Command::Grep {
+                    pattern: grep::parse_pattern(&pattern).unwrap_or_else(|err| panic!("{}", err)),
+                }
+            }
+            Some("info") => Command::Info {
+                entry: positional
+                    .next()
+                    .expect("Usage: info <archive> <entry> [--hex]"),
+                hex: std::mem::take(&mut hex),
+            },
+            Some("patch") => Command::Patch {
+                folder: positional
+                    .next()
+                    .expect("Usage: patch <archive> <folder> [output] [--watch]"),
+                output: positional.next().unwrap_or_else(|| {
+                    std::path::Path::new(&zip_file)
+                        .with_extension("patch.o2r")
+                        .to_string_lossy()
+                        .into_owned()
+                }),
+                watch: std::mem::take(&mut watch),
+            },
+            Some("encode") => {
+                let usage = "Usage: encode <png> <format> [output]";
+                let image = positional.next().expect(usage);
+                let format = encode::parse_format(&positional.next().expect(usage))
+                    .unwrap_or_else(|err| panic!("{}", err));
+                // Resources have no extension in archives
+                let output = positional.next().unwrap_or_else(|| {
+                    std::path::Path::new(&image)
+                        .with_extension("")
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                Command::Encode {
+                    image,
+                    format,
+                    output,
+                }
+            }
+            Some("merge") => {
+                let usage =
+                    "Usage: merge <archive> <output> <patch>... [--resolve priority|newest|ask]";
+                let output = positional.next().expect(usage);
+                let patches = positional.by_ref().collect::<Vec<_>>();
+                if patches.is_empty() {
+                    panic!("{}", usage);
+                }
+                Command::Merge {
+                    output,
+                    patches,
+                    resolve: resolve.take().unwrap_or(merge::Resolve::Priority),
+                }
+            }
+            Some("transform") => {
+                let usage = "Usage: transform <archive> --exec <script> [output]";
+                Command::Transform {
+                    script: exec.take().expect(usage),
+                    output: positional.next().unwrap_or_else(|| {
+                        std::path::Path::new(&zip_file)
+                            .with_extension("patch.o2r")
+                            .to_string_lossy()
+                            .into_owned()
+                    }),
+                }
+            }
+            #[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+            Some("mount") => Command::Mount {
+                mountpoint: positional
+                    .next()
+                    .expect("Usage: mount <archive> <mountpoint>"),
+            },
+            #[cfg(not(all(feature = "fuse", any(target_os = "linux", target_os = "macos"))))]
+            Some("mount") => panic!("mount needs a Linux or macOS build with the fuse feature"),
+            Some("schema") => Command::Schema {
+                output: positional.next().unwrap_or_else(|| "schema".to_owned()),
+            },
+            // Next to the binary by default, which is on the PATH
+            Some("install-aliases") => Command::InstallAliases {
+                dir: positional.next().unwrap_or_else(|| {
+                    std::env::current_exe()
+                        .ok()
+                        .and_then(|binary| Some(binary.parent()?.to_string_lossy().into_owned()))
+                        .expect("Usage: install-aliases [folder]")
+                }),
+            },
+            _ => Command::Convert,
+        };
+        if exec.is_some() {
+            panic!("--exec is only used by transform");
+        }
+        if hex {
+            panic!("--hex is only used by info");
+        }
+        if watch {
+            panic!("--watch is only used by patch");
+        }
+        if resolve.is_some() {
+            panic!("--resolve is only used by merge");
+        }
+        // Streamed outputs never land in a folder to resume, process or seal
+        if output == "-" {
+            let folder_options = [
+                ("--resume", resume),
+                ("--clear-output", clear_output),
+                ("--retry-failed", retry_failed.is_some()),
+                ("--post-process", post_process.is_some()),
+                ("--reproducible", reproducible),
+                ("--serve-rpc", serve_rpc),
+            ];
+            if let Some((name, _)) = folder_options.iter().find(|(_, used)| *used) {
+                panic!(
+                    "{} needs an output folder, it can't be used with --output -",
+                    name
+                );
+            }
+            if !matches!(command, Command::Convert) {
+                panic!("--output - only streams the outputs of a conversion");
+            }
+        }
+        // Entries that failed are retried in the output folder of their run
+        resume |= retry_failed.is_some();
+        if clear_output && resume {
+            panic!("--clear-output empties the folder --resume and --retry-failed continue in");
+        }
+        let threads = threads.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1)
+        });
+
+        if let Some(arg) = positional.next() {
+            panic!("Unexpected argument '{}'", arg);
+        }
+
+        Options {
+            command,
+            zip_file,
+            config,
+            output,
+            swap,
+            deinterleave,
+            engine_meta,
+            serve_rpc,
+            stdin,
+            tlut,
+            strict,
+            treat_i4_as_ia4,
+            threads,
+            io_threads: io_threads.unwrap_or_else(|| io_profile.io_threads(threads)),
+            io_profile,
+            read_retries,
+            retry_failed,
+            image_format,
+            text_format,
+            cutscene_format,
+            path_svg,
+            tmem_svg,
+            palette_report,
+            index_csv,
+            skip_placeholders,
+            resume,
+            keep_stale,
+            clear_output,
+            reproducible,
+            classify,
+            thumbnails,
+            derive,
+            dilate_alpha,
+            post_process,
+            post_process_jobs,
+            report_memory,
+            emit_c,
+            profile,
+            quiet_skip,
+            log_file,
+            log_routes,
+            memory_limit,
+            cache_budget,
+            serve_socket,
+            payload,
+            changelog,
+            hash_db,
+            base_archives,
+            language,
+            query,
+            header_filter,
+            types,
+            layout,
+            require_port_version,
+            symbols,
+            symbol_names,
+            ids,
+            yaml_dialect,
+            expand,
+            game,
+            compressed_texels: compressed_texels || game.compressed_texels(),
+            legacy: false,
+        }
+    }
+
+    /// Settings of the texture decoders given on the command line.
+    pub fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions {
+            expansion: self.expand,
+            swap: self.swap,
+            deinterleave: self.deinterleave,
+            compressed_texels: self.compressed_texels,
+            legacy_ci8_alpha: self.legacy,
+        }
+    }
+}
+
+/// Value of an option given either as `--name=value` or `--name value`.
+fn value<'a>(
+    name: &str,
+    inline_value: Option<&'a str>,
+    args: &mut impl Iterator<Item = &'a String>,
+) -> &'a str {
+    inline_value
+        .or_else(|| args.next().map(|arg| arg.as_str()))
+        .unwrap_or_else(|| panic!("Missing value for option '{}'", name))
+}
+
+/// Resource id given to `--id` in hex, as `info` prints it.
+fn resource_id(value: &str) -> u64 {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    match u64::from_str_radix(digits, 16) {
+        Ok(id) if digits.len() <= 16 => id,
+        _ => panic!(
+            "Invalid resource id '{}', expected up to 16 hex digits",
+            value
+        ),
+    }
+}
+
+/// Count given to `name`, which must be at least one and fit in a `T`.
+fn count<T: FromStr + PartialOrd + From<u8>>(name: &str, value: &str) -> T {
+    match value.parse() {
+        Ok(count) if count >= T::from(1) => count,
+        _ => panic!("Invalid value '{}' for option '{}'", value, name),
+    }
+}
+
+/// Size in bytes of `value` MiB given to `name`, which must be at least one
+/// and fit in a `u64` once in bytes.
+fn mebibytes(name: &str, value: &str) -> u64 {
+    match value
+        .parse::<u64>()
+        .map(|size| size.checked_mul(1024 * 1024))
+    {
+        Ok(Some(size)) if size > 0 => size,
+        _ => panic!("Invalid value '{}' for option '{}'", value, name),
+    }
+}
+
+/// Options set through `CTO2R_*` environment variables, as arguments.
+fn env_args() -> Vec<String> {
+    let env_name = |option: &str| {
+        ENV_PREFIX.to_owned()
+            + &option
+                .trim_start_matches("--")
+                .replace('-', "_")
+                .to_uppercase()
+    };
+
+    let mut args = Vec::new();
+    for option in ENV_VALUE_OPTIONS {
+        if let Ok(value) = env::var(env_name(option)) {
+            args.push(format!("{}={}", option, value));
+        }
+    }
+    for option in ENV_FLAG_OPTIONS {
+        let name = env_name(option);
+        match env::var(&name).as_deref() {
+            Ok("1" | "true" | "yes") => args.push(option.to_string()),
+            Ok("" | "0" | "false" | "no") | Err(_) => {}
+            Ok(value) => panic!("Invalid value '{}' for {}", value, name),
+        }
+    }
+    args
+}
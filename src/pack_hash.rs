@@ -0,0 +1,165 @@
+use crate::{TextureFormat, TextureType, json::Json, palette};
+
+/// Emulator plugin whose texture hashing is reproduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Rice,
+    GlideN64,
+}
+
+/// CRC of `height` rows of `width` texels of size `siz` (0 for 4-bit up to 3
+/// for 32-bit) starting `row_stride` bytes apart, the way the plugins hash
+/// texture data in RDRAM. Rows are read from the first in memory down, each
+/// as big-endian words from its end, while the row number mixed in counts
+/// down from `height - 1`. The schemes only differ on rows under 4 bytes:
+/// Rice adds the row number alone, GlideN64 mixes it with the last word read.
+pub fn crc(
+    scheme: Scheme,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    siz: u8,
+    row_stride: usize,
+) -> u32 {
+    let bytes_per_row = (((width as usize) << siz) + 1) >> 1;
+    let mut crc = 0u32;
+    let mut word = 0u32;
+    for (i, y) in (0..height as usize).rev().enumerate() {
+        let row = &data[i * row_stride..];
+        if scheme == Scheme::Rice {
+            word = 0;
+        }
+        if let Some(last) = bytes_per_row.checked_sub(4) {
+            for x in (0..=last).rev().step_by(4) {
+                word = u32::from_be_bytes([row[x], row[x + 1], row[x + 2], row[x + 3]]) ^ x as u32;
+                crc = crc.rotate_left(4).wrapping_add(word);
+            }
+        }
+        crc = crc.wrapping_add(word ^ y as u32);
+    }
+    crc
+}
+
+/// Texture identity in the Rice hi-res texture pack naming scheme,
+/// `<ROM>#<crc>#<fmt>#<siz>[#<palette crc>]_<kind>.png`, also loaded by
+/// GlideN64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PackHash {
+    pub crc: u32,
+    pub fmt: u8,
+    pub siz: u8,
+    /// CRC of the TLUT of CI textures. Rice hashes the first 16 colors for
+    /// CI4 and 256 for CI8, GlideN64 only the colors up to the highest index
+    /// the texture uses.
+    pub palette_crc: Option<u32>,
+}
+
+impl PackHash {
+    /// Hash in `scheme` of `texture`, its texel rows packed as in RDRAM, and
+    /// of its `tlut` for CI textures. IA1 textures have no RDP format and no
+    /// hash.
+    pub fn compute(
+        scheme: Scheme,
+        texture: &TextureFormat,
+        tlut: Option<&TextureFormat>,
+    ) -> Option<Self> {
+        let (fmt, siz) = texture.type_id.to_fmt_siz()?;
+        let row_stride = texture.row_size(texture.width).ok()?;
+        if texture.data.len() < texture.data_size().ok()? {
+            return None;
+        }
+        let palette_crc = tlut.map(|tlut| {
+            let colors = match (scheme, &texture.type_id) {
+                (Scheme::GlideN64, _) => {
+                    palette::indices(texture).into_iter().max().unwrap_or(0) as usize + 1
+                }
+                (Scheme::Rice, TextureType::Palette4bpp) => 16,
+                (Scheme::Rice, _) => 256,
+            };
+            // A short TLUT is hashed as if the rest of TMEM was zeroed
+            let mut palette = tlut.data[..palette::entry_count(tlut).min(colors) * 2].to_vec();
+            palette.resize(colors * 2, 0);
+            crc(scheme, &palette, colors as u32, 1, 2, colors * 2)
+        });
+        Some(PackHash {
+            crc: crc(
+                scheme,
+                &texture.data,
+                texture.width,
+                texture.height,
+                siz,
+                row_stride,
+            ),
+            fmt,
+            siz,
+            palette_crc,
+        })
+    }
+
+    /// The 64-bit checksum GlideN64 keys its texture cache with, the palette
+    /// CRC in the high half.
+    pub fn checksum64(&self) -> u64 {
+        (self.palette_crc.unwrap_or(0) as u64) << 32 | self.crc as u64
+    }
+
+    /// The hash fields of a pack file name, `<crc>#<fmt>#<siz>[#<palette crc>]`.
+    pub fn key(&self) -> String {
+        let key = format!("{:08X}#{}#{}", self.crc, self.fmt, self.siz);
+        match self.palette_crc {
+            Some(palette_crc) => format!("{}#{:08X}", key, palette_crc),
+            None => key,
+        }
+    }
+
+    /// Hash of a Rice format pack file name such as
+    /// `THE LEGEND OF ZELDA#2E0B8B4C#0#2_all.png`, the ROM name being ignored.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let file_name = name.rsplit('/').next()?;
+        let (fields, _kind) = file_name.rsplit_once('_')?;
+        let mut fields = fields.rsplit('#').collect::<Vec<_>>();
+        let palette_crc = match fields.first() {
+            Some(field) if field.len() == 8 => {
+                Some(u32::from_str_radix(fields.remove(0), 16).ok()?)
+            }
+            _ => None,
+        };
+        let [siz, fmt, crc, _rom, ..] = fields[..] else {
+            return None;
+        };
+        if crc.len() != 8 {
+            return None;
+        }
+        Some(PackHash {
+            crc: u32::from_str_radix(crc, 16).ok()?,
+            fmt: fmt.parse().ok()?,
+            siz: siz.parse().ok()?,
+            palette_crc,
+        })
+    }
+}
+
+/// Hashes of a texture in both plugin schemes.
+#[derive(Debug, Clone)]
+pub struct PackHashes {
+    pub rice: PackHash,
+    pub gliden64: PackHash,
+}
+
+impl PackHashes {
+    pub fn compute(texture: &TextureFormat, tlut: Option<&TextureFormat>) -> Option<Self> {
+        Some(PackHashes {
+            rice: PackHash::compute(Scheme::Rice, texture, tlut)?,
+            gliden64: PackHash::compute(Scheme::GlideN64, texture, tlut)?,
+        })
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object()
+            .with("rice", self.rice.key())
+            .with("gliden64", self.gliden64.key())
+            .with(
+                "gliden64_checksum",
+                format!("{:016X}", self.gliden64.checksum64()),
+            )
+    }
+}
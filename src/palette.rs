@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+
+use crate::{TextureFormat, TextureType, json::Json, pixels};
+
+/// Colors in a CI4 palette bank.
+pub const BANK_SIZE: usize = 16;
+
+/// File the `--palette-report` is written to in the output folder.
+pub const PALETTE_REPORT_FILE: &str = "palette_usage.json";
+
+/// Number of colors in a TLUT resource, each stored as a 16-bit RGBA5551 value.
+/// TLUTs don't always fill 256 entries and their width and height don't
+/// always say how many they hold, so the count comes from the payload size,
+/// capped to the data actually present.
+pub fn entry_count(tlut: &TextureFormat) -> usize {
+    let size = match tlut.size as usize {
+        0 => tlut.data.len(),
+        size => size.min(tlut.data.len()),
+    };
+    size / 2
+}
+
+/// Why pairing a texture of type `type_id` with a TLUT of `entries` colors
+/// looks wrong: CI8 textures come with palettes of up to 256 colors and CI4
+/// ones with 16-color banks, so a CI8 texture on a 16-color TLUT or a CI4
+/// texture on a larger one was likely given the TLUT of another texture.
+pub fn pairing_mismatch(type_id: &TextureType, entries: usize) -> Option<String> {
+    match type_id {
+        TextureType::Palette8bpp if entries <= BANK_SIZE => Some(format!(
+            "CI8 texture with a {}-color TLUT, the size of a CI4 bank",
+            entries
+        )),
+        TextureType::Palette4bpp if entries > BANK_SIZE => Some(format!(
+            "CI4 texture with a {}-color TLUT, it only reads the first {}",
+            entries, BANK_SIZE
+        )),
+        _ => None,
+    }
+}
+
+/// Palette indices of a CI texture, in pixel order.
+pub fn indices(texture_format: &TextureFormat) -> Vec<u8> {
+    let pixel_count = texture_format.width as usize * texture_format.height as usize;
+    match texture_format.type_id {
+        TextureType::Palette4bpp => pixels::unpack_4bpp(
+            &texture_format.data,
+            texture_format.width,
+            texture_format.height,
+        ),
+        TextureType::Palette8bpp => texture_format
+            .data
+            .iter()
+            .take(pixel_count)
+            .copied()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A CI texture referencing colors past the end of its TLUT.
+pub struct PaletteOverflow {
+    pub max_index: u8,
+    pub entries: usize,
+}
+
+impl PaletteOverflow {
+    /// Number of palette slots missing from the TLUT.
+    pub fn missing(&self) -> usize {
+        self.max_index as usize + 1 - self.entries
+    }
+}
+
+/// Checks that every index used by `texture_format` exists in `tlut`, which
+/// usually fails when the texture was associated with the wrong TLUT.
+pub fn check(texture_format: &TextureFormat, tlut: &TextureFormat) -> Option<PaletteOverflow> {
+    let max_index = indices(texture_format).into_iter().max()?;
+    let entries = entry_count(tlut);
+    (max_index as usize >= entries).then_some(PaletteOverflow { max_index, entries })
+}
+
+/// Palette slots of its TLUT a CI texture reads.
+pub struct PaletteUsage {
+    /// TLUT symbol from the asset definitions.
+    pub tlut: String,
+    /// TLUT entry the palette index 0 of the texture reads, the start of the
+    /// `palette_index` bank of a CI4 texture.
+    pub start: usize,
+    pub entries: usize,
+    /// Whether each TLUT entry from `start` on is used, by palette index.
+    pub used: Vec<bool>,
+}
+
+impl PaletteUsage {
+    /// Usage of `tlut`, the colors from the TLUT entry `start` on, by
+    /// `texture_format`.
+    pub fn new(
+        texture_format: &TextureFormat,
+        tlut_name: &str,
+        tlut: &TextureFormat,
+        start: usize,
+    ) -> Self {
+        let entries = entry_count(tlut);
+        let mut used = vec![false; entries];
+        for index in indices(texture_format) {
+            // Indices past the end are reported as overflows
+            if let Some(slot) = used.get_mut(index as usize) {
+                *slot = true;
+            }
+        }
+        PaletteUsage {
+            tlut: tlut_name.to_owned(),
+            start,
+            entries,
+            used,
+        }
+    }
+
+    /// TLUT entries of the usage no texture reads.
+    pub fn unused(&self) -> Vec<usize> {
+        (0..self.entries)
+            .filter(|i| !self.used[*i])
+            .map(|i| self.start + i)
+            .collect()
+    }
+
+    /// Adds the usage fields to the JSON object `json`.
+    fn add_to(&self, json: Json) -> Json {
+        let unused = self.unused();
+        json.with("tlut", self.tlut.as_str())
+            .with("entries", self.entries)
+            .with("used", self.entries - unused.len())
+            .with(
+                "unused",
+                Json::Array(unused.into_iter().map(Json::from).collect()),
+            )
+    }
+}
+
+/// Palette usage of every converted CI texture, aggregated per TLUT to show
+/// which slots no texture reads and can be repurposed.
+#[derive(Default)]
+pub struct PaletteReport {
+    textures: Vec<(String, PaletteUsage)>,
+}
+
+impl PaletteReport {
+    pub fn add(&mut self, name: String, usage: PaletteUsage) {
+        self.textures.push((name, usage));
+    }
+
+    /// Usage of each TLUT over all the textures sharing it, with the number of
+    /// textures, sorted by TLUT. Banks of CI4 textures count at their place
+    /// in the TLUT, which covers up to the end of the last bank read.
+    fn tluts(&self) -> Vec<(PaletteUsage, usize)> {
+        let mut tluts: BTreeMap<&str, (PaletteUsage, usize)> = BTreeMap::new();
+        for (_, usage) in &self.textures {
+            let (total, count) = tluts.entry(&usage.tlut).or_insert_with(|| {
+                let usage = PaletteUsage {
+                    tlut: usage.tlut.clone(),
+                    start: 0,
+                    entries: 0,
+                    used: Vec::new(),
+                };
+                (usage, 0)
+            });
+            let end = usage.start + usage.entries;
+            if end > total.entries {
+                total.entries = end;
+                total.used.resize(end, false);
+            }
+            for (total, used) in total.used[usage.start..].iter_mut().zip(&usage.used) {
+                *total |= used;
+            }
+            *count += 1;
+        }
+        tluts.into_values().collect()
+    }
+
+    /// Total of the TLUT slots no texture uses.
+    pub fn wasted_slots(&self) -> usize {
+        self.tluts()
+            .iter()
+            .map(|(usage, _)| usage.unused().len())
+            .sum()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut textures = self.textures.iter().collect::<Vec<_>>();
+        textures.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Json::object()
+            .with(
+                "tluts",
+                Json::Array(
+                    self.tluts()
+                        .into_iter()
+                        .map(|(usage, count)| usage.add_to(Json::object()).with("textures", count))
+                        .collect(),
+                ),
+            )
+            .with(
+                "textures",
+                Json::Array(
+                    textures
+                        .into_iter()
+                        .map(|(name, usage)| {
+                            usage.add_to(Json::object().with("entry", name.as_str()))
+                        })
+                        .collect(),
+                ),
+            )
+    }
+}
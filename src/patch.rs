@@ -0,0 +1,223 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use walkdir::WalkDir;
+
+use crate::{
+    DecodedTexture, asset_definitions,
+    config::Config,
+    decode_entry, load_pitches, load_tlut_config, log,
+    manifest::{MANIFEST_FILE, Manifest},
+    options::Options,
+    read_entry, replace, stream,
+    tlut::Tluts,
+};
+
+/// How often `--watch` looks for saved images.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The archive and what decoding its textures needs, for building patches
+/// holding only the textures whose image changed.
+pub struct Patcher<'a> {
+    pub options: &'a Options,
+    pub zip: zip::ZipArchive<File>,
+    pub file_names: Vec<String>,
+    config: Config,
+    tluts: Tluts,
+    pitches: HashMap<String, u32>,
+}
+
+impl<'a> Patcher<'a> {
+    pub fn open(options: &'a Options) -> Self {
+        let zip =
+            zip::ZipArchive::new(File::open(&options.zip_file).expect("Failed to open zip file"))
+                .expect("Failed to read zip file");
+        let file_names = zip
+            .file_names()
+            .map(|name| name.to_owned())
+            .collect::<Vec<String>>();
+        let config = Config::load(&options.config);
+        let definitions = asset_definitions(&config, options.yaml_dialect);
+        let tluts = Tluts::open(options, &file_names, load_tlut_config(&definitions));
+        Patcher {
+            options,
+            zip,
+            file_names,
+            config,
+            tluts,
+            pitches: load_pitches(&definitions),
+        }
+    }
+
+    /// The resource of the texture `name` and its texels, `None` for other
+    /// entries and textures that don't decode.
+    pub fn decode(&mut self, name: &str) -> Option<(Vec<u8>, DecodedTexture)> {
+        let data = read_entry(&mut self.zip, name, &self.options.payload)?;
+        let texture = self.decode_resource(name, &data)?;
+        Some((data, texture))
+    }
+
+    /// Texels of `data`, a version of the texture `name` such as one from
+    /// another archive, with the TLUTs of the archive.
+    pub fn decode_resource(&self, name: &str, data: &[u8]) -> Option<DecodedTexture> {
+        match decode_entry(
+            name,
+            data,
+            &self.options.decode_options(),
+            &self.tluts,
+            &self.pitches,
+            self.options.treat_i4_as_ia4 || self.config.treats_i4_as_ia4(name),
+        ) {
+            Ok(texture) => texture,
+            Err(err) => {
+                log::skip(err);
+                None
+            }
+        }
+    }
+
+    /// The textures of `images`, pairs of an archive entry and an image of
+    /// it, whose image differs from the decoded entry, re-encoded to their
+    /// original formats. Missing images are left out.
+    pub fn changed(&mut self, images: &[(String, PathBuf)]) -> HashMap<String, Vec<u8>> {
+        let mut replacements = HashMap::new();
+        for (name, path) in images {
+            let Ok(image) = image::open(path) else {
+                continue;
+            };
+            let Some((data, texture)) = self.decode(name) else {
+                continue;
+            };
+            let pixels = stream::rgba8(
+                texture.format,
+                &texture.data,
+                texture.width as usize * texture.height as usize,
+            );
+            if image.to_rgba8().into_raw() == pixels {
+                continue;
+            }
+            let tlut = self.tluts.for_texture(name, &texture.type_id);
+            match replace::encode_resource(
+                name,
+                &data,
+                &image,
+                tlut.as_deref(),
+                self.options.swap,
+                self.options.expand,
+            ) {
+                Ok(resource) => {
+                    replacements.insert(name.to_owned(), resource);
+                }
+                Err(err) => log::error(err),
+            }
+        }
+        replacements
+    }
+
+    /// Writes `replacements` to the patch archive `output`. The archive is
+    /// written next to it and moved over it, so a port reloading it never
+    /// reads it half written.
+    pub fn write(&mut self, output: &str, replacements: &HashMap<String, Vec<u8>>) {
+        let partial = format!("{}.partial", output);
+        replace::write_patch(&mut self.zip, &partial, replacements);
+        fs::rename(&partial, output)
+            .unwrap_or_else(|err| panic!("Failed to write {}: {}", output, err));
+        println!(
+            "Wrote {} changed textures to {}",
+            replacements.len(),
+            output
+        );
+    }
+}
+
+/// Writes the textures of the folder `folder` whose image differs from the
+/// archive to the patch archive `output`. With `watch`, keeps rebuilding it
+/// whenever an image is saved.
+pub fn run(options: &Options, folder: &str, output: &str, watch: bool) {
+    let mut patcher = Patcher::open(options);
+    let images = images(&patcher, folder);
+    // Taken first, images saved while the patch is built are compared again
+    let seen = stamps(&images);
+    let replacements = patcher.changed(&images);
+    patcher.write(output, &replacements);
+    if watch {
+        println!("Watching {} for edited images", folder);
+        rebuild_on_save(&mut patcher, folder, output, replacements, seen);
+    }
+}
+
+/// The images of `folder` with the archive entry each stands for. A folder
+/// written by a conversion maps its images back to entries with its
+/// manifest, otherwise every `<entry>.png` is matched to the entry of that
+/// path.
+fn images(patcher: &Patcher, folder: &str) -> Vec<(String, PathBuf)> {
+    let manifest = Path::new(folder).join(MANIFEST_FILE);
+    if manifest.exists() {
+        Manifest::load_textures(&manifest.to_string_lossy())
+            .unwrap_or_else(|err| panic!("Failed to read {}: {}", manifest.display(), err))
+            .into_iter()
+            .map(|texture| (texture.entry, Path::new(folder).join(texture.output)))
+            .collect::<Vec<_>>()
+    } else {
+        WalkDir::new(folder)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .filter_map(|file| {
+                let path = file.path().strip_prefix(folder).ok()?;
+                let name = path.to_string_lossy().replace('\\', "/");
+                let name = name.strip_suffix(".png")?;
+                patcher
+                    .file_names
+                    .iter()
+                    .any(|entry| entry == name)
+                    .then(|| (name.to_owned(), file.path().to_owned()))
+            })
+            .collect()
+    }
+}
+
+/// Modification time and size of every image of `images` that exists.
+fn stamps(images: &[(String, PathBuf)]) -> HashMap<PathBuf, (SystemTime, u64)> {
+    images
+        .iter()
+        .filter_map(|(_, path)| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((path.clone(), (metadata.modified().ok()?, metadata.len())))
+        })
+        .collect()
+}
+
+/// Rebuilds the patch archive `output` every time images of `folder` are
+/// saved or deleted, for ports that reload it while the game runs. Only the
+/// images whose stamp differs from `seen` are compared again, the others keep
+/// their entry of `replacements`. Runs until the process is stopped.
+fn rebuild_on_save(
+    patcher: &mut Patcher,
+    folder: &str,
+    output: &str,
+    mut replacements: HashMap<String, Vec<u8>>,
+    mut seen: HashMap<PathBuf, (SystemTime, u64)>,
+) {
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let images = images(patcher, folder);
+        let current = stamps(&images);
+        if current == seen {
+            continue;
+        }
+        let (saved, unchanged): (Vec<_>, Vec<_>) = images
+            .into_iter()
+            .filter(|(_, path)| current.contains_key(path))
+            .partition(|(_, path)| current.get(path) != seen.get(path));
+        // Deleted images and ones saved back to the original leave the patch
+        replacements.retain(|name, _| unchanged.iter().any(|(entry, _)| entry == name));
+        seen = current;
+        replacements.extend(patcher.changed(&saved));
+        patcher.write(output, &replacements);
+    }
+}
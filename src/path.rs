@@ -0,0 +1,146 @@
+use std::fmt::Write;
+
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, ResourceType, decoder::ResourceDecoder, json::Json,
+    log, reader::Reader,
+};
+
+/// Margin around the plotted points, in SVG units.
+const SVG_MARGIN: f64 = 20.0;
+
+/// Decodes a Path resource into its lists of points.
+pub fn parse(data: &[u8]) -> Result<Vec<Vec<[i16; 3]>>, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let path_count = reader.u32()?;
+    (0..path_count)
+        .map(|_| {
+            let point_count = reader.u32()?;
+            (0..point_count)
+                .map(|_| Ok([reader.i16()?, reader.i16()?, reader.i16()?]))
+                .collect()
+        })
+        .collect()
+}
+
+pub fn to_json(paths: &[Vec<[i16; 3]>]) -> Json {
+    Json::object().with(
+        "paths",
+        Json::Array(
+            paths
+                .iter()
+                .map(|points| {
+                    Json::Array(
+                        points
+                            .iter()
+                            .map(|point| {
+                                Json::Array(
+                                    point.iter().map(|value| (*value as f64).into()).collect(),
+                                )
+                            })
+                            .collect(),
+                    )
+                })
+                .collect(),
+        ),
+    )
+}
+
+/// Top-down plot of the paths on the X/Z plane, one polyline per path with its
+/// points numbered.
+pub fn svg(paths: &[Vec<[i16; 3]>]) -> String {
+    let points = paths.iter().flatten();
+    let (min_x, max_x, min_z, max_z) = points.fold(
+        (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+        |(min_x, max_x, min_z, max_z), [x, _, z]| {
+            let (x, z) = (*x as f64, *z as f64);
+            (min_x.min(x), max_x.max(x), min_z.min(z), max_z.max(z))
+        },
+    );
+    let (min_x, min_z, width, height) = if min_x > max_x {
+        (0.0, 0.0, 0.0, 0.0)
+    } else {
+        (min_x, min_z, max_x - min_x, max_z - min_z)
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+        min_x - SVG_MARGIN,
+        min_z - SVG_MARGIN,
+        width + SVG_MARGIN * 2.0,
+        height + SVG_MARGIN * 2.0
+    );
+    let stroke = (width.max(height) / 300.0).max(1.0);
+    for (i, points) in paths.iter().enumerate() {
+        let hue = i * 360 / paths.len().max(1);
+        let coordinates = points
+            .iter()
+            .map(|[x, _, z]| format!("{},{}", x, z))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(
+            svg,
+            "  <g id=\"path_{}\" fill=\"hsl({}, 70%, 40%)\" stroke=\"hsl({}, 70%, 40%)\" font-size=\"{}\">",
+            i,
+            hue,
+            hue,
+            stroke * 8.0
+        );
+        let _ = writeln!(
+            svg,
+            "    <polyline points=\"{}\" fill=\"none\" stroke-width=\"{}\"/>",
+            coordinates, stroke
+        );
+        for (j, [x, _, z]) in points.iter().enumerate() {
+            let _ = writeln!(
+                svg,
+                "    <circle cx=\"{}\" cy=\"{}\" r=\"{}\"/><text x=\"{}\" y=\"{}\" stroke=\"none\">{}</text>",
+                x,
+                z,
+                stroke * 2.0,
+                *x as f64 + stroke * 3.0,
+                *z as f64 - stroke * 3.0,
+                j
+            );
+        }
+        let _ = writeln!(svg, "  </g>");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Exports paths to JSON, and SVG with `--path-svg`.
+pub struct PathDecoder;
+
+impl ResourceDecoder for PathDecoder {
+    fn name(&self) -> &'static str {
+        "path"
+    }
+
+    fn directory(&self) -> &'static str {
+        "paths"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::Path, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let paths = match parse(data) {
+            Ok(paths) => paths,
+            Err(err) => {
+                log::error(format!("Failed to parse path {}: {}", name, err));
+                return;
+            }
+        };
+
+        let base = converter.output_base(self, name);
+        log::progress(format!("Exporting {} paths: {}.json", paths.len(), base));
+        converter.write(&(base.clone() + ".json"), to_json(&paths).pretty() + "\n");
+        if converter.options.path_svg {
+            converter.write(&(base + ".svg"), svg(&paths));
+        }
+    }
+}
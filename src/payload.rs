@@ -0,0 +1,179 @@
+use std::{borrow::Cow, str::FromStr};
+
+use crate::{OTR_HEADER_SIZE, OTRHeader, ResourceType, compression};
+
+/// Layers tried on top of each other when detecting them, a zlib stream of
+/// an XORed resource takes two.
+const MAX_LAYERS: usize = 4;
+
+/// Most bytes a layer unwraps to, well over the largest resources, so that a
+/// few bytes claiming gigabytes aren't allocated.
+const MAX_UNWRAPPED_SIZE: usize = 256 * 1024 * 1024;
+
+/// Obfuscation some community packers wrap the resources of their entries
+/// in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Layer {
+    /// zlib stream of the resource.
+    Zlib,
+    /// Resource XORed with a repeating key.
+    Xor(Vec<u8>),
+    /// Resource compressed with one of Nintendo's LZ formats.
+    Mio0,
+    Yay0,
+    Yaz0,
+}
+
+impl Layer {
+    fn unwrap(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Layer::Zlib => inflate_zlib(data),
+            Layer::Xor(key) => Ok(xor(data, key)),
+            Layer::Mio0 => compression::mio0(data, MAX_UNWRAPPED_SIZE),
+            Layer::Yay0 => compression::yay0(data, MAX_UNWRAPPED_SIZE),
+            Layer::Yaz0 => compression::yaz0(data, MAX_UNWRAPPED_SIZE),
+        }
+    }
+}
+
+/// How entry payloads are turned back into resources before their header is
+/// parsed, given with `--payload`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadTransform {
+    /// Unwraps the layers recognized by their magic, the default. Entries
+    /// are only touched when the unwrapped data is a resource.
+    Auto,
+    /// Entries are read as they are stored.
+    None,
+    /// Layers to remove in order, outermost first.
+    Layers(Vec<Layer>),
+}
+
+impl FromStr for PayloadTransform {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "auto" => return Ok(PayloadTransform::Auto),
+            "none" => return Ok(PayloadTransform::None),
+            _ => {}
+        }
+        value
+            .split(',')
+            .map(|layer| match layer.trim() {
+                "zlib" => Ok(Layer::Zlib),
+                "mio0" => Ok(Layer::Mio0),
+                "yay0" => Ok(Layer::Yay0),
+                "yaz0" => Ok(Layer::Yaz0),
+                layer => layer
+                    .strip_prefix("xor:")
+                    .and_then(parse_key)
+                    .map(Layer::Xor)
+                    .ok_or_else(|| {
+                        format!(
+                            "Unknown payload layer '{}', expected zlib, mio0, yay0, yaz0 or xor:<hex key>",
+                            layer
+                        )
+                    }),
+            })
+            .collect::<Result<_, _>>()
+            .map(PayloadTransform::Layers)
+    }
+}
+
+fn parse_key(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl PayloadTransform {
+    /// The resource stored in the payload `data`, borrowed when it isn't
+    /// wrapped. Errors when one of the given layers can't be removed.
+    pub fn unwrap<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>, String> {
+        match self {
+            PayloadTransform::Auto => {
+                Ok(unwrap_auto(data, 0).map_or(Cow::Borrowed(data), Cow::Owned))
+            }
+            PayloadTransform::None => Ok(Cow::Borrowed(data)),
+            PayloadTransform::Layers(layers) => {
+                layers.iter().try_fold(Cow::Borrowed(data), |data, layer| {
+                    layer.unwrap(&data).map(Cow::Owned)
+                })
+            }
+        }
+    }
+}
+
+/// Whether `data` starts like a resource, with a byte order and custom flag
+/// of 0 or 1 and a known type. Header reads of other entries need the whole
+/// payload to unwrap it.
+pub fn is_resource(data: &[u8]) -> bool {
+    data.len() >= OTR_HEADER_SIZE
+        && data[0] <= 1
+        && data[1] <= 1
+        && OTRHeader::parse(data).is_ok_and(|header| header.type_id != ResourceType::None)
+}
+
+/// The resource under the layers recognized on `data`, if it is one.
+fn unwrap_auto(data: &[u8], depth: usize) -> Option<Vec<u8>> {
+    if depth == MAX_LAYERS {
+        return None;
+    }
+    let zlib = is_zlib(data).then(|| inflate_zlib(data).ok()).flatten();
+    // The padding after the byte order and custom flag is zero, so XORed
+    // with a single byte key it gives the key away
+    let xor = data
+        .get(2..4)
+        .filter(|padding| padding[0] != 0 && padding[0] == padding[1])
+        .map(|padding| xor(data, &[padding[0]]));
+    let compressed = compression::decompress(data, MAX_UNWRAPPED_SIZE).and_then(Result::ok);
+    [zlib, xor, compressed]
+        .into_iter()
+        .flatten()
+        .find_map(|unwrapped| {
+            if is_resource(&unwrapped) {
+                Some(unwrapped)
+            } else {
+                unwrap_auto(&unwrapped, depth + 1)
+            }
+        })
+}
+
+fn xor(data: &[u8], key: &[u8]) -> Vec<u8> {
+    data.iter()
+        .zip(key.iter().cycle())
+        .map(|(byte, key)| byte ^ key)
+        .collect()
+}
+
+/// zlib header of a deflate stream without a preset dictionary.
+fn is_zlib(data: &[u8]) -> bool {
+    data.len() >= 2
+        && data[0] & 0x0F == 8
+        && data[0] >> 4 <= 7
+        && data[1] & 0x20 == 0
+        && u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31)
+}
+
+/// Resource in the zlib stream `data`, refused past `MAX_UNWRAPPED_SIZE`
+/// bytes.
+#[cfg(feature = "archive")]
+fn inflate_zlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_zlib(data) {
+        return Err("Not a zlib stream".to_owned());
+    }
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, MAX_UNWRAPPED_SIZE)
+        .map_err(|err| format!("Failed to inflate zlib stream: {}", err))
+}
+
+/// zlib comes with the deflate decoder of the archive reader, without it the
+/// layer is never unwrapped.
+#[cfg(not(feature = "archive"))]
+fn inflate_zlib(_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("zlib payloads need the archive feature".to_owned())
+}
@@ -0,0 +1,36 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    options::Options,
+    stream::{ImageOutputFormat, decode_to_writer},
+    unwrap_payload,
+};
+
+/// Converts the texture resource read from stdin to a PNG written to stdout,
+/// with `--stdin --stdout`. CI textures take their TLUT resource from the
+/// file given with `--tlut`.
+pub fn run(options: &Options) {
+    let mut resource = Vec::new();
+    io::stdin()
+        .lock()
+        .read_to_end(&mut resource)
+        .expect("Failed to read the resource from stdin");
+    let resource = unwrap_payload("stdin", resource, &options.payload);
+    let tlut = options.tlut.as_ref().map(|path| {
+        fs::read(path).unwrap_or_else(|err| panic!("Failed to read TLUT {}: {}", path, err))
+    });
+
+    let mut stdout = io::stdout().lock();
+    decode_to_writer(
+        &resource,
+        tlut.as_deref(),
+        &mut stdout,
+        ImageOutputFormat::Png,
+        &options.decode_options(),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    stdout.flush().expect("Failed to write the image to stdout");
+}
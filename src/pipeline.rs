@@ -0,0 +1,135 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    sync::{Mutex, mpsc},
+    thread,
+    time::Duration,
+};
+
+use crate::{io_profile::ReadAhead, log, payload::PayloadTransform, try_read_entry};
+
+/// Wait before the first retry of a failed read, doubled on each of the
+/// next ones.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+fn open(zip_file: &str, read_ahead: usize) -> zip::ZipArchive<ReadAhead<File>> {
+    let file = File::open(zip_file).expect("Failed to open zip file");
+    zip::ZipArchive::new(ReadAhead::new(file, read_ahead)).expect("Failed to read zip file")
+}
+
+/// Reads the entries `names` of the archive `zip_file` on `io_threads`
+/// threads, `read_ahead` bytes at a time, and hands them to `process` on
+/// `decode_threads` workers, passing each result to `collect` on the calling
+/// thread. Results are collected in the order of `names`, each after the
+/// messages logged while reading and processing its entry, so neither
+/// depends on the order the workers finish in.
+///
+/// Readers and workers are connected by a bounded channel, so a small number
+/// of readers can keep the disk access pattern sequential while decoding
+/// still uses every core.
+///
+/// A failed read is retried `retries` times on a reopened archive, for
+/// network shares that drop a connection now and then. Payloads are
+/// unwrapped with `payload`. Returns the entries
+/// that still couldn't be read, with the last error, sorted by name.
+#[allow(clippy::too_many_arguments)]
+pub fn run<T: Send>(
+    zip_file: &str,
+    names: Vec<String>,
+    io_threads: usize,
+    read_ahead: usize,
+    decode_threads: usize,
+    retries: usize,
+    payload: &PayloadTransform,
+    process: impl Fn(String, Vec<u8>) -> T + Sync,
+    mut collect: impl FnMut(T),
+) -> Vec<(String, String)> {
+    let queue = Mutex::new(names.into_iter().enumerate());
+    let failed = Mutex::new(Vec::new());
+    let (entry_sender, entry_receiver) = mpsc::sync_channel(decode_threads * 2);
+    let entry_receiver = Mutex::new(entry_receiver);
+    let (result_sender, result_receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..io_threads {
+            let entry_sender = entry_sender.clone();
+            let queue = &queue;
+            let failed = &failed;
+            scope.spawn(move || {
+                let mut zip = open(zip_file, read_ahead);
+                loop {
+                    let Some((index, name)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let (data, captured) = log::capture(|| {
+                        let mut read = try_read_entry(&mut zip, &name, payload);
+                        for attempt in 0..retries {
+                            let Err(err) = &read else {
+                                break;
+                            };
+                            log::progress(format!(
+                                "Retrying {} after a failed read: {}",
+                                name, err
+                            ));
+                            thread::sleep(RETRY_DELAY * 2u32.pow(attempt.min(16) as u32));
+                            zip = open(zip_file, read_ahead);
+                            read = try_read_entry(&mut zip, &name, payload);
+                        }
+                        read.unwrap_or_else(|err| {
+                            log::error(format!("Failed to read {}: {}", name, err));
+                            failed.lock().unwrap().push((name.clone(), err));
+                            None
+                        })
+                    });
+                    // Entries without data are still sent, the results after
+                    // them wait for their messages
+                    if entry_sender.send((index, name, data, captured)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(entry_sender);
+
+        for _ in 0..decode_threads {
+            let result_sender = result_sender.clone();
+            let entry_receiver = &entry_receiver;
+            let process = &process;
+            scope.spawn(move || {
+                loop {
+                    let entry = entry_receiver.lock().unwrap().recv();
+                    let Ok((index, name, data, mut captured)) = entry else {
+                        break;
+                    };
+                    let result = data.map(|data| {
+                        let (result, messages) = log::capture(|| process(name, data));
+                        captured.extend(messages);
+                        result
+                    });
+                    if result_sender.send((index, result, captured)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_sender);
+
+        // Results finished ahead of an earlier entry wait for it
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, result, captured) in result_receiver {
+            pending.insert(index, (result, captured));
+            while let Some((result, captured)) = pending.remove(&next) {
+                captured.flush();
+                if let Some(result) = result {
+                    collect(result);
+                }
+                next += 1;
+            }
+        }
+    });
+
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort();
+    failed
+}
@@ -0,0 +1,368 @@
+//! Hot pixel conversion loops, with SIMD versions on x86_64 (SSE2) and
+//! aarch64 (NEON). Both instruction sets are part of the baseline of their
+//! targets so no runtime detection is needed; other targets and the tails of
+//! the inputs use the scalar code the SIMD versions must match exactly.
+
+use std::str::FromStr;
+
+/// How color channels narrower than 8 bits are widened, set with `--expand`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Expansion {
+    /// Repeating the bits of the channel below it, `v << 3 | v >> 2` for 5
+    /// bits, as the RDP and most extractors do. The default.
+    #[default]
+    Replicate,
+    /// `v * 255 / max`, rounded down. Off by one from `Replicate` for some
+    /// 3-bit and 5-bit values.
+    Linear,
+}
+
+impl FromStr for Expansion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "replicate" => Ok(Expansion::Replicate),
+            "linear" => Ok(Expansion::Linear),
+            _ => Err(format!(
+                "Unknown expansion '{}', expected replicate or linear",
+                value
+            )),
+        }
+    }
+}
+
+/// Widens the `bits` wide channel `value` to 8 bits.
+pub fn expand(value: u8, bits: u32, expansion: Expansion) -> u8 {
+    let value = value as u32 & ((1 << bits) - 1);
+    match expansion {
+        Expansion::Replicate => {
+            let mut expanded = 0;
+            let mut shift = 8 - bits as i32;
+            while shift > -(bits as i32) {
+                expanded |= match shift {
+                    0.. => value << shift,
+                    _ => value >> -shift,
+                };
+                shift -= bits as i32;
+            }
+            expanded as u8
+        }
+        Expansion::Linear => (value * 255 / ((1 << bits) - 1)) as u8,
+    }
+}
+
+/// Expands a big-endian RGBA5551 texel to RGBA8888.
+pub fn rgba5551(high: u8, low: u8, expansion: Expansion) -> [u8; 4] {
+    [
+        expand(high >> 3, 5, expansion),
+        expand((high & 0x07) << 2 | low >> 6, 5, expansion),
+        expand((low & 0x3E) >> 1, 5, expansion),
+        if low & 0x01 != 0 { 0xFF } else { 0x00 },
+    ]
+}
+
+/// Appends the RGBA8888 expansion of the big-endian RGBA5551 texels of `src`
+/// to `dst`.
+pub fn rgba5551_to_rgba8888(src: &[u8], dst: &mut Vec<u8>, expansion: Expansion) {
+    dst.reserve(src.len() * 2);
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    let replicate = expansion == Expansion::Replicate;
+    #[cfg(target_arch = "x86_64")]
+    let src = {
+        let simd_length = src.len() / 16 * 16;
+        // SAFETY: SSE2 is always available on x86_64
+        unsafe { sse2::rgba5551_to_rgba8888(&src[..simd_length], dst, replicate) };
+        &src[simd_length..]
+    };
+    #[cfg(target_arch = "aarch64")]
+    let src = {
+        let simd_length = src.len() / 16 * 16;
+        // SAFETY: NEON is always available on aarch64
+        unsafe { neon::rgba5551_to_rgba8888(&src[..simd_length], dst, replicate) };
+        &src[simd_length..]
+    };
+    for texel in src.chunks_exact(2) {
+        dst.extend(rgba5551(texel[0], texel[1], expansion));
+    }
+}
+
+/// Appends the nibbles of `src` to `dst`, high nibble first.
+fn unpack_nibbles(src: &[u8], dst: &mut Vec<u8>) {
+    dst.reserve(src.len() * 2);
+    #[cfg(target_arch = "x86_64")]
+    let src = {
+        let simd_length = src.len() / 16 * 16;
+        // SAFETY: SSE2 is always available on x86_64
+        unsafe { sse2::unpack_nibbles(&src[..simd_length], dst) };
+        &src[simd_length..]
+    };
+    #[cfg(target_arch = "aarch64")]
+    let src = {
+        let simd_length = src.len() / 16 * 16;
+        // SAFETY: NEON is always available on aarch64
+        unsafe { neon::unpack_nibbles(&src[..simd_length], dst) };
+        &src[simd_length..]
+    };
+    for byte in src {
+        dst.extend([byte >> 4, byte & 0x0F]);
+    }
+}
+
+/// Texels of a 4-bit texture, one per byte in pixel order. Rows start on a
+/// byte boundary.
+pub fn unpack_4bpp(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let row_size = width.div_ceil(2) as usize;
+    let mut texels = Vec::with_capacity(row_size * 2 * height as usize);
+    // Without a pad nibble ending the rows they follow each other, and small
+    // textures get whole SIMD blocks rather than a scalar tail every row
+    if width.is_multiple_of(2) {
+        let size = data.len().min(row_size * height as usize);
+        unpack_nibbles(&data[..size], &mut texels);
+        return texels;
+    }
+    for row in data.chunks(row_size).take(height as usize) {
+        let start = texels.len();
+        unpack_nibbles(row, &mut texels);
+        texels.truncate(start + width as usize);
+    }
+    texels
+}
+
+/// Texels of a 1-bit texture, 0 or 1 in pixel order. Rows start on a byte
+/// boundary, as in `unpack_4bpp`.
+pub fn unpack_1bpp(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let row_size = width.div_ceil(8) as usize;
+    let mut texels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height as usize {
+        texels.extend((0..width as usize).map(|x| {
+            data.get(y * row_size + x / 8)
+                .map_or(0, |byte| byte >> (7 - x % 8) & 1)
+        }));
+    }
+    texels
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    /// Converts 8 texels per iteration, `src.len()` must be a multiple of 16.
+    /// Channels are widened by replicating their bits when `replicate` is
+    /// set, linearly otherwise.
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn rgba5551_to_rgba8888(src: &[u8], dst: &mut Vec<u8>, replicate: bool) {
+        let mask = _mm_set1_epi16(0x1F);
+        // floor(x / 31) == (x * 33826) >> 20 for every x up to 31 * 255
+        let reciprocal = _mm_set1_epi16(33826u16 as i16);
+        let full = _mm_set1_epi16(255);
+        let scale = |channel: __m128i| {
+            let channel = _mm_and_si128(channel, mask);
+            if replicate {
+                return _mm_or_si128(_mm_slli_epi16(channel, 3), _mm_srli_epi16(channel, 2));
+            }
+            let scaled = _mm_mullo_epi16(channel, full);
+            _mm_srli_epi16(_mm_mulhi_epu16(scaled, reciprocal), 4)
+        };
+
+        for chunk in src.chunks_exact(16) {
+            let mut out = [0u8; 32];
+            unsafe {
+                let texels = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+                // Big-endian to native 16-bit lanes
+                let texels = _mm_or_si128(_mm_slli_epi16(texels, 8), _mm_srli_epi16(texels, 8));
+
+                let r = scale(_mm_srli_epi16(texels, 11));
+                let g = scale(_mm_srli_epi16(texels, 6));
+                let b = scale(_mm_srli_epi16(texels, 1));
+                let a = _mm_sub_epi16(
+                    _mm_setzero_si128(),
+                    _mm_and_si128(texels, _mm_set1_epi16(1)),
+                );
+                let a = _mm_and_si128(a, full);
+
+                let rg = _mm_or_si128(r, _mm_slli_epi16(g, 8));
+                let ba = _mm_or_si128(b, _mm_slli_epi16(a, 8));
+                _mm_storeu_si128(out.as_mut_ptr() as *mut __m128i, _mm_unpacklo_epi16(rg, ba));
+                _mm_storeu_si128(
+                    out.as_mut_ptr().add(16) as *mut __m128i,
+                    _mm_unpackhi_epi16(rg, ba),
+                );
+            }
+            dst.extend_from_slice(&out);
+        }
+    }
+
+    /// Unpacks 16 bytes per iteration, `src.len()` must be a multiple of 16.
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn unpack_nibbles(src: &[u8], dst: &mut Vec<u8>) {
+        let mask = _mm_set1_epi8(0x0F);
+        for chunk in src.chunks_exact(16) {
+            let mut out = [0u8; 32];
+            unsafe {
+                let bytes = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+                let high = _mm_and_si128(_mm_srli_epi16(bytes, 4), mask);
+                let low = _mm_and_si128(bytes, mask);
+                _mm_storeu_si128(
+                    out.as_mut_ptr() as *mut __m128i,
+                    _mm_unpacklo_epi8(high, low),
+                );
+                _mm_storeu_si128(
+                    out.as_mut_ptr().add(16) as *mut __m128i,
+                    _mm_unpackhi_epi8(high, low),
+                );
+            }
+            dst.extend_from_slice(&out);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    /// Converts 8 texels per iteration, `src.len()` must be a multiple of 16.
+    /// Channels are widened by replicating their bits when `replicate` is
+    /// set, linearly otherwise.
+    #[target_feature(enable = "neon")]
+    pub unsafe fn rgba5551_to_rgba8888(src: &[u8], dst: &mut Vec<u8>, replicate: bool) {
+        let mask = vdupq_n_u16(0x1F);
+        let full = vdupq_n_u16(255);
+        // floor(x / 31) == (x * 33826) >> 20 for every x up to 31 * 255
+        let scale = |channel: uint16x8_t| {
+            let channel = vandq_u16(channel, mask);
+            if replicate {
+                return vmovn_u16(vorrq_u16(vshlq_n_u16(channel, 3), vshrq_n_u16(channel, 2)));
+            }
+            let scaled = vmulq_u16(channel, full);
+            let low = vmull_n_u16(vget_low_u16(scaled), 33826);
+            let high = vmull_n_u16(vget_high_u16(scaled), 33826);
+            let quotient = vcombine_u16(vshrn_n_u32(low, 16), vshrn_n_u32(high, 16));
+            vmovn_u16(vshrq_n_u16(quotient, 4))
+        };
+
+        for chunk in src.chunks_exact(16) {
+            let mut out = [0u8; 32];
+            unsafe {
+                // Big-endian to native 16-bit lanes
+                let texels = vreinterpretq_u16_u8(vrev16q_u8(vld1q_u8(chunk.as_ptr())));
+                let r = scale(vshrq_n_u16(texels, 11));
+                let g = scale(vshrq_n_u16(texels, 6));
+                let b = scale(vshrq_n_u16(texels, 1));
+                let a = vmovn_u16(vtstq_u16(texels, vdupq_n_u16(1)));
+                vst4_u8(out.as_mut_ptr(), uint8x8x4_t(r, g, b, a));
+            }
+            dst.extend_from_slice(&out);
+        }
+    }
+
+    /// Unpacks 16 bytes per iteration, `src.len()` must be a multiple of 16.
+    #[target_feature(enable = "neon")]
+    pub unsafe fn unpack_nibbles(src: &[u8], dst: &mut Vec<u8>) {
+        let mask = vdupq_n_u8(0x0F);
+        for chunk in src.chunks_exact(16) {
+            let mut out = [0u8; 32];
+            unsafe {
+                let bytes = vld1q_u8(chunk.as_ptr());
+                vst2q_u8(
+                    out.as_mut_ptr(),
+                    uint8x16x2_t(vshrq_n_u8(bytes, 4), vandq_u8(bytes, mask)),
+                );
+            }
+            dst.extend_from_slice(&out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every big-endian 16-bit texel.
+    fn all_texels() -> Vec<u8> {
+        (0..=u16::MAX).flat_map(u16::to_be_bytes).collect()
+    }
+
+    /// Bytes from a fixed linear congruential sequence.
+    fn noise(length: usize) -> Vec<u8> {
+        let mut state = 0x2545_F491u32;
+        (0..length)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expands_every_rgba5551_texel_like_the_scalar_code() {
+        let texels = all_texels();
+        for expansion in [Expansion::Replicate, Expansion::Linear] {
+            let scalar = texels
+                .chunks_exact(2)
+                .flat_map(|texel| rgba5551(texel[0], texel[1], expansion))
+                .collect::<Vec<_>>();
+            // Lengths that aren't a multiple of 16 bytes, to cover the tails
+            for texel_count in [65536, 65535, 65529, 7, 1, 0] {
+                let mut converted = Vec::new();
+                rgba5551_to_rgba8888(&texels[..texel_count * 2], &mut converted, expansion);
+                assert_eq!(
+                    converted,
+                    &scalar[..texel_count * 4],
+                    "{:?} {}",
+                    expansion,
+                    texel_count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn expands_5_bit_channels() {
+        for value in 0..32 {
+            assert_eq!(
+                expand(value, 5, Expansion::Replicate),
+                value << 3 | value >> 2
+            );
+            assert_eq!(
+                expand(value, 5, Expansion::Linear) as u32,
+                value as u32 * 255 / 31
+            );
+        }
+    }
+
+    #[test]
+    fn unpacks_nibbles_like_the_scalar_code() {
+        let bytes = noise(256);
+        for length in 0..=bytes.len() {
+            let mut unpacked = Vec::new();
+            unpack_nibbles(&bytes[..length], &mut unpacked);
+            let scalar = bytes[..length]
+                .iter()
+                .flat_map(|byte| [byte >> 4, byte & 0x0F])
+                .collect::<Vec<_>>();
+            assert_eq!(unpacked, scalar, "{}", length);
+        }
+    }
+
+    #[test]
+    fn unpacks_4bpp_rows_of_any_width() {
+        let bytes = noise(70 * 70);
+        for width in 1..=70u32 {
+            let height = 3;
+            let row_size = width.div_ceil(2) as usize;
+            let texels = unpack_4bpp(&bytes, width, height);
+            assert_eq!(texels.len(), (width * height) as usize);
+            for (y, row) in texels.chunks(width as usize).enumerate() {
+                for (x, texel) in row.iter().enumerate() {
+                    let byte = bytes[y * row_size + x / 2];
+                    let nibble = if x % 2 == 0 { byte >> 4 } else { byte & 0x0F };
+                    assert_eq!(*texel, nibble, "{}x{} at {},{}", width, height, x, y);
+                }
+            }
+        }
+        // Data ending in a row, even widths or not
+        assert_eq!(unpack_4bpp(&[0x12, 0x34, 0x56], 4, 2), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(unpack_4bpp(&[0x12, 0x34, 0x56], 3, 2), [1, 2, 3, 5, 6]);
+    }
+}
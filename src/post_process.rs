@@ -0,0 +1,136 @@
+use std::{
+    process::{self, Stdio},
+    str::FromStr,
+    sync::Mutex,
+    thread,
+};
+
+use crate::json::Json;
+
+/// Folder of the output folder post-processed textures go to, mirroring the
+/// texture paths.
+pub const POST_PROCESS_FOLDER: &str = "processed";
+
+/// Report of the post-processing run, listing the textures that failed.
+pub const POST_PROCESS_REPORT_FILE: &str = "post_process.json";
+
+/// External command run on every converted texture with `--post-process`,
+/// such as an upscaler CLI: `upscale -i {in} -o {out}`. The template is split
+/// on whitespace without going through a shell, then `{in}` and `{out}` are
+/// replaced by the converted texture and the path to write to.
+#[derive(Debug, Clone)]
+pub struct PostProcess {
+    template: String,
+    args: Vec<String>,
+}
+
+impl FromStr for PostProcess {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let args = value
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        if args.is_empty() {
+            return Err("The post-process command is empty".to_owned());
+        }
+        for placeholder in ["{in}", "{out}"] {
+            if !args.iter().any(|arg| arg.contains(placeholder)) {
+                return Err(format!(
+                    "The post-process command '{}' doesn't use {}",
+                    value, placeholder
+                ));
+            }
+        }
+        Ok(PostProcess {
+            template: value.to_owned(),
+            args,
+        })
+    }
+}
+
+/// A texture the command failed on.
+pub struct Failure {
+    pub output: String,
+    pub error: String,
+}
+
+impl PostProcess {
+    /// Runs the command on `input`, writing to `output`.
+    fn run_one(&self, input: &str, output: &str) -> Result<(), String> {
+        let _ = std::fs::create_dir_all(std::path::Path::new(output).parent().unwrap());
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.replace("{in}", input).replace("{out}", output))
+            .collect::<Vec<_>>();
+        let result = process::Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .output()
+            .map_err(|err| format!("Failed to run {}: {}", args[0], err))?;
+        if result.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        match stderr.trim().lines().next_back() {
+            Some(line) => Err(format!("{}: {}", result.status, line)),
+            None => Err(result.status.to_string()),
+        }
+    }
+
+    /// Runs the command on the textures `outputs` of the output folder
+    /// `folder`, `jobs` at a time, writing the results under
+    /// `POST_PROCESS_FOLDER`. Returns the textures it failed on, in the order
+    /// of `outputs`.
+    pub fn run(&self, folder: &str, outputs: &[String], jobs: usize) -> Vec<Failure> {
+        let queue = Mutex::new(outputs.iter().enumerate());
+        let failures = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..jobs.min(outputs.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let Some((i, output)) = queue.lock().unwrap().next() else {
+                            break;
+                        };
+                        let input = format!("{}/{}", folder, output);
+                        let processed = format!("{}/{}/{}", folder, POST_PROCESS_FOLDER, output);
+                        if let Err(error) = self.run_one(&input, &processed) {
+                            failures.lock().unwrap().push((
+                                i,
+                                Failure {
+                                    output: output.to_owned(),
+                                    error,
+                                },
+                            ));
+                        }
+                    }
+                });
+            }
+        });
+        let mut failures = failures.into_inner().unwrap();
+        failures.sort_by_key(|(i, _)| *i);
+        failures.into_iter().map(|(_, failure)| failure).collect()
+    }
+
+    pub fn report(&self, processed: usize, failures: &[Failure]) -> Json {
+        Json::object()
+            .with("command", self.template.as_str())
+            .with("processed", processed)
+            .with(
+                "failures",
+                Json::Array(
+                    failures
+                        .iter()
+                        .map(|failure| {
+                            Json::object()
+                                .with("output", failure.output.as_str())
+                                .with("error", failure.error.as_str())
+                        })
+                        .collect(),
+                ),
+            )
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+/// Number of entries listed by `--profile` when not given.
+pub const DEFAULT_SLOWEST: usize = 10;
+
+/// Stage of converting an entry timed with `--profile`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    /// Parsing the resource and expanding its data, everything a decoder
+    /// does besides encoding and writing.
+    Decode,
+    /// Encoding images to the output format.
+    Encode,
+    /// Writing outputs to disk.
+    Write,
+}
+
+const STAGES: [Stage; 3] = [Stage::Decode, Stage::Encode, Stage::Write];
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Decode => "decode",
+            Stage::Encode => "encode",
+            Stage::Write => "write",
+        }
+    }
+}
+
+/// Time spent converting an entry, per stage.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    stages: [Duration; 3],
+}
+
+impl Timings {
+    /// Runs `f`, adding the time it takes to `stage`.
+    pub fn time<T>(&mut self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let value = f();
+        self.stages[stage as usize] += start.elapsed();
+        value
+    }
+
+    /// Attributes what `elapsed` doesn't spend in the timed stages to
+    /// decoding.
+    pub fn finish(&mut self, elapsed: Duration) {
+        let timed = self.get(Stage::Encode) + self.get(Stage::Write);
+        self.stages[Stage::Decode as usize] += elapsed.saturating_sub(timed);
+    }
+
+    pub fn get(&self, stage: Stage) -> Duration {
+        self.stages[stage as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().sum()
+    }
+}
+
+/// Timings of the converted entries, reported at the end with `--profile`.
+#[derive(Default)]
+pub struct Profile {
+    entries: Vec<(String, Timings)>,
+}
+
+impl Profile {
+    pub fn add(&mut self, name: String, timings: Timings) {
+        self.entries.push((name, timings));
+    }
+
+    /// Time spent in each stage over all entries, then the `slowest` entries
+    /// taking the longest.
+    pub fn report(&mut self, slowest: usize) -> String {
+        let total = self
+            .entries
+            .iter()
+            .map(|(_, timings)| timings.total())
+            .sum::<Duration>();
+        let mut report = format!(
+            "Converted {} entries in {} of worker time\n",
+            self.entries.len(),
+            format_duration(total)
+        );
+        for stage in STAGES {
+            let time = self
+                .entries
+                .iter()
+                .map(|(_, timings)| timings.get(stage))
+                .sum::<Duration>();
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                time.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            let _ = writeln!(
+                report,
+                "  {:<6} {:>10} {:>5.1}%",
+                stage.name(),
+                format_duration(time),
+                share
+            );
+        }
+
+        self.entries
+            .sort_by(|(a_name, a), (b_name, b)| b.total().cmp(&a.total()).then(a_name.cmp(b_name)));
+        let slowest = &self.entries[..slowest.min(self.entries.len())];
+        if !slowest.is_empty() {
+            let _ = writeln!(report, "Slowest {} entries:", slowest.len());
+        }
+        for (name, timings) in slowest {
+            let stages = STAGES
+                .iter()
+                .map(|stage| format!("{} {}", stage.name(), format_duration(timings.get(*stage))))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                report,
+                "  {:>10}  {} ({})",
+                format_duration(timings.total()),
+                name,
+                stages.join(", ")
+            );
+        }
+        report
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,31 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::{log, names};
+
+/// Deletes the files `previous` of the output folder `folder`, written by the
+/// previous run, that aren't in `current`, along with the folders that leaves
+/// empty. Files nobody listed are left alone, and paths leaving the folder
+/// are ignored since the list comes from a manifest on disk. Returns the
+/// number of files deleted.
+pub fn prune(folder: &str, previous: &[String], current: &[String]) -> usize {
+    let current = current.iter().map(String::as_str).collect::<HashSet<_>>();
+    let mut pruned = 0;
+    for file in previous {
+        if current.contains(file.as_str()) || !names::is_contained(file) {
+            continue;
+        }
+        let path = Path::new(folder).join(file);
+        if fs::remove_file(&path).is_err() {
+            continue;
+        }
+        log::progress(format!("Removed stale output {}", file));
+        pruned += 1;
+        // Only empty folders can be removed, the first one failing stops it
+        for parent in path.ancestors().skip(1) {
+            if parent == Path::new(folder) || fs::remove_dir(parent).is_err() {
+                break;
+            }
+        }
+    }
+    pruned
+}
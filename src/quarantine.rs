@@ -0,0 +1,36 @@
+use std::fs;
+
+use crate::json::Json;
+
+/// Entries whose reads kept failing, in the output folder, to convert again
+/// with `--retry-failed` once the archive can be read.
+pub const FAILED_FILE: &str = "failed.json";
+
+/// The failed entries `failed`, as `(entry, error)`, as the failed list.
+pub fn to_json(failed: &[(String, String)]) -> Json {
+    Json::object().with(
+        "failed",
+        Json::Array(
+            failed
+                .iter()
+                .map(|(entry, error)| {
+                    Json::object()
+                        .with("entry", entry.as_str())
+                        .with("error", error.as_str())
+                })
+                .collect(),
+        ),
+    )
+}
+
+/// Entries of the failed list `path`.
+pub fn load(path: &str) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    match Json::parse(&text)?.get("failed") {
+        Some(Json::Array(failed)) => Ok(failed
+            .iter()
+            .filter_map(|failed| failed.get("entry")?.as_str().map(str::to_owned))
+            .collect()),
+        _ => Err("No failed list".to_owned()),
+    }
+}
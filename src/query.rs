@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+use crate::{TextureFormat, torch};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Format,
+    Width,
+    Height,
+    Bpp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Operator::Equal => left == right,
+            Operator::NotEqual => left != right,
+            Operator::Less => left < right,
+            Operator::LessOrEqual => left <= right,
+            Operator::Greater => left > right,
+            Operator::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+struct Condition {
+    field: Field,
+    operator: Operator,
+    value: String,
+}
+
+/// Texture selection given with `--where`, conditions on the texture header
+/// joined with `&&`: `format=CI8 && width>=64`. Fields are `format`, as
+/// Torch names it or by its texture type, `width`, `height` and `bpp`.
+pub struct Query {
+    conditions: Vec<Condition>,
+}
+
+impl Query {
+    pub fn matches(&self, texture: &TextureFormat) -> bool {
+        self.conditions.iter().all(|condition| {
+            let number = |value: u32| {
+                condition
+                    .value
+                    .parse::<u32>()
+                    .is_ok_and(|expected| condition.operator.compare(value, expected))
+            };
+            match condition.field {
+                Field::Format => {
+                    let type_name = format!("{:?}", texture.type_id);
+                    let names = [
+                        torch::torch_format(&texture.type_id).unwrap_or_default(),
+                        type_name.as_str(),
+                    ];
+                    let equal = names
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(&condition.value));
+                    equal == (condition.operator == Operator::Equal)
+                }
+                Field::Width => number(texture.width),
+                Field::Height => number(texture.height),
+                Field::Bpp => texture
+                    .type_id
+                    .bits_per_pixel()
+                    .is_some_and(|bits| number(bits as u32)),
+            }
+        })
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // The first operator of a condition is the one used, the longest
+        // one at that position so `>=` isn't read as `>`
+        const OPERATORS: &[(&str, Operator)] = &[
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessOrEqual),
+            (">=", Operator::GreaterOrEqual),
+            ("=", Operator::Equal),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ];
+
+        let conditions = value
+            .split("&&")
+            .map(|condition| {
+                let condition = condition.trim();
+                let (position, symbol, operator) = OPERATORS
+                    .iter()
+                    .filter_map(|(symbol, operator)| {
+                        condition
+                            .find(symbol)
+                            .map(|position| (position, *symbol, *operator))
+                    })
+                    .min_by_key(|(position, symbol, _)| (*position, usize::MAX - symbol.len()))
+                    .ok_or_else(|| format!("No comparison in condition '{}'", condition))?;
+                let field = match condition[..position].trim() {
+                    "format" => Field::Format,
+                    "width" => Field::Width,
+                    "height" => Field::Height,
+                    "bpp" => Field::Bpp,
+                    field => {
+                        return Err(format!(
+                            "Unknown field '{}', expected format, width, height or bpp",
+                            field
+                        ));
+                    }
+                };
+                let value = condition[position + symbol.len()..].trim().to_owned();
+                if field == Field::Format
+                    && !matches!(operator, Operator::Equal | Operator::NotEqual)
+                {
+                    return Err(format!(
+                        "format can only be compared with = or != in '{}'",
+                        condition
+                    ));
+                }
+                if field != Field::Format && value.parse::<u32>().is_err() {
+                    return Err(format!("Expected a number in '{}'", condition));
+                }
+                Ok(Condition {
+                    field,
+                    operator,
+                    value,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Query { conditions })
+    }
+}
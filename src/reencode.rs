@@ -0,0 +1,226 @@
+use std::{collections::HashMap, fs::File};
+
+use crate::{
+    OTR_HEADER_SIZE, OTRHeader, ResourceType, TextureFormat, TextureType, asset_definitions,
+    config::Config,
+    decode::decode_with_swap,
+    decode_texels,
+    encode::{pack_texels, texel_codes},
+    load_tlut_config,
+    metadata::ArchiveMetadata,
+    options::Options,
+    pack_rows,
+    palette::{self, BANK_SIZE},
+    read_entry,
+    replace::write_archive,
+    texture_header,
+    tlut::Tluts,
+};
+
+/// RGBA5551 value of an RGBA pixel, the channels truncated to 5 bits.
+fn rgba5551(pixel: &[u8]) -> [u8; 2] {
+    let value = (pixel[0] as u16 >> 3) << 11
+        | (pixel[1] as u16 >> 3) << 6
+        | (pixel[2] as u16 >> 3) << 1
+        | (pixel[3] >= 0x80) as u16;
+    value.to_be_bytes()
+}
+
+/// Turns `image`, an edited export of the CI texture `entry`, back into
+/// palette indices without quantizing, and writes the patched archive to
+/// `output`.
+///
+/// Pixels left as exported keep their original index, so an image without
+/// new colors gives back the same texel data. Other pixels take the first
+/// TLUT entry with their color, and colors the TLUT lacks are appended to
+/// it while the texture's palette has room.
+pub fn run(options: &Options, entry: &str, image: &str, output: &str) {
+    let mut zip =
+        zip::ZipArchive::new(File::open(&options.zip_file).expect("Failed to open zip file"))
+            .expect("Failed to read zip file");
+    if let Some(required) = &options.require_port_version {
+        ArchiveMetadata::read(&mut zip, &options.payload)
+            .check_port_version(required)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    let data = read_entry(&mut zip, entry, &options.payload)
+        .unwrap_or_else(|| panic!("Entry {} not found in {}", entry, options.zip_file));
+    if !OTRHeader::parse(&data).is_ok_and(|header| header.type_id == ResourceType::Texture) {
+        panic!("{} is not a texture resource", entry);
+    }
+    let mut texture_format =
+        TextureFormat::parse(&data).unwrap_or_else(|err| panic!("{}: {}", entry, err));
+    let type_id = texture_format.type_id.clone();
+    let capacity = match type_id {
+        TextureType::Palette4bpp => BANK_SIZE,
+        TextureType::Palette8bpp => 256,
+        _ => panic!("{} is a {:?} texture, not a CI one", entry, type_id),
+    };
+    if let Some(stride) = TextureFormat::stride(&data).filter(|stride| *stride > 0) {
+        texture_format.data = texture_format
+            .row_size(texture_format.width)
+            .and_then(|row_size| {
+                pack_rows(
+                    &texture_format.data,
+                    row_size,
+                    stride as usize,
+                    texture_format.height,
+                )
+            })
+            .unwrap_or_else(|err| panic!("{}: {}", entry, err));
+    }
+
+    let file_names = zip
+        .file_names()
+        .map(|name| name.to_owned())
+        .collect::<Vec<String>>();
+    let tluts = Tluts::open(
+        options,
+        &file_names,
+        load_tlut_config(&asset_definitions(
+            &Config::load(&options.config),
+            options.yaml_dialect,
+        )),
+    );
+    let file_name = entry.split('/').next_back().unwrap();
+    let texture_tlut = tluts
+        .texture_tlut(file_name)
+        .unwrap_or_else(|| panic!("Texture TLUT not found for {}", file_name));
+    let tlut_path = tluts
+        .path(&texture_tlut.symbol, entry)
+        .unwrap_or_else(|| panic!("TLUT {} not found in the archive", texture_tlut.symbol))
+        .to_owned();
+    let tlut = tluts
+        .for_texture(entry, &type_id)
+        .unwrap_or_else(|| panic!("Texture TLUT not found for {}", file_name));
+
+    let image =
+        image::open(image).unwrap_or_else(|err| panic!("Failed to read {}: {}", image, err));
+    if (image.width(), image.height()) != (texture_format.width, texture_format.height) {
+        panic!(
+            "{} is {}x{} but the image is {}x{}",
+            entry,
+            texture_format.width,
+            texture_format.height,
+            image.width(),
+            image.height()
+        );
+    }
+    let pixels = image.to_rgba8().into_raw();
+
+    // Stored with the byte order the original was found in
+    let (swap, original_pixels) =
+        decode_with_swap(&texture_format, Some(&tlut), options.swap, options.expand)
+            .unwrap_or_else(|| panic!("Unsupported texture type: {:?}", type_id));
+    let original_indices = palette::indices(&TextureFormat::new(
+        type_id.clone(),
+        texture_format.width,
+        texture_format.height,
+        texture_format.size,
+        swap.apply(&texture_format.data),
+    ));
+
+    let mut colors = tlut.data.clone();
+    let mut codes =
+        texel_codes(&type_id, Some(&tlut), options.expand).unwrap_or_else(|err| panic!("{}", err));
+    let mut indices = Vec::with_capacity(original_indices.len());
+    for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+        if original_pixels.get(i * 4..i * 4 + 4) == Some(pixel) {
+            indices.push(original_indices[i] as u32);
+            continue;
+        }
+        if let Some(code) = codes.get(pixel) {
+            indices.push(*code);
+            continue;
+        }
+        let index = colors.len() / 2;
+        if index >= capacity {
+            panic!(
+                "Pixel ({}, {}) {:?} is a new color but the palette of {} is full",
+                i as u32 % texture_format.width,
+                i as u32 / texture_format.width,
+                pixel,
+                entry
+            );
+        }
+        colors.extend_from_slice(&rgba5551(pixel));
+        codes.insert(pixel.to_vec(), index as u32);
+        indices.push(index as u32);
+    }
+    let added = colors.len() / 2 - tlut.data.len() / 2;
+
+    let texels = pack_texels(&type_id, texture_format.width, &indices)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let new_tlut = TextureFormat::new(
+        tlut.type_id.clone(),
+        (colors.len() / 2) as u32,
+        1,
+        colors.len() as u32,
+        colors.clone(),
+    );
+    // New colors are truncated to RGBA5551, so only exact ones are accepted
+    let new_texture = TextureFormat::new(
+        type_id.clone(),
+        texture_format.width,
+        texture_format.height,
+        texels.len() as u32,
+        texels,
+    );
+    let decoded = decode_texels(
+        &new_texture,
+        &new_texture.data,
+        Some(&new_tlut),
+        options.expand,
+    )
+    .unwrap_or_else(|| panic!("Unsupported texture type: {:?}", type_id));
+    if let Some(i) =
+        (0..pixels.len() / 4).find(|i| decoded[i * 4..i * 4 + 4] != pixels[i * 4..i * 4 + 4])
+    {
+        panic!(
+            "Pixel ({}, {}) {:?} can't be represented exactly as RGBA5551",
+            i as u32 % texture_format.width,
+            i as u32 / texture_format.width,
+            &pixels[i * 4..i * 4 + 4]
+        );
+    }
+
+    let texels = swap.apply(&new_texture.data);
+    let mut resource = texture_header(&data);
+    resource.extend_from_slice(&(texels.len() as u32).to_le_bytes());
+    resource.extend_from_slice(&texels);
+    let mut replacements = HashMap::from([(entry.to_owned(), resource)]);
+
+    if added > 0 {
+        let tlut_data = read_entry(&mut zip, &tlut_path, &options.payload)
+            .unwrap_or_else(|| panic!("Failed to read TLUT {}", tlut_path));
+        let full_tlut =
+            TextureFormat::parse(&tlut_data).unwrap_or_else(|err| panic!("{}: {}", tlut_path, err));
+        // The colors of a CI4 bank start inside the TLUT
+        let start = match type_id {
+            TextureType::Palette4bpp => texture_tlut.palette_index as usize * BANK_SIZE * 2,
+            _ => 0,
+        };
+        if start + tlut.data.len() != full_tlut.data.len() {
+            panic!(
+                "{} new colors don't fit, the palette of {} doesn't end the TLUT {}",
+                added, entry, tlut_path
+            );
+        }
+        let mut full_colors = full_tlut.data.clone();
+        full_colors.extend_from_slice(&colors[tlut.data.len()..]);
+
+        let mut resource = tlut_data[..OTR_HEADER_SIZE + 4].to_vec();
+        let count = (full_colors.len() / 2) as u32;
+        // Width and height give the color count, laid out as a single row
+        resource.extend_from_slice(&count.to_le_bytes());
+        resource.extend_from_slice(&1u32.to_le_bytes());
+        resource.extend_from_slice(&(full_colors.len() as u32).to_le_bytes());
+        resource.extend_from_slice(&full_colors);
+        replacements.insert(tlut_path.clone(), resource);
+        println!("Added {} colors to {}", added, tlut_path);
+    }
+
+    write_archive(&mut zip, output, &replacements);
+    println!("Re-encoded {} in {}", entry, output);
+}
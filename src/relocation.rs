@@ -0,0 +1,197 @@
+use crate::{
+    Converter, EntryResult, ResourceType, TextureFormat, TextureType,
+    decoder::ResourceDecoder,
+    dilate,
+    display_list::{self, Command, Reference},
+    json::Json,
+    log,
+    stream::{self, ImageOutputFormat},
+    texture, tmem,
+};
+
+/// Resource a display list command points to.
+pub struct Relocation {
+    /// What the command loads: `texture`, `vertices` or `display_list`.
+    pub kind: &'static str,
+    pub reference: Reference,
+    /// Texture type a texture command samples the image as.
+    pub format: Option<TextureType>,
+    /// Archive entry the reference resolves to.
+    pub target: Option<String>,
+}
+
+impl Relocation {
+    fn to_json(&self) -> Json {
+        let json = Json::object().with("kind", self.kind);
+        let json = match &self.reference {
+            Reference::Hash(hash) => json.with("hash", format!("{:016x}", hash)),
+            Reference::Path(path) => json.with("path", path.as_str()),
+            Reference::Segmented(address) => json.with("address", format!("0x{:08x}", address)),
+        };
+        let json = match &self.format {
+            Some(format) => json.with("format", format!("{:?}", format)),
+            None => json,
+        };
+        json.with("target", self.target.as_deref())
+    }
+}
+
+/// Resolves `reference` to an archive entry. Segmented addresses go through
+/// the `segments` table of the config, which names the archive directory a
+/// segment holds, and the offsets of the YAML definitions of that directory.
+pub fn resolve(converter: &Converter, reference: &Reference) -> Option<String> {
+    match reference {
+        Reference::Hash(hash) => converter.resource_name(*hash).map(str::to_owned),
+        Reference::Path(path) => converter.has_resource(path).then(|| path.to_owned()),
+        Reference::Segmented(address) => {
+            let segment = (address >> 24) as u8 & 0x0F;
+            let (_, directory) = converter
+                .config
+                .segments
+                .iter()
+                .find(|(number, _)| *number == segment)?;
+            converter
+                .symbols()
+                .path_at(directory, address & 0x00FFFFFF)
+                .filter(|path| converter.has_resource(path))
+        }
+    }
+}
+
+/// Resources loaded by the commands of a display list, in command order.
+pub fn relocations(converter: &Converter, commands: Vec<Command>) -> Vec<Relocation> {
+    commands
+        .into_iter()
+        .filter_map(|command| match command {
+            Command::Texture { image, format } => Some(("texture", image, format)),
+            Command::Vertex { source, .. } => Some(("vertices", source, None)),
+            Command::Call { target, .. } => Some(("display_list", target, None)),
+            _ => None,
+        })
+        .map(|(kind, reference, format)| Relocation {
+            kind,
+            target: resolve(converter, &reference),
+            reference,
+            format,
+        })
+        .collect()
+}
+
+/// Writes the textures embedded in the display list `name` next to its
+/// outputs, as `<base>.inline_<offset>` with the offset in hex. CI textures
+/// take the last TLUT embedded before them.
+fn write_inline_textures(converter: &Converter, name: &str, base: &str, commands: &[Command]) {
+    let mut tlut = None;
+    for command in commands {
+        let Command::InlineTexture { offset, resource } = command else {
+            continue;
+        };
+        if TextureFormat::parse(resource).is_ok_and(|texture| texture.type_id == TextureType::TLUT)
+        {
+            tlut = Some(resource.as_slice());
+            continue;
+        }
+        let mut rgba = Vec::new();
+        let image_format = converter.options.image_format;
+        let encoded = stream::decode_to_writer(
+            resource,
+            tlut,
+            &mut rgba,
+            ImageOutputFormat::Rgba8,
+            &converter.options.decode_options(),
+        )
+        .and_then(|(width, height)| {
+            if let Some(radius) = converter.options.dilate_alpha {
+                dilate::dilate(
+                    &mut rgba,
+                    image::ExtendedColorType::Rgba8,
+                    width,
+                    height,
+                    radius,
+                );
+            }
+            texture::encode_image(
+                image_format,
+                &rgba,
+                width,
+                height,
+                image::ExtendedColorType::Rgba8,
+            )
+        });
+        match encoded {
+            Ok(encoded) => {
+                let path = format!("{}.inline_{:x}.{}", base, offset, image_format.extension());
+                log::progress(format!("Exporting inline texture: {}", path));
+                converter.write(&path, encoded);
+            }
+            Err(err) => log::error(format!(
+                "Failed to convert the texture at 0x{:x} of {}: {}",
+                offset, name, err
+            )),
+        }
+    }
+}
+
+/// Exports the relocation map of display lists, linking them to the
+/// textures, vertices and display lists they use, the textures embedded in
+/// them, and with `--tmem-svg` a diagram of their TMEM loads.
+pub struct DisplayListDecoder;
+
+impl ResourceDecoder for DisplayListDecoder {
+    fn name(&self) -> &'static str {
+        "display-list"
+    }
+
+    fn directory(&self) -> &'static str {
+        "display_lists"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::DisplayList, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let commands = match display_list::parse_display_list(data) {
+            Ok(commands) => commands,
+            Err(err) => {
+                log::error(format!("Failed to parse display list {}: {}", name, err));
+                return;
+            }
+        };
+
+        let base = converter.output_base(self, name);
+        if converter.options.tmem_svg {
+            let loads = tmem::loads(&commands, |reference| {
+                resolve(converter, reference).unwrap_or_else(|| match reference {
+                    Reference::Hash(hash) => format!("{:016x}", hash),
+                    Reference::Path(path) => path.clone(),
+                    Reference::Segmented(address) => format!("0x{:08x}", address),
+                })
+            });
+            if !loads.is_empty() {
+                converter.write(&(base.clone() + ".tmem.svg"), tmem::svg(&loads));
+            }
+        }
+
+        write_inline_textures(converter, name, &base, &commands);
+
+        let relocations = relocations(converter, commands);
+        let unresolved = relocations
+            .iter()
+            .filter(|relocation| relocation.target.is_none())
+            .count();
+        let path = base + ".relocations.json";
+        log::progress(format!(
+            "Exporting {} relocations ({} unresolved): {}",
+            relocations.len(),
+            unresolved,
+            path
+        ));
+        let json = Json::object().with(
+            "relocations",
+            Json::Array(relocations.iter().map(Relocation::to_json).collect()),
+        );
+        converter.write(&path, json.pretty() + "\n");
+    }
+}
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, fs::File, io::Write};
+
+use zip::write::SimpleFileOptions;
+
+use crate::{
+    OTRHeader, ResourceType, TextureFormat, TextureType, asset_definitions, config::Config,
+    decode::decode_with_swap, encode::encode_texture, load_tlut_config, metadata::ArchiveMetadata,
+    options::Options, pixels::Expansion, read_entry, swap::ByteSwap, texture_header, tlut::Tluts,
+};
+
+/// Replaces the texture `entry` of the archive with `image`, encoded to the
+/// entry's original format, and writes the patched archive to `output`.
+/// Every other entry is copied over untouched.
+pub fn run(options: &Options, entry: &str, image: &str, output: &str) {
+    let mut zip =
+        zip::ZipArchive::new(File::open(&options.zip_file).expect("Failed to open zip file"))
+            .expect("Failed to read zip file");
+    if let Some(required) = &options.require_port_version {
+        ArchiveMetadata::read(&mut zip, &options.payload)
+            .check_port_version(required)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    let data = read_entry(&mut zip, entry, &options.payload)
+        .unwrap_or_else(|| panic!("Entry {} not found in {}", entry, options.zip_file));
+    if !OTRHeader::parse(&data).is_ok_and(|header| header.type_id == ResourceType::Texture) {
+        panic!("{} is not a texture resource", entry);
+    }
+    let texture_format =
+        TextureFormat::parse(&data).unwrap_or_else(|err| panic!("{}: {}", entry, err));
+
+    let tlut = match texture_format.type_id {
+        TextureType::Palette4bpp | TextureType::Palette8bpp => {
+            let file_names = zip
+                .file_names()
+                .map(|name| name.to_owned())
+                .collect::<Vec<String>>();
+            let tluts = Tluts::open(
+                options,
+                &file_names,
+                load_tlut_config(&asset_definitions(
+                    &Config::load(&options.config),
+                    options.yaml_dialect,
+                )),
+            );
+            let file_name = entry.split('/').next_back().unwrap();
+            Some(
+                tluts
+                    .for_texture(entry, &texture_format.type_id)
+                    .unwrap_or_else(|| panic!("Texture TLUT not found for {}", file_name)),
+            )
+        }
+        _ => None,
+    };
+
+    let image =
+        image::open(image).unwrap_or_else(|err| panic!("Failed to read {}: {}", image, err));
+    let resource = encode_resource(
+        entry,
+        &data,
+        &image,
+        tlut.as_deref(),
+        options.swap,
+        options.expand,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    write_archive(
+        &mut zip,
+        output,
+        &HashMap::from([(entry.to_owned(), resource)]),
+    );
+
+    println!("Replaced {} in {}", entry, output);
+}
+
+/// The texture resource `data` of the archive entry `entry` with its texels
+/// replaced by `image`, encoded to the original format with `tlut` for CI
+/// textures and stored in the byte order the original was found in. Colors
+/// are matched widened as `expansion` says.
+pub fn encode_resource(
+    entry: &str,
+    data: &[u8],
+    image: &image::DynamicImage,
+    tlut: Option<&TextureFormat>,
+    swap: ByteSwap,
+    expansion: Expansion,
+) -> Result<Vec<u8>, String> {
+    let texture_format = TextureFormat::parse(data).map_err(|err| format!("{}: {}", entry, err))?;
+    if (image.width(), image.height()) != (texture_format.width, texture_format.height) {
+        return Err(format!(
+            "{} is {}x{} but the image is {}x{}",
+            entry,
+            texture_format.width,
+            texture_format.height,
+            image.width(),
+            image.height()
+        ));
+    }
+    let pixels = match texture_format.type_id.to_image_type() {
+        Some(image::ExtendedColorType::La8) => image.to_luma_alpha8().into_raw(),
+        Some(_) => image.to_rgba8().into_raw(),
+        None => {
+            return Err(format!(
+                "Unsupported texture type: {:?}",
+                texture_format.type_id
+            ));
+        }
+    };
+
+    let (swap, _) = decode_with_swap(&texture_format, tlut, swap, expansion)
+        .ok_or_else(|| format!("Unsupported texture type: {:?}", texture_format.type_id))?;
+    let texels = swap.apply(&encode_texture(&texture_format, &pixels, tlut, expansion)?);
+
+    let mut resource = texture_header(data);
+    resource.extend_from_slice(&(texels.len() as u32).to_le_bytes());
+    resource.extend_from_slice(&texels);
+    Ok(resource)
+}
+
+/// Writes a copy of the archive `zip` to `output` with the entries of
+/// `replacements` swapped for the given data, keeping their compression.
+/// Every other entry is copied over untouched.
+pub fn write_archive(
+    zip: &mut zip::ZipArchive<File>,
+    output: &str,
+    replacements: &HashMap<String, Vec<u8>>,
+) {
+    let mut writer =
+        zip::ZipWriter::new(File::create(output).expect("Failed to create output archive"));
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i).expect("Failed to read zip entry");
+        match replacements.get(file.name()) {
+            Some(data) => {
+                let name = file.name().to_owned();
+                let file_options =
+                    SimpleFileOptions::default().compression_method(file.compression());
+                writer
+                    .start_file(name, file_options)
+                    .expect("Failed to write zip entry");
+                writer.write_all(data).expect("Failed to write zip entry");
+            }
+            None => writer
+                .raw_copy_file(file)
+                .expect("Failed to copy zip entry"),
+        }
+    }
+    writer.finish().expect("Failed to write output archive");
+}
+
+/// Writes an archive with only the entries of `replacements`, to be loaded
+/// over the archive `zip` as a mod. Entries keep their order and compression
+/// in `zip`.
+pub fn write_patch(
+    zip: &mut zip::ZipArchive<File>,
+    output: &str,
+    replacements: &HashMap<String, Vec<u8>>,
+) {
+    let mut writer =
+        zip::ZipWriter::new(File::create(output).expect("Failed to create output archive"));
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i).expect("Failed to read zip entry");
+        if let Some(data) = replacements.get(file.name()) {
+            let file_options = SimpleFileOptions::default().compression_method(file.compression());
+            writer
+                .start_file(file.name().to_owned(), file_options)
+                .expect("Failed to write zip entry");
+            writer.write_all(data).expect("Failed to write zip entry");
+        }
+    }
+    writer.finish().expect("Failed to write output archive");
+}
@@ -0,0 +1,67 @@
+use std::{
+    env,
+    fs::{self, File},
+    io,
+    time::{Duration, SystemTime},
+};
+
+use walkdir::WalkDir;
+
+use crate::sha256;
+
+/// Checksums of the output files written with `--reproducible`, as
+/// `sha256sum -c` reads them.
+pub const SUMS_FILE: &str = "SHA256SUMS";
+
+/// Modification time outputs get: `SOURCE_DATE_EPOCH` when set, like other
+/// reproducible build tools, the Unix epoch otherwise.
+fn timestamp() -> SystemTime {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+/// Writes the SHA-256 of every file of the output folder `folder` to
+/// `SUMS_FILE`, sorted by path, and gives every file and folder the same
+/// modification time. Returns the SHA-256 of the sums file, which two
+/// machines can compare to check they produced identical trees.
+pub fn seal(folder: &str) -> io::Result<String> {
+    let mut files = Vec::new();
+    let mut folders = Vec::new();
+    for entry in WalkDir::new(folder).sort_by_file_name() {
+        let entry = entry.map_err(io::Error::other)?;
+        let path = entry.path().strip_prefix(folder).unwrap();
+        let path = path.to_string_lossy().replace('\\', "/");
+        if entry.file_type().is_dir() {
+            folders.push(entry.into_path());
+        } else if path != SUMS_FILE {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let mut sums = String::new();
+    for path in &files {
+        let data = fs::read(format!("{}/{}", folder, path))?;
+        sums += &format!("{}  {}\n", sha256::hex_digest(&data), path);
+    }
+    fs::write(format!("{}/{}", folder, SUMS_FILE), &sums)?;
+
+    let timestamp = timestamp();
+    for path in files.iter().chain([&SUMS_FILE.to_owned()]) {
+        File::options()
+            .write(true)
+            .open(format!("{}/{}", folder, path))?
+            .set_modified(timestamp)?;
+    }
+    // Folders can't be opened for writing everywhere, their times are best
+    // effort
+    for path in folders.iter().rev() {
+        if let Ok(folder) = File::open(path) {
+            let _ = folder.set_modified(timestamp);
+        }
+    }
+    Ok(sha256::hex_digest(sums.as_bytes()))
+}
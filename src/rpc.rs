@@ -0,0 +1,292 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    DecodedTexture, TextureQuery,
+    config::Config,
+    json::Json,
+    metadata::ArchiveMetadata,
+    names, open_query,
+    options::Options,
+    socket,
+    stream::{self, ImageOutputFormat},
+    texture_query::EntryMetadata,
+    tlut::Tluts,
+};
+
+// Error codes defined by the JSON-RPC 2.0 specification
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+// Implementation defined server error
+const DECODE_ERROR: i32 = -32000;
+
+struct Server {
+    /// Decoder and its cache, the only state requests change, locked only
+    /// while a texture is decoded.
+    query: Mutex<TextureQuery>,
+    metadata: ArchiveMetadata,
+    /// Header fields of every entry, read once when the server starts.
+    index: Vec<EntryMetadata>,
+    /// Folder the `output` paths of `decode` are relative to, `--output`.
+    output_folder: String,
+}
+
+/// Answers JSON-RPC 2.0 requests read line by line from stdin until it is
+/// closed or `shutdown` is called. The archive is indexed once up front so
+/// repeated queries don't pay for opening and scanning it again, and decoded
+/// textures are cached up to `--cache-budget`.
+///
+/// With `--serve-socket` requests are read from every client of the socket
+/// instead, all sharing the index and cache, and `shutdown` only closes the
+/// connection it came from. Clients only wait on each other to decode.
+pub fn serve(
+    options: &Options,
+    zip: zip::ZipArchive<File>,
+    metadata: ArchiveMetadata,
+    file_names: Vec<String>,
+    tluts: Tluts,
+    pitches: HashMap<String, u32>,
+    config: Config,
+) {
+    let mut query = open_query(options, zip, tluts, pitches, config);
+    let index = file_names.iter().map(|name| query.metadata(name)).collect();
+    let server = Server {
+        query: Mutex::new(query),
+        metadata,
+        index,
+        output_folder: options.output.clone(),
+    };
+
+    match &options.serve_socket {
+        Some(path) => socket::listen(path, |reader, writer| answer(&server, reader, writer)),
+        None => answer(&server, io::stdin().lock(), io::stdout()),
+    }
+}
+
+/// Answers the requests of one client until it disconnects or calls
+/// `shutdown`.
+fn answer(server: &Server, reader: impl BufRead, mut writer: impl Write) {
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match Json::parse(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                respond(&mut writer, error(Json::Null, PARSE_ERROR, &err));
+                continue;
+            }
+        };
+        // Requests without an id are notifications and get no response
+        let id = request.get("id").cloned();
+        let params = request.get("params").cloned().unwrap_or_else(Json::object);
+        let Some(method) = request.get("method").and_then(Json::as_str) else {
+            respond(
+                &mut writer,
+                error(id.unwrap_or(Json::Null), INVALID_REQUEST, "Missing method"),
+            );
+            continue;
+        };
+
+        let shutdown = method == "shutdown";
+        let result = if shutdown {
+            Ok(Json::Null)
+        } else {
+            server.call(method, &params)
+        };
+        if let Some(id) = id {
+            let response = match result {
+                Ok(result) => Json::object()
+                    .with("jsonrpc", "2.0")
+                    .with("id", id)
+                    .with("result", result),
+                Err((code, message)) => error(id, code, &message),
+            };
+            respond(&mut writer, response);
+        }
+        if shutdown {
+            break;
+        }
+    }
+}
+
+impl Server {
+    fn call(&self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+        match method {
+            "list" => {
+                let prefix = params.get("prefix").and_then(Json::as_str).unwrap_or("");
+                Ok(Json::Array(
+                    self.index
+                        .iter()
+                        .filter(|entry| entry.name.starts_with(prefix))
+                        .map(EntryMetadata::to_json)
+                        .collect(),
+                ))
+            }
+            // Without a path, describes the archive itself
+            "info" if params.get("path").is_none() => {
+                Ok(self.metadata.to_json().with("entries", self.index.len()))
+            }
+            "info" => {
+                let entry = self.entry(params)?;
+                let mut info = entry
+                    .to_json()
+                    .with("version", entry.version)
+                    .with("id", format!("{:016x}", entry.id));
+                // The hi-res pack hashes textures get named after, when they
+                // decode
+                if entry.texture.is_some() {
+                    let name = entry.name.clone();
+                    let pack_hashes = self
+                        .decode(&name)
+                        .ok()
+                        .and_then(|texture| texture.pack_hashes.clone());
+                    if let Some(pack_hashes) = pack_hashes {
+                        info.insert("pack_hashes", pack_hashes.to_json());
+                    }
+                }
+                Ok(info)
+            }
+            "decode" => {
+                let name = self.entry(params)?.name.clone();
+                let texture = self.decode(&name)?;
+
+                let format = match params.get("format").and_then(Json::as_str) {
+                    Some(format) => format
+                        .parse::<ImageOutputFormat>()
+                        .map_err(|err| (INVALID_PARAMS, err))?,
+                    None => ImageOutputFormat::Png,
+                };
+                let mut image = Vec::new();
+                stream::write_image(
+                    &mut image,
+                    &texture.data,
+                    texture.width,
+                    texture.height,
+                    texture.format,
+                    format,
+                )
+                .map_err(|err| (DECODE_ERROR, err))?;
+
+                let mut result = Json::object()
+                    .with("path", name.as_str())
+                    .with("format", format!("{:?}", texture.type_id))
+                    .with("width", texture.width)
+                    .with("height", texture.height)
+                    .with("swap", format!("{:?}", texture.swap));
+                // For tools working with RDP commands rather than the OTR enum
+                if let Some((fmt, siz)) = texture.type_id.to_fmt_siz() {
+                    result.insert("fmt", fmt as u32);
+                    result.insert("siz", siz as u32);
+                }
+                match params.get("output").and_then(Json::as_str) {
+                    Some(output) => {
+                        let path = self.output_path(output)?;
+                        if let Some(parent) = path.parent() {
+                            fs::create_dir_all(parent)
+                                .map_err(|err| (DECODE_ERROR, err.to_string()))?;
+                        }
+                        fs::write(&path, image).map_err(|err| (DECODE_ERROR, err.to_string()))?;
+                        Ok(result.with("output", path.to_string_lossy().as_ref()))
+                    }
+                    None => {
+                        let key = match format {
+                            ImageOutputFormat::Png => "png",
+                            ImageOutputFormat::Rgba8 => "rgba8",
+                        };
+                        Ok(result.with(key, base64(&image)))
+                    }
+                }
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        }
+    }
+
+    fn decode(&self, name: &str) -> Result<Arc<DecodedTexture>, (i32, String)> {
+        // A poisoned lock only means another client's request panicked
+        self.query
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .decode(name)
+            .map_err(|err| (DECODE_ERROR, err))
+    }
+
+    /// Where the `output` of a `decode` call is written. Clients only get to
+    /// write inside the output folder, so the path has to be relative and
+    /// stay in it.
+    fn output_path(&self, output: &str) -> Result<PathBuf, (i32, String)> {
+        if self.output_folder == "-" {
+            return Err((
+                INVALID_PARAMS,
+                "Writing outputs needs an --output folder".to_owned(),
+            ));
+        }
+        if !names::is_contained(output) {
+            return Err((
+                INVALID_PARAMS,
+                format!(
+                    "Output {} is not a relative path inside the output folder",
+                    output
+                ),
+            ));
+        }
+        Ok(Path::new(&self.output_folder).join(output))
+    }
+
+    fn entry(&self, params: &Json) -> Result<&EntryMetadata, (i32, String)> {
+        let path = params
+            .get("path")
+            .and_then(Json::as_str)
+            .ok_or((INVALID_PARAMS, "Missing 'path' parameter".to_owned()))?;
+        self.index
+            .iter()
+            .find(|entry| entry.name == path)
+            .ok_or_else(|| (INVALID_PARAMS, format!("No entry named {}", path)))
+    }
+}
+
+fn error(id: Json, code: i32, message: &str) -> Json {
+    Json::object().with("jsonrpc", "2.0").with("id", id).with(
+        "error",
+        Json::object()
+            .with("code", code as f64)
+            .with("message", message),
+    )
+}
+
+fn respond(writer: &mut impl Write, response: Json) {
+    let _ = writeln!(writer, "{}", response);
+    let _ = writer.flush();
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(triple >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
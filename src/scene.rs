@@ -0,0 +1,279 @@
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, ResourceType, decoder::ResourceDecoder, json::Json,
+    light, log, reader::Reader, skeleton::resource_path,
+};
+
+/// Decodes the command list of a Scene or Room resource. Parsing stops at the
+/// first command whose layout isn't known, as its size can't be skipped; the
+/// commands read so far are kept and the problem is reported under `error`.
+pub fn parse(data: &[u8]) -> Json {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let mut commands = Vec::new();
+    let error = parse_commands(&mut reader, &mut commands).err();
+
+    let scene = Json::object().with("commands", Json::Array(commands));
+    match error {
+        Some(error) => scene.with("error", error),
+        None => scene,
+    }
+}
+
+fn parse_commands(reader: &mut Reader, commands: &mut Vec<Json>) -> Result<(), String> {
+    let count = reader.u32()?;
+    for _ in 0..count {
+        commands.push(parse_command(reader)?);
+    }
+    Ok(())
+}
+
+fn parse_command(reader: &mut Reader) -> Result<Json, String> {
+    let id = reader.i32()?;
+    let command = |name: &str| Json::object().with("command", name);
+    let list = |reader: &mut Reader,
+                parse: &dyn Fn(&mut Reader) -> Result<Json, String>|
+     -> Result<Json, String> {
+        let count = reader.u32()?;
+        Ok(Json::Array(
+            (0..count)
+                .map(|_| parse(reader))
+                .collect::<Result<Vec<_>, _>>()?,
+        ))
+    };
+
+    Ok(match id {
+        0x00 => command("spawn_list").with("actors", list(reader, &actor_entry)?),
+        0x01 => command("actor_list").with("actors", list(reader, &actor_entry)?),
+        0x03 => command("collision_header").with("path", path(reader)?),
+        0x04 => command("room_list").with(
+            "rooms",
+            list(reader, &|reader| {
+                Ok(Json::object()
+                    .with("path", path(reader)?)
+                    .with("vrom_start", reader.u32()?)
+                    .with("vrom_end", reader.u32()?))
+            })?,
+        ),
+        0x05 => command("wind_settings")
+            .with("x", reader.u8()? as i8 as f64)
+            .with("y", reader.u8()? as i8 as f64)
+            .with("z", reader.u8()? as i8 as f64)
+            .with("strength", reader.u8()? as u32),
+        0x06 => command("entrance_list").with(
+            "entrances",
+            list(reader, &|reader| {
+                Ok(Json::object()
+                    .with("spawn", reader.u8()? as u32)
+                    .with("room", reader.u8()? as u32))
+            })?,
+        ),
+        0x07 => command("special_files")
+            .with("navi_message", reader.u8()? as i8 as f64)
+            .with("global_object", reader.u16()?),
+        0x08 => command("room_behavior")
+            .with("type", reader.u8()? as u32)
+            .with("flags", reader.u32()?),
+        0x09 => command("unused_09"),
+        0x0A => mesh(reader)?,
+        0x0B => command("object_list")
+            .with("objects", list(reader, &|reader| Ok(reader.u16()?.into()))?),
+        0x0C => command("light_list").with(
+            "lights",
+            list(reader, &|reader| {
+                Ok(Json::object()
+                    .with("type", reader.u8()? as u32)
+                    .with("position", vec3s(reader)?)
+                    .with("color", color(reader)?)
+                    .with("draw_glow", reader.u8()? != 0)
+                    .with("radius", reader.i16()? as f64))
+            })?,
+        ),
+        0x0D => command("path_list").with("paths", list(reader, &|reader| path(reader))?),
+        0x0E => command("transition_actor_list").with(
+            "actors",
+            list(reader, &|reader| {
+                Ok(Json::object()
+                    .with("front_room", reader.u8()? as u32)
+                    .with("front_effects", reader.u8()? as u32)
+                    .with("back_room", reader.u8()? as u32)
+                    .with("back_effects", reader.u8()? as u32)
+                    .with("id", reader.i16()? as f64)
+                    .with("position", vec3s(reader)?)
+                    .with("rotation_y", reader.i16()? as f64)
+                    .with("params", reader.u16()?))
+            })?,
+        ),
+        0x0F => command("env_light_settings").with(
+            "settings",
+            list(reader, &|reader| {
+                Ok(Json::object()
+                    .with("ambient_color", color(reader)?)
+                    .with("light1_direction", direction(reader)?)
+                    .with("light1_color", color(reader)?)
+                    .with("light2_direction", direction(reader)?)
+                    .with("light2_color", color(reader)?)
+                    .with("fog_color", color(reader)?)
+                    .with("fog_near", reader.i16()? as f64)
+                    .with("fog_far", reader.u16()?))
+            })?,
+        ),
+        0x10 => command("time_settings")
+            .with("hour", reader.u8()? as u32)
+            .with("minute", reader.u8()? as u32)
+            .with("speed", reader.u8()? as u32),
+        0x11 => command("skybox_settings")
+            .with("unk_13", reader.u8()? as u32)
+            .with("skybox", reader.u8()? as u32)
+            .with("weather", reader.u8()? as u32)
+            .with("indoors", reader.u8()? != 0),
+        0x12 => command("skybox_disables")
+            .with("disable_sky", reader.u8()? != 0)
+            .with("disable_sun_moon", reader.u8()? != 0),
+        0x13 => {
+            command("exit_list").with("exits", list(reader, &|reader| Ok(reader.u16()?.into()))?)
+        }
+        0x14 => command("end"),
+        0x15 => command("sound_settings")
+            .with("reverb", reader.u8()? as u32)
+            .with("nature_ambience", reader.u8()? as u32)
+            .with("sequence", reader.u8()? as u32),
+        0x16 => command("echo_settings").with("echo", reader.u8()? as u32),
+        0x17 => command("cutscene_data").with("path", path(reader)?),
+        0x18 => {
+            command("alternate_header_list").with("headers", list(reader, &|reader| path(reader))?)
+        }
+        0x19 => command("misc_settings")
+            .with("camera_movement", reader.u8()? as u32)
+            .with("world_map_area", reader.u32()?),
+        _ => return Err(format!("Unsupported scene command 0x{:02X}", id)),
+    })
+}
+
+/// `SCENE_CMD_MESH`, for the normal and culled mesh headers. Prerendered
+/// backgrounds aren't decoded.
+fn mesh(reader: &mut Reader) -> Result<Json, String> {
+    let _data = reader.u8()?;
+    let mesh_type = reader.u8()?;
+    if mesh_type == 1 {
+        return Err("Unsupported prerendered mesh header".to_owned());
+    }
+    let count = reader.u8()?;
+    let polygons = (0..count)
+        .map(|_| {
+            let _polygon_type = reader.u8()?;
+            let mut polygon = Json::object();
+            if mesh_type == 2 {
+                polygon.insert("position", vec3s(reader)?);
+                polygon.insert("radius", reader.i16()? as f64);
+            }
+            polygon.insert("opa", path(reader)?);
+            polygon.insert("xlu", path(reader)?);
+            Ok(polygon)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Json::object()
+        .with("command", "mesh")
+        .with("type", mesh_type as u32)
+        .with("polygons", Json::Array(polygons)))
+}
+
+fn actor_entry(reader: &mut Reader) -> Result<Json, String> {
+    Ok(Json::object()
+        .with("id", reader.u16()?)
+        .with("position", vec3s(reader)?)
+        .with("rotation", vec3s(reader)?)
+        .with("params", reader.u16()?))
+}
+
+fn path(reader: &mut Reader) -> Result<Json, String> {
+    Ok(resource_path(&reader.string()?).into())
+}
+
+fn vec3s(reader: &mut Reader) -> Result<Json, String> {
+    Ok(Json::Array(
+        (0..3)
+            .map(|_| reader.i16().map(|value| (value as f64).into()))
+            .collect::<Result<Vec<_>, _>>()?,
+    ))
+}
+
+fn direction(reader: &mut Reader) -> Result<Json, String> {
+    Ok(Json::Array(
+        (0..3)
+            .map(|_| reader.u8().map(|value| (value as i8 as f64).into()))
+            .collect::<Result<Vec<_>, _>>()?,
+    ))
+}
+
+fn color(reader: &mut Reader) -> Result<Json, String> {
+    Ok(Json::Array(
+        (0..3)
+            .map(|_| reader.u8().map(|value| (value as u32).into()))
+            .collect::<Result<Vec<_>, _>>()?,
+    ))
+}
+
+/// Ambient, light and fog colors of each environment light setting of
+/// `scene`, in the order `env_light_settings` lists them.
+fn light_colors(scene: &Json) -> Vec<Vec<[u8; 3]>> {
+    let Some(Json::Array(commands)) = scene.get("commands") else {
+        return Vec::new();
+    };
+    let color = |setting: &Json, key| -> Option<[u8; 3]> {
+        let Some(Json::Array(channels)) = setting.get(key) else {
+            return None;
+        };
+        let channel = |i: usize| channels.get(i)?.as_f64().map(|value| value as u8);
+        Some([channel(0)?, channel(1)?, channel(2)?])
+    };
+    commands
+        .iter()
+        .filter(|command| {
+            command.get("command").and_then(Json::as_str) == Some("env_light_settings")
+        })
+        .filter_map(|command| match command.get("settings") {
+            Some(Json::Array(settings)) => Some(settings),
+            _ => None,
+        })
+        .flatten()
+        .map(|setting| {
+            ["ambient_color", "light1_color", "light2_color", "fog_color"]
+                .into_iter()
+                .filter_map(|key| color(setting, key))
+                .collect()
+        })
+        .collect()
+}
+
+/// Dumps scene and room command lists to JSON, with a swatch sheet of the
+/// environment light settings, a row of ambient, light and fog colors each.
+pub struct SceneDecoder;
+
+impl ResourceDecoder for SceneDecoder {
+    fn name(&self) -> &'static str {
+        "scene"
+    }
+
+    fn directory(&self) -> &'static str {
+        "scenes"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::Scene, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let scene = parse(data);
+        if let Some(error) = scene.get("error").and_then(Json::as_str) {
+            log::error(format!("Scene {} only partially decoded: {}", name, error));
+        }
+
+        let base = converter.output_base(self, name);
+        log::progress(format!("Exporting scene commands: {}.json", base));
+        converter.write(&(base.clone() + ".json"), scene.pretty() + "\n");
+        let colors = light_colors(&scene);
+        if !colors.is_empty() {
+            converter.write(&(base + "_lights.png"), light::swatches(&colors));
+        }
+    }
+}
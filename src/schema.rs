@@ -0,0 +1,297 @@
+use std::fs;
+
+use crate::{json::Json, manifest::MANIFEST_FILE};
+
+/// JSON Schema dialect the schemas are written in.
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// File the schema of `manifest.json` is written to.
+pub const MANIFEST_SCHEMA_FILE: &str = "manifest.schema.json";
+/// File the schema of `config.yml` is written to. YAML editors validate the
+/// config against it like any JSON Schema.
+pub const CONFIG_SCHEMA_FILE: &str = "config.schema.json";
+
+fn typed(kind: &str) -> Json {
+    Json::object().with("type", kind)
+}
+
+fn described(kind: &str, description: &str) -> Json {
+    typed(kind).with("description", description)
+}
+
+fn nullable(kind: &str, description: &str) -> Json {
+    Json::object()
+        .with("type", vec![Json::from(kind), Json::from("null")])
+        .with("description", description)
+}
+
+fn array_of(items: Json, description: &str) -> Json {
+    described("array", description).with("items", items)
+}
+
+fn object(required: &[&str], properties: Vec<(&str, Json)>) -> Json {
+    typed("object")
+        .with(
+            "required",
+            required
+                .iter()
+                .map(|key| Json::from(*key))
+                .collect::<Vec<_>>(),
+        )
+        .with(
+            "properties",
+            properties
+                .into_iter()
+                .fold(Json::object(), |json, (key, value)| json.with(key, value)),
+        )
+}
+
+/// Schema of the `manifest.json` written next to the converted textures, see
+/// `Manifest::to_json`.
+pub fn manifest() -> Json {
+    let alpha = object(
+        &["class", "opaque", "transparent", "translucent"],
+        vec![
+            (
+                "class",
+                typed("string").with(
+                    "enum",
+                    vec![
+                        Json::from("opaque"),
+                        Json::from("binary"),
+                        Json::from("mixed"),
+                    ],
+                ),
+            ),
+            ("opaque", typed("integer").with("minimum", 0u32)),
+            ("transparent", typed("integer").with("minimum", 0u32)),
+            ("translucent", typed("integer").with("minimum", 0u32)),
+        ],
+    )
+    .with("description", "Texels by alpha");
+    let texture = object(
+        &["entry", "output", "format", "width", "height"],
+        vec![
+            (
+                "entry",
+                described("string", "Path of the resource in the archive"),
+            ),
+            (
+                "output",
+                described("string", "Path of the image relative to the output folder"),
+            ),
+            ("format", typed("string")),
+            ("width", typed("integer").with("minimum", 0u32)),
+            ("height", typed("integer").with("minimum", 0u32)),
+            (
+                "hash",
+                nullable("string", "CRC-64 of the decoded texels, in hex")
+                    .with("pattern", "^[0-9a-f]{16}$"),
+            ),
+            (
+                "alpha",
+                Json::object().with("anyOf", vec![alpha, typed("null")]),
+            ),
+            (
+                "pack_hash",
+                described("string", "Rice hash fields of the texture, with --hash-db"),
+            ),
+            (
+                "pack_name",
+                nullable("string", "File of the hi-res pack with the same hash"),
+            ),
+            (
+                "category",
+                described("string", "Guessed use of the texture, with --classify").with(
+                    "enum",
+                    vec![
+                        Json::from("font"),
+                        Json::from("ui"),
+                        Json::from("skybox"),
+                        Json::from("terrain"),
+                        Json::from("other"),
+                    ],
+                ),
+            ),
+        ],
+    );
+    let tiled = object(
+        &[
+            "entry",
+            "output",
+            "columns",
+            "rows",
+            "tile_width",
+            "tile_height",
+            "tiles",
+        ],
+        vec![
+            (
+                "entry",
+                described(
+                    "string",
+                    "Path of the image in the archive, without the tile suffix",
+                ),
+            ),
+            (
+                "output",
+                described("string", "Path of the image relative to the output folder"),
+            ),
+            ("columns", typed("integer").with("minimum", 1u32)),
+            ("rows", typed("integer").with("minimum", 1u32)),
+            ("tile_width", typed("integer").with("minimum", 0u32)),
+            ("tile_height", typed("integer").with("minimum", 0u32)),
+            (
+                "tiles",
+                array_of(typed("string"), "Tile entries in row-major order"),
+            ),
+        ],
+    );
+    Json::object()
+        .with("$schema", DIALECT)
+        .with("title", MANIFEST_FILE)
+        .with(
+            "description",
+            "Record of a conversion run, mapping the output tree back to the archive",
+        )
+        .with("type", "object")
+        .with(
+            "required",
+            vec![
+                Json::from("archive"),
+                Json::from("path_map"),
+                Json::from("textures"),
+                Json::from("files"),
+            ],
+        )
+        .with(
+            "properties",
+            Json::object()
+                .with("archive", described("string", "Archive the run converted"))
+                .with(
+                    "path_map",
+                    described("object", "Archive path prefixes rewritten in output paths")
+                        .with("additionalProperties", typed("string")),
+                )
+                .with(
+                    "textures",
+                    array_of(texture, "Textures written to the output folder"),
+                )
+                .with(
+                    "tiled",
+                    array_of(tiled, "Images stitched together from tile entries"),
+                )
+                .with(
+                    "placeholders",
+                    array_of(
+                        typed("string"),
+                        "Archive paths of the filler textures skipped with --skip-placeholders",
+                    ),
+                )
+                .with(
+                    "files",
+                    array_of(
+                        typed("string"),
+                        "Every file the run wrote besides the manifest and the journal",
+                    ),
+                ),
+        )
+}
+
+/// Schema of `config.yml`, see `Config::load`: games by name, each with the
+/// folder of its decomp asset definitions.
+pub fn config() -> Json {
+    let game = object(
+        &["path"],
+        vec![
+            (
+                "path",
+                described("string", "Directory holding the decomp YAML asset definitions"),
+            ),
+            (
+                "path_map",
+                described("object", "Archive path prefixes to rewrite in output paths")
+                    .with("additionalProperties", typed("string")),
+            ),
+            (
+                "segments",
+                described(
+                    "object",
+                    "Archive directory each segment, from 0 to 15, of segmented addresses points into",
+                )
+                .with("additionalProperties", typed("string")),
+            ),
+            (
+                "tiled",
+                described(
+                    "object",
+                    "Images stored as a grid of tile entries, by path, with their column count",
+                )
+                .with(
+                    "additionalProperties",
+                    typed("integer").with("minimum", 1u32),
+                ),
+            ),
+            (
+                "i4_as_ia4",
+                array_of(
+                    typed("string"),
+                    "Globs of the archive paths of textures tagged I4 that hold IA4 texels",
+                ),
+            ),
+            (
+                "ci16",
+                array_of(
+                    typed("string"),
+                    "Globs of the archive paths of textures holding 12-bit palette indices in 16-bit texels",
+                ),
+            ),
+            (
+                "placeholders",
+                object(
+                    &[],
+                    vec![
+                        (
+                            "max_size",
+                            typed("integer")
+                                .with("minimum", 0u32)
+                                .with("description", "Largest width and height of the textures skipped whatever they hold, 1 by default"),
+                        ),
+                        (
+                            "solid",
+                            described("boolean", "Whether textures of a single color are skipped at any size, true by default"),
+                        ),
+                        (
+                            "keep",
+                            array_of(typed("string"), "Globs of the archive paths of textures never skipped"),
+                        ),
+                    ],
+                )
+                .with("description", "How --skip-placeholders tells filler textures apart"),
+            ),
+        ],
+    );
+    Json::object()
+        .with("$schema", DIALECT)
+        .with("title", "config.yml")
+        .with(
+            "description",
+            "Games by name, the first with an existing path is used",
+        )
+        .with("type", "object")
+        .with("additionalProperties", game)
+}
+
+/// Writes the schemas of the manifest and the config to the folder `output`.
+pub fn write(output: &str) {
+    fs::create_dir_all(output).unwrap_or_else(|err| panic!("Failed to create {}: {}", output, err));
+    for (file, schema) in [
+        (MANIFEST_SCHEMA_FILE, manifest()),
+        (CONFIG_SCHEMA_FILE, config()),
+    ] {
+        let path = format!("{}/{}", output, file);
+        fs::write(&path, schema.pretty() + "\n")
+            .unwrap_or_else(|err| panic!("Failed to write {}: {}", path, err));
+        println!("Wrote {}", path);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{OTR_HEADER_SIZE, reader::Reader};
+
+/// Marks a missing child or sibling in a limb.
+pub const NO_LIMB: u8 = 0xFF;
+
+/// `LimbType` as used by the ZAPD/LUS skeleton resources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimbType {
+    Invalid,
+    Standard,
+    Lod,
+    Skin,
+    Curve,
+    Legacy,
+}
+
+impl LimbType {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => LimbType::Standard,
+            2 => LimbType::Lod,
+            3 => LimbType::Skin,
+            4 => LimbType::Curve,
+            5 => LimbType::Legacy,
+            _ => LimbType::Invalid,
+        }
+    }
+}
+
+/// A SkeletonLimb resource. Skin limb vertex modifications are read past but
+/// not kept, the limb is exported as rigid geometry.
+pub struct Limb {
+    pub translation: [f32; 3],
+    pub display_list: Option<String>,
+    pub child: u8,
+    pub sibling: u8,
+}
+
+/// A Skeleton resource, listing the archive paths of its limbs.
+pub struct Skeleton {
+    pub limbs: Vec<String>,
+}
+
+/// A Normal animation. `joint_indices` holds the root translation followed by
+/// one rotation per limb, each component indexing `values`.
+pub struct Animation {
+    pub frame_count: u16,
+    pub values: Vec<i16>,
+    pub joint_indices: Vec<[u16; 3]>,
+    pub static_index_max: u16,
+}
+
+impl Animation {
+    /// Value of a joint component at `frame`. Indices below the limit point at
+    /// a single static value, the others at one value per frame.
+    pub fn value(&self, index: u16, frame: u16) -> i16 {
+        let index = if index >= self.static_index_max {
+            index as usize + frame as usize
+        } else {
+            index as usize
+        };
+        self.values.get(index).copied().unwrap_or(0)
+    }
+}
+
+/// Strips the `__OTR__` marker decomp sources put in front of resource paths.
+pub fn resource_path(path: &str) -> &str {
+    path.strip_prefix("__OTR__").unwrap_or(path)
+}
+
+pub fn parse_limb(data: &[u8]) -> Result<Limb, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let limb_type = LimbType::from_u8(reader.u8()?);
+    let _skin_segment_type = reader.u8()?;
+    let _skin_display_list = reader.u32()?;
+    let _skin_vertex_count = reader.u16()?;
+    let skin_modification_count = reader.u32()?;
+    for _ in 0..skin_modification_count {
+        let _unk_4 = reader.u16()?;
+        let skin_vertex_count = reader.u32()?;
+        // index, s, t, normal x/y/z and alpha
+        reader.bytes(skin_vertex_count as usize * 10)?;
+        let transformation_count = reader.u32()?;
+        // index, x, y, z and scale
+        reader.bytes(transformation_count as usize * 8)?;
+    }
+    let _skin_display_list_2 = reader.string()?;
+
+    let legacy_translation = [reader.f32()?, reader.f32()?, reader.f32()?];
+    let _legacy_rotation = [reader.u16()?, reader.u16()?, reader.u16()?];
+
+    let _child_path = reader.string()?;
+    let _sibling_path = reader.string()?;
+    let display_list = reader.string()?;
+    let _display_list_2 = reader.string()?;
+
+    let translation = [reader.i16()?, reader.i16()?, reader.i16()?];
+    let child = reader.u8()?;
+    let sibling = reader.u8()?;
+
+    Ok(Limb {
+        translation: match limb_type {
+            LimbType::Legacy => legacy_translation,
+            _ => translation.map(|value| value as f32),
+        },
+        display_list: (!display_list.is_empty()).then(|| resource_path(&display_list).to_owned()),
+        child,
+        sibling,
+    })
+}
+
+pub fn parse_skeleton(data: &[u8]) -> Result<Skeleton, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let _skeleton_type = reader.u8()?;
+    let _limb_type = reader.u8()?;
+    let _limb_count = reader.u32()?;
+    let _display_list_count = reader.u32()?;
+    let _limb_table_type = reader.u8()?;
+    let limb_table_count = reader.u32()?;
+    let limbs = (0..limb_table_count)
+        .map(|_| reader.string().map(|path| resource_path(&path).to_owned()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Skeleton { limbs })
+}
+
+/// Parses an Animation resource, or returns `Ok(None)` for the Link, Curve
+/// and Legacy kinds which keep their data elsewhere.
+pub fn parse_animation(data: &[u8]) -> Result<Option<Animation>, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    if reader.u32()? != 0 {
+        return Ok(None);
+    }
+    let frame_count = reader.i16()?.max(0) as u16;
+    let value_count = reader.u32()?;
+    let values = (0..value_count)
+        .map(|_| reader.i16())
+        .collect::<Result<Vec<_>, _>>()?;
+    let index_count = reader.u32()?;
+    let joint_indices = (0..index_count)
+        .map(|_| Ok([reader.u16()?, reader.u16()?, reader.u16()?]))
+        .collect::<Result<Vec<_>, String>>()?;
+    let static_index_max = reader.u16()?;
+    Ok(Some(Animation {
+        frame_count,
+        values,
+        joint_indices,
+        static_index_max,
+    }))
+}
@@ -0,0 +1,258 @@
+use std::io::BufReader;
+#[cfg(any(unix, windows))]
+use std::time::Duration;
+
+#[cfg(any(unix, windows))]
+use crate::log;
+
+/// Connection to a client of the control socket.
+#[cfg(unix)]
+pub type Stream = std::os::unix::net::UnixStream;
+/// Connection to a client of the control pipe.
+#[cfg(windows)]
+pub type Stream = std::fs::File;
+
+/// Wait after a failed accept, doubled on each failure in a row up to
+/// `MAX_BACKOFF`.
+#[cfg(any(unix, windows))]
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+#[cfg(any(unix, windows))]
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Accepts local clients on `path`, a Unix socket or, on Windows, a named
+/// pipe, and hands each connection to `connection` on its own thread until
+/// the process ends.
+#[cfg(any(unix, windows))]
+pub fn listen(path: &str, connection: impl Fn(BufReader<Stream>, Stream) + Sync) {
+    std::thread::scope(|scope| {
+        let connection = &connection;
+        let mut accept = acceptor(path);
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let stream = match accept() {
+                Ok(stream) => {
+                    backoff = MIN_BACKOFF;
+                    stream
+                }
+                Err(err) => {
+                    // Errors like running out of file descriptors persist for
+                    // a while, so wait for them to clear instead of spinning
+                    log::error(format!("Failed to accept a client on {}: {}", path, err));
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            };
+            match stream.try_clone() {
+                Ok(reader) => {
+                    scope.spawn(move || connection(BufReader::new(reader), stream));
+                }
+                Err(err) => log::error(format!("Failed to set up a client of {}: {}", path, err)),
+            }
+        }
+    });
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn listen(_path: &str, _connection: impl Fn(BufReader<std::fs::File>, std::fs::File) + Sync) {
+    panic!("--serve-socket is only supported on Unix and Windows");
+}
+
+#[cfg(unix)]
+fn acceptor(path: &str) -> impl FnMut() -> std::io::Result<Stream> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            panic!(
+                "{} exists and is not a socket, refusing to replace it",
+                path
+            );
+        }
+        if Stream::connect(path).is_ok() {
+            panic!("Another server is already listening on {}", path);
+        }
+        // Left behind by a server that didn't exit cleanly
+        std::fs::remove_file(path)
+            .unwrap_or_else(|err| panic!("Failed to remove the stale socket {}: {}", path, err));
+    }
+    let listener = std::os::unix::net::UnixListener::bind(path)
+        .unwrap_or_else(|err| panic!("Failed to listen on {}: {}", path, err));
+    println!("Listening on {}", path);
+    move || listener.accept().map(|(stream, _)| stream)
+}
+
+#[cfg(windows)]
+fn acceptor(path: &str) -> impl FnMut() -> std::io::Result<Stream> {
+    let name = match path.starts_with(r"\\.\pipe\") {
+        true => path.to_owned(),
+        false => format!(r"\\.\pipe\{}", path),
+    };
+    let security = pipe::Security::current_user()
+        .unwrap_or_else(|err| panic!("Failed to restrict {} to the current user: {}", name, err));
+    println!("Listening on {}", name);
+    move || pipe::accept(&name, &security)
+}
+
+#[cfg(windows)]
+mod pipe {
+    use std::{
+        ffi::OsStr,
+        fs::File,
+        io,
+        os::windows::{ffi::OsStrExt, io::FromRawHandle},
+        ptr,
+    };
+
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE, LocalFree},
+        Security::{
+            Authorization::{
+                ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+                SDDL_REVISION_1,
+            },
+            GetTokenInformation, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY,
+            TOKEN_USER, TokenUser,
+        },
+        Storage::FileSystem::PIPE_ACCESS_DUPLEX,
+        System::{
+            Pipes::{
+                ConnectNamedPipe, CreateNamedPipeW, PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+            },
+            Threading::{GetCurrentProcess, OpenProcessToken},
+        },
+    };
+
+    // Byte stream, blocking reads and writes, local clients only
+    const PIPE_MODE: u32 = PIPE_REJECT_REMOTE_CLIENTS;
+    const BUFFER_SIZE: u32 = 64 * 1024;
+
+    /// Security descriptor whose DACL only lets the user running the server
+    /// open the pipe, so other users of the machine can't send it requests.
+    pub struct Security {
+        descriptor: PSECURITY_DESCRIPTOR,
+    }
+
+    impl Security {
+        pub fn current_user() -> io::Result<Self> {
+            let sid = current_user_sid()?;
+            // Protected DACL with a single entry granting the user all access
+            let sddl = OsStr::new(&format!("D:P(A;;GA;;;{})", sid))
+                .encode_wide()
+                .chain([0])
+                .collect::<Vec<u16>>();
+            let mut descriptor = ptr::null_mut();
+            // SAFETY: the SDDL is NUL terminated, and the descriptor is freed
+            // by `drop`
+            let converted = unsafe {
+                ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                    sddl.as_ptr(),
+                    SDDL_REVISION_1,
+                    &mut descriptor,
+                    ptr::null_mut(),
+                )
+            };
+            if converted == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Security { descriptor })
+        }
+    }
+
+    impl Drop for Security {
+        fn drop(&mut self) {
+            // SAFETY: the descriptor was allocated by the conversion
+            unsafe { LocalFree(self.descriptor) };
+        }
+    }
+
+    /// SID of the user the process runs as, in its `S-1-...` string form.
+    fn current_user_sid() -> io::Result<String> {
+        let mut token = ptr::null_mut();
+        // SAFETY: the pseudo handle of the current process needs no closing
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // A `TOKEN_USER` points into the rest of the buffer for its SID, and
+        // the buffer has to be aligned for it
+        let mut information = vec![0u64; 64];
+        let mut length = 0;
+        // SAFETY: the token is open, and the length is that of the buffer
+        let queried = unsafe {
+            GetTokenInformation(
+                token,
+                TokenUser,
+                information.as_mut_ptr().cast(),
+                (information.len() * 8) as u32,
+                &mut length,
+            )
+        };
+        let err = io::Error::last_os_error();
+        // SAFETY: the token was opened above
+        unsafe { CloseHandle(token) };
+        if queried == 0 {
+            return Err(err);
+        }
+
+        // SAFETY: the buffer starts with the `TOKEN_USER` just written to it
+        let user = unsafe { &*information.as_ptr().cast::<TOKEN_USER>() };
+        let mut string = ptr::null_mut();
+        // SAFETY: the SID is that of the token, still in the buffer
+        if unsafe { ConvertSidToStringSidW(user.User.Sid, &mut string) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the conversion gives a NUL terminated string, freed once
+        // copied
+        let sid = unsafe {
+            let length = (0..).take_while(|&i| *string.add(i) != 0).count();
+            let sid = String::from_utf16_lossy(std::slice::from_raw_parts(string, length));
+            LocalFree(string.cast());
+            sid
+        };
+        Ok(sid)
+    }
+
+    /// Creates an instance of the pipe `name`, open to who `security` lets
+    /// in, and waits for a client to connect to it.
+    pub fn accept(name: &str, security: &Security) -> io::Result<File> {
+        let name = OsStr::new(name)
+            .encode_wide()
+            .chain([0])
+            .collect::<Vec<u16>>();
+        let attributes = SECURITY_ATTRIBUTES {
+            nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: security.descriptor,
+            bInheritHandle: 0,
+        };
+        // SAFETY: the name is NUL terminated, and it and the security
+        // attributes outlive the call
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_MODE,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                &attributes,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the handle was just created and nothing else owns it
+        let pipe = unsafe { File::from_raw_handle(handle) };
+        // SAFETY: the handle is open, and a null overlapped waits for the
+        // client
+        if unsafe { ConnectNamedPipe(handle, ptr::null_mut()) } == 0 {
+            let err = io::Error::last_os_error();
+            // A client connecting before the wait is already there
+            if err.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+                return Err(err);
+            }
+        }
+        Ok(pipe)
+    }
+}
@@ -0,0 +1,127 @@
+//! Decoding straight to a writer, for embedding the conversion in servers
+//! and for `--stdin`.
+
+use std::{io::Write, str::FromStr, sync::Arc};
+
+use image::{ExtendedColorType, ImageEncoder, codecs::png::PngEncoder};
+
+use crate::{DecodeError, DecodeOptions, SingleTlut, decode, parse_texture};
+
+/// Encoding of images written to a stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageOutputFormat {
+    Png,
+    /// Bare RGBA texels, 4 bytes per pixel row by row, for callers doing their
+    /// own encoding.
+    Rgba8,
+}
+
+impl FromStr for ImageOutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "png" => Ok(ImageOutputFormat::Png),
+            "rgba8" => Ok(ImageOutputFormat::Rgba8),
+            _ => Err(format!(
+                "Unknown image output format '{}', expected png or rgba8",
+                value
+            )),
+        }
+    }
+}
+
+/// Expands decoded texels of type `color` to RGBA, grayscale going to the
+/// three color channels.
+pub fn rgba8(color: ExtendedColorType, data: &[u8], pixels: usize) -> Vec<u8> {
+    match color {
+        ExtendedColorType::Rgba8 => data.to_vec(),
+        ExtendedColorType::La8 => data
+            .chunks_exact(2)
+            .flat_map(|la| [la[0], la[0], la[0], la[1]])
+            .collect(),
+        // One bit per texel, standing for both intensity and alpha
+        _ => (0..pixels)
+            .flat_map(|i| {
+                let bit = data.get(i / 8).map_or(0, |byte| byte >> (7 - i % 8) & 1);
+                [bit * 0xFF; 4]
+            })
+            .collect(),
+    }
+}
+
+/// Fails unless `data` holds exactly the texels of a `width`x`height` image
+/// of type `color`, which the image encoders assert instead of reporting.
+pub fn check_buffer(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+) -> Result<(), String> {
+    let expected = (color.bits_per_pixel() as u64 * width as u64).div_ceil(8) * height as u64;
+    if data.len() as u64 != expected {
+        return Err(format!(
+            "Expected {} bytes of {:?} texels for a {}x{} image, got {}",
+            expected,
+            color,
+            width,
+            height,
+            data.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Encodes decoded texels straight to `writer`, without a seekable buffer or
+/// an intermediate image.
+pub fn write_image(
+    writer: &mut impl Write,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+    format: ImageOutputFormat,
+) -> Result<(), String> {
+    check_buffer(data, width, height, color)?;
+    match format {
+        ImageOutputFormat::Png => PngEncoder::new(writer)
+            .write_image(data, width, height, color)
+            .map_err(|err| err.to_string()),
+        ImageOutputFormat::Rgba8 => writer
+            .write_all(&rgba8(color, data, width as usize * height as usize))
+            .map_err(|err| err.to_string()),
+    }
+}
+
+/// Decodes the texture resource `resource` to `writer`, for embedding the
+/// conversion in servers and for `--stdin`. CI textures need the bytes of
+/// their TLUT resource in `tlut`. Returns the width and height of the image.
+pub fn decode_to_writer(
+    resource: &[u8],
+    tlut: Option<&[u8]>,
+    writer: &mut impl Write,
+    format: ImageOutputFormat,
+    options: &DecodeOptions,
+) -> Result<(u32, u32), String> {
+    let texture = parse_texture(resource).map_err(|err| err.to_string())?;
+    if texture.type_id.to_image_type().is_none() {
+        return Err(DecodeError::Unsupported(texture.type_id).to_string());
+    }
+    let tlut = tlut
+        .map(parse_texture)
+        .transpose()
+        .map_err(|err| format!("TLUT: {}", err))?
+        .map(Arc::new);
+    let decoded = decode::decode_entry("resource", resource, options, &SingleTlut(tlut))
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| DecodeError::Unsupported(texture.type_id).to_string())?;
+    write_image(
+        writer,
+        &decoded.data,
+        decoded.width,
+        decoded.height,
+        decoded.format,
+        format,
+    )?;
+    Ok((decoded.width, decoded.height))
+}
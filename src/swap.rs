@@ -0,0 +1,91 @@
+use std::str::FromStr;
+
+/// Byte order of a texture payload as stored in the archive.
+///
+/// Some PC ports write texel data pre-swapped the way it sits in emulated
+/// RDRAM, so it has to be swapped back before it can be decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ByteSwap {
+    #[default]
+    None,
+    Swap16,
+    Swap32,
+    Auto,
+}
+
+impl ByteSwap {
+    /// Swaps tried, in order, when detecting the byte order automatically.
+    pub const CANDIDATES: [ByteSwap; 3] = [ByteSwap::None, ByteSwap::Swap16, ByteSwap::Swap32];
+
+    pub fn apply(&self, data: &[u8]) -> Vec<u8> {
+        let mut data = data.to_vec();
+        let word_size = match self {
+            ByteSwap::None | ByteSwap::Auto => return data,
+            ByteSwap::Swap16 => 2,
+            ByteSwap::Swap32 => 4,
+        };
+        // A trailing partial word is left as is
+        for word in data.chunks_exact_mut(word_size) {
+            word.reverse();
+        }
+        data
+    }
+}
+
+impl FromStr for ByteSwap {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(ByteSwap::None),
+            "16" => Ok(ByteSwap::Swap16),
+            "32" => Ok(ByteSwap::Swap32),
+            "auto" => Ok(ByteSwap::Auto),
+            _ => Err(format!(
+                "Unknown swap mode '{}', expected none, 16, 32 or auto",
+                value
+            )),
+        }
+    }
+}
+
+/// Shannon entropy (in bits) of the differences between the pixels of a
+/// decoded image and their neighbors `dx` pixels left and `dy` rows up.
+///
+/// Wrongly swapped data scrambles horizontal neighbors and interleaved rows
+/// don't line up with the ones above, so the layout giving the lowest value
+/// is the most likely one.
+pub fn neighbor_entropy(
+    pixels: &[u8],
+    width: usize,
+    channels: usize,
+    (dx, dy): (usize, usize),
+) -> f64 {
+    let stride = width * channels;
+    if stride == 0 {
+        return 0.0;
+    }
+
+    let mut histogram = [0u64; 256];
+    let mut total = 0u64;
+    let rows = pixels.chunks_exact(stride).collect::<Vec<_>>();
+    for (y, row) in rows.iter().enumerate().skip(dy) {
+        let shifted = row.get(dx * channels..).unwrap_or_default();
+        for (current, neighbor) in shifted.iter().zip(rows[y - dy]) {
+            histogram[current.wrapping_sub(*neighbor) as usize] += 1;
+            total += 1;
+        }
+    }
+    if total == 0 {
+        return 0.0;
+    }
+
+    histogram
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let probability = *count as f64 / total as f64;
+            -probability * probability.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,186 @@
+use std::collections::{HashMap, HashSet};
+
+use walkdir::WalkDir;
+use yaml_rust2::Yaml;
+
+use crate::{config::Config, yaml_dialect::YamlDialect};
+
+/// Where a YAML asset definition puts its symbol in the archive.
+struct Definition {
+    /// YAML file path relative to the config path, without extension.
+    file: String,
+    symbol: String,
+    offset: Option<u32>,
+}
+
+/// Resolves C symbol names to archive paths through the YAML asset
+/// definitions, and optionally a decomp symbol file for names the YAML
+/// only knows by offset.
+pub struct SymbolResolver {
+    definitions: Vec<Definition>,
+    addresses: HashMap<String, u32>,
+}
+
+impl SymbolResolver {
+    pub fn new(config: &Config, dialect: YamlDialect, symbol_file: Option<&str>) -> Self {
+        let root = std::path::Path::new(&config.path);
+        let mut definitions = Vec::new();
+        for file in WalkDir::new(root)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .filter(|file| file.file_type().is_file())
+        {
+            let path = file.path();
+            if !matches!(
+                path.extension().and_then(|extension| extension.to_str()),
+                Some("yml" | "yaml")
+            ) {
+                continue;
+            }
+            let Some(documents) = std::fs::read_to_string(path)
+                .ok()
+                .and_then(|text| yaml_rust2::YamlLoader::load_from_str(&text).ok())
+            else {
+                continue;
+            };
+            let file = path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .with_extension("")
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            for (key, value) in documents
+                .into_iter()
+                .flat_map(|document| dialect.definitions(document))
+            {
+                let Some(object) = value.as_hash() else {
+                    continue;
+                };
+                let symbol = object
+                    .get(&Yaml::String("symbol".to_owned()))
+                    .and_then(Yaml::as_str)
+                    .unwrap_or(&key);
+                let offset = object
+                    .get(&Yaml::String("offset".to_owned()))
+                    .and_then(Yaml::as_i64)
+                    .map(|offset| offset as u32);
+                definitions.push(Definition {
+                    file: file.clone(),
+                    symbol: symbol.to_owned(),
+                    offset,
+                });
+            }
+        }
+
+        let addresses =
+            symbol_file
+                .map(|path| {
+                    parse_symbol_file(&std::fs::read_to_string(path).unwrap_or_else(|err| {
+                        panic!("Failed to read symbol file {}: {}", path, err)
+                    }))
+                })
+                .unwrap_or_default();
+
+        SymbolResolver {
+            definitions,
+            addresses,
+        }
+    }
+
+    /// Archive path of the asset defined at `offset` in the YAML file matching
+    /// the archive directory `directory`.
+    pub fn path_at(&self, directory: &str, offset: u32) -> Option<String> {
+        self.definitions
+            .iter()
+            .find(|definition| definition.file == directory && definition.offset == Some(offset))
+            .map(|definition| format!("{}/{}", definition.file, definition.symbol))
+    }
+
+    /// Archive path of `symbol`. The YAML definition naming it wins; failing
+    /// that the symbol file gives its offset to look the definition up by.
+    /// Paths not in `file_names` fall back to the entry named after the
+    /// symbol, which must then be unique.
+    pub fn resolve(&self, symbol: &str, file_names: &HashSet<&str>) -> Result<String, String> {
+        let mut candidates = self
+            .definitions
+            .iter()
+            .filter(|definition| definition.symbol == symbol)
+            .collect::<Vec<_>>();
+        if candidates.is_empty()
+            && let Some(address) = self.addresses.get(symbol)
+        {
+            // Definitions give offsets within the segment
+            let offset = address & 0x00FFFFFF;
+            candidates = self
+                .definitions
+                .iter()
+                .filter(|definition| definition.offset == Some(offset))
+                .collect();
+        }
+
+        let mut paths = candidates
+            .iter()
+            .map(|definition| format!("{}/{}", definition.file, definition.symbol))
+            .filter(|path| file_names.contains(path.as_str()))
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            let names = candidates
+                .iter()
+                .map(|definition| definition.symbol.as_str())
+                .chain([symbol])
+                .collect::<HashSet<_>>();
+            paths = file_names
+                .iter()
+                .filter(|path| names.contains(path.rsplit('/').next().unwrap()))
+                .map(|path| path.to_string())
+                .collect();
+        }
+
+        paths.sort();
+        paths.dedup();
+        match paths.len() {
+            0 => Err(format!("Symbol {} not found in the archive", symbol)),
+            1 => Ok(paths.remove(0)),
+            _ => Err(format!(
+                "Symbol {} is ambiguous: {}",
+                symbol,
+                paths.join(", ")
+            )),
+        }
+    }
+}
+
+/// Reads symbol addresses from either `name = 0xADDRESS;` lines, as in linker
+/// scripts and splat symbol files, or `ADDRESS name` lines, as in map files
+/// and nm output.
+fn parse_symbol_file(text: &str) -> HashMap<String, u32> {
+    let parse_address = |text: &str| {
+        let text = text.trim().trim_end_matches(';');
+        let digits = text
+            .strip_prefix("0x")
+            .or_else(|| text.strip_prefix("0X"))
+            .unwrap_or(text);
+        u32::from_str_radix(digits, 16).ok()
+    };
+
+    let mut addresses = HashMap::new();
+    for line in text.lines() {
+        let line = line.split("//").next().unwrap().trim();
+        if let Some((name, rest)) = line.split_once('=') {
+            // Splat puts options after the address, `name = 0x1234; // size:0x10`
+            if let Some(address) = parse_address(rest.split(';').next().unwrap()) {
+                addresses.insert(name.trim().to_owned(), address);
+            }
+        } else {
+            let mut words = line.split_whitespace();
+            if let (Some(address), Some(name)) = (words.next(), words.next_back())
+                && let Some(address) = parse_address(address)
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                addresses.insert(name.to_owned(), address);
+            }
+        }
+    }
+    addresses
+}
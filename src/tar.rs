@@ -0,0 +1,105 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+/// Size of a tar block, headers and padded file data alike.
+const BLOCK_SIZE: usize = 512;
+/// Longest name the `name` field of a ustar header holds.
+const NAME_SIZE: usize = 100;
+/// Name of the GNU entries carrying the path of the next entry when it
+/// doesn't fit the header.
+const LONG_NAME: &str = "././@LongLink";
+
+/// Writes files as a ustar archive, for `--output -`.
+pub struct TarWriter<W: Write> {
+    writer: W,
+    /// Modification time of every file, in seconds since the epoch.
+    mtime: u64,
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(writer: W, mtime: u64) -> Self {
+        TarWriter { writer, mtime }
+    }
+
+    /// Appends the file `path` holding `data`. Paths too long for the header
+    /// get a GNU long name entry first, which `tar` and `bsdtar` both read.
+    pub fn append(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
+        if path.len() > NAME_SIZE {
+            let mut name = path.as_bytes().to_vec();
+            name.push(0);
+            self.entry(LONG_NAME, b'L', &name)?;
+        }
+        self.entry(path, b'0', data)
+    }
+
+    /// Ends the archive with its two zero blocks and flushes it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&[0; BLOCK_SIZE * 2])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn entry(&mut self, path: &str, kind: u8, data: &[u8]) -> io::Result<()> {
+        let mut header = [0u8; BLOCK_SIZE];
+        let name = &path.as_bytes()[..path.len().min(NAME_SIZE)];
+        header[..name.len()].copy_from_slice(name);
+        let mut field = |offset: usize, size: usize, value: u64| {
+            let octal = format!("{:0width$o}", value, width = size - 1);
+            header[offset..offset + size - 1].copy_from_slice(octal.as_bytes());
+        };
+        field(100, 8, 0o644);
+        field(108, 8, 0);
+        field(116, 8, 0);
+        field(124, 12, data.len() as u64);
+        field(136, 12, self.mtime);
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        // Summed with the checksum field as spaces
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|byte| *byte as u32).sum::<u32>();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(data)?;
+        let padding = data.len().next_multiple_of(BLOCK_SIZE) - data.len();
+        self.writer.write_all(&vec![0; padding])
+    }
+}
+
+/// Takes stdout over for the tar stream, pointing file descriptor 1 at
+/// stderr so the messages printed while converting can't corrupt it.
+#[cfg(unix)]
+pub fn take_stdout() -> io::Result<File> {
+    use std::os::fd::FromRawFd;
+
+    mod sys {
+        use std::ffi::c_int;
+
+        unsafe extern "C" {
+            pub fn dup(fd: c_int) -> c_int;
+            pub fn dup2(fd: c_int, target: c_int) -> c_int;
+        }
+    }
+
+    io::stdout().flush()?;
+    // SAFETY: dup and dup2 only take file descriptors, the duplicate is owned
+    // by the returned file alone
+    unsafe {
+        let fd = sys::dup(1);
+        if fd < 0 || sys::dup2(2, 1) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from_raw_fd(fd))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn take_stdout() -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "streaming a tar to stdout needs a Unix build",
+    ))
+}
@@ -0,0 +1,200 @@
+use std::str::FromStr;
+
+use crate::{
+    Converter, EntryResult, OTR_HEADER_SIZE, ResourceType, decoder::ResourceDecoder, json::Json,
+    log, reader::Reader,
+};
+
+/// File format text resources are exported to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextFormat {
+    Json,
+    Csv,
+}
+
+impl TextFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TextFormat::Json => "json",
+            TextFormat::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for TextFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(TextFormat::Json),
+            "csv" => Ok(TextFormat::Csv),
+            _ => Err(format!(
+                "Unknown text format '{}', expected json or csv",
+                value
+            )),
+        }
+    }
+}
+
+/// A message of a text resource.
+pub struct Message {
+    pub id: u16,
+    pub textbox_type: u8,
+    pub textbox_y_pos: u8,
+    /// Message content with control codes replaced by `<TAG>` markers.
+    pub content: String,
+}
+
+/// Parses a text resource: a message count followed by, for each message, its
+/// id, textbox type and position, and length-prefixed content.
+pub fn parse(data: &[u8]) -> Result<Vec<Message>, String> {
+    let mut reader = Reader::new(data, OTR_HEADER_SIZE);
+    let count = reader.u32()?;
+    let mut messages = Vec::new();
+    for _ in 0..count {
+        let id = reader.u16()?;
+        let textbox_type = reader.u8()?;
+        let textbox_y_pos = reader.u8()?;
+        let length = reader.u32()? as usize;
+        let content = decode_content(reader.bytes(length)?);
+        messages.push(Message {
+            id,
+            textbox_type,
+            textbox_y_pos,
+            content,
+        });
+    }
+    Ok(messages)
+}
+
+pub fn export(messages: &[Message], format: TextFormat) -> String {
+    match format {
+        TextFormat::Json => {
+            Json::Array(
+                messages
+                    .iter()
+                    .map(|message| {
+                        Json::object()
+                            .with("id", format!("0x{:04X}", message.id))
+                            .with("textbox_type", message.textbox_type as u32)
+                            .with("textbox_y_pos", message.textbox_y_pos as u32)
+                            .with("content", message.content.as_str())
+                    })
+                    .collect(),
+            )
+            .pretty()
+                + "\n"
+        }
+        TextFormat::Csv => {
+            let mut csv = String::from("id,textbox_type,textbox_y_pos,content\n");
+            for message in messages {
+                csv += &format!(
+                    "0x{:04X},{},{},\"{}\"\n",
+                    message.id,
+                    message.textbox_type,
+                    message.textbox_y_pos,
+                    message.content.replace('"', "\"\"")
+                );
+            }
+            csv
+        }
+    }
+}
+
+/// Replaces the control codes of the game's message format with readable
+/// tags, keeping their arguments as hexadecimal.
+fn decode_content(bytes: &[u8]) -> String {
+    let mut content = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = bytes[i];
+        i += 1;
+        let (tag, arguments) = match code {
+            0x01 => {
+                content.push('\n');
+                continue;
+            }
+            0x02 => ("END", 0),
+            0x04 => ("BOX_BREAK", 0),
+            0x05 => ("COLOR", 1),
+            0x06 => ("SHIFT", 1),
+            0x07 => ("TEXTID", 2),
+            0x08 => ("QUICKTEXT_ENABLE", 0),
+            0x09 => ("QUICKTEXT_DISABLE", 0),
+            0x0A => ("PERSISTENT", 0),
+            0x0B => ("EVENT", 0),
+            0x0C => ("BOX_BREAK_DELAYED", 1),
+            0x0E => ("FADE", 1),
+            0x0F => ("NAME", 0),
+            0x10 => ("OCARINA", 0),
+            0x11 => ("FADE2", 2),
+            0x12 => ("SFX", 2),
+            0x13 => ("ITEM_ICON", 1),
+            0x14 => ("TEXT_SPEED", 1),
+            0x15 => ("BACKGROUND", 3),
+            0x16 => ("MARATHON_TIME", 0),
+            0x17 => ("RACE_TIME", 0),
+            0x18 => ("POINTS", 0),
+            0x19 => ("TOKENS", 0),
+            0x1A => ("UNSKIPPABLE", 0),
+            0x1B => ("TWO_CHOICE", 0),
+            0x1C => ("THREE_CHOICE", 0),
+            0x1D => ("FISH_INFO", 0),
+            0x1E => ("HIGHSCORE", 1),
+            0x1F => ("TIME", 0),
+            0x20..=0x7E => {
+                content.push(code as char);
+                continue;
+            }
+            _ => {
+                content += &format!("<0x{:02X}>", code);
+                continue;
+            }
+        };
+        content += "<";
+        content += tag;
+        for argument in bytes.iter().skip(i).take(arguments) {
+            content += &format!(":{:02X}", argument);
+        }
+        content += ">";
+        i += arguments;
+    }
+    content
+}
+
+/// Exports text resources to the format picked with `--text-format`.
+pub struct TextDecoder;
+
+impl ResourceDecoder for TextDecoder {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn directory(&self) -> &'static str {
+        "text"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[(ResourceType::Text, 0)]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let messages = match parse(data) {
+            Ok(messages) => messages,
+            Err(err) => {
+                log::error(format!("Failed to parse text resource {}: {}", name, err));
+                return;
+            }
+        };
+
+        let format = converter.options.text_format;
+        let path = format!(
+            "{}.{}",
+            converter.output_base(self, name),
+            format.extension()
+        );
+        log::progress(format!("Exporting {} messages: {}", messages.len(), path));
+        converter.write(&path, export(&messages, format));
+    }
+}
@@ -0,0 +1,315 @@
+use std::str::FromStr;
+
+use crate::{
+    Converter, DecodeError, EntryResult, ResourceType, TEXTURE_STRIDE_VERSION,
+    alpha::AlphaStats,
+    classify, crc64, decode_entry,
+    decoder::ResourceDecoder,
+    derive, dilate, emit_c,
+    engine_meta::{self, TextureSettings},
+    interleave::Deinterleave,
+    log,
+    manifest::ManifestEntry,
+    profile::Stage,
+    stream::{self, ImageOutputFormat},
+    swap::ByteSwap,
+    thumbnail,
+};
+
+/// Image format textures are exported to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    /// OpenEXR with linear-light float channels, for HDR tooling.
+    #[cfg(feature = "exr")]
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            #[cfg(feature = "exr")]
+            ImageFormat::Exr => "exr",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "png" => Ok(ImageFormat::Png),
+            #[cfg(feature = "exr")]
+            "exr" => Ok(ImageFormat::Exr),
+            #[cfg(not(feature = "exr"))]
+            "exr" => Err("EXR output needs the exr feature".to_owned()),
+            _ => Err(format!(
+                "Unknown image format '{}', expected png or exr",
+                value
+            )),
+        }
+    }
+}
+
+/// sRGB encoded channel value to linear light.
+pub fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Expands decoded texels to linear RGBA floats. Alpha is already linear and
+/// only gets rescaled.
+#[cfg(feature = "exr")]
+fn linear_rgba(format: image::ExtendedColorType, data: &[u8], pixels: usize) -> Vec<f32> {
+    stream::rgba8(format, data, pixels)
+        .chunks_exact(4)
+        .flat_map(|rgba| {
+            [
+                srgb_to_linear(rgba[0]),
+                srgb_to_linear(rgba[1]),
+                srgb_to_linear(rgba[2]),
+                rgba[3] as f32 / 255.0,
+            ]
+        })
+        .collect()
+}
+
+/// Encodes decoded texels of type `color` to `image_format`. Texels that
+/// don't match the size of the image are an error rather than a panic, so
+/// one bad entry doesn't stop a conversion.
+pub fn encode_image(
+    image_format: ImageFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    color: image::ExtendedColorType,
+) -> Result<Vec<u8>, String> {
+    let mut encoded = Vec::new();
+    match image_format {
+        ImageFormat::Png => stream::write_image(
+            &mut encoded,
+            data,
+            width,
+            height,
+            color,
+            ImageOutputFormat::Png,
+        )?,
+        #[cfg(feature = "exr")]
+        ImageFormat::Exr => {
+            stream::check_buffer(data, width, height, color)?;
+            let pixels = width as usize * height as usize;
+            image::Rgba32FImage::from_raw(width, height, linear_rgba(color, data, pixels))
+                .ok_or("Texture data doesn't match its size")?
+                .write_to(
+                    &mut std::io::Cursor::new(&mut encoded),
+                    image::ImageFormat::OpenExr,
+                )
+                .map_err(|err| err.to_string())?;
+        }
+    }
+    Ok(encoded)
+}
+
+/// Converts textures to PNG, or EXR with `--image-format exr`.
+pub struct TextureDecoder;
+
+impl ResourceDecoder for TextureDecoder {
+    fn name(&self) -> &'static str {
+        "texture"
+    }
+
+    fn directory(&self) -> &'static str {
+        "textures"
+    }
+
+    fn handles(&self) -> &'static [(ResourceType, u32)] {
+        &[
+            (ResourceType::Texture, 0),
+            (ResourceType::Texture, TEXTURE_STRIDE_VERSION),
+        ]
+    }
+
+    fn decode(&self, converter: &Converter, data: &[u8], result: &mut EntryResult) {
+        let name = &result.name;
+        let options = converter.options;
+
+        let mut texture = match decode_entry(
+            name,
+            data,
+            &options.decode_options(),
+            converter.tluts,
+            converter.pitches,
+            converter.i4_as_ia4(name),
+        ) {
+            Ok(Some(texture)) => texture,
+            Ok(None) => {
+                emit_c::write_tlut(converter, &converter.output_base(self, name), name, data);
+                return;
+            }
+            Err(err @ DecodeError::MissingTlut(_)) => {
+                log::skip(err);
+                return;
+            }
+            Err(err) => {
+                log::error(err);
+                return;
+            }
+        };
+
+        // Filler textures are left out before anything is reported about them
+        if options.skip_placeholders
+            && converter.config.placeholders.matches(
+                name,
+                texture.width,
+                texture.height,
+                &texture.data,
+            )
+        {
+            log::skip(format!("Skipping placeholder texture {}", name));
+            result.placeholder = true;
+            return;
+        }
+
+        if texture.ia4_suspect {
+            log::error(format!(
+                "Texture {} is tagged I4 but its texels look like IA4, see --treat-i4-as-ia4",
+                name
+            ));
+        }
+
+        if let Some(overflow) = &texture.tmem_overflow {
+            log::error(format!("Texture {} can't fit in TMEM: {}", name, overflow));
+        }
+
+        if let Some(mismatch) = &texture.palette_mismatch {
+            log::error(format!("Suspicious TLUT for {}: {}", name, mismatch));
+        }
+        result.palette_usage = texture.palette_usage.take();
+        if let Some(overflow) = texture.palette_overflow.take() {
+            log::error(format!(
+                "Texture {} uses palette index {} but its TLUT only has {} entries",
+                name, overflow.max_index, overflow.entries
+            ));
+            result.palette_overflow = Some(overflow);
+            if options.strict {
+                return;
+            }
+        }
+
+        let output = converter.output_name(self, name) + "." + options.image_format.extension();
+        let path = converter.folder_name.to_owned() + "/" + &output;
+
+        log::progress(format!("Processing texture: {}", path));
+        log::progress(format!("Converting {:?} texture", texture.type_id));
+        if options.swap == ByteSwap::Auto && texture.swap != ByteSwap::None {
+            log::progress(format!(
+                "Detected {:?} byte order for {}",
+                texture.swap, name
+            ));
+        }
+        if options.deinterleave == Deinterleave::Auto && texture.deinterleaved {
+            log::progress(format!("Detected TMEM interleaved rows for {}", name));
+        }
+
+        if let Some(radius) = options.dilate_alpha {
+            dilate::dilate(
+                &mut texture.data,
+                texture.format,
+                texture.width,
+                texture.height,
+                radius,
+            );
+        }
+        let encoded = result.timings.time(Stage::Encode, || {
+            encode_image(
+                options.image_format,
+                &texture.data,
+                texture.width,
+                texture.height,
+                texture.format,
+            )
+        });
+        let encoded = match encoded {
+            Ok(encoded) => encoded,
+            Err(err) => {
+                log::error(format!("Failed to encode {}: {}", name, err));
+                result.encode_failure = Some(err);
+                return;
+            }
+        };
+        result
+            .timings
+            .time(Stage::Write, || converter.write(&path, encoded));
+        thumbnail::write(
+            converter,
+            &output,
+            texture.format,
+            &texture.data,
+            texture.width,
+            texture.height,
+        );
+        derive::write(
+            converter,
+            &output,
+            texture.format,
+            &texture.data,
+            texture.width,
+            texture.height,
+        );
+
+        emit_c::write_texture(
+            converter,
+            &converter.output_base(self, name),
+            name,
+            data,
+            &texture,
+        );
+
+        if let Some(engine) = options.engine_meta {
+            let sampling = converter.sampling(name).unwrap_or_default();
+            let settings = TextureSettings::new(texture.type_id.is_grayscale(), sampling);
+            let (sidecar_path, sidecar) = engine_meta::sidecar(engine, &output, &settings);
+            converter.write(
+                &format!("{}/{}", converter.folder_name, sidecar_path),
+                sidecar,
+            );
+        }
+
+        // Only looked up when matching against a pack, the hash alone is
+        // of no use in the manifest
+        let pack_hash = converter
+            .hash_db
+            .and(texture.pack_hashes)
+            .map(|pack_hashes| pack_hashes.rice);
+        let pack_name = converter
+            .hash_db
+            .zip(pack_hash.as_ref())
+            .and_then(|(hash_db, pack_hash)| hash_db.name(pack_hash).map(str::to_owned));
+        result.converted = Some(ManifestEntry {
+            entry: name.to_owned(),
+            output,
+            format: format!("{:?}", texture.type_id),
+            width: texture.width,
+            height: texture.height,
+            hash: Some(crc64::checksum(&texture.data)),
+            alpha: Some(AlphaStats::new(
+                texture.format,
+                &texture.data,
+                texture.width as usize * texture.height as usize,
+            )),
+            pack_hash: pack_hash.map(|pack_hash| pack_hash.key()),
+            pack_name,
+            category: options.classify.then(|| {
+                classify::classify(name, &texture.type_id, texture.width, texture.height)
+                    .name()
+                    .to_owned()
+            }),
+        });
+    }
+}
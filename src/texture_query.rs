@@ -0,0 +1,236 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{Read, Seek},
+    sync::Arc,
+};
+
+use crate::{
+    DecodeOptions, OTR_HEADER_SIZE, OTRHeader, ResourceType, TEXTURE_STRIDE_VERSION, TextureFormat,
+    TextureType,
+    decode::{self, DecodedTexture, TextureDefinitions},
+    json::Json,
+    payload::{self, PayloadTransform},
+};
+
+/// Byte budget of the decoded texture cache when `--cache-budget` isn't
+/// given.
+pub const DEFAULT_CACHE_BUDGET: u64 = 64 * 1024 * 1024;
+
+/// Header fields of an archive entry, read without decoding it.
+pub struct EntryMetadata {
+    pub name: String,
+    pub resource_type: String,
+    pub version: u32,
+    pub id: u64,
+    /// Format, width and height of a texture.
+    pub texture: Option<(String, u32, u32)>,
+    /// Colors of a TLUT, inferred from its payload size.
+    pub colors: Option<usize>,
+}
+
+impl EntryMetadata {
+    pub fn to_json(&self) -> Json {
+        let mut entry = Json::object()
+            .with("path", self.name.as_str())
+            .with("type", self.resource_type.as_str());
+        if let Some((format, width, height)) = &self.texture {
+            entry.insert("format", format.as_str());
+            entry.insert("width", *width);
+            entry.insert("height", *height);
+        }
+        if let Some(colors) = self.colors {
+            entry.insert("colors", colors);
+        }
+        entry
+    }
+}
+
+struct CachedTexture {
+    texture: Arc<DecodedTexture>,
+    last_used: u64,
+}
+
+/// Decoded textures kept until their texels take more than `budget` bytes,
+/// dropping the least recently used first.
+struct TextureCache {
+    budget: u64,
+    size: u64,
+    clock: u64,
+    textures: HashMap<String, CachedTexture>,
+}
+
+impl TextureCache {
+    fn get(&mut self, name: &str) -> Option<Arc<DecodedTexture>> {
+        self.clock += 1;
+        let cached = self.textures.get_mut(name)?;
+        cached.last_used = self.clock;
+        Some(cached.texture.clone())
+    }
+
+    fn insert(&mut self, name: &str, texture: Arc<DecodedTexture>) {
+        let size = texture.data.len() as u64;
+        // Caching a texture over the whole budget would only empty the cache
+        if size > self.budget {
+            return;
+        }
+        while self.size + size > self.budget {
+            let Some(oldest) = self
+                .textures
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(name, _)| name.clone())
+            else {
+                break;
+            };
+            let evicted = self.textures.remove(&oldest).unwrap();
+            self.size -= evicted.texture.data.len() as u64;
+        }
+        self.size += size;
+        self.clock += 1;
+        let cached = CachedTexture {
+            texture,
+            last_used: self.clock,
+        };
+        if let Some(replaced) = self.textures.insert(name.to_owned(), cached) {
+            self.size -= replaced.texture.data.len() as u64;
+        }
+    }
+}
+
+/// Texture lookups over an archive for viewers, which ask for the same
+/// textures again as they scroll. Textures are only decoded when first asked
+/// for and then cached, while metadata comes from the entry headers alone.
+pub struct TextureQuery<R, D> {
+    zip: zip::ZipArchive<R>,
+    definitions: D,
+    decode_options: DecodeOptions,
+    payload: PayloadTransform,
+    cache: TextureCache,
+}
+
+impl<R: Read + Seek, D: TextureDefinitions> TextureQuery<R, D> {
+    /// Queries over the archive `zip`, whose textures are decoded with
+    /// `decode_options` and what `definitions` say about them. Entries are
+    /// unwrapped with `payload`, and decoded textures kept up to
+    /// `cache_budget` bytes.
+    pub fn new(
+        zip: zip::ZipArchive<R>,
+        definitions: D,
+        decode_options: DecodeOptions,
+        payload: PayloadTransform,
+        cache_budget: u64,
+    ) -> Self {
+        TextureQuery {
+            zip,
+            definitions,
+            decode_options,
+            payload,
+            cache: TextureCache {
+                budget: cache_budget,
+                size: 0,
+                clock: 0,
+                textures: HashMap::new(),
+            },
+        }
+    }
+
+    /// The resource of the entry `name`, its payload unwrapped.
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let mut file = self
+            .zip
+            .by_name(name)
+            .map_err(|err| format!("Failed to read {}: {}", name, err))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)
+            .map_err(|err| format!("Failed to read {}: {}", name, err))?;
+        let resource = self
+            .payload
+            .unwrap(&data)
+            .map_err(|err| format!("Failed to unwrap the payload of {}: {}", name, err))?;
+        Ok(match resource {
+            Cow::Owned(resource) => resource,
+            Cow::Borrowed(_) => data,
+        })
+    }
+
+    /// Header fields of the entry `name`, reading only its first bytes.
+    pub fn metadata(&mut self, name: &str) -> EntryMetadata {
+        let mut header = Vec::new();
+        let mut entry_size = 0;
+        if let Ok(file) = self.zip.by_name(name) {
+            entry_size = file.size() as usize;
+            // Version 2 textures have a stride before the size
+            let _ = file
+                .take(OTR_HEADER_SIZE as u64 + 20)
+                .read_to_end(&mut header);
+        }
+        // Wrapped payloads have to be unwrapped whole
+        if !payload::is_resource(&header) {
+            header = self.read(name).unwrap_or_default();
+            entry_size = header.len();
+        }
+        let otr_format = match OTRHeader::parse(&header) {
+            Ok(otr_format) if header.len() >= OTR_HEADER_SIZE => otr_format,
+            _ => {
+                return EntryMetadata {
+                    name: name.to_owned(),
+                    resource_type: "Unknown".to_owned(),
+                    version: 0,
+                    id: 0,
+                    texture: None,
+                    colors: None,
+                };
+            }
+        };
+        let fields_size = match otr_format.version {
+            TEXTURE_STRIDE_VERSION => 20,
+            _ => 16,
+        };
+        let texture_format = (otr_format.type_id == ResourceType::Texture
+            && header.len() >= OTR_HEADER_SIZE + fields_size)
+            // Without the texels, which may be compressed
+            .then(|| TextureFormat::parse(&header[..OTR_HEADER_SIZE + fields_size]).ok())
+            .flatten();
+        // Counted like `palette::entry_count`, without reading the colors
+        let colors = texture_format
+            .as_ref()
+            .filter(|texture_format| texture_format.type_id == TextureType::TLUT)
+            .map(|texture_format| {
+                let payload = entry_size.saturating_sub(OTR_HEADER_SIZE + fields_size);
+                let size = match texture_format.size as usize {
+                    0 => payload,
+                    size => size.min(payload),
+                };
+                size / 2
+            });
+        EntryMetadata {
+            name: name.to_owned(),
+            resource_type: format!("{:?}", otr_format.type_id),
+            version: otr_format.version,
+            id: otr_format.id,
+            texture: texture_format.map(|texture_format| {
+                (
+                    format!("{:?}", texture_format.type_id),
+                    texture_format.width,
+                    texture_format.height,
+                )
+            }),
+            colors,
+        }
+    }
+
+    /// The texture `name` decoded, from the cache when it was asked for
+    /// recently.
+    pub fn decode(&mut self, name: &str) -> Result<Arc<DecodedTexture>, String> {
+        if let Some(texture) = self.cache.get(name) {
+            return Ok(texture);
+        }
+        let data = self.read(name)?;
+        let texture = decode::decode_entry(name, &data, &self.decode_options, &self.definitions)?
+            .ok_or_else(|| format!("{} is not a texture", name))?;
+        let texture = Arc::new(texture);
+        self.cache.insert(name, texture.clone());
+        Ok(texture)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    Converter, log,
+    stream::{self, ImageOutputFormat},
+    texture::srgb_to_linear,
+};
+
+/// Folder of the output folder thumbnails go to, mirroring the texture paths.
+pub const THUMBNAILS_FOLDER: &str = "thumbs";
+
+/// Path of the thumbnail of the output `output`, relative to the output
+/// folder. Thumbnails are always PNG.
+pub fn path(output: &str) -> String {
+    let output = std::path::Path::new(output).with_extension("png");
+    format!("{}/{}", THUMBNAILS_FOLDER, output.to_string_lossy())
+}
+
+/// Linear-light channel value back to sRGB.
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Shrinks RGBA texels so neither side is over `max` pixels, averaging the
+/// area each thumbnail pixel covers in linear light with premultiplied
+/// alpha, so dark and transparent texels don't bleed into their neighbors.
+/// Images already small enough are returned as they are.
+pub fn downscale(rgba: &[u8], width: u32, height: u32, max: u32) -> (Vec<u8>, u32, u32) {
+    if width <= max && height <= max {
+        return (rgba.to_vec(), width, height);
+    }
+    let scale = max as f32 / width.max(height) as f32;
+    let thumb_width = ((width as f32 * scale).round() as u32).max(1);
+    let thumb_height = ((height as f32 * scale).round() as u32).max(1);
+    let (step_x, step_y) = (
+        width as f32 / thumb_width as f32,
+        height as f32 / thumb_height as f32,
+    );
+
+    let linear = rgba
+        .chunks_exact(4)
+        .map(|texel| {
+            let alpha = texel[3] as f32 / 255.0;
+            [
+                srgb_to_linear(texel[0]) * alpha,
+                srgb_to_linear(texel[1]) * alpha,
+                srgb_to_linear(texel[2]) * alpha,
+                alpha,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    // Overlap of the first `size` source texels with the span [start, end)
+    let coverage = |start: f32, end: f32, size: u32| {
+        (start.floor() as u32..(end.ceil() as u32).min(size))
+            .map(move |i| (i, (end.min(i as f32 + 1.0) - start.max(i as f32)).max(0.0)))
+    };
+
+    let mut thumbnail = Vec::with_capacity((thumb_width * thumb_height * 4) as usize);
+    for y in 0..thumb_height {
+        for x in 0..thumb_width {
+            let mut sum = [0.0f32; 4];
+            let mut total = 0.0;
+            for (source_y, weight_y) in coverage(y as f32 * step_y, (y + 1) as f32 * step_y, height)
+            {
+                for (source_x, weight_x) in
+                    coverage(x as f32 * step_x, (x + 1) as f32 * step_x, width)
+                {
+                    let texel = &linear[source_y as usize * width as usize + source_x as usize];
+                    let weight = weight_x * weight_y;
+                    for (sum, value) in sum.iter_mut().zip(texel) {
+                        *sum += value * weight;
+                    }
+                    total += weight;
+                }
+            }
+            let alpha = sum[3] / total;
+            let color = |value: f32| {
+                if sum[3] > 0.0 {
+                    linear_to_srgb(value / sum[3])
+                } else {
+                    0
+                }
+            };
+            thumbnail.extend([
+                color(sum[0]),
+                color(sum[1]),
+                color(sum[2]),
+                (alpha * 255.0).round() as u8,
+            ]);
+        }
+    }
+    (thumbnail, thumb_width, thumb_height)
+}
+
+/// Writes the thumbnail of the decoded texels of the output `output`.
+pub fn write(
+    converter: &Converter,
+    output: &str,
+    color: image::ExtendedColorType,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) {
+    let Some(max) = converter.options.thumbnails else {
+        return;
+    };
+    let rgba = stream::rgba8(color, data, width as usize * height as usize);
+    let (thumbnail, width, height) = downscale(&rgba, width, height, max);
+    let path = format!("{}/{}", converter.folder_name, path(output));
+    let mut png = Vec::new();
+    match stream::write_image(
+        &mut png,
+        &thumbnail,
+        width,
+        height,
+        image::ExtendedColorType::Rgba8,
+        ImageOutputFormat::Png,
+    ) {
+        Ok(()) => converter.write(&path, png),
+        Err(err) => log::error(format!("Failed to encode thumbnail {}: {}", path, err)),
+    }
+}
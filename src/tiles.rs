@@ -0,0 +1,172 @@
+use std::{collections::HashSet, fs::File};
+
+use crate::{
+    Converter,
+    alpha::AlphaStats,
+    classify, crc64, decode_entry, derive, dilate, log,
+    manifest::{ManifestEntry, TiledTexture},
+    read_entry, stream,
+    texture::{self, TextureDecoder},
+    thumbnail,
+};
+
+/// Tile entries of the tiled image `path`, `<path>_0`, `<path>_1`... up to
+/// the first one missing from the archive.
+pub fn tile_names(path: &str, file_names: &HashSet<&str>) -> Vec<String> {
+    (0..)
+        .map(|i| format!("{}_{}", path, i))
+        .take_while(|name| file_names.contains(name.as_str()))
+        .collect()
+}
+
+/// Decodes the `tiles` of the image `path` and writes them as one image
+/// `columns` tiles wide. Tiles must all have the same size; cells past the
+/// last tile are left transparent.
+pub fn stitch(
+    converter: &Converter,
+    zip: &mut zip::ZipArchive<File>,
+    path: &str,
+    columns: u32,
+    tiles: &[String],
+) -> Result<(ManifestEntry, TiledTexture), String> {
+    let options = converter.options;
+    let mut decoded = Vec::new();
+    for tile in tiles {
+        let data = read_entry(zip, tile, &options.payload)
+            .ok_or_else(|| format!("Failed to read {}", tile))?;
+        let texture = decode_entry(
+            tile,
+            &data,
+            &options.decode_options(),
+            converter.tluts,
+            converter.pitches,
+            converter.i4_as_ia4(tile),
+        )?
+        .ok_or_else(|| format!("Tile {} is not a texture", tile))?;
+        decoded.push(texture);
+    }
+    let Some(first) = decoded.first() else {
+        return Err(format!("No tiles found for {}", path));
+    };
+    let (tile_width, tile_height) = (first.width, first.height);
+    if let Some((tile, texture)) = tiles
+        .iter()
+        .zip(&decoded)
+        .find(|(_, texture)| (texture.width, texture.height) != (tile_width, tile_height))
+    {
+        return Err(format!(
+            "Tile {} is {}x{} but {} is {}x{}",
+            tile, texture.width, texture.height, tiles[0], tile_width, tile_height
+        ));
+    }
+
+    let rows = (tiles.len() as u32).div_ceil(columns);
+    let too_large = || {
+        format!(
+            "{} tiles of {}x{} are too large to stitch",
+            tiles.len(),
+            tile_width,
+            tile_height
+        )
+    };
+    let width = columns.checked_mul(tile_width).ok_or_else(too_large)?;
+    let height = rows.checked_mul(tile_height).ok_or_else(too_large)?;
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(too_large)?;
+    let row_size = tile_width as usize * 4;
+    let mut data = vec![0; size];
+    for (i, texture) in decoded.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        let pixels = stream::rgba8(
+            texture.format,
+            &texture.data,
+            tile_width as usize * tile_height as usize,
+        );
+        for (y, line) in pixels.chunks_exact(row_size).enumerate() {
+            let start = ((row * tile_height + y as u32) as usize * width as usize
+                + (column * tile_width) as usize)
+                * 4;
+            data[start..start + row_size].copy_from_slice(line);
+        }
+    }
+
+    if let Some(radius) = options.dilate_alpha {
+        dilate::dilate(
+            &mut data,
+            image::ExtendedColorType::Rgba8,
+            width,
+            height,
+            radius,
+        );
+    }
+
+    let output =
+        converter.output_name(&TextureDecoder, path) + "." + options.image_format.extension();
+    let file = converter.folder_name.to_owned() + "/" + &output;
+    log::progress(format!(
+        "Stitching {} tiles into {}x{} texture: {}",
+        tiles.len(),
+        width,
+        height,
+        file
+    ));
+    converter.write(
+        &file,
+        texture::encode_image(
+            options.image_format,
+            &data,
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+        )?,
+    );
+    thumbnail::write(
+        converter,
+        &output,
+        image::ExtendedColorType::Rgba8,
+        &data,
+        width,
+        height,
+    );
+    derive::write(
+        converter,
+        &output,
+        image::ExtendedColorType::Rgba8,
+        &data,
+        width,
+        height,
+    );
+
+    let entry = ManifestEntry {
+        entry: path.to_owned(),
+        output: output.clone(),
+        format: format!("{:?}", first.type_id),
+        width,
+        height,
+        hash: Some(crc64::checksum(&data)),
+        alpha: Some(AlphaStats::new(
+            image::ExtendedColorType::Rgba8,
+            &data,
+            width as usize * height as usize,
+        )),
+        pack_hash: None,
+        pack_name: None,
+        category: converter.options.classify.then(|| {
+            classify::classify(path, &first.type_id, width, height)
+                .name()
+                .to_owned()
+        }),
+    };
+    let layout = TiledTexture {
+        entry: path.to_owned(),
+        output,
+        columns,
+        rows,
+        tile_width,
+        tile_height,
+        tiles: tiles.to_vec(),
+    };
+    Ok((entry, layout))
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    OTRHeader, ResourceType, TextureFormat, TextureType, game::Game, options::Options,
+    palette::BANK_SIZE, payload::PayloadTransform, read_entry,
+};
+
+/// TLUT a CI texture reads its colors from, as given by its YAML definition.
+pub struct TextureTlut {
+    pub symbol: String,
+    /// 16-color bank of the TLUT a CI4 texture starts at, from the
+    /// `palette_index` key.
+    pub palette_index: u8,
+}
+
+/// Archive TLUTs are looked up in.
+struct Layer {
+    zip: Mutex<zip::ZipArchive<File>>,
+    file_names: Vec<String>,
+}
+
+/// TLUTs of the archive, read the first time a texture needs them and kept
+/// for the rest of the run. Loading on demand means a CI texture can be
+/// converted before its TLUT comes up in the archive, and TLUTs the YAML
+/// doesn't mention can be loaded by path.
+///
+/// A patch archive is layered over the base archives it was made against, so
+/// a texture it replaces can still use a TLUT only the base has. Layers are
+/// searched from the top, the converted archive, down.
+pub struct Tluts {
+    layers: Vec<Layer>,
+    payload: PayloadTransform,
+    /// Game whose TLUT scopes are searched first.
+    game: Game,
+    /// TLUT symbol of each CI texture, from the YAML definitions.
+    texture_tlut: HashMap<String, TextureTlut>,
+    /// TLUTs by archive path, `None` for entries that aren't textures.
+    cache: Mutex<HashMap<String, Option<Arc<TextureFormat>>>>,
+}
+
+impl Tluts {
+    /// TLUTs of the archive of `options` over its base archives, read as its
+    /// payload and game options say.
+    pub fn open(
+        options: &Options,
+        file_names: &[String],
+        texture_tlut: HashMap<String, TextureTlut>,
+    ) -> Self {
+        let zip =
+            zip::ZipArchive::new(File::open(&options.zip_file).expect("Failed to open zip file"))
+                .expect("Failed to read zip file");
+        let mut layers = vec![Layer {
+            zip: Mutex::new(zip),
+            file_names: file_names.to_vec(),
+        }];
+        for base in options.base_archives.iter().rev() {
+            let zip = File::open(base)
+                .map_err(zip::result::ZipError::from)
+                .and_then(zip::ZipArchive::new)
+                .unwrap_or_else(|err| panic!("Failed to read base archive {}: {}", base, err));
+            layers.push(Layer {
+                file_names: zip.file_names().map(str::to_owned).collect(),
+                zip: Mutex::new(zip),
+            });
+        }
+        Tluts {
+            layers,
+            payload: options.payload.clone(),
+            game: options.game,
+            texture_tlut,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of textures the YAML gives a TLUT for.
+    pub fn len(&self) -> usize {
+        self.texture_tlut.len()
+    }
+
+    /// TLUT the YAML gives the texture `file_name`.
+    pub fn texture_tlut(&self, file_name: &str) -> Option<&TextureTlut> {
+        self.texture_tlut.get(file_name)
+    }
+
+    /// TLUT symbol the YAML gives the texture `file_name`.
+    pub fn symbol(&self, file_name: &str) -> Option<&str> {
+        self.texture_tlut(file_name)
+            .map(|tlut| tlut.symbol.as_str())
+    }
+
+    /// TLUT symbol and colors of the CI texture `name` of type `type_id`,
+    /// for `TextureDefinitions`.
+    pub fn definition(
+        &self,
+        name: &str,
+        type_id: &TextureType,
+    ) -> Option<(&str, Arc<TextureFormat>)> {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        Some((self.symbol(file_name)?, self.for_texture(name, type_id)?))
+    }
+
+    /// Archive entry of the TLUT `symbol` of the texture `texture`, the entry
+    /// named after it or failing that the first one mentioning it. Entries in
+    /// the scope the game gives the texture come first, and a layer is only
+    /// searched when the ones above it have no match.
+    pub fn path(&self, symbol: &str, texture: &str) -> Option<&str> {
+        let scope = self.game.tlut_scope(texture);
+        let in_scope = |name: &str| {
+            scope.is_some_and(|scope| {
+                name.strip_prefix(scope)
+                    .is_some_and(|name| name.starts_with('/'))
+            })
+        };
+        let named = |name: &str| name.rsplit('/').next() == Some(symbol);
+        self.layers.iter().find_map(|layer| {
+            let find = |matches: &dyn Fn(&str) -> bool| {
+                layer
+                    .file_names
+                    .iter()
+                    .map(String::as_str)
+                    .find(|name| matches(name))
+            };
+            find(&|name| in_scope(name) && named(name))
+                .or_else(|| find(&|name| in_scope(name) && name.contains(symbol)))
+                .or_else(|| find(&named))
+                .or_else(|| find(&|name| name.contains(symbol)))
+        })
+    }
+
+    /// TLUT entry the colors `for_texture` gives the texture `name` start at,
+    /// the start of the bank of a CI4 texture with a `palette_index`.
+    pub fn start(&self, name: &str, type_id: &TextureType) -> usize {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        match self.texture_tlut.get(file_name) {
+            Some(texture_tlut) if *type_id == TextureType::Palette4bpp => {
+                texture_tlut.palette_index as usize * BANK_SIZE
+            }
+            _ => 0,
+        }
+    }
+
+    /// TLUT of the texture `name`, found by `path`. CI4 textures with a
+    /// `palette_index` get the 16 colors of that bank.
+    pub fn for_texture(&self, name: &str, type_id: &TextureType) -> Option<Arc<TextureFormat>> {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        let texture_tlut = self.texture_tlut.get(file_name)?;
+        let path = self.path(&texture_tlut.symbol, name)?;
+        let tlut = self.get(path)?;
+        if *type_id != TextureType::Palette4bpp || texture_tlut.palette_index == 0 {
+            return Some(tlut);
+        }
+
+        // A bank past the end leaves no colors, reported as a palette overflow
+        let start = texture_tlut.palette_index as usize * BANK_SIZE * 2;
+        let data = tlut
+            .data
+            .get(start..)
+            .map(|data| data[..data.len().min(BANK_SIZE * 2)].to_vec())
+            .unwrap_or_default();
+        Some(Arc::new(TextureFormat::new(
+            tlut.type_id.clone(),
+            (data.len() / 2) as u32,
+            1,
+            data.len() as u32,
+            data,
+        )))
+    }
+
+    /// TLUT stored in the archive entry `path` of the topmost layer having it.
+    pub fn get(&self, path: &str) -> Option<Arc<TextureFormat>> {
+        if let Some(tlut) = self.cache.lock().unwrap().get(path) {
+            return tlut.clone();
+        }
+        let layer = self
+            .layers
+            .iter()
+            .find(|layer| layer.file_names.iter().any(|name| name == path))?;
+        let tlut = read_entry(&mut layer.zip.lock().unwrap(), path, &self.payload)
+            .filter(|data| {
+                OTRHeader::parse(data).is_ok_and(|header| header.type_id == ResourceType::Texture)
+            })
+            .and_then(|data| TextureFormat::parse(&data).ok())
+            .map(Arc::new);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(path.to_owned(), tlut.clone());
+        tlut
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt::Write;
+
+use crate::{
+    TextureType,
+    display_list::{Command, Load, Reference},
+};
+
+/// Bytes of TMEM, the upper half holding the TLUTs of CI textures.
+const TMEM_SIZE: u32 = 4096;
+/// Bytes a TLUT entry takes in TMEM, where it is repeated over a 64-bit line.
+const TLUT_ENTRY_SIZE: u32 = 8;
+/// Bytes of TMEM per SVG unit.
+const BYTES_PER_UNIT: u32 = 4;
+/// Height of a load in the diagram, in SVG units.
+const ROW_HEIGHT: u32 = 20;
+/// Margin around the diagram, in SVG units.
+const SVG_MARGIN: u32 = 20;
+
+/// A copy of the texture image into TMEM by a display list.
+pub struct TmemLoad {
+    /// What the image is shown as, its archive entry when it resolves.
+    pub label: String,
+    /// Whether the image is loaded as a TLUT.
+    pub tlut: bool,
+    /// Bytes of TMEM written, from `start` up to `end`.
+    pub start: u32,
+    pub end: u32,
+    /// Set when the load overwrites part of a TLUT still in TMEM, or texels
+    /// over a TLUT, or runs past the end of TMEM.
+    pub conflict: bool,
+}
+
+/// Loads of the commands of a display list in command order, labeling the
+/// images with `label`. Display lists it calls aren't followed.
+pub fn loads(commands: &[Command], label: impl Fn(&Reference) -> String) -> Vec<TmemLoad> {
+    // Line size and TMEM address of the tile descriptors, in 64-bit words
+    let mut tiles = [(0u32, 0u32); 8];
+    let mut image = None;
+    let mut loads: Vec<TmemLoad> = Vec::new();
+    // Indices of the loads whose bytes are still in TMEM
+    let mut resident = Vec::new();
+    for command in commands {
+        match command {
+            Command::Texture {
+                image: reference,
+                format,
+            } => image = Some((reference, format)),
+            Command::Tile {
+                tile, line, tmem, ..
+            } => {
+                tiles[*tile as usize] = (*line as u32, *tmem as u32);
+            }
+            Command::Load { tile, load } => {
+                let Some((reference, format)) = image else {
+                    continue;
+                };
+                let (line, tmem) = tiles[*tile as usize];
+                let bits = format
+                    .as_ref()
+                    .and_then(TextureType::bits_per_pixel)
+                    .map_or(16, |bits| bits as u32);
+                let size = match *load {
+                    Load::Block { texels } => (texels as u32 * bits).div_ceil(64) * 8,
+                    Load::Tile { width, height } => match line {
+                        0 => (width as u32 * bits).div_ceil(64) * 8 * height as u32,
+                        line => line * 8 * height as u32,
+                    },
+                    Load::Tlut { count } => count as u32 * TLUT_ENTRY_SIZE,
+                };
+                let tlut = matches!(load, Load::Tlut { .. });
+                let start = tmem * 8;
+                let end = start + size;
+                let conflict = end > TMEM_SIZE
+                    || resident.iter().any(|index: &usize| {
+                        let other = &loads[*index];
+                        other.tlut != tlut && other.start < end && start < other.end
+                    });
+                // Loads entirely overwritten are gone
+                resident.retain(|index| loads[*index].start < start || loads[*index].end > end);
+                resident.push(loads.len());
+                loads.push(TmemLoad {
+                    label: label(reference),
+                    tlut,
+                    start,
+                    end,
+                    conflict,
+                });
+            }
+            _ => {}
+        }
+    }
+    loads
+}
+
+/// Diagram of TMEM with a row per load, placing the bytes it writes. TLUTs
+/// are green, texels blue and conflicting loads red.
+pub fn svg(loads: &[TmemLoad]) -> String {
+    let width = TMEM_SIZE / BYTES_PER_UNIT;
+    let height = (loads.len() as u32 + 1) * ROW_HEIGHT;
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" font-size=\"10\">",
+        -(SVG_MARGIN as i32),
+        -(SVG_MARGIN as i32),
+        width + SVG_MARGIN * 2,
+        height + SVG_MARGIN * 2
+    );
+    // Both halves of TMEM, with their addresses
+    for (start, name) in [(0, "texels"), (TMEM_SIZE / 2, "TLUT")] {
+        let x = start / BYTES_PER_UNIT;
+        let _ = writeln!(
+            svg,
+            "  <rect x=\"{}\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"gray\"/>",
+            x,
+            width / 2,
+            height
+        );
+        let _ = writeln!(
+            svg,
+            "  <text x=\"{}\" y=\"{}\">0x{:03x} {}</text>",
+            x + 2,
+            ROW_HEIGHT - 6,
+            start,
+            name
+        );
+    }
+    for (i, load) in loads.iter().enumerate() {
+        let fill = match (load.conflict, load.tlut) {
+            (true, _) => "hsl(0, 70%, 50%)",
+            (false, true) => "hsl(120, 50%, 40%)",
+            (false, false) => "hsl(210, 60%, 50%)",
+        };
+        let y = (i as u32 + 1) * ROW_HEIGHT;
+        let end = load.end.min(TMEM_SIZE);
+        let description = format!(
+            "{}: 0x{:03x}..0x{:03x}{}",
+            load.label,
+            load.start,
+            load.end,
+            if load.conflict { ", conflict" } else { "" }
+        );
+        let _ = writeln!(
+            svg,
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>{}</title></rect>",
+            load.start / BYTES_PER_UNIT,
+            y + 2,
+            (end.saturating_sub(load.start) / BYTES_PER_UNIT).max(1),
+            ROW_HEIGHT - 4,
+            fill,
+            escape(&description)
+        );
+        let _ = writeln!(
+            svg,
+            "  <text x=\"{}\" y=\"{}\">{}</text>",
+            (end / BYTES_PER_UNIT + 4).min(width - 4),
+            y + ROW_HEIGHT - 6,
+            escape(&description)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// `text` with the characters XML gives a meaning escaped.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,158 @@
+use std::{collections::BTreeMap, fmt::Write, fs::File};
+
+use crate::{
+    OTRHeader, ResourceType, TextureFormat, TextureType, names, options::Options, palette,
+    read_entry,
+};
+
+/// Alignment of the offsets given to assets, matching how textures are laid
+/// out in ROM segments.
+const OFFSET_ALIGNMENT: u32 = 8;
+
+/// A texture resource as Torch describes it.
+struct Asset {
+    symbol: String,
+    format: &'static str,
+    width: u32,
+    height: u32,
+    /// Size of the texel data in bytes.
+    size: u32,
+    /// Colors of a TLUT.
+    colors: Option<usize>,
+    /// Palette indices a CI texture needs.
+    indices_needed: Option<usize>,
+}
+
+/// Torch's name for a texture format.
+pub fn torch_format(type_id: &TextureType) -> Option<&'static str> {
+    match type_id {
+        TextureType::RGBA32bpp => Some("RGBA32"),
+        TextureType::RGBA16bpp => Some("RGBA16"),
+        TextureType::Palette4bpp => Some("CI4"),
+        TextureType::Palette8bpp => Some("CI8"),
+        TextureType::Grayscale4bpp => Some("I4"),
+        TextureType::Grayscale8bpp => Some("I8"),
+        TextureType::GrayscaleAlpha4bpp => Some("IA4"),
+        TextureType::GrayscaleAlpha8bpp => Some("IA8"),
+        TextureType::GrayscaleAlpha16bpp => Some("IA16"),
+        TextureType::GrayscaleAlpha1bpp => Some("IA1"),
+        TextureType::TLUT => Some("TLUT"),
+        TextureType::Error => None,
+    }
+}
+
+/// Writes skeleton Torch asset YAML for the textures of the archive, one file
+/// per archive directory in `output`, so archives without decomp definitions
+/// can go back through the C++ tooling. Offsets are made up by laying the
+/// textures out one after the other, and CI textures get the nearest TLUT of
+/// their directory with enough colors; both are marked for review.
+pub fn generate(options: &Options, output: &str) {
+    let mut zip =
+        zip::ZipArchive::new(File::open(&options.zip_file).expect("Failed to open zip file"))
+            .expect("Failed to read zip file");
+    let mut file_names = zip
+        .file_names()
+        .map(|name| name.to_owned())
+        .collect::<Vec<String>>();
+    file_names.sort();
+
+    let mut directories: BTreeMap<String, Vec<Asset>> = BTreeMap::new();
+    for name in &file_names {
+        let Some(data) = read_entry(&mut zip, name, &options.payload) else {
+            continue;
+        };
+        if !OTRHeader::parse(&data).is_ok_and(|header| header.type_id == ResourceType::Texture) {
+            continue;
+        }
+        let Ok(texture) = TextureFormat::parse(&data) else {
+            continue;
+        };
+        let Some(format) = torch_format(&texture.type_id) else {
+            continue;
+        };
+        let (directory, symbol) = name.rsplit_once('/').unwrap_or(("", name));
+        let indices_needed = palette::indices(&texture)
+            .into_iter()
+            .max()
+            .map(|index| index as usize + 1);
+        directories
+            .entry(directory.to_owned())
+            .or_default()
+            .push(Asset {
+                symbol: symbol.to_owned(),
+                format,
+                width: texture.width,
+                height: texture.height,
+                size: texture.data.len() as u32,
+                colors: (texture.type_id == TextureType::TLUT)
+                    .then(|| palette::entry_count(&texture)),
+                indices_needed,
+            });
+    }
+
+    let mut files = 0;
+    for (directory, assets) in &directories {
+        let path = if directory.is_empty() {
+            format!("{}/root.yml", output)
+        } else {
+            format!("{}/{}.yml", output, names::sanitize(directory))
+        };
+        let _ = std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap());
+        match std::fs::write(&path, yaml(assets)) {
+            Ok(()) => files += 1,
+            Err(err) => println!("Failed to write {}: {}", path, err),
+        }
+    }
+    println!(
+        "Wrote {} YAML files for {} textures to {}",
+        files,
+        directories.values().map(Vec::len).sum::<usize>(),
+        output
+    );
+}
+
+fn yaml(assets: &[Asset]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated from an O2R archive. Offsets are placeholders and TLUTs marked\n");
+    out.push_str("# as guessed need checking before this is used on a ROM.\n");
+
+    let mut offset = 0;
+    for (i, asset) in assets.iter().enumerate() {
+        let _ = writeln!(out, "\n{}:", asset.symbol);
+        let _ = writeln!(out, "  type: TEXTURE");
+        let _ = writeln!(out, "  format: {}", asset.format);
+        match asset.colors {
+            Some(colors) => {
+                let _ = writeln!(out, "  colors: {}", colors);
+            }
+            None => {
+                let _ = writeln!(out, "  width: {}", asset.width);
+                let _ = writeln!(out, "  height: {}", asset.height);
+            }
+        }
+        let _ = writeln!(out, "  offset: 0x{:X}", offset);
+        if let Some(needed) = asset.indices_needed {
+            match guess_tlut(assets, i, needed) {
+                Some(tlut) => {
+                    let _ = writeln!(out, "  tlut: {} # guessed", tlut);
+                }
+                None => {
+                    let _ = writeln!(out, "  # no TLUT with {} colors found", needed);
+                }
+            }
+        }
+        offset = (offset + asset.size).next_multiple_of(OFFSET_ALIGNMENT);
+    }
+    out
+}
+
+/// Symbol of the TLUT closest to `assets[index]` with at least `needed`
+/// colors.
+fn guess_tlut(assets: &[Asset], index: usize, needed: usize) -> Option<&str> {
+    assets
+        .iter()
+        .enumerate()
+        .filter(|(_, asset)| asset.colors.is_some_and(|colors| colors >= needed))
+        .min_by_key(|(i, _)| i.abs_diff(index))
+        .map(|(_, asset)| asset.symbol.as_str())
+}
@@ -0,0 +1,87 @@
+use std::{env, fs, process};
+
+use crate::{
+    OTRHeader, ResourceType, TextureFormat, log, names,
+    options::Options,
+    patch::Patcher,
+    read_entry,
+    stream::{self, ImageOutputFormat},
+};
+
+/// Extracts the textures of the archive matching `--where` to PNGs in a
+/// temporary folder, runs `script` with the folder as its argument, then
+/// writes the textures whose image it changed to the patch archive `output`,
+/// encoded to their original formats.
+pub fn run(options: &Options, script: &str, output: &str) {
+    let mut patcher = Patcher::open(options);
+    let folder = env::temp_dir().join(format!("convert-texture-o2r-{}", process::id()));
+    // A folder already there may have been planted, so it isn't reused
+    fs::create_dir(&folder)
+        .unwrap_or_else(|err| panic!("Failed to create {}: {}", folder.display(), err));
+    let mut images = Vec::new();
+    for name in patcher.file_names.clone() {
+        if !names::is_contained(&name) {
+            log::error(format!(
+                "Refusing to extract {} outside of {}",
+                name,
+                folder.display()
+            ));
+            continue;
+        }
+        if let Some(query) = &options.query {
+            let matches =
+                read_entry(&mut patcher.zip, &name, &options.payload).is_some_and(|data| {
+                    OTRHeader::parse(&data)
+                        .is_ok_and(|header| header.type_id == ResourceType::Texture)
+                        && TextureFormat::parse(&data).is_ok_and(|texture| query.matches(&texture))
+                });
+            if !matches {
+                continue;
+            }
+        }
+        let Some((_, texture)) = patcher.decode(&name) else {
+            continue;
+        };
+
+        let path = folder.join(format!("{}.png", name));
+        let mut png = Vec::new();
+        if let Err(err) = stream::write_image(
+            &mut png,
+            &texture.data,
+            texture.width,
+            texture.height,
+            texture.format,
+            ImageOutputFormat::Png,
+        ) {
+            log::error(format!("Failed to encode {}: {}", name, err));
+            continue;
+        }
+        fs::create_dir_all(path.parent().unwrap())
+            .and_then(|()| fs::write(&path, png))
+            .unwrap_or_else(|err| panic!("Failed to write {}: {}", path.display(), err));
+        images.push((name, path));
+    }
+    println!(
+        "Extracted {} textures to {}",
+        images.len(),
+        folder.display()
+    );
+
+    let status = process::Command::new(script).arg(&folder).status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            let _ = fs::remove_dir_all(&folder);
+            panic!("{} failed: {}", script, status);
+        }
+        Err(err) => {
+            let _ = fs::remove_dir_all(&folder);
+            panic!("Failed to run {}: {}", script, err);
+        }
+    }
+
+    // Images the script deleted or left as they were aren't patched
+    let replacements = patcher.changed(&images);
+    let _ = fs::remove_dir_all(&folder);
+    patcher.write(output, &replacements);
+}
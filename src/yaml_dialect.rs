@@ -0,0 +1,99 @@
+use std::str::FromStr;
+
+use yaml_rust2::Yaml;
+
+/// Keys naming the TLUT of a CI texture in the asset definitions, in order
+/// of preference.
+pub const TLUT_KEYS: &[&str] = &["tlut", "tlut_symbol", "tlutSymbol", "tlut_name"];
+
+/// How the decomp YAML files lay out asset definitions, given with
+/// `--yaml-dialect`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YamlDialect {
+    /// Every key of a document is an asset named after it, as Torch writes
+    /// them. The default.
+    Flat,
+    /// Assets may sit in lists or under group keys at any depth. Any map with
+    /// a `type` is an asset, named by its `symbol` or `name` and otherwise by
+    /// its key.
+    Nested,
+}
+
+impl FromStr for YamlDialect {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "flat" => Ok(YamlDialect::Flat),
+            "nested" => Ok(YamlDialect::Nested),
+            _ => Err(format!(
+                "Unknown YAML dialect '{}', expected flat or nested",
+                value
+            )),
+        }
+    }
+}
+
+impl YamlDialect {
+    /// Asset definitions of a YAML document, as `(symbol, definition)`.
+    /// Torch's `:config:` and similar keys aren't assets.
+    pub fn definitions(self, document: Yaml) -> Vec<(String, Yaml)> {
+        let mut definitions = Vec::new();
+        match self {
+            YamlDialect::Flat => {
+                definitions.extend(
+                    document
+                        .into_hash()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|(key, value)| Some((key.into_string()?, value))),
+                );
+            }
+            YamlDialect::Nested => collect(document, None, &mut definitions),
+        }
+        definitions.retain(|(symbol, _)| !symbol.starts_with(':'));
+        definitions
+    }
+}
+
+/// Adds the assets under `node`, found under the map key `key`, to
+/// `definitions`.
+fn collect(node: Yaml, key: Option<String>, definitions: &mut Vec<(String, Yaml)>) {
+    match node {
+        Yaml::Hash(hash) => {
+            if hash.contains_key(&Yaml::String("type".to_owned())) {
+                let symbol = ["symbol", "name"]
+                    .iter()
+                    .find_map(|name| hash.get(&Yaml::String(name.to_string()))?.as_str())
+                    .map(str::to_owned)
+                    .or(key);
+                if let Some(symbol) = symbol {
+                    definitions.push((symbol, Yaml::Hash(hash)));
+                }
+                return;
+            }
+            for (key, value) in hash {
+                collect(value, key.into_string(), definitions);
+            }
+        }
+        Yaml::Array(items) => {
+            for item in items {
+                collect(item, None, definitions);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Symbol of the TLUT a definition names under any of the `TLUT_KEYS`,
+/// directly or as the `symbol` of a nested map.
+pub fn tlut_symbol(definition: &yaml_rust2::yaml::Hash) -> Option<&str> {
+    let tlut = TLUT_KEYS
+        .iter()
+        .find_map(|key| definition.get(&Yaml::String(key.to_string())))?;
+    tlut.as_str().or_else(|| {
+        tlut.as_hash()?
+            .get(&Yaml::String("symbol".to_owned()))?
+            .as_str()
+    })
+}
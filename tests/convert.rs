@@ -0,0 +1,4285 @@
+//! Runs the converter on `fixtures/mini.o2r`, built by `fixtures/make_mini.py`,
+//! and checks the whole output folder.
+
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Red, green, blue and transparent black, what the RGBA textures hold.
+const RGBA: [u8; 16] = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 0, 0, 0, 0];
+
+/// Runs the converter with the extra arguments `args`, returning what it
+/// printed to stdout and stderr.
+fn convert(output: &Path, args: &[&str]) -> (String, String) {
+    convert_archive(&Path::new(FIXTURES).join("mini.o2r"), output, args)
+}
+
+/// Runs the converter on `archive` like `convert`.
+fn convert_archive(archive: &Path, output: &Path, args: &[&str]) -> (String, String) {
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(archive)
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg(format!("--output={}", output.display()))
+        .args(args)
+        .output()
+        .expect("Failed to run the converter");
+    let stdout = String::from_utf8_lossy(&result.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+    assert!(
+        result.status.success(),
+        "Conversion failed:\n{}\n{}",
+        stdout,
+        stderr
+    );
+    (stdout, stderr)
+}
+
+/// A resource of `resource_type`, its header followed by `payload`.
+fn resource(resource_type: u32, payload: &[u8]) -> Vec<u8> {
+    let mut resource = vec![0; 4];
+    resource.extend(resource_type.to_le_bytes());
+    resource.extend(0u32.to_le_bytes());
+    resource.extend(0xDEADBEEFDEADBEEFu64.to_le_bytes());
+    resource.resize(0x40, 0);
+    resource.extend(payload);
+    resource
+}
+
+/// Writes an archive of the resources `entries` under `CARGO_TARGET_TMPDIR`.
+fn write_archive(file_name: &str, entries: &[(&str, Vec<u8>)]) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(file_name);
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+    for (name, entry) in entries {
+        zip.start_file(*name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(entry).unwrap();
+    }
+    zip.finish().unwrap();
+    path
+}
+
+fn rgba(output: &Path, name: &str) -> Vec<u8> {
+    image::open(output.join(name))
+        .unwrap_or_else(|err| panic!("Failed to open {}: {}", name, err))
+        .to_rgba8()
+        .into_raw()
+}
+
+fn luma_alpha(output: &Path, name: &str) -> Vec<u8> {
+    image::open(output.join(name))
+        .unwrap_or_else(|err| panic!("Failed to open {}: {}", name, err))
+        .to_luma_alpha8()
+        .into_raw()
+}
+
+#[test]
+fn converts_mini_archive() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, stderr) = convert(&output, &["--compressed-texels"]);
+
+    let files = walkdir::WalkDir::new(&output)
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            let path = entry.path().strip_prefix(&output).unwrap();
+            path.to_string_lossy().replace('\\', "/")
+        })
+        .collect::<BTreeSet<_>>();
+    // The TLUTs, the broken texture and the unknown resource aren't written
+    let expected = [
+        "courses/mario_raceway/road.png",
+        "journal.jsonl",
+        "manifest.json",
+        "models/model.inline_110.png",
+        "models/model.relocations.json",
+        "textures/ci4.png",
+        "textures/ci8.png",
+        "textures/i4.png",
+        "textures/i4_stripes.png",
+        "textures/i8.png",
+        "textures/ia16.png",
+        "textures/ia4.png",
+        "textures/ia8.png",
+        "textures/oversized.png",
+        "textures/rgba16.png",
+        "textures/rgba16_yay0.png",
+        "textures/rgba32.png",
+        "textures/rgba32_stride.png",
+        "textures/rgba32_yaz0.png",
+    ];
+    assert_eq!(files, expected.into_iter().map(str::to_owned).collect());
+    // Errors are the only messages on stderr
+    assert_eq!(
+        stderr.lines().collect::<Vec<_>>(),
+        ["Data size does not match expected size for textures/broken: 8 vs 32"]
+    );
+    // The texels past the 2x2 image are left out, leaving nothing the
+    // encoders reject
+    assert_eq!(rgba(&output, "textures/oversized.png"), [0; 16]);
+    assert!(!stdout.contains("couldn't be encoded"), "{}", stdout);
+
+    let rgba_names = [
+        "rgba32",
+        "rgba32_stride",
+        "rgba32_yaz0",
+        "rgba16",
+        "rgba16_yay0",
+        "ci4",
+        "ci8",
+    ];
+    for name in rgba_names {
+        assert_eq!(
+            rgba(&output, &format!("textures/{}.png", name)),
+            RGBA,
+            "{}",
+            name
+        );
+    }
+    // Decoded with the TLUT embedded before it
+    assert_eq!(rgba(&output, "models/model.inline_110.png"), RGBA);
+    let grayscale = [
+        ("i4", [0, 0, 255, 255, 136, 136, 68, 68]),
+        ("i8", [0, 0, 255, 255, 128, 128, 64, 64]),
+        ("ia4", [255, 255, 0, 0, 146, 255, 255, 0]),
+        ("ia8", [255, 255, 0, 255, 255, 0, 136, 136]),
+        ("ia16", [255, 255, 0, 255, 128, 0, 64, 128]),
+    ];
+    for (name, pixels) in grayscale {
+        assert_eq!(
+            luma_alpha(&output, &format!("textures/{}.png", name)),
+            pixels,
+            "{}",
+            name
+        );
+    }
+
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    assert!(manifest.contains(&format!("\"archive\": \"{}/mini.o2r\"", FIXTURES)));
+    assert_eq!(manifest.matches("\"entry\": ").count(), 15);
+    // rgba32 has a transparent texel, ia16 a half transparent one
+    assert!(manifest.contains("\"class\": \"binary\""));
+    assert!(manifest.contains("\"class\": \"mixed\""));
+    let formats = [
+        ("rgba32", "RGBA32bpp"),
+        ("rgba32_stride", "RGBA32bpp"),
+        ("rgba32_yaz0", "RGBA32bpp"),
+        ("rgba16", "RGBA16bpp"),
+        ("rgba16_yay0", "RGBA16bpp"),
+        ("ci4", "Palette4bpp"),
+        ("ci8", "Palette8bpp"),
+        ("i4", "Grayscale4bpp"),
+        ("i8", "Grayscale8bpp"),
+        ("ia4", "GrayscaleAlpha4bpp"),
+        ("ia8", "GrayscaleAlpha8bpp"),
+        ("ia16", "GrayscaleAlpha16bpp"),
+    ];
+    for (name, format) in formats {
+        let entry = format!(
+            concat!(
+                "\"entry\": \"textures/{0}\",\n",
+                "      \"output\": \"textures/{0}.png\",\n",
+                "      \"format\": \"{1}\",\n",
+                "      \"width\": 2,\n",
+                "      \"height\": 2,\n",
+            ),
+            name, format
+        );
+        assert!(
+            manifest.contains(&entry),
+            "{} missing from\n{}",
+            name,
+            manifest
+        );
+    }
+
+    let relocations =
+        std::fs::read_to_string(output.join("models/model.relocations.json")).unwrap();
+    assert!(relocations.contains("\"path\": \"textures/ci4\""));
+    assert!(relocations.contains("\"format\": \"Palette4bpp\""));
+    assert!(relocations.contains("\"target\": \"textures/ci4\""));
+}
+
+#[test]
+fn skips_textures_the_encoders_reject() {
+    // A 0x2 RGBA32 texture, which PNG has no room for
+    let mut empty = Vec::new();
+    for field in [1u32, 0, 2, 0] {
+        empty.extend(field.to_le_bytes());
+    }
+    let archive = write_archive(
+        "mini-unencodable.o2r",
+        &[
+            ("textures/empty", resource(0x4F544558, &empty)),
+            ("textures/rgba32", archive_entry("textures/rgba32")),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-unencodable");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, stderr) = convert_archive(&archive, &output, &[]);
+
+    // The failure is summed up at the end, the other textures are written
+    let (_, failure) = stderr
+        .split_once("Failed to encode textures/empty: ")
+        .unwrap_or_else(|| panic!("No encoding failure in {}", stderr));
+    let failure = failure.lines().next().unwrap();
+    assert!(stdout.contains(&format!(
+        "1 textures couldn't be encoded and were left out:\n  textures/empty: {}\n",
+        failure
+    )));
+    assert!(!output.join("textures/empty.png").exists());
+    assert_eq!(rgba(&output, "textures/rgba32.png"), RGBA);
+}
+
+#[test]
+fn stale_outputs_are_pruned_on_rerun() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-rerun");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--index-csv", "--thumbnails=1"]);
+    assert!(output.join("thumbs/textures/ci4.png").exists());
+    let unrelated = output.join("textures/notes.txt");
+    std::fs::write(&unrelated, b"").unwrap();
+
+    convert(&output, &[]);
+    // Outputs of the first run only are gone, with the folders they leave empty
+    assert!(!output.join("index.csv").exists());
+    assert!(!output.join("thumbs").exists());
+    assert!(unrelated.exists());
+    assert!(output.join("textures/ci4.png").exists());
+
+    convert(&output, &["--keep-stale", "--index-csv"]);
+    convert(&output, &["--keep-stale"]);
+    assert!(output.join("index.csv").exists());
+}
+
+#[test]
+fn reproducible_output_is_identical() {
+    let mut sums = Vec::new();
+    let mut times = Vec::new();
+    for name in ["mini-reproducible-1", "mini-reproducible-2"] {
+        let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let _ = std::fs::remove_dir_all(&output);
+        let (stdout, _) = convert(&output, &["--reproducible", "--threads=4"]);
+        assert!(stdout.contains("Output tree hash: "));
+        let modified = std::fs::metadata(output.join("manifest.json"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        times.push(modified);
+        sums.push(std::fs::read_to_string(output.join("SHA256SUMS")).unwrap());
+    }
+    assert_eq!(sums[0], sums[1]);
+    assert_eq!(times[0], times[1]);
+    assert!(sums[0].contains("  textures/ci4.png\n"));
+    assert!(sums[0].contains("  manifest.json\n"));
+}
+
+#[test]
+fn thread_counts_dont_change_the_output() {
+    let sums = [
+        ["--threads=1", "--threads-io=1"],
+        ["--threads=4", "--threads-io=3"],
+    ]
+    .map(|threads| {
+        let output =
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("mini-{}", &threads[0][2..]));
+        let _ = std::fs::remove_dir_all(&output);
+        convert(&output, &[&["--reproducible"], &threads[..]].concat());
+        std::fs::read_to_string(output.join("SHA256SUMS")).unwrap()
+    });
+    assert_eq!(sums[0], sums[1]);
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg("--threads-io=0")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Invalid value '0' for option '--threads-io'")
+    );
+}
+
+#[test]
+fn logs_entries_in_order_whatever_the_workers() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-log-order");
+    let log_file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-log-order.log");
+    let logs = [
+        ["--threads=1", "--threads-io=1"],
+        ["--threads=4", "--threads-io=3"],
+        ["--threads=4", "--threads-io=3"],
+    ]
+    .map(|threads| {
+        let _ = std::fs::remove_dir_all(&output);
+        let log = format!("--log-file={}", log_file.display());
+        let routes = "--log=progress=both,skip=both,error=both";
+        let (stdout, stderr) = convert(&output, &[&[&log, routes], &threads[..]].concat());
+        // After the line echoing the arguments
+        let (_, stdout) = stdout.split_once('\n').unwrap();
+        let log = std::fs::read_to_string(&log_file).unwrap();
+        (stdout.to_owned(), stderr, log)
+    });
+    // Progress and errors of the entries, interleaved in one file
+    assert!(logs[0].2.contains("[Progress] Processing texture: "));
+    assert!(logs[0].2.contains("[Error] Data size does not match "));
+    assert_eq!(logs[0], logs[1]);
+    assert_eq!(logs[1], logs[2]);
+}
+
+#[test]
+fn streams_outputs_as_a_tar() {
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg("--output=-")
+        .output()
+        .expect("Failed to run the converter");
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(result.status.success(), "Conversion failed:\n{}", stderr);
+    assert!(stderr.contains("Number of files in zip: "));
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-tar");
+    let _ = std::fs::remove_dir_all(&output);
+    std::fs::create_dir_all(&output).unwrap();
+    let mut tar = Command::new("tar")
+        .arg("-x")
+        .arg("-C")
+        .arg(&output)
+        .stdin(Stdio::piped())
+        .spawn()
+        .expect("Failed to run tar");
+    tar.stdin.take().unwrap().write_all(&result.stdout).unwrap();
+    assert!(tar.wait().unwrap().success());
+    assert_eq!(rgba(&output, "textures/rgba32.png"), RGBA);
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    assert!(manifest.contains("\"textures/rgba32.png\""));
+}
+
+/// Flips a texel byte of the entry `name` in the zip `archive`, so reading it
+/// fails its checksum.
+fn corrupt_entry(archive: &mut [u8], name: &str) {
+    let mut offset = 0;
+    while archive[offset..offset + 4] == *b"PK\x03\x04" {
+        let field = |at: usize| u16::from_le_bytes([archive[at], archive[at + 1]]) as usize;
+        let size = u32::from_le_bytes(archive[offset + 18..offset + 22].try_into().unwrap());
+        let (name_length, extra_length) = (field(offset + 26), field(offset + 28));
+        let data = offset + 30 + name_length + extra_length;
+        if archive[offset + 30..offset + 30 + name_length] == *name.as_bytes() {
+            archive[data + 80] ^= 0xFF;
+            return;
+        }
+        offset = data + size as usize;
+    }
+    panic!("{} not found", name);
+}
+
+#[test]
+fn quarantines_unreadable_entries_for_a_retry() {
+    let archive = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-flaky.o2r");
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-flaky");
+    let _ = std::fs::remove_dir_all(&output);
+    let original = std::fs::read(format!("{}/mini.o2r", FIXTURES)).unwrap();
+    let mut corrupted = original.clone();
+    corrupt_entry(&mut corrupted, "textures/rgba32");
+    std::fs::write(&archive, corrupted).unwrap();
+
+    let run = |args: &[&str]| {
+        let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg(&archive)
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .arg(format!("--output={}", output.display()))
+            .arg("--compressed-texels")
+            .args(args)
+            .output()
+            .expect("Failed to run the converter");
+        assert!(result.status.success());
+        (
+            String::from_utf8_lossy(&result.stdout).into_owned(),
+            String::from_utf8_lossy(&result.stderr).into_owned(),
+        )
+    };
+    let (stdout, stderr) = run(&["--read-retries=1"]);
+    assert!(stdout.contains("Retrying textures/rgba32 after a failed read"));
+    assert!(stderr.contains("Failed to read textures/rgba32: "));
+    assert!(stdout.contains("1 entries couldn't be read and were quarantined"));
+    let failed = output.join("failed.json");
+    assert!(
+        std::fs::read_to_string(&failed)
+            .unwrap()
+            .contains("\"entry\": \"textures/rgba32\"")
+    );
+    assert!(!output.join("textures/rgba32.png").exists());
+
+    // Once the archive reads again only the quarantined entry is converted
+    std::fs::write(&archive, original).unwrap();
+    let (stdout, _) = run(&[&format!("--retry-failed={}", failed.display())]);
+    assert_eq!(stdout.matches("Processing texture: ").count(), 1);
+    assert_eq!(rgba(&output, "textures/rgba32.png"), RGBA);
+    assert!(!failed.exists());
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    assert_eq!(manifest.matches("\"entry\": ").count(), 15);
+}
+
+fn archive_entry(name: &str) -> Vec<u8> {
+    let file = std::fs::File::open(format!("{}/mini.o2r", FIXTURES)).unwrap();
+    let mut zip = zip::ZipArchive::new(file).unwrap();
+    let mut data = Vec::new();
+    std::io::Read::read_to_end(&mut zip.by_name(name).unwrap(), &mut data).unwrap();
+    data
+}
+
+/// Pipes `resource` through the converter, returning the image it printed.
+fn pipe(resource: &[u8], args: &[&str]) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .args(["--stdin", "--stdout"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the converter");
+    child.stdin.take().unwrap().write_all(resource).unwrap();
+    let result = child.wait_with_output().unwrap();
+    assert!(result.status.success());
+    image::load_from_memory(&result.stdout)
+        .expect("stdout isn't an image")
+        .to_rgba8()
+        .into_raw()
+}
+
+#[test]
+fn converts_stdin_to_stdout() {
+    assert_eq!(pipe(&archive_entry("textures/rgba32_stride"), &[]), RGBA);
+
+    let tlut = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-tlut");
+    std::fs::write(&tlut, archive_entry("textures/tlut")).unwrap();
+    let tlut = format!("--tlut={}", tlut.display());
+    assert_eq!(pipe(&archive_entry("textures/ci4"), &[&tlut]), RGBA);
+}
+
+#[test]
+fn encodes_images_as_texture_resources() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let image = dir.join("mini-encode.png");
+    image::RgbaImage::from_raw(2, 2, RGBA.to_vec())
+        .unwrap()
+        .save(&image)
+        .unwrap();
+    let encode = |format: &str| {
+        let output = dir.join(format!("mini-encode-{}", format));
+        let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .arg("encode")
+            .arg(&image)
+            .arg(format)
+            .arg(&output)
+            .output()
+            .expect("Failed to run the converter");
+        assert!(result.status.success());
+        output
+    };
+
+    // The colors fit RGBA5551, so the resource is the fixture's byte for byte
+    let rgba16 = encode("rgba16");
+    assert_eq!(
+        std::fs::read(rgba16).unwrap(),
+        archive_entry("textures/rgba16")
+    );
+    let ci4 = encode("CI4");
+    let tlut = format!("--tlut={}_tlut", ci4.display());
+    assert_eq!(pipe(&std::fs::read(ci4).unwrap(), &[&tlut]), RGBA);
+}
+
+#[test]
+fn decodes_textures_with_the_library() {
+    use convert_texture_o2r::{
+        DecodeError, DecodeOptions, TextureFormat, TextureType, decode_texels, decode_texture,
+        decode_texture_with_tlut, pixels::Expansion,
+    };
+
+    let options = DecodeOptions::default();
+    let decode_texture = |data: &[u8]| decode_texture(data, &options);
+    let rgba32 = decode_texture(&archive_entry("textures/rgba32_stride")).unwrap();
+    assert_eq!((rgba32.width(), rgba32.height()), (2, 2));
+    assert_eq!(rgba32.into_raw(), RGBA);
+    let ci4 = decode_texture_with_tlut(
+        &archive_entry("textures/ci4"),
+        Some(&archive_entry("textures/tlut")),
+        &options,
+    );
+    assert_eq!(ci4.unwrap().into_raw(), RGBA);
+
+    assert!(matches!(
+        decode_texture(&archive_entry("textures/ci4")),
+        Err(DecodeError::MissingTlut(_))
+    ));
+    assert!(matches!(
+        decode_texture(&archive_entry("textures/broken")),
+        Err(DecodeError::Invalid(_))
+    ));
+    assert_eq!(decode_texture(&[0; 16]), Err(DecodeError::Truncated(16)));
+
+    // Headers the decoders don't know are errors rather than panics
+    let mut unknown = archive_entry("textures/rgba32_stride");
+    unknown[64..68].copy_from_slice(&12u32.to_le_bytes());
+    assert_eq!(decode_texture(&unknown), Err(DecodeError::UnknownType(12)));
+    assert_eq!(
+        decode_texture(&archive_entry("textures/tlut")),
+        Err(DecodeError::Unsupported(TextureType::TLUT))
+    );
+    assert_eq!(TextureType::from_u32(12), None);
+    assert_eq!(TextureType::TLUT.bits_per_pixel(), None);
+    assert_eq!(TextureType::Error.to_image_type(), None);
+
+    // Raw texels are decoded to the size of the texture, or not at all
+    let rgba16 = TextureFormat::new(TextureType::RGBA16bpp, 2, 2, 8, vec![0xFF; 8]);
+    assert_eq!(
+        decode_texels(&rgba16, &[0xFF; 6], None, Expansion::default()),
+        None
+    );
+    let rgba32 = TextureFormat::new(TextureType::RGBA32bpp, 2, 2, 20, vec![0xFF; 20]);
+    assert_eq!(
+        decode_texels(&rgba32, &rgba32.data, None, Expansion::default()),
+        Some(vec![0xFF; 16])
+    );
+
+    // Settings are per call, so callers can mix them in one process
+    let ia4 = archive_entry("textures/ia4");
+    let linear = DecodeOptions {
+        expansion: Expansion::Linear,
+        ..options
+    };
+    assert_ne!(
+        decode_texture(&ia4).unwrap(),
+        convert_texture_o2r::decode_texture(&ia4, &linear).unwrap()
+    );
+    assert_eq!(
+        decode_texture(&ia4).unwrap(),
+        convert_texture_o2r::decode_texture(&ia4, &options).unwrap()
+    );
+}
+
+#[test]
+fn decodes_to_a_writer_with_the_library() {
+    use convert_texture_o2r::{
+        DecodeOptions,
+        stream::{ImageOutputFormat, decode_to_writer},
+    };
+
+    let options = DecodeOptions::default();
+    let mut rgba = Vec::new();
+    let size = decode_to_writer(
+        &archive_entry("textures/ci4"),
+        Some(&archive_entry("textures/tlut")),
+        &mut rgba,
+        ImageOutputFormat::Rgba8,
+        &options,
+    );
+    assert_eq!(size, Ok((2, 2)));
+    assert_eq!(rgba, RGBA);
+
+    let mut png = Vec::new();
+    decode_to_writer(
+        &archive_entry("textures/rgba32_stride"),
+        None,
+        &mut png,
+        ImageOutputFormat::Png,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        image::load_from_memory(&png).unwrap().to_rgba8().into_raw(),
+        RGBA
+    );
+
+    let mut untouched = Vec::new();
+    assert!(
+        decode_to_writer(
+            &archive_entry("textures/ci4"),
+            None,
+            &mut untouched,
+            ImageOutputFormat::Rgba8,
+            &options,
+        )
+        .is_err()
+    );
+    assert!(untouched.is_empty());
+}
+
+#[test]
+fn caches_textures_queried_with_the_library() {
+    use std::sync::Arc;
+
+    use convert_texture_o2r::{
+        DecodeOptions, TextureFormat, TextureType, decode::TextureDefinitions,
+        payload::PayloadTransform, texture_query::TextureQuery,
+    };
+
+    struct NoDefinitions;
+
+    impl TextureDefinitions for NoDefinitions {
+        fn tlut(&self, _: &str, _: &TextureType) -> Option<(&str, Arc<TextureFormat>)> {
+            None
+        }
+    }
+
+    let open = |budget| {
+        let file = std::fs::File::open(format!("{}/mini.o2r", FIXTURES)).unwrap();
+        TextureQuery::new(
+            zip::ZipArchive::new(file).unwrap(),
+            NoDefinitions,
+            DecodeOptions::default(),
+            PayloadTransform::Auto,
+            budget,
+        )
+    };
+
+    // Room for one 2x2 RGBA texture
+    let mut query = open(16);
+    let first = query.decode("textures/rgba32").unwrap();
+    assert_eq!(first.data, RGBA);
+    assert!(Arc::ptr_eq(
+        &first,
+        &query.decode("textures/rgba32").unwrap()
+    ));
+    query.decode("textures/rgba32_stride").unwrap();
+    let again = query.decode("textures/rgba32").unwrap();
+    assert!(!Arc::ptr_eq(&first, &again));
+    assert_eq!(again.data, RGBA);
+
+    // The least recently used goes first
+    let mut query = open(32);
+    let first = query.decode("textures/rgba32").unwrap();
+    let stride = query.decode("textures/rgba32_stride").unwrap();
+    query.decode("textures/rgba32").unwrap();
+    query.decode("textures/rgba16").unwrap();
+    assert!(Arc::ptr_eq(
+        &first,
+        &query.decode("textures/rgba32").unwrap()
+    ));
+    assert!(!Arc::ptr_eq(
+        &stride,
+        &query.decode("textures/rgba32_stride").unwrap()
+    ));
+
+    let metadata = query.metadata("textures/tlut");
+    assert_eq!(metadata.resource_type, "Texture");
+    assert_eq!(metadata.colors, Some(16));
+    assert!(query.decode("textures/ci4").is_err());
+}
+
+#[test]
+fn unwraps_obfuscated_payloads() {
+    let resource = archive_entry("textures/rgba32_stride");
+    let xored = resource.iter().map(|byte| byte ^ 0x5A).collect::<Vec<_>>();
+    assert_eq!(pipe(&xored, &[]), RGBA);
+
+    // zlib stream of a single stored block
+    let (a, b) = resource.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    let length = resource.len() as u16;
+    let mut zlib = vec![0x78, 0x01, 0x01];
+    zlib.extend(length.to_le_bytes());
+    zlib.extend((!length).to_le_bytes());
+    zlib.extend(&resource);
+    zlib.extend((b << 16 | a).to_be_bytes());
+    assert_eq!(pipe(&zlib, &[]), RGBA);
+    assert_eq!(pipe(&xored, &["--payload=xor:5a"]), RGBA);
+
+    // Huffman coded blocks as a real compressor writes them, taken from a
+    // zip entry. Bytes past the texels are ignored, and skewed enough that
+    // the compressor builds codes of its own for them.
+    let compress = |resource: &[u8]| {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("entry", options).unwrap();
+        zip.write_all(resource).unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+        let size = u32::from_le_bytes(archive[18..22].try_into().unwrap()) as usize;
+        let start = 30
+            + u16::from_le_bytes([archive[26], archive[27]]) as usize
+            + u16::from_le_bytes([archive[28], archive[29]]) as usize;
+        let (a, b) = resource.iter().fold((1u32, 0u32), |(a, b), byte| {
+            let a = (a + *byte as u32) % 65521;
+            (a, (b + a) % 65521)
+        });
+        let mut zlib = vec![0x78, 0x9C];
+        zlib.extend(&archive[start..start + size]);
+        zlib.extend((b << 16 | a).to_be_bytes());
+        zlib
+    };
+    let fixed = compress(&resource);
+    assert_eq!(fixed[2] >> 1 & 3, 1);
+    assert_eq!(pipe(&fixed, &[]), RGBA);
+    let mut padded = resource.clone();
+    padded.extend((0..4096u64).map(|i| (i * i * i % 1009 % 5) as u8));
+    let dynamic = compress(&padded);
+    assert_eq!(dynamic[2] >> 1 & 3, 2);
+    assert_eq!(pipe(&dynamic, &["--payload=zlib"]), RGBA);
+
+    let yaz0 = archive_entry("textures/rgba32_yaz0");
+    assert_eq!(pipe(&yaz0, &[]), RGBA);
+    assert_eq!(pipe(&yaz0, &["--payload=yaz0"]), RGBA);
+}
+
+#[test]
+fn decodes_padded_rows_of_1bpp_textures() {
+    // A 7x2 IA1 texture, each row taking a whole byte
+    let mut resource = archive_entry("textures/rgba32")[..0x40].to_vec();
+    for field in [10u32, 7, 2, 2] {
+        resource.extend(field.to_le_bytes());
+    }
+    resource.extend([0b1010_1010, 0b0101_0100]);
+    let expected = [1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0]
+        .into_iter()
+        .flat_map(|bit| [bit * 0xFF; 4])
+        .collect::<Vec<_>>();
+    assert_eq!(pipe(&resource, &[]), expected);
+}
+
+#[test]
+fn decodes_odd_widths_of_4bpp_textures() {
+    // A 3x2 I4 texture, each row ending on a padding nibble
+    let mut resource = archive_entry("textures/rgba32")[..0x40].to_vec();
+    for field in [5u32, 3, 2, 4] {
+        resource.extend(field.to_le_bytes());
+    }
+    resource.extend([0x0F, 0x8A, 0x48, 0xCA]);
+    let expected = [0x00, 0xFF, 0x88, 0x44, 0x88, 0xCC]
+        .into_iter()
+        .flat_map(|intensity| [intensity; 4])
+        .collect::<Vec<_>>();
+    assert_eq!(pipe(&resource, &[]), expected);
+}
+
+#[test]
+fn expands_5_bit_channels_by_replication_or_linearly() {
+    // 3x3 RGBA16, 8 texels for the SIMD loop and one for the scalar tail
+    let mut resource = archive_entry("textures/rgba32")[..0x40].to_vec();
+    for field in [2u32, 3, 3, 18] {
+        resource.extend(field.to_le_bytes());
+    }
+    let texel: u16 = 4 << 11 | 2 << 6 | 31 << 1 | 1;
+    resource.extend(texel.to_be_bytes().repeat(9));
+
+    assert_eq!(pipe(&resource, &[]), [33, 16, 255, 255].repeat(9));
+    assert_eq!(
+        pipe(&resource, &["--expand=linear"]),
+        [32, 16, 255, 255].repeat(9)
+    );
+}
+
+#[test]
+fn rejects_dimensions_too_large_to_decode() {
+    let mut resource = archive_entry("textures/rgba32");
+    resource[0x44..0x4C].fill(0xFF);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .args(["--stdin", "--stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the converter");
+    child.stdin.take().unwrap().write_all(&resource).unwrap();
+    let result = child.wait_with_output().unwrap();
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Texture size 4294967295x4294967295 is too large to decode")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn transform_patches_changed_textures() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let script = dir.join("mini-transform.sh");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\ncp \"$1/textures/ia16.png\" \"$1/textures/rgba32.png\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let patch = dir.join("mini-transform.o2r");
+    let _ = std::fs::remove_file(&patch);
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("transform")
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg(format!("--exec={}", script.display()))
+        .arg(&patch)
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+
+    // Only the texture the script changed is in the patch, as RGBA32
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&patch).unwrap()).unwrap();
+    assert_eq!(
+        zip.file_names().collect::<Vec<_>>(),
+        vec!["textures/rgba32"]
+    );
+    let mut patched = Vec::new();
+    std::io::Read::read_to_end(&mut zip.by_name("textures/rgba32").unwrap(), &mut patched).unwrap();
+    assert_eq!(
+        pipe(&patched, &[]),
+        pipe(&archive_entry("textures/ia16"), &[])
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn transform_keeps_hostile_entry_names_inside_its_folder() {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-transform-hostile");
+    let _ = std::fs::remove_dir_all(&root);
+    let temp = root.join("parent/tmp");
+    std::fs::create_dir_all(&temp).unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .env("TMPDIR", &temp)
+        .arg("transform")
+        .arg(format!("{}/hostile.o2r", FIXTURES))
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg("--exec=true")
+        .arg(root.join("patch.o2r"))
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stdout.contains("Extracted 0 textures to "), "{}", stdout);
+    assert!(stderr.contains("Refusing to extract ../../evil outside of "));
+    assert!(!root.join("evil.png").exists());
+    assert!(!root.join("parent/evil.png").exists());
+    assert!(!Path::new("/abs.png").exists());
+}
+
+#[test]
+fn patch_holds_only_edited_textures() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let output = dir.join("mini-patch");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &[]);
+    std::fs::copy(
+        output.join("textures/ia16.png"),
+        output.join("textures/rgba32.png"),
+    )
+    .unwrap();
+    let patch = dir.join("mini-patch.o2r");
+    let _ = std::fs::remove_file(&patch);
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("patch")
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(&output)
+        .arg(&patch)
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+
+    let zip = zip::ZipArchive::new(std::fs::File::open(&patch).unwrap()).unwrap();
+    assert_eq!(
+        zip.file_names().collect::<Vec<_>>(),
+        vec!["textures/rgba32"]
+    );
+}
+
+#[test]
+fn patch_watch_rebuilds_on_save() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let output = dir.join("mini-watch");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &[]);
+    let patch = dir.join("mini-watch.o2r");
+    let _ = std::fs::remove_file(&patch);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("patch")
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(&output)
+        .arg(&patch)
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg("--watch")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Failed to run the converter");
+    let entries = || {
+        let file = std::fs::File::open(&patch).ok()?;
+        let zip = zip::ZipArchive::new(file).ok()?;
+        Some(zip.file_names().map(str::to_owned).collect::<Vec<_>>())
+    };
+    let wait_for = |expected: &[&str]| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while entries().is_none_or(|entries| entries != expected) {
+            assert!(std::time::Instant::now() < deadline, "Patch wasn't rebuilt");
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    };
+
+    wait_for(&[]);
+    let original = std::fs::read(output.join("textures/rgba32.png")).unwrap();
+    std::fs::copy(
+        output.join("textures/ia16.png"),
+        output.join("textures/rgba32.png"),
+    )
+    .unwrap();
+    wait_for(&["textures/rgba32"]);
+    // Saving the original image back takes it out of the patch
+    std::fs::write(output.join("textures/rgba32.png"), original).unwrap();
+    wait_for(&[]);
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+/// Writes the patch archive `path` holding `entries`, as `(name, entry of
+/// the fixture)`, modified in the year `year`.
+fn write_patch(path: &Path, entries: &[(&str, &str)], year: u16) {
+    let time = zip::DateTime::from_date_and_time(year, 1, 1, 0, 0, 0).unwrap();
+    let options = zip::write::SimpleFileOptions::default().last_modified_time(time);
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    for (name, entry) in entries {
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(&archive_entry(entry)).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn merge_resolves_conflicting_patches() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let newer = dir.join("mini-merge-newer.o2r");
+    let older = dir.join("mini-merge-older.o2r");
+    // Both change rgba32, and i4 the same way
+    write_patch(
+        &newer,
+        &[
+            ("textures/rgba32", "textures/rgba32"),
+            ("textures/i4", "textures/i4"),
+            ("textures/i8", "textures/i8"),
+        ],
+        2024,
+    );
+    write_patch(
+        &older,
+        &[
+            ("textures/rgba32", "textures/ia16"),
+            ("textures/i4", "textures/i4"),
+        ],
+        2020,
+    );
+    let merged = dir.join("mini-merged.o2r");
+    let merge = |resolve: &str, answer: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .arg("merge")
+            .arg(format!("{}/mini.o2r", FIXTURES))
+            .arg(&merged)
+            .arg(&newer)
+            .arg(&older)
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .arg(format!("--resolve={}", resolve))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to run the converter");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(answer.as_bytes())
+            .unwrap();
+        let result = child.wait_with_output().unwrap();
+        assert!(result.status.success());
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&merged).unwrap()).unwrap();
+        assert_eq!(
+            zip.file_names().collect::<BTreeSet<_>>(),
+            BTreeSet::from(["textures/i4", "textures/i8", "textures/rgba32"])
+        );
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut zip.by_name("textures/rgba32").unwrap(), &mut data)
+            .unwrap();
+        (String::from_utf8_lossy(&result.stdout).into_owned(), data)
+    };
+
+    // The patch given last wins by default
+    let (stdout, data) = merge("priority", "");
+    assert_eq!(data, archive_entry("textures/ia16"));
+    assert!(stdout.contains("1 conflicts"));
+    let (_, data) = merge("newest", "");
+    assert_eq!(data, archive_entry("textures/rgba32"));
+    let (stdout, data) = merge("ask", "1\n");
+    assert_eq!(data, archive_entry("textures/rgba32"));
+    assert!(stdout.contains(", preview "));
+    assert!(!dir.join("mini-merged.o2r.previews").exists());
+}
+
+#[test]
+fn info_hexdump_labels_the_header() {
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("info")
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg("textures/rgba32")
+        .arg("--hex")
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("  width: 2\n"));
+    // LUS CRC-64 of the entry path, as computed bit by bit
+    assert!(stdout.contains("Resource id: 6221968E845A481B\n"));
+    assert!(stdout.contains("; 00..01 byte_order=0, 01..02 is_custom=false"));
+    assert!(stdout.contains("00000050 |ff 00 00 ff"));
+    assert!(stdout.contains("; 50 payload"));
+}
+
+#[test]
+fn aliases_run_their_subcommand() {
+    let aliases = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-aliases");
+    let _ = std::fs::remove_dir_all(&aliases);
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("install-aliases")
+        .arg(&aliases)
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+
+    let info = aliases.join(format!("o2r-info{}", std::env::consts::EXE_SUFFIX));
+    let result = Command::new(info)
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg("textures/rgba32")
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .output()
+        .expect("Failed to run the alias");
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stdout).contains("  width: 2\n"));
+}
+
+#[test]
+fn derives_height_and_normal_maps() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-derive");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--derive=height,normal"]);
+
+    // Pure red, green and blue texels get their Rec. 709 luma
+    let height = image::open(output.join("textures/rgba32_height.png"))
+        .unwrap()
+        .to_luma8()
+        .into_raw();
+    assert_eq!(height, [54, 182, 18, 0]);
+    let normal = image::open(output.join("textures/rgba32_normal.png")).unwrap();
+    assert_eq!((normal.width(), normal.height()), (2, 2));
+}
+
+#[test]
+fn dilates_colors_under_transparent_texels() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-dilate");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--dilate-alpha=1"]);
+
+    // The transparent texel takes the average of red, green and blue
+    let mut dilated = RGBA;
+    dilated[12..15].copy_from_slice(&[85, 85, 85]);
+    assert_eq!(rgba(&output, "textures/rgba32.png"), dilated);
+
+    // One past u32::MAX, which used to wrap around to 0
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg("--dilate-alpha=4294967296")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Invalid value '4294967296' for option '--dilate-alpha'")
+    );
+}
+
+#[test]
+fn follows_spaghetti_kart_course_conventions() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-kart");
+    let _ = std::fs::remove_dir_all(&output);
+    let (_, stderr) = convert(&output, &["--game=spaghetti-kart"]);
+    assert!(!stderr.contains("courses/"), "{}", stderr);
+
+    // Decompressed and decoded with the palette of its own course, not the
+    // one of the same name in the course before it
+    let road = rgba(&output, "courses/mario_raceway/road.png");
+    assert_eq!(road.len(), 8 * 8 * 4);
+    for row in road.chunks_exact(8 * 4) {
+        assert_eq!(row[..16], RGBA);
+        assert_eq!(row[16..], RGBA);
+    }
+}
+
+#[test]
+fn reads_texels_as_stored_unless_compressed_texels_are_asked_for() {
+    use convert_texture_o2r::{DecodeError, DecodeOptions, decode_texture};
+
+    // Without a game storing compressed texels they are decoded as they are
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-stored-texels");
+    let _ = std::fs::remove_dir_all(&output);
+    let (_, stderr) = convert(&output, &["--types=texture"]);
+    assert!(stderr.contains(
+        "Data size does not match expected size for courses/mario_raceway/road: 26 vs 32"
+    ));
+    assert_ne!(rgba(&output, "textures/rgba16_yay0.png"), RGBA);
+
+    let options = DecodeOptions {
+        compressed_texels: true,
+        ..DecodeOptions::default()
+    };
+    let mut yay0 = archive_entry("textures/rgba16_yay0");
+    let decoded = decode_texture(&yay0, &options).unwrap();
+    assert_eq!(decoded.into_raw(), RGBA);
+
+    // The size in the Yay0 header can't be more than the 2x2 texture takes
+    yay0[0x54..0x58].copy_from_slice(&0xFFFFFFFFu32.to_be_bytes());
+    let Err(DecodeError::Invalid(err)) = decode_texture(&yay0, &options) else {
+        panic!("Oversized Yay0 texels decoded");
+    };
+    assert!(
+        err.contains("Failed to decompress texels: Decompressed size 4294967295 is more than the 8 bytes expected"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn writes_schemas_without_an_archive() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("schema");
+    let _ = std::fs::remove_dir_all(&output);
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("schema")
+        .arg(&output)
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+    let manifest = std::fs::read_to_string(output.join("manifest.schema.json")).unwrap();
+    assert!(manifest.contains("\"$schema\": \"https://json-schema.org/draft/2020-12/schema\""));
+    assert!(manifest.contains("\"pack_hash\""));
+    let config = std::fs::read_to_string(output.join("config.schema.json")).unwrap();
+    assert!(config.contains("\"i4_as_ia4\""));
+}
+
+#[test]
+fn classifies_textures_in_the_manifest() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-classify");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--classify"]);
+
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    let category = |entry: &str| {
+        manifest
+            .split("\"entry\": ")
+            .find(|texture| texture.starts_with(&format!("\"{}\"", entry)))
+            .and_then(|texture| texture.split("\"category\": \"").nth(1))
+            .and_then(|rest| rest.split('"').next())
+            .map(str::to_owned)
+    };
+    // Tiny 4-bit intensity textures look like font glyphs
+    assert_eq!(category("textures/i4").as_deref(), Some("font"));
+    assert_eq!(category("textures/rgba32").as_deref(), Some("other"));
+}
+
+#[test]
+fn skips_placeholder_textures() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-placeholders");
+    let placeholders = |config: &str| {
+        let _ = std::fs::remove_dir_all(&output);
+        let config = format!("--config={}/{}", FIXTURES, config);
+        convert(&output, &["--skip-placeholders", &config]);
+        let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+        manifest
+            .split("\"placeholders\": [")
+            .nth(1)
+            .and_then(|rest| rest.split(']').next())
+            .map(|list| {
+                list.split('"')
+                    .skip(1)
+                    .step_by(2)
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    // Only the texture of zeroes is a single color, the I4 stripes repeat a
+    // byte but not a texel
+    assert_eq!(placeholders("config.yml"), ["textures/oversized"]);
+    // Every 2x2 texture is small enough, but the RGBA32 ones are kept
+    let skipped = placeholders("placeholders.yml");
+    assert!(skipped.iter().any(|entry| entry == "textures/i4"));
+    assert!(
+        !skipped
+            .iter()
+            .any(|entry| entry.starts_with("textures/rgba32"))
+    );
+    assert!(!output.join("textures/i4.png").exists());
+    assert!(output.join("textures/rgba32.png").exists());
+}
+
+#[test]
+fn diagrams_tmem_loads_of_display_lists() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-tmem");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--types=display-list", "--tmem-svg"]);
+
+    // The 2x2 CI4 texture takes one line, its 16 entry TLUT a line per entry
+    let svg = std::fs::read_to_string(output.join("models/model.tmem.svg")).unwrap();
+    assert!(svg.contains("<title>textures/ci4: 0x000..0x008</title>"));
+    assert!(svg.contains("<title>textures/tlut: 0x800..0x880</title>"));
+    assert!(!svg.contains("conflict"));
+}
+
+#[test]
+fn version_info_lists_capabilities() {
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("--version-info=json")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("\"archive_formats\": [\n    \"o2r\"\n  ]"));
+    assert!(stdout.contains("\"name\": \"GrayscaleAlpha1bpp\""));
+    assert!(stdout.contains("\"type\": \"Texture\",\n          \"version\": 2"));
+}
+
+#[test]
+fn reads_nested_yaml_definitions() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-nested");
+    let _ = std::fs::remove_dir_all(&output);
+    let config = format!("--config={}/config_nested.yml", FIXTURES);
+    convert(
+        &output,
+        &[&config, "--yaml-dialect=nested", "--types=texture"],
+    );
+
+    // The TLUTs are only found in the lists of the nested definitions
+    for name in ["ci4", "ci8"] {
+        assert_eq!(
+            rgba(&output, &format!("textures/{}.png", name)),
+            RGBA,
+            "{}",
+            name
+        );
+    }
+}
+
+/// Sends the JSON-RPC `requests`, one per line, to the converter serving
+/// with `--serve-rpc`, returning its responses.
+fn rpc(output: &Path, requests: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg(format!("--output={}", output.display()))
+        .arg("--serve-rpc")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the converter");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(requests.as_bytes())
+        .unwrap();
+    let result = child.wait_with_output().unwrap();
+    assert!(result.status.success());
+    String::from_utf8_lossy(&result.stdout).into_owned()
+}
+
+#[test]
+fn rpc_writes_outputs_only_inside_the_output_folder() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-rpc");
+    let _ = std::fs::remove_dir_all(&output);
+    let decode = |id: u32, output: &str| {
+        format!(
+            "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"method\": \"decode\", \"params\": {{\"path\": \"textures/rgba32\", \"output\": {:?}}}}}\n",
+            id, output
+        )
+    };
+    let requests = [
+        decode(1, "previews/rgba32.png"),
+        decode(2, "../escaped.png"),
+        decode(3, "/tmp/absolute.png"),
+        decode(4, "previews/../../escaped.png"),
+    ]
+    .concat();
+    let responses = rpc(&output, &requests);
+    let responses = responses.lines().collect::<Vec<_>>();
+
+    assert_eq!(responses.len(), 4, "{:?}", responses);
+    assert!(responses[0].contains("\"result\""), "{}", responses[0]);
+    assert_eq!(rgba(&output, "previews/rgba32.png"), RGBA);
+    for response in &responses[1..] {
+        assert!(response.contains("\"code\":-32602"), "{}", response);
+    }
+    assert!(!output.join("../escaped.png").exists());
+    assert!(!Path::new("/tmp/absolute.png").exists());
+}
+
+#[test]
+fn rpc_decodes_to_bare_rgba_texels() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-rpc-rgba8");
+    let _ = std::fs::remove_dir_all(&output);
+    let decode = |id: u32, path: &str, format: &str| {
+        format!(
+            "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"method\": \"decode\", \"params\": {{\"path\": {:?}, \"format\": {:?}, \"output\": \"{}.rgba\"}}}}\n",
+            id, path, format, path
+        )
+    };
+    let requests = [
+        decode(1, "textures/ci4", "rgba8"),
+        decode(2, "textures/ia8", "rgba8"),
+        decode(3, "textures/rgba32", "bmp"),
+    ]
+    .concat();
+    let responses = rpc(&output, &requests);
+    let responses = responses.lines().collect::<Vec<_>>();
+
+    assert_eq!(responses.len(), 3, "{:?}", responses);
+    assert!(responses[0].contains("\"result\""), "{}", responses[0]);
+    assert_eq!(
+        std::fs::read(output.join("textures/ci4.rgba")).unwrap(),
+        RGBA
+    );
+    // Grayscale goes to the three color channels
+    assert_eq!(
+        std::fs::read(output.join("textures/ia8.rgba")).unwrap(),
+        [
+            255, 255, 255, 255, 0, 0, 0, 255, 255, 255, 255, 0, 0x88, 0x88, 0x88, 0x88
+        ]
+    );
+    assert!(responses[2].contains("\"code\":-32602"), "{}", responses[2]);
+    assert!(!output.join("textures/rgba32.rgba").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn serve_socket_only_replaces_stale_sockets() {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let folder = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-socket");
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let serve = |path: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg(format!("{}/mini.o2r", FIXTURES))
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .arg("--serve-rpc")
+            .arg(format!("--serve-socket={}", path.display()))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to run the converter")
+    };
+
+    let file = folder.join("file");
+    std::fs::write(&file, "keep me").unwrap();
+    assert!(!serve(&file).wait().unwrap().success());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+
+    let live = folder.join("live.sock");
+    let _listener = UnixListener::bind(&live).unwrap();
+    assert!(!serve(&live).wait().unwrap().success());
+    assert!(UnixStream::connect(&live).is_ok());
+
+    let stale = folder.join("stale.sock");
+    drop(UnixListener::bind(&stale).unwrap());
+    let mut server = serve(&stale);
+    let started = std::time::Instant::now();
+    while UnixStream::connect(&stale).is_err() {
+        assert!(server.try_wait().unwrap().is_none(), "The server exited");
+        assert!(started.elapsed().as_secs() < 30, "The server didn't listen");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
+
+#[test]
+fn refuses_foreign_output_folders_unless_cleared() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-foreign");
+    let _ = std::fs::remove_dir_all(&output);
+    std::fs::create_dir_all(&output).unwrap();
+    std::fs::write(output.join("notes.txt"), "not from a conversion").unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg(format!("--output={}", output.display()))
+        .output()
+        .expect("Failed to run the converter");
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert_eq!(result.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("--clear-output"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(output.join("notes.txt").exists());
+
+    convert(&output, &["--clear-output"]);
+    assert!(!output.join("notes.txt").exists());
+    assert_eq!(rgba(&output, "textures/rgba32.png"), RGBA);
+}
+
+#[test]
+fn legacy_mode_matches_the_old_tool() {
+    let folder = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-legacy");
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(folder.join("assets")).unwrap();
+    std::fs::write(
+        folder.join("config.yml"),
+        format!("mini:\n  path: {}/yaml\n", FIXTURES),
+    )
+    .unwrap();
+    // The old tool emptied `assets` whatever was in it
+    std::fs::write(folder.join("assets/stale.png"), "").unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(&folder)
+        .args(["legacy", &format!("{}/mini.o2r", FIXTURES)])
+        .output()
+        .expect("Failed to run the converter");
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let assets = folder.join("assets");
+    assert!(!assets.join("stale.png").exists());
+
+    // The old tool's scale_*_8, `value * 255 / max` rounded down
+    let scale = |value: u8, bits: u32| (value as u32 * 255 / ((1 << bits) - 1)) as u8;
+    for name in ["rgba32", "rgba16", "ci4", "ci8"] {
+        assert_eq!(
+            rgba(&assets, &format!("textures/{}.png", name)),
+            RGBA,
+            "{}",
+            name
+        );
+    }
+    let grayscale = [
+        // 0x0F 0x84
+        (
+            "i4",
+            [
+                0,
+                0,
+                255,
+                255,
+                scale(8, 4),
+                scale(8, 4),
+                scale(4, 4),
+                scale(4, 4),
+            ],
+        ),
+        // 0xF0 0x9E, 3-bit intensity and a 1-bit alpha
+        ("ia4", [255, 255, 0, 0, scale(4, 3), 255, 255, 0]),
+        // 0xFF 0x0F 0xF0 0x88
+        ("ia8", [255, 255, 0, 255, 255, 0, scale(8, 4), scale(8, 4)]),
+    ];
+    for (name, pixels) in grayscale {
+        assert_eq!(
+            luma_alpha(&assets, &format!("textures/{}.png", name)),
+            pixels,
+            "{}",
+            name
+        );
+    }
+    // Off by one from the replicated 146 the converter writes by default
+    assert_eq!(scale(4, 3), 145);
+}
+
+#[test]
+fn legacy_mode_writes_the_old_tool_output() {
+    let folder = Path::new(env!("CARGO_TARGET_TMPDIR")).join("legacy-golden");
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(
+        folder.join("config.yml"),
+        format!("mini:\n  path: {}/yaml\n", FIXTURES),
+    )
+    .unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(&folder)
+        .args(["legacy", &format!("{}/legacy.o2r", FIXTURES)])
+        .output()
+        .expect("Failed to run the converter");
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    // tests/fixtures/legacy holds what the tool wrote for legacy.o2r before
+    // it took any option, no manifest or journal next to the images
+    let files = |folder: &Path| {
+        let mut files = std::fs::read_dir(folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    };
+    let golden = Path::new(FIXTURES).join("legacy");
+    let assets = folder.join("assets");
+    assert_eq!(files(&assets), ["textures"]);
+    assert_eq!(
+        files(&assets.join("textures")),
+        files(&golden.join("textures"))
+    );
+    for name in files(&golden.join("textures")) {
+        assert_eq!(
+            std::fs::read(assets.join("textures").join(&name)).unwrap(),
+            std::fs::read(golden.join("textures").join(&name)).unwrap(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn writes_engine_import_settings_from_display_lists() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-engine-meta");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--types=texture", "--engine-meta=godot"]);
+
+    // The display list mirrors ci4 on s, clamps it on t and turns filtering
+    // off, textures it doesn't load keep the defaults
+    let import = std::fs::read_to_string(output.join("textures/ci4.png.import")).unwrap();
+    assert!(import.contains("source_file=\"res://textures/ci4.png\"\n"));
+    assert!(import.contains("flags/repeat=2\n"));
+    assert!(import.contains("flags/filter=false\n"));
+    let import = std::fs::read_to_string(output.join("textures/i4.png.import")).unwrap();
+    assert!(import.contains("source_file=\"res://textures/i4.png\"\n"));
+    assert!(import.contains("flags/repeat=1\n"));
+    assert!(import.contains("flags/filter=true\n"));
+    assert!(import.contains("flags/srgb=0\n"));
+
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--types=texture", "--engine-meta=unity"]);
+    let meta = std::fs::read_to_string(output.join("textures/ci4.png.meta")).unwrap();
+    assert!(meta.contains("filterMode: 0\n"));
+    assert!(meta.contains("wrapU: 2\n"));
+    assert!(meta.contains("wrapV: 1\n"));
+}
+
+/// Mounts mini.o2r and reads a texture back as a PNG. Skipped where FUSE
+/// can't be mounted: without /dev/fuse or fusermount on Linux, or without
+/// macFUSE on macOS.
+#[cfg(all(feature = "fuse", any(target_os = "linux", target_os = "macos")))]
+#[test]
+fn mounts_archives_with_fuse() {
+    if cfg!(target_os = "linux") && !Path::new("/dev/fuse").exists() {
+        eprintln!("Skipping, /dev/fuse is unavailable");
+        return;
+    }
+    let mountpoint = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-mount");
+    let _ = std::fs::create_dir_all(&mountpoint);
+    let mut server = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg("mount")
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(&mountpoint)
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the converter");
+
+    let texture = mountpoint.join("textures/rgba32.png");
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !texture.exists() {
+        if let Some(status) = server.try_wait().unwrap() {
+            let mut stderr = String::new();
+            std::io::Read::read_to_string(&mut server.stderr.take().unwrap(), &mut stderr).unwrap();
+            eprintln!("Skipping, mounting failed with {}: {}", status, stderr);
+            return;
+        }
+        if std::time::Instant::now() > deadline {
+            server.kill().unwrap();
+            panic!("The archive wasn't mounted in time");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+
+    let png = std::fs::read(&texture);
+    let listing = std::fs::read_dir(mountpoint.join("textures")).map(|entries| {
+        entries
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<BTreeSet<_>>()
+    });
+    let unmounted = ["fusermount3", "fusermount", "umount"]
+        .iter()
+        .any(|program| {
+            let mut command = Command::new(program);
+            if program.starts_with("fusermount") {
+                command.arg("-u");
+            }
+            command
+                .arg(&mountpoint)
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        });
+    if !unmounted {
+        server.kill().unwrap();
+    }
+    // The server stops once the mount goes away
+    server.wait().unwrap();
+    assert!(unmounted, "Failed to unmount {}", mountpoint.display());
+
+    let image = image::load_from_memory(&png.unwrap()).unwrap();
+    assert_eq!(image.to_rgba8().into_raw(), RGBA);
+    let listing = listing.unwrap();
+    assert!(listing.contains("ci4.png"));
+    // TLUTs aren't textures of their own
+    assert!(!listing.contains("tlut.png"));
+}
+
+#[test]
+fn keeps_hostile_entry_names_inside_the_output_folder() {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-hostile");
+    let _ = std::fs::remove_dir_all(&root);
+    let output = root.join("parent/out");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg(format!("{}/hostile.o2r", FIXTURES))
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .arg(format!("--output={}", output.display()))
+            .args(args)
+            .output()
+            .expect("Failed to run the converter")
+    };
+    let files = || {
+        walkdir::WalkDir::new(&root)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let path = entry.path().strip_prefix(&root).unwrap();
+                path.to_string_lossy().replace('\\', "/")
+            })
+            .collect::<BTreeSet<_>>()
+    };
+
+    // ../../evil, C:\x and /abs are written sanitized
+    let result = run(&[]);
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("3 entries have paths leaving the output folder"));
+    let expected = [
+        "parent/out/C_/x.png",
+        "parent/out/abs.png",
+        "parent/out/evil.png",
+        "parent/out/journal.jsonl",
+        "parent/out/manifest.json",
+    ];
+    assert_eq!(files(), expected.into_iter().map(str::to_owned).collect());
+    assert_eq!(rgba(&output, "evil.png"), RGBA);
+    assert!(!Path::new("/abs.png").exists());
+    assert!(
+        !Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("C:\\x.png")
+            .exists()
+    );
+
+    let _ = std::fs::remove_dir_all(&root);
+    let result = run(&["--strict"]);
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Entries with unsafe paths, run without --strict to write them sanitized")
+    );
+    assert!(files().iter().all(|file| !file.ends_with(".png")));
+}
+
+#[test]
+fn undoes_word_swapped_texels() {
+    // Texels past the 64-byte header and the type, width, height and size
+    let swapped = |name: &str, word_size: usize| {
+        let mut resource = archive_entry(name);
+        for word in resource[80..].chunks_exact_mut(word_size) {
+            word.reverse();
+        }
+        resource
+    };
+    let rgba16 = swapped("textures/rgba16", 2);
+    assert_ne!(pipe(&rgba16, &[]), RGBA);
+    assert_eq!(pipe(&rgba16, &["--swap=16"]), RGBA);
+    let rgba32 = swapped("textures/rgba32", 4);
+    assert_eq!(pipe(&rgba32, &["--swap=32"]), RGBA);
+    // Texels stored in order are left alone
+    assert_eq!(
+        pipe(&archive_entry("textures/rgba32"), &["--swap=none"]),
+        RGBA
+    );
+}
+
+#[test]
+fn writes_unreal_import_settings() {
+    use convert_texture_o2r::json::Json;
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-engine-unreal");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--types=texture", "--engine-meta=unreal"]);
+
+    let settings = |name: &str| {
+        let path = output.join(format!("textures/{}.png.json", name));
+        Json::parse(&std::fs::read_to_string(path).unwrap()).unwrap()
+    };
+    let ci4 = settings("ci4");
+    assert_eq!(ci4.get("SRGB"), Some(&Json::Bool(true)));
+    assert_eq!(ci4.get("Filter").and_then(Json::as_str), Some("TF_Nearest"));
+    assert_eq!(
+        ci4.get("AddressX").and_then(Json::as_str),
+        Some("TA_Mirror")
+    );
+    assert_eq!(ci4.get("AddressY").and_then(Json::as_str), Some("TA_Clamp"));
+    // Intensity textures are masks, kept linear
+    let i4 = settings("i4");
+    assert_eq!(i4.get("SRGB"), Some(&Json::Bool(false)));
+    assert_eq!(i4.get("Filter").and_then(Json::as_str), Some("TF_Bilinear"));
+    assert_eq!(i4.get("AddressX").and_then(Json::as_str), Some("TA_Wrap"));
+}
+
+#[test]
+fn rpc_lists_and_decodes_entries() {
+    use convert_texture_o2r::json::Json;
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-rpc-calls");
+    let _ = std::fs::remove_dir_all(&output);
+    let requests = [
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "list", "params": {"prefix": "courses/mario"}}"#,
+        // A notification, answered with nothing
+        r#"{"jsonrpc": "2.0", "method": "list"}"#,
+        r#"{"jsonrpc": "2.0", "id": 2, "method": "decode", "params": {"path": "textures/ci4", "format": "rgba8"}}"#,
+        r#"{"jsonrpc": "2.0", "id": 3, "method": "frobnicate"}"#,
+        r#"{"jsonrpc": "2.0", "id": 4, "method": "shutdown"}"#,
+        r#"{"jsonrpc": "2.0", "id": 5, "method": "list"}"#,
+    ]
+    .join("\n");
+    let responses = rpc(&output, &requests);
+    let responses = responses
+        .lines()
+        .map(|line| Json::parse(line).unwrap())
+        .collect::<Vec<_>>();
+
+    // Nothing is read past the shutdown
+    let ids = responses
+        .iter()
+        .map(|response| response.get("id").cloned())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [1.0, 2.0, 3.0, 4.0].map(|id| Some(Json::Number(id))));
+
+    let Some(Json::Array(entries)) = responses[0].get("result") else {
+        panic!("list didn't return an array: {}", responses[0]);
+    };
+    let paths = entries
+        .iter()
+        .filter_map(|entry| entry.get("path").and_then(Json::as_str))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "courses/mario_raceway/course_tlut",
+            "courses/mario_raceway/road"
+        ]
+    );
+
+    let decoded = responses[1].get("result").unwrap();
+    assert_eq!(
+        decoded.get("format").and_then(Json::as_str),
+        Some("Palette4bpp")
+    );
+    assert_eq!(
+        decoded.get("rgba8").and_then(Json::as_str),
+        Some("/wAA/wD/AP8AAP//AAAAAA==")
+    );
+
+    let error = responses[2].get("error").unwrap();
+    assert_eq!(error.get("code"), Some(&Json::Number(-32601.0)));
+}
+
+#[test]
+fn replaces_a_texture_with_an_edited_png() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let run = |pixels: &[u8], name: &str| {
+        let image = dir.join(format!("mini-replace-{}.png", name));
+        image::RgbaImage::from_raw(2, 2, pixels.to_vec())
+            .unwrap()
+            .save(&image)
+            .unwrap();
+        let output = dir.join(format!("mini-replace-{}.o2r", name));
+        let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .arg("replace")
+            .arg(format!("{}/mini.o2r", FIXTURES))
+            .arg("textures/rgba16")
+            .arg(&image)
+            .arg(&output)
+            .output()
+            .expect("Failed to run the converter");
+        (result, output)
+    };
+    let replace = |pixels: &[u8], name: &str| {
+        let (result, output) = run(pixels, name);
+        assert!(
+            result.status.success(),
+            "{}",
+            String::from_utf8_lossy(&result.stderr)
+        );
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(output).unwrap()).unwrap();
+        let mut read = |entry: &str| {
+            let mut data = Vec::new();
+            std::io::Read::read_to_end(&mut zip.by_name(entry).unwrap(), &mut data).unwrap();
+            data
+        };
+        (read("textures/rgba16"), read("textures/rgba32"))
+    };
+
+    // The unedited image gives back the resource byte for byte
+    let (rgba16, rgba32) = replace(&RGBA, "unedited");
+    assert_eq!(rgba16, archive_entry("textures/rgba16"));
+    assert_eq!(rgba32, archive_entry("textures/rgba32"));
+
+    // Transparent black, blue, green and red, the fixture's texels reversed
+    let mut reversed = RGBA
+        .chunks_exact(4)
+        .rev()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    let (rgba16, rgba32) = replace(&reversed, "reversed");
+    assert_eq!(pipe(&rgba16, &[]), reversed);
+    assert_eq!(rgba32, archive_entry("textures/rgba32"));
+
+    // Colors RGBA5551 can't hold are refused rather than rounded
+    reversed[4] = 0x07;
+    let (result, _) = run(&reversed, "unrepresentable");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Pixel (1, 0) [7, 0, 255, 255] can't be represented as RGBA16bpp")
+    );
+}
+
+#[test]
+fn maps_archive_prefixes_to_output_folders() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let config = dir.join("mini-path-map.yml");
+    std::fs::write(
+        &config,
+        format!(
+            "mini:\n  path: {}/yaml\n  path_map:\n    textures/: tex/\n    textures/ia: alpha/\n",
+            FIXTURES
+        ),
+    )
+    .unwrap();
+    let output = dir.join("mini-path-map");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(
+        &output,
+        &[
+            "--types=texture",
+            "--compressed-texels",
+            &format!("--config={}", config.display()),
+        ],
+    );
+
+    assert_eq!(rgba(&output, "tex/rgba32.png"), RGBA);
+    // The longest matching prefix wins
+    assert!(output.join("alpha/4.png").exists());
+    assert!(output.join("alpha/16.png").exists());
+    assert!(!output.join("tex/ia4.png").exists());
+    assert!(!output.join("textures").exists());
+    // Unmapped entries keep their archive path
+    assert!(output.join("courses/mario_raceway/road.png").exists());
+
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    assert!(manifest.contains("\"output\": \"alpha/4.png\""));
+}
+
+#[test]
+fn warns_about_indices_past_the_end_of_the_tlut() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-overflow");
+    let run = |args: &[&str]| {
+        let _ = std::fs::remove_dir_all(&output);
+        Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg(format!("{}/overflow.o2r", FIXTURES))
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .arg(format!("--output={}", output.display()))
+            .args(args)
+            .output()
+            .expect("Failed to run the converter")
+    };
+
+    // The texture is still written
+    let result = run(&[]);
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains(
+            "Texture textures/ci8 uses palette index 255 but its TLUT only has 16 entries"
+        )
+    );
+    assert!(stdout.contains("1 textures use palette indices past the end of their TLUT"));
+    assert!(stdout.contains("  textures/ci8: index 255 with 16 TLUT entries"));
+    assert_eq!(rgba(&output, "textures/ci8.png")[..8], RGBA[..8]);
+
+    let result = run(&["--strict"]);
+    assert!(!result.status.success());
+    assert!(!output.join("textures/ci8.png").exists());
+}
+
+#[test]
+fn decodes_extended_ci_textures() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-ci16");
+    let _ = std::fs::remove_dir_all(&output);
+
+    let (_, stderr) = convert_archive(&Path::new(FIXTURES).join("ci16.o2r"), &output, &[]);
+    // Indices past 255 are read from the TLUT, the one past its end is transparent
+    assert_eq!(rgba(&output, "textures/ci16.png"), RGBA);
+    assert_eq!(rgba(&output, "textures/ci16_stride.png"), RGBA);
+    assert!(stderr.contains(
+        "Texture textures/ci16 uses palette index 4095 but its TLUT only has 300 entries"
+    ));
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    assert!(manifest.contains("\"format\": \"CI16\""));
+}
+
+#[test]
+fn exports_text_with_readable_control_codes() {
+    let mut payload = 1u32.to_le_bytes().to_vec();
+    payload.extend(0x1234u16.to_le_bytes());
+    payload.extend([2, 0x10]);
+    let content = b"Hi \"Link\"\x01\x05\x41Go\x02\xFF";
+    payload.extend((content.len() as u32).to_le_bytes());
+    payload.extend(content);
+    let archive = write_archive(
+        "mini-text.o2r",
+        &[("text/message_data", resource(0x4F545854, &payload))],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-text");
+    let _ = std::fs::remove_dir_all(&output);
+
+    convert_archive(&archive, &output, &[]);
+    let json = std::fs::read_to_string(output.join("text/message_data.json")).unwrap();
+    assert!(json.contains("\"id\": \"0x1234\""));
+    assert!(json.contains("\"textbox_type\": 2"));
+    assert!(json.contains("\"textbox_y_pos\": 16"));
+    assert!(json.contains(r#""content": "Hi \"Link\"\n<COLOR:41>Go<END><0xFF>""#));
+
+    convert_archive(&archive, &output, &["--text-format=csv"]);
+    assert_eq!(
+        std::fs::read_to_string(output.join("text/message_data.csv")).unwrap(),
+        "id,textbox_type,textbox_y_pos,content\n0x1234,2,16,\"Hi \"\"Link\"\"\n<COLOR:41>Go<END><0xFF>\"\n"
+    );
+}
+
+#[test]
+fn exports_audio_samples_and_sequences() {
+    let sample = |codec: u8, data: &[u8], order: i32, book: &[i16]| {
+        let mut payload = vec![codec, 0, 0, 0];
+        payload.extend((data.len() as u32).to_le_bytes());
+        payload.extend(data);
+        // Loop start, end and count, no loop state
+        for field in [0u32, 16, 0, 0] {
+            payload.extend(field.to_le_bytes());
+        }
+        payload.extend(order.to_le_bytes());
+        payload.extend(1i32.to_le_bytes());
+        payload.extend((book.len() as u32).to_le_bytes());
+        payload.extend(book.iter().flat_map(|value| value.to_le_bytes()));
+        resource(0x4F534D50, &payload)
+    };
+    let mut sequence = 3u32.to_le_bytes().to_vec();
+    sequence.extend([0xD3, 0x20, 0xFF, 7, 2, 0]);
+    sequence.extend(2u32.to_le_bytes());
+    sequence.extend([1, 4]);
+    let archive = write_archive(
+        "mini-audio.o2r",
+        &[
+            ("audio/pcm", sample(5, &[0x12, 0x34, 0xFF, 0xFE], 0, &[])),
+            // A zero codebook leaves the scaled residuals as they are
+            (
+                "audio/adpcm",
+                sample(0, &[0x10, 0x17, 0x8F, 0, 0, 0, 0, 0, 0], 2, &[0; 16]),
+            ),
+            ("audio/small_adpcm", sample(3, &[0; 9], 0, &[])),
+            ("audio/sequence", resource(0x4F534551, &sequence)),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-audio");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(&archive, &output, &[]);
+
+    let samples = |name: &str| {
+        let wav = std::fs::read(output.join(name)).unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 32000);
+        wav[44..]
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(samples("audio/pcm.wav"), [0x1234, -2]);
+    let mut expected = [0; 16];
+    expected[..4].copy_from_slice(&[2, 14, -16, -2]);
+    assert_eq!(samples("audio/adpcm.wav"), expected);
+    let metadata = std::fs::read_to_string(output.join("audio/adpcm.json")).unwrap();
+    assert!(metadata.contains("\"codec\": \"Adpcm\""));
+    assert!(metadata.contains("\"order\": 2"));
+
+    // Codecs without a decoder keep their metadata
+    assert!(output.join("audio/small_adpcm.json").exists());
+    assert!(!output.join("audio/small_adpcm.wav").exists());
+
+    assert_eq!(
+        std::fs::read(output.join("audio/sequence.seq")).unwrap(),
+        [0xD3, 0x20, 0xFF]
+    );
+    let metadata = std::fs::read_to_string(output.join("audio/sequence.json")).unwrap();
+    assert!(metadata.contains("\"number\": 7"));
+    assert!(metadata.contains("\"fonts\": [\n    1,\n    4\n  ]"));
+}
+
+#[test]
+fn exports_skeletons_and_animations_to_gltf() {
+    use convert_texture_o2r::json::Json;
+
+    let string = |value: &str| [&(value.len() as u32).to_le_bytes()[..], value.as_bytes()].concat();
+    let limb = |translation: [i16; 3], child: u8| {
+        let mut payload = vec![1, 0];
+        payload.extend([0; 10]);
+        payload.extend(string(""));
+        payload.extend([0; 18]);
+        for _ in 0..4 {
+            payload.extend(string(""));
+        }
+        payload.extend(translation.iter().flat_map(|value| value.to_le_bytes()));
+        payload.extend([child, 0xFF]);
+        resource(0x4F534C42, &payload)
+    };
+    let mut skeleton = vec![0, 1];
+    skeleton.extend([0; 9]);
+    skeleton.extend(2u32.to_le_bytes());
+    skeleton.extend(string("__OTR__objects/link/root_limb"));
+    skeleton.extend(string("objects/link/arm_limb"));
+    // Two frames moving the root up, the limbs not rotating
+    let mut animation = 0u32.to_le_bytes().to_vec();
+    animation.extend(2i16.to_le_bytes());
+    animation.extend(4u32.to_le_bytes());
+    animation.extend(
+        [0i16, 100, 200, 300]
+            .iter()
+            .flat_map(|value| value.to_le_bytes()),
+    );
+    animation.extend(3u32.to_le_bytes());
+    for indices in [[0u16, 1, 2], [0, 0, 0], [0, 0, 0]] {
+        animation.extend(indices.iter().flat_map(|index| index.to_le_bytes()));
+    }
+    animation.extend(2u16.to_le_bytes());
+    let archive = write_archive(
+        "mini-skeleton.o2r",
+        &[
+            ("objects/link/skeleton", resource(0x4F534B4C, &skeleton)),
+            ("objects/link/root_limb", limb([0, 0, 0], 1)),
+            ("objects/link/arm_limb", limb([10, 20, 30], 0xFF)),
+            ("objects/link/walk", resource(0x4F414E4D, &animation)),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-skeleton");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(&archive, &output, &[]);
+
+    let gltf = std::fs::read_to_string(output.join("objects/link/skeleton.gltf")).unwrap();
+    let gltf = Json::parse(&gltf).unwrap();
+    let Some(Json::Array(nodes)) = gltf.get("nodes") else {
+        panic!("No nodes in {}", gltf.pretty());
+    };
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(nodes[0].get("name").unwrap().as_str(), Some("root_limb"));
+    assert_eq!(
+        nodes[0].get("children").unwrap().pretty(),
+        Json::Array(vec![Json::from(1usize)]).pretty()
+    );
+    assert_eq!(nodes[1].get("name").unwrap().as_str(), Some("arm_limb"));
+    assert_eq!(
+        nodes[1].get("translation").unwrap().pretty(),
+        Json::parse("[10, 20, 30]").unwrap().pretty()
+    );
+
+    let Some(Json::Array(animations)) = gltf.get("animations") else {
+        panic!("No animations in {}", gltf.pretty());
+    };
+    assert_eq!(animations[0].get("name").unwrap().as_str(), Some("walk"));
+    let Some(Json::Array(channels)) = animations[0].get("channels") else {
+        panic!("No channels in {}", gltf.pretty());
+    };
+    assert_eq!(channels.len(), 3);
+
+    let buffer = std::fs::read(output.join("objects/link/skeleton.bin")).unwrap();
+    let Some(Json::Array(buffers)) = gltf.get("buffers") else {
+        panic!("No buffers in {}", gltf.pretty());
+    };
+    assert_eq!(
+        buffers[0].get("uri").unwrap().as_str(),
+        Some("skeleton.bin")
+    );
+    assert_eq!(
+        buffers[0].get("byteLength").unwrap().as_f64(),
+        Some(buffer.len() as f64)
+    );
+    // Root translations of both frames, one after the other
+    let translations = buffer
+        .chunks_exact(4)
+        .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert!(
+        translations
+            .windows(6)
+            .any(|values| values == [0.0, 100.0, 200.0, 0.0, 100.0, 300.0])
+    );
+}
+
+#[test]
+fn exports_collision_meshes_and_water_boxes() {
+    use convert_texture_o2r::json::Json;
+
+    // Bounds and vertices
+    let mut payload = [-10i16, 0, -10, 10, 5, 10]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect::<Vec<_>>();
+    payload.extend(4u32.to_le_bytes());
+    for vertex in [[-10i16, 0, -10], [10, 0, -10], [10, 0, 10], [-10, 0, 10]] {
+        payload.extend(vertex.iter().flat_map(|value| value.to_le_bytes()));
+    }
+    // The first polygon has a flag in the top bits of its first vertex
+    payload.extend(2u32.to_le_bytes());
+    for [surface_type, a, b, c] in [[1u16, 0x2000, 1, 2], [0, 0, 2, 3]] {
+        for field in [surface_type, a, b, c, 0, 0x7FFF, 0, 0] {
+            payload.extend(field.to_le_bytes());
+        }
+    }
+    // Surface types, their second word first
+    payload.extend(2u32.to_le_bytes());
+    for [data1, data0] in [[0u32, 0], [0x3, 1 << 30]] {
+        payload.extend(data1.to_le_bytes());
+        payload.extend(data0.to_le_bytes());
+    }
+    // Camera data and positions, skipped
+    payload.extend(1u32.to_le_bytes());
+    payload.extend([0; 8]);
+    payload.extend(1u32.to_le_bytes());
+    payload.extend([0; 6]);
+    payload.extend(1u32.to_le_bytes());
+    for field in [-100i16, 20, -100, 200, 200] {
+        payload.extend(field.to_le_bytes());
+    }
+    payload.extend((2u32 << 13 | 3 << 8 | 5).to_le_bytes());
+    let archive = write_archive(
+        "mini-collision.o2r",
+        &[("scenes/field/collision", resource(0x4F434F4C, &payload))],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-collision");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(&archive, &output, &[]);
+
+    // Faces are grouped by surface type, the flag masked out of the index
+    assert_eq!(
+        std::fs::read_to_string(output.join("scenes/field/collision.obj")).unwrap(),
+        "v -10 0 -10\nv 10 0 -10\nv 10 0 10\nv -10 0 10\n\
+         g surface_0\nf 1 3 4\ng surface_1\nf 1 2 3\n"
+    );
+
+    let json = std::fs::read_to_string(output.join("scenes/field/collision.json")).unwrap();
+    let json = Json::parse(&json).unwrap();
+    assert_eq!(json.get("vertex_count").unwrap().as_f64(), Some(4.0));
+    let Some(Json::Array(polygons)) = json.get("polygons") else {
+        panic!("No polygons in {}", json.pretty());
+    };
+    assert_eq!(polygons[0].get("flags").unwrap().as_f64(), Some(1.0));
+    assert_eq!(polygons[1].get("flags").unwrap().as_f64(), Some(0.0));
+    let Some(Json::Array(surface_types)) = json.get("surface_types") else {
+        panic!("No surface types in {}", json.pretty());
+    };
+    assert!(matches!(
+        surface_types[1].get("is_soft"),
+        Some(Json::Bool(true))
+    ));
+    assert_eq!(
+        surface_types[1].get("material").unwrap().as_f64(),
+        Some(3.0)
+    );
+    let Some(Json::Array(water_boxes)) = json.get("water_boxes") else {
+        panic!("No water boxes in {}", json.pretty());
+    };
+    let water_box = &water_boxes[0];
+    assert_eq!(water_box.get("y_surface").unwrap().as_f64(), Some(20.0));
+    assert_eq!(water_box.get("camera").unwrap().as_f64(), Some(5.0));
+    assert_eq!(water_box.get("light").unwrap().as_f64(), Some(3.0));
+    assert_eq!(water_box.get("room").unwrap().as_f64(), Some(2.0));
+}
+
+#[test]
+fn dumps_scene_commands_up_to_an_unknown_one() {
+    use convert_texture_o2r::json::Json;
+
+    let path = "__OTR__scenes/field/collision";
+    let mut payload = 4u32.to_le_bytes().to_vec();
+    payload.extend(0x03i32.to_le_bytes());
+    payload.extend((path.len() as u32).to_le_bytes());
+    payload.extend(path.as_bytes());
+    // One environment light setting: ambient, light 1 and 2, fog
+    payload.extend(0x0Fi32.to_le_bytes());
+    payload.extend(1u32.to_le_bytes());
+    payload.extend([
+        10, 20, 30, 0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 255, 0, 0, 0, 255,
+    ]);
+    payload.extend(996i16.to_le_bytes());
+    payload.extend(3200u16.to_le_bytes());
+    payload.extend(0x10i32.to_le_bytes());
+    payload.extend([12, 30, 1]);
+    // Its size isn't known, so nothing after it can be read
+    payload.extend(0x30i32.to_le_bytes());
+    payload.extend([0; 16]);
+    let archive = write_archive(
+        "mini-scene.o2r",
+        &[("scenes/field/scene", resource(0x4F524F4D, &payload))],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-scene");
+    let _ = std::fs::remove_dir_all(&output);
+    let (_, stderr) = convert_archive(&archive, &output, &[]);
+    assert!(stderr.contains(
+        "Scene scenes/field/scene only partially decoded: Unsupported scene command 0x30"
+    ));
+
+    let scene = std::fs::read_to_string(output.join("scenes/field/scene.json")).unwrap();
+    let scene = Json::parse(&scene).unwrap();
+    let Some(Json::Array(commands)) = scene.get("commands") else {
+        panic!("No commands in {}", scene.pretty());
+    };
+    assert_eq!(commands.len(), 3);
+    assert_eq!(
+        commands[0].get("path").unwrap().as_str(),
+        Some("scenes/field/collision")
+    );
+    assert_eq!(
+        commands[1].get("command").unwrap().as_str(),
+        Some("env_light_settings")
+    );
+    assert_eq!(commands[2].get("hour").unwrap().as_f64(), Some(12.0));
+
+    // A row of ambient, light and fog colors
+    let swatches = image::open(output.join("scenes/field/scene_lights.png"))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(swatches.width(), swatches.height() * 4);
+    assert_eq!(swatches.get_pixel(0, 0).0, [10, 20, 30, 255]);
+    let last = swatches.width() - 1;
+    assert_eq!(swatches.get_pixel(last, 0).0, [0, 0, 255, 255]);
+}
+
+#[test]
+fn exports_cutscenes_as_macros_or_json() {
+    use convert_texture_o2r::json::Json;
+
+    let cutscene = |words: &[u32]| {
+        let mut payload = (words.len() as u32).to_le_bytes().to_vec();
+        payload.extend(words.iter().flat_map(|word| word.to_le_bytes()));
+        resource(0x4F435554, &payload)
+    };
+    let archive = write_archive(
+        "mini-cutscene.o2r",
+        &[
+            (
+                "scenes/field/intro",
+                cutscene(&[
+                    2,
+                    100,
+                    // CS_TRANSITION
+                    0x2D,
+                    1,
+                    1 << 16 | 10,
+                    20 << 16,
+                    // CS_TEXT_LIST, a message and no message
+                    0x13,
+                    2,
+                    0x1234 << 16,
+                    30 << 16,
+                    0,
+                    0xFFFF << 16 | 30,
+                    40 << 16,
+                    0,
+                    // CS_CAM_EYE_SPLINE of a single point
+                    0x01,
+                    0,
+                    50 << 16,
+                    0xFF << 24 | 5,
+                    60f32.to_bits(),
+                    1 << 16 | 2,
+                    0xFFFD << 16,
+                    0xFFFFFFFF,
+                ]),
+            ),
+            ("scenes/field/broken", cutscene(&[1, 10, 0x0B, 0])),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-cutscene");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(&archive, &output, &["--cutscene-format=macro"]);
+    assert_eq!(
+        std::fs::read_to_string(output.join("scenes/field/intro.c")).unwrap(),
+        "CS_BEGIN_CUTSCENE(2, 100),\n\
+         CS_TRANSITION(1, 10, 20),\n\
+         CS_TEXT_LIST(2),\n    \
+         CS_TEXT(4660, 0, 30, 0, 0, 0),\n    \
+         CS_TEXT_NONE(30, 40),\n\
+         CS_CAM_EYE_SPLINE(0, 50),\n    \
+         CS_CAM_POINT(-1, 0, 5, 60.0f, 1, 2, -3, 0),\n\
+         CS_END(),\n"
+    );
+
+    // What was read before an unknown command is kept
+    let (_, stderr) = convert_archive(&archive, &output, &[]);
+    assert!(stderr.contains(
+        "Cutscene scenes/field/broken only partially decoded: Unknown cutscene command 0xB"
+    ));
+    let broken = std::fs::read_to_string(output.join("scenes/field/broken.json")).unwrap();
+    let broken = Json::parse(&broken).unwrap();
+    let Some(Json::Array(commands)) = broken.get("commands") else {
+        panic!("No commands in {}", broken.pretty());
+    };
+    assert_eq!(commands.len(), 1);
+    assert_eq!(
+        commands[0].get("command").unwrap().as_str(),
+        Some("begin_cutscene")
+    );
+    let intro = std::fs::read_to_string(output.join("scenes/field/intro.json")).unwrap();
+    assert!(intro.contains("\"command\": \"cam_point\""));
+}
+
+#[test]
+fn exports_paths_to_json_and_svg() {
+    use convert_texture_o2r::json::Json;
+
+    let mut payload = 2u32.to_le_bytes().to_vec();
+    for points in [&[[0i16, 5, 0], [100, 5, 50]][..], &[[-20, 0, 10]]] {
+        payload.extend((points.len() as u32).to_le_bytes());
+        payload.extend(
+            points
+                .iter()
+                .flatten()
+                .flat_map(|value| value.to_le_bytes()),
+        );
+    }
+    let archive = write_archive(
+        "mini-path.o2r",
+        &[("scenes/field/path", resource(0x4F505448, &payload))],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-path");
+    let _ = std::fs::remove_dir_all(&output);
+
+    convert_archive(&archive, &output, &[]);
+    let json = std::fs::read_to_string(output.join("scenes/field/path.json")).unwrap();
+    let json = Json::parse(&json).unwrap();
+    assert_eq!(
+        json.pretty(),
+        Json::parse(r#"{"paths": [[[0, 5, 0], [100, 5, 50]], [[-20, 0, 10]]]}"#)
+            .unwrap()
+            .pretty()
+    );
+    assert!(!output.join("scenes/field/path.svg").exists());
+
+    // Plotted top down, the view box around every point with a margin
+    convert_archive(&archive, &output, &["--path-svg"]);
+    let svg = std::fs::read_to_string(output.join("scenes/field/path.svg")).unwrap();
+    assert!(svg.contains("viewBox=\"-40 -20 160 90\""));
+    assert!(svg.contains("<polyline points=\"0,0 100,50\""));
+    assert!(svg.contains("<g id=\"path_1\""));
+}
+
+#[test]
+fn converts_only_the_selected_resource_types() {
+    let text = resource(0x4F545854, &0u32.to_le_bytes());
+    let mut newer_text = text.clone();
+    newer_text[8..12].copy_from_slice(&1u32.to_le_bytes());
+    let archive = write_archive(
+        "mini-types.o2r",
+        &[
+            ("misc/text", text),
+            ("misc/newer_text", newer_text),
+            ("misc/path", resource(0x4F505448, &0u32.to_le_bytes())),
+            ("misc/rgba32", archive_entry("textures/rgba32")),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-types");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, stderr) = convert_archive(&archive, &output, &["--types=text,texture"]);
+    assert!(output.join("misc/text.json").exists());
+    assert!(output.join("misc/rgba32.png").exists());
+    assert!(!output.join("misc/path.json").exists());
+    // A selected type in a version no decoder reads is skipped
+    assert!(!output.join("misc/newer_text.json").exists());
+    assert!(
+        (stdout + &stderr)
+            .contains("Skipping misc/newer_text: Unsupported Text resource version 1")
+    );
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(&archive)
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg(format!("--output={}", output.display()))
+        .arg("--types=text,sound")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Unknown resource type 'sound', expected one of texture, text, audio")
+    );
+}
+
+#[test]
+fn arranges_outputs_by_path_type_or_flat() {
+    let archive = write_archive(
+        "mini-layout.o2r",
+        &[
+            ("misc/text", resource(0x4F545854, &0u32.to_le_bytes())),
+            ("misc/rgba32", archive_entry("textures/rgba32")),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-layout");
+    for (layout, outputs) in [
+        ("by-path", ["misc/text.json", "misc/rgba32.png"]),
+        (
+            "by-type",
+            ["text/misc/text.json", "textures/misc/rgba32.png"],
+        ),
+        ("flat", ["misc_text.json", "misc_rgba32.png"]),
+    ] {
+        let _ = std::fs::remove_dir_all(&output);
+        convert_archive(&archive, &output, &[&format!("--layout={}", layout)]);
+        for file in outputs {
+            assert!(
+                output.join(file).exists(),
+                "{} missing with {}",
+                file,
+                layout
+            );
+        }
+        let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+        assert!(manifest.contains(&format!("\"output\": \"{}\"", outputs[1])));
+    }
+}
+
+#[test]
+fn reports_archive_versions_and_comment() {
+    let archive = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-metadata.o2r");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    // Little endian port version 3.0.2, big endian game CRC
+    zip.start_file("portVersion", options).unwrap();
+    zip.write_all(&[0, 3, 0, 0, 0, 2, 0]).unwrap();
+    zip.start_file("version", options).unwrap();
+    zip.write_all(&[1, 0x12, 0x34, 0x56, 0x78]).unwrap();
+    zip.start_file("textures/rgba32", options).unwrap();
+    zip.write_all(&archive_entry("textures/rgba32")).unwrap();
+    zip.set_comment(" Built for the tests \n");
+    zip.finish().unwrap();
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-metadata");
+    let _ = std::fs::remove_dir_all(&output);
+
+    let (stdout, _) = convert_archive(&archive, &output, &["--require-port-version=3.0"]);
+    assert!(stdout.contains("Port version: 3.0.2\n"));
+    assert!(stdout.contains("Game versions: 12345678\n"));
+    assert!(stdout.contains("Archive comment: Built for the tests\n"));
+    assert_eq!(rgba(&output, "textures/rgba32.png"), RGBA);
+
+    for (required, error) in [
+        (
+            "3.1",
+            "Archive was written by port version 3.0.2, 3.1 is required",
+        ),
+        ("3.x", "Invalid port version '3.x'"),
+    ] {
+        let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg(&archive)
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .arg(format!("--output={}", output.display()))
+            .arg(format!("--require-port-version={}", required))
+            .output()
+            .expect("Failed to run the converter");
+        assert!(!result.status.success());
+        assert!(String::from_utf8_lossy(&result.stderr).contains(error));
+    }
+}
+
+#[test]
+fn reads_options_from_the_environment() {
+    use convert_texture_o2r::crc64::crc64;
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-env");
+    let _ = std::fs::remove_dir_all(&output);
+    let run = |args: &[&str], env: &[(&str, &str)]| {
+        Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(args)
+            .env("CTO2R_ARCHIVE", format!("{}/mini.o2r", FIXTURES))
+            .env("CTO2R_CONFIG", format!("{}/config.yml", FIXTURES))
+            .env("CTO2R_OUTPUT", &output)
+            .env("CTO2R_LAYOUT", "flat")
+            .envs(env.iter().copied())
+            .output()
+            .expect("Failed to run the converter")
+    };
+
+    let result = run(
+        &[],
+        &[("CTO2R_TYPES", "texture"), ("CTO2R_REPRODUCIBLE", "yes")],
+    );
+    assert!(result.status.success());
+    assert_eq!(rgba(&output, "textures_rgba32.png"), RGBA);
+    assert!(output.join("SHA256SUMS").exists());
+
+    // The command line takes precedence
+    let _ = std::fs::remove_dir_all(&output);
+    let result = run(&["--layout=by-path"], &[("CTO2R_TYPES", "texture")]);
+    assert!(result.status.success());
+    assert_eq!(rgba(&output, "textures/rgba32.png"), RGBA);
+
+    // Lists too, rather than adding to them
+    let _ = std::fs::remove_dir_all(&output);
+    let id = |name: &str| format!("{:016X}", crc64(name));
+    let result = run(
+        &[&format!("--id={}", id("textures/rgba32"))],
+        &[("CTO2R_TYPES", "texture"), ("CTO2R_ID", &id("textures/i4"))],
+    );
+    assert!(result.status.success());
+    assert_eq!(rgba(&output, "textures_rgba32.png"), RGBA);
+    assert!(!output.join("textures_i4.png").exists());
+
+    let result = run(&[], &[("CTO2R_STRICT", "maybe")]);
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr).contains("Invalid value 'maybe' for CTO2R_STRICT")
+    );
+}
+
+#[test]
+fn selects_entries_by_symbol_name() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-symbols-config");
+    std::fs::create_dir_all(dir.join("yaml/courses")).unwrap();
+    std::fs::copy(
+        format!("{}/yaml/textures.yml", FIXTURES),
+        dir.join("yaml/textures.yml"),
+    )
+    .unwrap();
+    // Known to the YAML only by its offset, named in the symbol file
+    std::fs::write(
+        dir.join("yaml/courses/mario_raceway.yml"),
+        "road:\n  type: TEXTURE\n  format: CI4\n  width: 8\n  height: 8\n  offset: 0x100\n  tlut: course_tlut\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("symbols.ld"),
+        "gMarioRacewayRoadTex = 0x06000100; // size:0x20\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("config.yml"),
+        format!("mini:\n  path: {}\n", dir.join("yaml").display()),
+    )
+    .unwrap();
+    let config = format!("--config={}", dir.join("config.yml").display());
+    let symbols = format!("--symbols={}", dir.join("symbols.ld").display());
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-symbols");
+    let _ = std::fs::remove_dir_all(&output);
+
+    let (stdout, _) = convert(
+        &output,
+        &[
+            &config,
+            &symbols,
+            "--compressed-texels",
+            "--symbol=gMarioRacewayRoadTex, ci4",
+        ],
+    );
+    assert!(stdout.contains("Symbol gMarioRacewayRoadTex resolved to courses/mario_raceway/road"));
+    assert!(stdout.contains("Symbol ci4 resolved to textures/ci4"));
+    assert_eq!(rgba(&output, "textures/ci4.png"), RGBA);
+    assert!(output.join("courses/mario_raceway/road.png").exists());
+    assert!(!output.join("textures/rgba32.png").exists());
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(&config)
+        .arg(format!("--output={}", output.display()))
+        .arg("--symbol=course_tlut")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains(
+        "Symbol course_tlut is ambiguous: courses/luigi_raceway/course_tlut, courses/mario_raceway/course_tlut"
+    ));
+}
+
+#[test]
+fn selects_entries_by_resource_id() {
+    use convert_texture_o2r::crc64::crc64;
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-ids");
+    let _ = std::fs::remove_dir_all(&output);
+    let (ci4, rgba32) = (crc64("textures/ci4"), crc64("textures/rgba32"));
+    let (stdout, _) = convert(&output, &[&format!("--id={:016X}, 0x{:x}", ci4, rgba32)]);
+    assert!(stdout.contains(&format!("Id {:016X} resolved to textures/ci4", ci4)));
+    assert!(stdout.contains(&format!("Id {:016X} resolved to textures/rgba32", rgba32)));
+    assert_eq!(rgba(&output, "textures/ci4.png"), RGBA);
+    assert_eq!(rgba(&output, "textures/rgba32.png"), RGBA);
+    assert!(!output.join("textures/i4.png").exists());
+
+    let run = |id: &str| {
+        Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg(format!("{}/mini.o2r", FIXTURES))
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .arg(format!("--output={}", output.display()))
+            .arg(format!("--id={}", id))
+            .output()
+            .expect("Failed to run the converter")
+    };
+    let result = run("0");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("No entry has the resource id 0000000000000000")
+    );
+    let result = run("textures/ci4");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Invalid resource id 'textures/ci4', expected up to 16 hex digits")
+    );
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn writes_linear_exr_textures() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-exr");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--types=texture", "--image-format=exr"]);
+    assert!(!output.join("textures/rgba32.png").exists());
+
+    let pixels = |name: &str| {
+        image::open(output.join(name))
+            .unwrap_or_else(|err| panic!("Failed to open {}: {}", name, err))
+            .to_rgba32f()
+            .into_raw()
+    };
+    let expected = RGBA.map(|value| value as f32 / 255.0);
+    assert_eq!(pixels("textures/rgba32.exr"), expected);
+    // Colors are linear light, alpha stays as it is
+    let i8 = pixels("textures/i8.exr");
+    assert!((i8[8] - 0.2159).abs() < 1e-3, "{:?}", i8);
+    assert_eq!(i8[8], i8[10]);
+    assert!((i8[11] - 128.0 / 255.0).abs() < 1e-6, "{:?}", i8);
+}
+
+#[test]
+fn reports_unused_palette_slots() {
+    use convert_texture_o2r::json::Json;
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-palette-report");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--types=texture", "--compressed-texels"]);
+    assert!(!output.join("palette_usage.json").exists());
+
+    // The three CI textures read the first 4 slots of their TLUT
+    let (stdout, _) = convert(
+        &output,
+        &["--types=texture", "--compressed-texels", "--palette-report"],
+    );
+    assert!(stdout.contains("276 palette slots unused by any texture"));
+    let report = std::fs::read_to_string(output.join("palette_usage.json")).unwrap();
+    let report = Json::parse(&report).unwrap();
+    let Some(Json::Array(tluts)) = report.get("tluts") else {
+        panic!("No TLUTs in {}", report.pretty());
+    };
+    let names = tluts
+        .iter()
+        .map(|tlut| tlut.get("tlut").unwrap().as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["course_tlut", "tlut", "tlut256"]);
+    assert_eq!(tluts[1].get("entries").unwrap().as_f64(), Some(16.0));
+    assert_eq!(tluts[1].get("used").unwrap().as_f64(), Some(4.0));
+    assert_eq!(
+        tluts[1].get("unused").unwrap().pretty(),
+        Json::Array((4..16).map(|slot| Json::from(slot as usize)).collect()).pretty()
+    );
+    let Some(Json::Array(textures)) = report.get("textures") else {
+        panic!("No textures in {}", report.pretty());
+    };
+    let entries = textures
+        .iter()
+        .map(|texture| {
+            (
+                texture.get("entry").unwrap().as_str().unwrap(),
+                texture.get("tlut").unwrap().as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        [
+            ("courses/mario_raceway/road", "course_tlut"),
+            ("textures/ci4", "tlut"),
+            ("textures/ci8", "tlut256")
+        ]
+    );
+}
+
+#[test]
+fn resumes_an_interrupted_conversion() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-resume");
+    let _ = std::fs::remove_dir_all(&output);
+    convert(&output, &["--compressed-texels"]);
+    let full_manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+
+    // Interrupted before the manifest, while writing the journal line of the
+    // first non-texture entry. The ci4 output went missing since and the ci8
+    // entry changed in the archive
+    std::fs::remove_file(output.join("manifest.json")).unwrap();
+    std::fs::remove_file(output.join("textures/ci4.png")).unwrap();
+    let journal = std::fs::read_to_string(output.join("journal.jsonl")).unwrap();
+    let mut lines = journal
+        .lines()
+        .filter(|line| line.starts_with("{\"entry\":\"textures/"))
+        .map(|line| {
+            if line.starts_with("{\"entry\":\"textures/ci8\"") {
+                line.replacen("\"crc\":", "\"crc\":1", 1)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect::<Vec<_>>();
+    lines.push("{\"entry\":\"courses/mario_raceway/road\",\"si".to_owned());
+    std::fs::write(output.join("journal.jsonl"), lines.join("\n")).unwrap();
+    let modified = |name: &str| {
+        std::fs::metadata(output.join(name))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    let rgba32_modified = modified("textures/rgba32.png");
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let (stdout, _) = convert(&output, &["--compressed-texels", "--resume"]);
+    assert!(stdout.contains("Resuming, 15 entries already converted"));
+    assert_eq!(modified("textures/rgba32.png"), rgba32_modified);
+    assert_eq!(rgba(&output, "textures/ci4.png"), RGBA);
+    assert!(output.join("courses/mario_raceway/road.png").exists());
+    assert_eq!(
+        std::fs::read_to_string(output.join("manifest.json")).unwrap(),
+        full_manifest
+    );
+}
+
+#[test]
+fn strict_skips_entries_with_unexpected_headers() {
+    let texture = archive_entry("textures/rgba32");
+    let mut reserved = texture.clone();
+    reserved[2] = 7;
+    reserved[0x30] = 1;
+    let mut id = texture.clone();
+    id[12..20].copy_from_slice(&0x1234u64.to_le_bytes());
+    let archive = write_archive(
+        "mini-header.o2r",
+        &[
+            ("textures/stock", texture),
+            ("textures/reserved", reserved),
+            ("textures/id", id),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-header");
+    let _ = std::fs::remove_dir_all(&output);
+
+    // Packers don't agree on these bytes, they're only checked with --strict
+    convert_archive(&archive, &output, &[]);
+    for name in ["stock", "reserved", "id"] {
+        assert_eq!(rgba(&output, &format!("textures/{}.png", name)), RGBA);
+    }
+
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert_archive(&archive, &output, &["--strict"]);
+    assert!(stdout.contains(
+        "Skipping textures/reserved, unexpected header: \
+         reserved bytes 0x02..0x04 are [07, 00], reserved byte 0x30 is not zero"
+    ));
+    assert!(stdout.contains(
+        "Skipping textures/id, unexpected header: \
+         id is 0000000000001234 instead of DEADBEEFDEADBEEF"
+    ));
+    assert_eq!(rgba(&output, "textures/stock.png"), RGBA);
+    assert!(!output.join("textures/reserved.png").exists());
+    assert!(!output.join("textures/id.png").exists());
+}
+
+#[test]
+fn resolves_segmented_addresses_through_the_segment_table() {
+    use convert_texture_o2r::json::Json;
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-segments-config");
+    std::fs::create_dir_all(dir.join("yaml")).unwrap();
+    std::fs::write(
+        dir.join("yaml/textures.yml"),
+        "rgba32:\n  type: TEXTURE\n  format: RGBA32\n  width: 2\n  height: 2\n  offset: 0x100\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("config.yml"),
+        format!(
+            "mini:\n  path: {}\n  segments:\n    6: textures/\n",
+            dir.join("yaml").display()
+        ),
+    )
+    .unwrap();
+
+    // G_SETTIMG of an RGBA32 image in segment 6, G_DL into segment 7 which
+    // isn't in the table, then G_ENDDL
+    let mut display_list = Vec::new();
+    for (w0, w1) in [
+        (0xFDu32 << 24 | 3 << 19, 0x06000100u32),
+        (0xDE << 24, 0x07000000),
+        (0xDF << 24, 0),
+    ] {
+        display_list.extend(w0.to_le_bytes());
+        display_list.extend(w1.to_le_bytes());
+    }
+    let archive = write_archive(
+        "mini-segments.o2r",
+        &[
+            ("textures/rgba32", archive_entry("textures/rgba32")),
+            ("models/segmented", resource(0x4F444C54, &display_list)),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-segments");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert_archive(
+        &archive,
+        &output,
+        &[&format!("--config={}", dir.join("config.yml").display())],
+    );
+    assert!(stdout.contains("Exporting 2 relocations (1 unresolved)"));
+
+    let relocations =
+        std::fs::read_to_string(output.join("models/segmented.relocations.json")).unwrap();
+    assert_eq!(
+        Json::parse(&relocations).unwrap().pretty(),
+        Json::parse(
+            r#"{"relocations": [
+                {"kind": "texture", "address": "0x06000100", "format": "RGBA32bpp", "target": "textures/rgba32"},
+                {"kind": "display_list", "address": "0x07000000", "target": null}
+            ]}"#
+        )
+        .unwrap()
+        .pretty()
+    );
+}
+
+#[test]
+fn loads_tluts_stored_after_their_textures() {
+    // The texture comes first, then an entry merely mentioning the TLUT
+    // symbol, with the colors rotated, and last the entry named after it
+    let archive = write_archive(
+        "mini-tlut-order.o2r",
+        &[
+            ("a/ci4", archive_entry("textures/ci4")),
+            (
+                "b/tlut_old",
+                archive_entry("courses/luigi_raceway/course_tlut"),
+            ),
+            ("z/palettes/tlut", archive_entry("textures/tlut")),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-tlut-order");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(&archive, &output, &["--threads=1"]);
+    assert_eq!(rgba(&output, "a/ci4.png"), RGBA);
+}
+
+#[test]
+fn reads_ci4_colors_from_the_palette_index_bank() {
+    use convert_texture_o2r::json::Json;
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-palette-index-config");
+    std::fs::create_dir_all(dir.join("yaml")).unwrap();
+    let definition = |name: &str, palette_index: u32| {
+        format!(
+            "{}:\n  type: TEXTURE\n  format: CI4\n  width: 2\n  height: 2\n  tlut: banks\n  palette_index: {}\n",
+            name, palette_index
+        )
+    };
+    std::fs::write(
+        dir.join("yaml/textures.yml"),
+        definition("bank1", 1) + &definition("bank2", 2),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("config.yml"),
+        format!("mini:\n  path: {}\n", dir.join("yaml").display()),
+    )
+    .unwrap();
+
+    // Two banks, the colors of the fixtures in the second
+    let mut tlut = [0, 1].repeat(16);
+    tlut.extend(&archive_entry("textures/tlut")[0x50..]);
+    let mut banks = Vec::new();
+    for field in [11u32, 16, 2, tlut.len() as u32] {
+        banks.extend(field.to_le_bytes());
+    }
+    banks.extend(tlut);
+    let archive = write_archive(
+        "mini-palette-index.o2r",
+        &[
+            ("textures/bank1", archive_entry("textures/ci4")),
+            ("textures/bank2", archive_entry("textures/ci4")),
+            ("textures/banks", resource(0x4F544558, &banks)),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-palette-index");
+    let _ = std::fs::remove_dir_all(&output);
+    let (_, stderr) = convert_archive(
+        &archive,
+        &output,
+        &[
+            &format!("--config={}", dir.join("config.yml").display()),
+            "--palette-report",
+        ],
+    );
+    assert_eq!(rgba(&output, "textures/bank1.png"), RGBA);
+    // A bank past the end of the TLUT has no colors
+    assert!(
+        stderr.contains(
+            "Texture textures/bank2 uses palette index 3 but its TLUT only has 0 entries"
+        )
+    );
+
+    // The 4 colors of the second bank are reported where they are in the TLUT
+    let report = std::fs::read_to_string(output.join("palette_usage.json")).unwrap();
+    let report = Json::parse(&report).unwrap();
+    let Some(Json::Array(tluts)) = report.get("tluts") else {
+        panic!("No TLUTs in {}", report.pretty());
+    };
+    let tlut = &tluts[0];
+    assert_eq!(tlut.get("entries").unwrap().as_f64(), Some(32.0));
+    assert_eq!(tlut.get("used").unwrap().as_f64(), Some(4.0));
+    let unused = (0..16).chain(20..32).map(|slot| slot as usize);
+    assert_eq!(
+        tlut.get("unused").unwrap().pretty(),
+        Json::Array(unused.map(Json::from).collect()).pretty()
+    );
+}
+
+#[test]
+fn finds_tluts_in_the_base_archives_of_a_patch() {
+    let base = write_archive(
+        "mini-base.o2r",
+        &[
+            ("textures/ci4", archive_entry("textures/ci4")),
+            ("textures/tlut", archive_entry("textures/tlut")),
+        ],
+    );
+    let patch = write_archive(
+        "mini-patch.o2r",
+        &[("textures/ci4", archive_entry("textures/ci4"))],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-patch");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert_archive(&patch, &output, &["--types=texture"]);
+    assert!(stdout.contains("Texture TLUT not found for ci4"));
+    assert!(!output.join("textures/ci4.png").exists());
+
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(
+        &patch,
+        &output,
+        &["--types=texture", &format!("--base={}", base.display())],
+    );
+    assert_eq!(rgba(&output, "textures/ci4.png"), RGBA);
+    assert!(!output.join("textures/tlut.png").exists());
+}
+
+#[test]
+fn reads_ci8_alpha_from_the_low_bit_of_the_tlut_color() {
+    use convert_texture_o2r::json::Json;
+
+    // The fixture colors, the red one with its alpha bit clear but the bit
+    // above it set
+    let mut colors = vec![0xF8, 0x02, 0x07, 0xC1, 0x00, 0x3F, 0x00, 0x00];
+    colors.resize(512, 0);
+    let mut tlut = Vec::new();
+    for field in [11u32, 16, 16, colors.len() as u32] {
+        tlut.extend(field.to_le_bytes());
+    }
+    tlut.extend(colors);
+    let archive = write_archive(
+        "mini-ci8-alpha.o2r",
+        &[
+            ("textures/ci8", archive_entry("textures/ci8")),
+            ("textures/tlut256", resource(0x4F544558, &tlut)),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-ci8-alpha");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(&archive, &output, &["--types=texture"]);
+
+    let pixels = rgba(&output, "textures/ci8.png");
+    assert_eq!(pixels[..4], [255, 0, 8, 0]);
+    assert_eq!(pixels[4..], RGBA[4..]);
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    let manifest = Json::parse(&manifest).unwrap();
+    let Some(Json::Array(textures)) = manifest.get("textures") else {
+        panic!("No textures in the manifest");
+    };
+    let alpha = textures[0].get("alpha").unwrap();
+    assert_eq!(alpha.get("class").and_then(Json::as_str), Some("binary"));
+    assert_eq!(alpha.get("opaque").and_then(Json::as_f64), Some(2.0));
+    assert_eq!(alpha.get("transparent").and_then(Json::as_f64), Some(2.0));
+}
+
+#[test]
+fn drops_the_row_padding_of_textures_with_a_pitch() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-pitch-config");
+    std::fs::create_dir_all(dir.join("yaml")).unwrap();
+    std::fs::write(
+        dir.join("yaml/textures.yml"),
+        "padded:\n  type: TEXTURE\n  format: I8\n  width: 2\n  height: 2\n  pitch: 4\n\
+         font:\n  type: TEXTURE\n  format: I8\n  width: 2\n  height: 2\n  line_width: 4\n\
+         narrow:\n  type: TEXTURE\n  format: I8\n  width: 2\n  height: 2\n  pitch: 1\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("config.yml"),
+        format!("mini:\n  path: {}\n", dir.join("yaml").display()),
+    )
+    .unwrap();
+
+    // Two texels of every 4 texel row are padding
+    let mut padded = Vec::new();
+    for field in [6u32, 2, 2, 8] {
+        padded.extend(field.to_le_bytes());
+    }
+    padded.extend([0x10, 0x20, 0xEE, 0xEE, 0x30, 0x40, 0xEE, 0xEE]);
+    let padded = resource(0x4F544558, &padded);
+    let archive = write_archive(
+        "mini-pitch.o2r",
+        &[
+            ("textures/padded", padded.clone()),
+            ("textures/font", padded.clone()),
+            ("textures/narrow", padded),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-pitch");
+    let _ = std::fs::remove_dir_all(&output);
+    let (_, stderr) = convert_archive(
+        &archive,
+        &output,
+        &[&format!("--config={}", dir.join("config.yml").display())],
+    );
+
+    for name in ["textures/padded.png", "textures/font.png"] {
+        assert_eq!(
+            luma_alpha(&output, name),
+            [0x10, 0x10, 0x20, 0x20, 0x30, 0x30, 0x40, 0x40]
+        );
+    }
+    assert!(
+        stderr.contains("textures/narrow: Pitch 1 is less than the width 2"),
+        "{}",
+        stderr
+    );
+    assert!(!output.join("textures/narrow.png").exists());
+}
+
+#[test]
+fn writes_a_changelog_against_a_previous_manifest() {
+    use convert_texture_o2r::json::Json;
+
+    let previous = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-changelog-previous");
+    let _ = std::fs::remove_dir_all(&previous);
+    convert(&previous, &["--compressed-texels"]);
+    let manifest = std::fs::read_to_string(previous.join("manifest.json")).unwrap();
+    let manifest = Json::parse(&manifest).unwrap();
+    let Some(Json::Array(textures)) = manifest.get("textures") else {
+        panic!("No textures in the manifest");
+    };
+
+    // The previous release had ci4 as it is now, an older i8 and a texture
+    // since removed
+    let mut textures = textures
+        .iter()
+        .filter(|texture| {
+            matches!(
+                texture.get("entry").and_then(Json::as_str),
+                Some("textures/ci4" | "textures/i8")
+            )
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(textures.len(), 2);
+    textures[1].insert("hash", "0000000000000000");
+    textures[1].insert("width", 4u32);
+    textures.push(
+        Json::object()
+            .with("entry", "textures/gone")
+            .with("output", "textures/gone.png")
+            .with("format", "RGBA16bpp")
+            .with("width", 8u32)
+            .with("height", 8u32),
+    );
+    let previous_manifest = previous.join("previous.json");
+    std::fs::write(
+        &previous_manifest,
+        Json::object()
+            .with("textures", Json::Array(textures))
+            .pretty(),
+    )
+    .unwrap();
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-changelog");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert(
+        &output,
+        &[
+            "--compressed-texels",
+            &format!("--changelog={}", previous_manifest.display()),
+        ],
+    );
+    assert!(
+        stdout.contains("13 textures added, 1 removed and 1 changed"),
+        "{}",
+        stdout
+    );
+    let changelog = std::fs::read_to_string(output.join("CHANGELOG.md")).unwrap();
+    assert!(changelog.starts_with("# Texture changes in mini.o2r\n"));
+    assert!(changelog.contains("13 added, 1 removed, 1 changed."));
+    assert!(changelog.contains("| `textures/i8` | <img src=\"textures/i8.png\" width=\"8\"> | Grayscale8bpp 4x2 → Grayscale8bpp 2x2 |"), "{}", changelog);
+    assert!(changelog.contains(
+        "## Removed\n\n| Texture | Format |\n| --- | --- |\n| `textures/gone` | RGBA16bpp 8x8 |\n"
+    ));
+    assert!(!changelog.contains("`textures/ci4`"));
+}
+
+#[test]
+fn generates_yaml_the_converter_reads_back() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-generate-yaml");
+    let _ = std::fs::remove_dir_all(&dir);
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("generate-yaml")
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(dir.join("yaml"))
+        .output()
+        .expect("Failed to run the converter");
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(
+        stdout.contains("Wrote 3 YAML files for 20 textures"),
+        "{}",
+        stdout
+    );
+
+    // One file per directory, the textures laid out one after the other
+    let yaml = std::fs::read_to_string(dir.join("yaml/textures.yml")).unwrap();
+    assert!(yaml.contains(
+        "\nci4:\n  type: TEXTURE\n  format: CI4\n  width: 2\n  height: 2\n  offset: 0x8\n  tlut: tlut # guessed\n"
+    ));
+    assert!(yaml.contains("\ntlut:\n  type: TEXTURE\n  format: TLUT\n  colors: 16\n"));
+    assert!(dir.join("yaml/courses/mario_raceway.yml").exists());
+
+    // The guessed TLUTs are enough to convert the CI textures
+    std::fs::write(
+        dir.join("config.yml"),
+        format!("mini:\n  path: {}\n", dir.join("yaml").display()),
+    )
+    .unwrap();
+    let output = dir.join("output");
+    convert(
+        &output,
+        &[&format!("--config={}", dir.join("config.yml").display())],
+    );
+    assert_eq!(rgba(&output, "textures/ci4.png"), RGBA);
+}
+
+#[test]
+fn decodes_textures_wider_than_a_simd_block() {
+    use convert_texture_o2r::{DecodeOptions, decode_texture};
+
+    let texture = |type_id: u32, width: u32, height: u32, data: &[u8]| {
+        let mut payload = Vec::new();
+        for field in [type_id, width, height, data.len() as u32] {
+            payload.extend(field.to_le_bytes());
+        }
+        payload.extend(data);
+        resource(0x4F544558, &payload)
+    };
+    let options = DecodeOptions::default();
+
+    // 9 texels a row, 16 bytes for the vector loop and a texel of tail
+    let texels = (0..18u16)
+        .map(|i| i.wrapping_mul(0x9E37))
+        .collect::<Vec<_>>();
+    let data = texels
+        .iter()
+        .flat_map(|texel| texel.to_be_bytes())
+        .collect::<Vec<_>>();
+    let expand = |value: u16| ((value << 3 | value >> 2) & 0xFF) as u8;
+    let expected = texels
+        .iter()
+        .flat_map(|texel| {
+            [
+                expand(texel >> 11 & 0x1F),
+                expand(texel >> 6 & 0x1F),
+                expand(texel >> 1 & 0x1F),
+                if texel & 1 != 0 { 0xFF } else { 0 },
+            ]
+        })
+        .collect::<Vec<_>>();
+    let rgba16 = decode_texture(&texture(2, 9, 2, &data), &options).unwrap();
+    assert_eq!(rgba16.into_raw(), expected);
+
+    // 35 texel rows of 18 bytes, the odd texel ending each row is padding
+    let data = (0..36u8).map(|i| i.wrapping_mul(37)).collect::<Vec<_>>();
+    let expected = data
+        .chunks(18)
+        .flat_map(|row| {
+            row.iter()
+                .flat_map(|byte| [byte >> 4, byte & 0x0F])
+                .take(35)
+        })
+        .flat_map(|intensity| [intensity * 17; 4])
+        .collect::<Vec<_>>();
+    let i4 = decode_texture(&texture(5, 35, 2, &data), &options).unwrap();
+    assert_eq!(i4.into_raw(), expected);
+}
+
+#[test]
+fn memory_limit_gives_up_parallelism() {
+    // A 64x64 RGBA32 texture, 16 KiB, so 4 workers and 4 readers are
+    // predicted to need more than a MiB
+    let mut large = Vec::new();
+    for field in [1u32, 64, 64, 64 * 64 * 4] {
+        large.extend(field.to_le_bytes());
+    }
+    large.extend(RGBA.repeat(64 * 64 / 4));
+    let archive = write_archive(
+        "mini-memory.o2r",
+        &[("textures/large", resource(0x4F544558, &large))],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-memory");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert_archive(
+        &archive,
+        &output,
+        &[
+            "--threads=4",
+            "--threads-io=4",
+            "--memory-limit=1",
+            "--report-memory",
+        ],
+    );
+
+    assert!(
+        stdout.contains("is over the limit of 1.0 MiB, decoding with 3 workers and 3 readers"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("still over the limit"), "{}", stdout);
+    assert!(
+        stdout.contains("Peak memory of the decode pipeline: "),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        rgba(&output, "textures/large.png"),
+        RGBA.repeat(64 * 64 / 4)
+    );
+
+    // At 256x256, 256 KiB, a single worker and reader are still predicted to
+    // go over, which stops the run before anything is decoded
+    let mut huge = Vec::new();
+    for field in [1u32, 256, 256, 256 * 256 * 4] {
+        huge.extend(field.to_le_bytes());
+    }
+    huge.extend(RGBA.repeat(256 * 256 / 4));
+    let huge = write_archive(
+        "mini-memory-huge.o2r",
+        &[("textures/huge", resource(0x4F544558, &huge))],
+    );
+    let _ = std::fs::remove_dir_all(&output);
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(&huge)
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg(format!("--output={}", output.display()))
+        .arg("--memory-limit=1")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("is still over the limit of 1.0 MiB with a single worker and reader"),
+        "{}",
+        stderr
+    );
+    assert!(!output.join("textures/huge.png").exists());
+
+    // 2^44 MiB is 2^64 bytes, one past what the limit holds
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg(&archive)
+        .arg("--memory-limit=17592186044416")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Invalid value '17592186044416' for option '--memory-limit'")
+    );
+}
+
+#[test]
+fn renames_outputs_colliding_by_case_or_normalization() {
+    let texture = archive_entry("textures/rgba32");
+    let archive = write_archive(
+        "mini-collisions.o2r",
+        &[
+            ("textures/Tile", texture.clone()),
+            ("textures/tile", texture.clone()),
+            // Decomposed, as archives packed on macOS have it, and composed
+            ("textures/cafe\u{301}", texture.clone()),
+            ("textures/caf\u{e9}", texture.clone()),
+            // Stacked marks outside Latin-1
+            ("textures/lu\u{308}\u{304}", texture.clone()),
+            ("textures/l\u{1d6}", texture),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-collisions");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert_archive(&archive, &output, &[]);
+
+    assert!(
+        stdout.contains("3 groups of entries have output paths only differing by case"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Writing textures/tile to textures/tile~2 to avoid a collision"));
+    for name in [
+        "textures/Tile.png",
+        "textures/tile~2.png",
+        "textures/caf\u{e9}.png",
+        "textures/caf\u{e9}~2.png",
+        "textures/l\u{1d6}.png",
+        "textures/l\u{1d6}~2.png",
+    ] {
+        assert_eq!(rgba(&output, name), RGBA);
+    }
+    assert!(!output.join("textures/cafe\u{301}.png").exists());
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(&archive)
+        .arg(format!("--config={}/config.yml", FIXTURES))
+        .arg(format!("--output={}-strict", output.display()))
+        .arg("--strict")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Output path collisions"));
+}
+
+#[test]
+fn converts_texture_types_to_and_from_rdp_codes() {
+    use convert_texture_o2r::TextureType;
+
+    // Every format and texel size combination of the RDP
+    let mut sampled = 0;
+    for fmt in 0..8 {
+        for siz in 0..4 {
+            let Some(type_id) = TextureType::from_fmt_siz(fmt, siz) else {
+                continue;
+            };
+            assert_eq!(type_id.to_fmt_siz(), Some((fmt, siz)), "{:?}", type_id);
+            assert_eq!(type_id.bits_per_pixel(), Some(4 << siz));
+            sampled += 1;
+        }
+    }
+    assert_eq!(sampled, 9);
+    // G_IM_FMT_YUV and 32-bit intensity
+    assert_eq!(TextureType::from_fmt_siz(1, 2), None);
+    assert_eq!(TextureType::from_fmt_siz(4, 3), None);
+    // TLUTs are loaded as RGBA16, 1-bit intensity has no RDP format
+    assert_eq!(TextureType::TLUT.to_fmt_siz(), Some((0, 2)));
+    assert_eq!(TextureType::GrayscaleAlpha1bpp.to_fmt_siz(), None);
+}
+
+#[test]
+fn stitches_tile_grids_into_one_image() {
+    use convert_texture_o2r::json::Json;
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-tiles-config");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("config.yml"),
+        format!(
+            "mini:\n  path: {}/yaml\n  tiled:\n    textures/big: 2\n",
+            FIXTURES
+        ),
+    )
+    .unwrap();
+
+    // Three tiles, the second with its texels reversed
+    let reversed = RGBA
+        .chunks_exact(4)
+        .rev()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    let tile = |texels: &[u8]| {
+        let mut payload = Vec::new();
+        for field in [1u32, 2, 2, 16] {
+            payload.extend(field.to_le_bytes());
+        }
+        payload.extend(texels);
+        resource(0x4F544558, &payload)
+    };
+    let archive = write_archive(
+        "mini-tiles.o2r",
+        &[
+            ("textures/big_0", tile(&RGBA)),
+            ("textures/big_1", tile(&reversed)),
+            ("textures/big_2", tile(&RGBA)),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-tiles");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(
+        &archive,
+        &output,
+        &[&format!("--config={}", dir.join("config.yml").display())],
+    );
+
+    // Two columns and two rows of 2x2 tiles, the last cell left transparent
+    let expected = [
+        &RGBA[..8],
+        &reversed[..8],
+        &RGBA[8..],
+        &reversed[8..],
+        &RGBA[..8],
+        &[0; 8],
+        &RGBA[8..],
+        &[0; 8],
+    ]
+    .concat();
+    assert_eq!(rgba(&output, "textures/big.png"), expected);
+    assert!(!output.join("textures/big_0.png").exists());
+
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    let manifest = Json::parse(&manifest).unwrap();
+    let Some(Json::Array(tiled)) = manifest.get("tiled") else {
+        panic!("No tiled textures in the manifest");
+    };
+    assert_eq!(tiled.len(), 1);
+    assert_eq!(
+        tiled[0].get("output").and_then(Json::as_str),
+        Some("textures/big.png")
+    );
+    assert_eq!(tiled[0].get("rows").and_then(Json::as_f64), Some(2.0));
+    assert_eq!(
+        tiled[0].get("tiles"),
+        Some(&Json::Array(
+            ["textures/big_0", "textures/big_1", "textures/big_2"]
+                .map(Json::from)
+                .to_vec()
+        ))
+    );
+}
+
+#[test]
+fn explains_why_entries_are_converted_or_not() {
+    let explain = |entry: &str| {
+        let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("explain")
+            .arg(format!("{}/mini.o2r", FIXTURES))
+            .arg(entry)
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .output()
+            .expect("Failed to run the converter");
+        assert!(result.status.success());
+        String::from_utf8_lossy(&result.stdout).into_owned()
+    };
+
+    let ci4 = explain("textures/ci4");
+    assert!(ci4.contains("  tlut: tlut, palette_index 0\n  TLUT entry textures/tlut: 16 colors\n"));
+    assert!(
+        ci4.ends_with("Decision: converted to a 2x2 png\n"),
+        "{}",
+        ci4
+    );
+    assert!(
+        explain("textures/tlut").ends_with("Decision: skipped, TLUT textures aren't converted\n")
+    );
+    let broken = explain("textures/broken");
+    assert!(
+        broken.contains("  Size: 32 bytes expected, 8 found\n"),
+        "{}",
+        broken
+    );
+    assert!(broken.contains("Decision: skipped, Data size does not match"));
+    assert!(
+        explain("textures/missing").ends_with("Decision: not converted, the entry doesn't exist\n")
+    );
+}
+
+#[test]
+fn generate_yaml_keeps_hostile_directories_inside_the_output_folder() {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-hostile-yaml");
+    let _ = std::fs::remove_dir_all(&root);
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg("generate-yaml")
+        .arg(format!("{}/hostile.o2r", FIXTURES))
+        .arg(root.join("parent/yaml"))
+        .output()
+        .expect("Failed to run the converter");
+    assert!(result.status.success());
+
+    // The ../.. directory of ../../evil is named _, C:\x and /abs are at the
+    // root
+    let files = walkdir::WalkDir::new(&root)
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            let path = entry.path().strip_prefix(&root).unwrap();
+            path.to_string_lossy().replace('\\', "/")
+        })
+        .collect::<BTreeSet<_>>();
+    let expected = ["parent/yaml/_.yml", "parent/yaml/root.yml"];
+    assert_eq!(files, expected.into_iter().map(str::to_owned).collect());
+    let evil = std::fs::read_to_string(root.join("parent/yaml/_.yml")).unwrap();
+    assert!(evil.contains("\nevil:\n"));
+}
+
+#[test]
+fn thumbnails_average_in_linear_light() {
+    let thumbnails = |max: &str| {
+        let output =
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("mini-thumbnails-{}", max));
+        let _ = std::fs::remove_dir_all(&output);
+        convert(
+            &output,
+            &["--compressed-texels", &format!("--thumbnails={}", max)],
+        );
+        output
+    };
+
+    // Textures small enough are kept as they are
+    let output = thumbnails("4");
+    assert_eq!(rgba(&output, "thumbs/textures/rgba32.png"), RGBA);
+    let road = image::open(output.join("thumbs/courses/mario_raceway/road.png")).unwrap();
+    assert_eq!((road.width(), road.height()), (4, 4));
+
+    // Red, green, blue and transparent black: the transparent texel doesn't
+    // darken the others, and a third of each in linear light is brighter
+    // than 85 in sRGB
+    let output = thumbnails("1");
+    assert_eq!(
+        rgba(&output, "thumbs/textures/rgba32.png"),
+        [156, 156, 156, 191]
+    );
+
+    // One past u32::MAX, which used to wrap around to 0
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg("--thumbnails=4294967296")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Invalid value '4294967296' for option '--thumbnails'")
+    );
+}
+
+#[test]
+fn greps_decompressed_entries() {
+    let archive = write_archive(
+        "mini-grep.o2r",
+        &[
+            ("text/greeting", resource(0x4F545854, b"hello hello")),
+            ("text/other", resource(0x4F545854, b"goodbye")),
+        ],
+    );
+    let grep = |pattern: &str| {
+        Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("grep")
+            .arg(&archive)
+            .arg(pattern)
+            .output()
+            .expect("Failed to run the converter")
+    };
+
+    let result = grep("hello");
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&result.stdout),
+        "text/greeting: 0x40, 0x46\n2 matches in 1 entries\n"
+    );
+    // Headers are searched too, the resource id is in both
+    let result = grep("hex:EF BE AD DE");
+    assert_eq!(
+        String::from_utf8_lossy(&result.stdout),
+        "text/greeting: 0xC, 0x10\ntext/other: 0xC, 0x10\n4 matches in 2 entries\n"
+    );
+
+    let result = grep("hex:ABC");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Invalid hex pattern 'ABC', expected whole bytes")
+    );
+}
+
+#[test]
+fn selects_textures_matching_the_where_query() {
+    let images = |name: &str, query: &str| {
+        let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let _ = std::fs::remove_dir_all(&output);
+        convert(
+            &output,
+            &["--compressed-texels", &format!("--where={}", query)],
+        );
+        walkdir::WalkDir::new(&output)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "png"))
+            .map(|entry| {
+                let path = entry.path().strip_prefix(&output).unwrap();
+                path.to_string_lossy().replace('\\', "/")
+            })
+            .collect::<BTreeSet<_>>()
+    };
+
+    assert_eq!(
+        images("mini-where-format", "format=CI4 && width>=8"),
+        BTreeSet::from(["courses/mario_raceway/road.png".to_owned()])
+    );
+    // Formats go by their texture type too, and the model isn't a texture
+    let expected = [
+        "textures/i4.png",
+        "textures/i4_stripes.png",
+        "textures/ia4.png",
+    ];
+    assert_eq!(
+        images("mini-where-bpp", "bpp<8 && format != Palette4bpp"),
+        expected.into_iter().map(str::to_owned).collect()
+    );
+}
+
+#[test]
+fn deinterleaves_odd_rows_dumped_from_tmem() {
+    // Vertical stripes of red, green, blue and white, 4x4 RGBA32
+    let stripes = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 255, 255],
+    ]
+    .concat()
+    .repeat(4);
+    // TMEM swaps the 32-bit words of the odd rows in pairs
+    let mut interleaved = stripes.clone();
+    for row in interleaved.chunks_mut(16).skip(1).step_by(2) {
+        for pair in row.chunks_mut(8) {
+            pair.rotate_left(4);
+        }
+    }
+    let mut payload = Vec::new();
+    for field in [1u32, 4, 4, 64] {
+        payload.extend(field.to_le_bytes());
+    }
+    payload.extend(&interleaved);
+    let archive = write_archive(
+        "mini-deinterleave.o2r",
+        &[("textures/stripes", resource(0x4F544558, &payload))],
+    );
+
+    for (mode, expected) in [("off", &interleaved), ("on", &stripes), ("auto", &stripes)] {
+        let output =
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("mini-deinterleave-{}", mode));
+        let _ = std::fs::remove_dir_all(&output);
+        convert_archive(&archive, &output, &[&format!("--deinterleave={}", mode)]);
+        assert_eq!(&rgba(&output, "textures/stripes.png"), expected, "{}", mode);
+    }
+}
+
+#[test]
+fn reencodes_edited_ci_textures_without_quantizing() {
+    use convert_texture_o2r::{DecodeOptions, decode_texture_with_tlut};
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let reencode = |archive: &Path, entry: &str, pixels: &[u8], name: &str| {
+        let image = dir.join(format!("mini-reencode-{}.png", name));
+        image::RgbaImage::from_raw(2, 2, pixels.to_vec())
+            .unwrap()
+            .save(&image)
+            .unwrap();
+        let output = dir.join(format!("mini-reencode-{}.o2r", name));
+        let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("reencode-ci")
+            .arg(archive)
+            .arg(entry)
+            .arg(&image)
+            .arg(&output)
+            .arg(format!("--config={}/config.yml", FIXTURES))
+            .output()
+            .expect("Failed to run the converter");
+        (result, output)
+    };
+    let read = |archive: &Path, entry: &str| {
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(archive).unwrap()).unwrap();
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut zip.by_name(entry).unwrap(), &mut data).unwrap();
+        data
+    };
+    let mini = Path::new(FIXTURES).join("mini.o2r");
+
+    // The unedited export gives back the texels bit for bit
+    let (result, output) = reencode(&mini, "textures/ci4", &RGBA, "unedited");
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(read(&output, "textures/ci4"), archive_entry("textures/ci4"));
+
+    // Moved pixels take the TLUT slot of their color
+    let reversed = RGBA
+        .chunks_exact(4)
+        .rev()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    let (result, output) = reencode(&mini, "textures/ci4", &reversed, "reversed");
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let decoded = decode_texture_with_tlut(
+        &read(&output, "textures/ci4"),
+        Some(&read(&output, "textures/tlut")),
+        &DecodeOptions::default(),
+    );
+    assert_eq!(decoded.unwrap().into_raw(), reversed);
+
+    // A new color is appended to a TLUT with room for it, white here
+    let mut tlut = Vec::new();
+    for field in [11u32, 4, 1, 8] {
+        tlut.extend(field.to_le_bytes());
+    }
+    tlut.extend(&archive_entry("textures/tlut")[0x50..0x58]);
+    let archive = write_archive(
+        "mini-reencode-short-tlut.o2r",
+        &[
+            ("textures/ci8", archive_entry("textures/ci8")),
+            ("textures/tlut256", resource(0x4F544558, &tlut)),
+        ],
+    );
+    let mut painted = RGBA;
+    painted[12..].copy_from_slice(&[255; 4]);
+    let (result, output) = reencode(&archive, "textures/ci8", &painted, "painted");
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let new_tlut = read(&output, "textures/tlut256");
+    assert_eq!(
+        &new_tlut[0x40..0x50],
+        [11u32, 5, 1, 10].map(u32::to_le_bytes).concat()
+    );
+    let decoded = decode_texture_with_tlut(
+        &read(&output, "textures/ci8"),
+        Some(&new_tlut),
+        &DecodeOptions::default(),
+    );
+    assert_eq!(decoded.unwrap().into_raw(), painted);
+
+    // The 16 colors of the CI4 TLUT leave no room
+    let (result, _) = reencode(&mini, "textures/ci4", &painted, "full");
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains(
+        "Pixel (1, 1) [255, 255, 255, 255] is a new color but the palette of textures/ci4 is full"
+    ));
+}
+
+#[test]
+fn names_textures_after_their_hi_res_pack_file() {
+    use convert_texture_o2r::json::Json;
+
+    // An RGBA16 texture of three rows, hashed by the plugins' RiceCRC32 from
+    // the first row in memory with the row number counting down
+    let mut payload = Vec::new();
+    for field in [2u32, 4, 3, 24] {
+        payload.extend(field.to_le_bytes());
+    }
+    payload.extend((0..24u8).map(|i| i.wrapping_mul(37).wrapping_add(11)));
+    let archive = write_archive(
+        "mini-hash-db.o2r",
+        &[("textures/tall", resource(0x4F544558, &payload))],
+    );
+    let pack = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-hash-db.txt");
+    std::fs::write(
+        &pack,
+        "SUPER MARIO 64#55EE65C7#0#2_all.png\nSUPER MARIO 64#65C755EE#0#2_all.png\n",
+    )
+    .unwrap();
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-hash-db");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(
+        &archive,
+        &output,
+        &[&format!("--hash-db={}", pack.display())],
+    );
+
+    let manifest = std::fs::read_to_string(output.join("manifest.json")).unwrap();
+    let manifest = Json::parse(&manifest).unwrap();
+    let Some(Json::Array(textures)) = manifest.get("textures") else {
+        panic!("No textures in the manifest");
+    };
+    assert_eq!(
+        textures[0].get("pack_hash").and_then(Json::as_str),
+        Some("55EE65C7#0#2")
+    );
+    assert_eq!(
+        textures[0].get("pack_name").and_then(Json::as_str),
+        Some("SUPER MARIO 64#55EE65C7#0#2_all.png")
+    );
+}
+
+#[test]
+fn hashes_textures_like_rice_and_gliden64() {
+    use convert_texture_o2r::{
+        TextureFormat, TextureType,
+        pack_hash::{PackHashes, Scheme, crc},
+    };
+
+    // Expected values from a port of the plugins' RiceCRC32 C code
+    let rgba16 = (0..24u8)
+        .map(|i| i.wrapping_mul(37).wrapping_add(11))
+        .collect::<Vec<_>>();
+    for scheme in [Scheme::Rice, Scheme::GlideN64] {
+        assert_eq!(crc(scheme, &rgba16, 4, 3, 2, 8), 0x55EE65C7);
+    }
+
+    // A CI4 texture using the first 8 colors of an 8 color TLUT: Rice hashes
+    // 16 colors, the missing ones as zeros, GlideN64 the colors used
+    let ci4 = TextureFormat::new(
+        TextureType::Palette4bpp,
+        8,
+        2,
+        8,
+        vec![0x01, 0x23, 0x45, 0x67, 0x76, 0x54, 0x32, 0x10],
+    );
+    let colors = [
+        0xF801u16, 0x07C1, 0x003F, 0x0000, 0xFFFF, 0x8421, 0x4211, 0x2109,
+    ];
+    let tlut = TextureFormat::new(
+        TextureType::TLUT,
+        8,
+        1,
+        16,
+        colors
+            .iter()
+            .flat_map(|color| color.to_be_bytes())
+            .collect(),
+    );
+    let hashes = PackHashes::compute(&ci4, Some(&tlut)).unwrap();
+    assert_eq!(hashes.rice.key(), "111110F0#2#0#DED68CE4");
+    assert_eq!(hashes.gliden64.key(), "111110F0#2#0#05868CE3");
+    assert_eq!(hashes.gliden64.checksum64(), 0x05868CE3_111110F0);
+}
+
+#[test]
+fn routes_skips_and_errors_to_the_log_file() {
+    // A CI texture without its TLUT is skipped, a truncated one is an error
+    let archive = write_archive(
+        "mini-log.o2r",
+        &[
+            ("textures/rgba32", archive_entry("textures/rgba32")),
+            ("textures/ci4", archive_entry("textures/ci4")),
+            ("textures/broken", archive_entry("textures/broken")),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-log");
+    let log_file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-log.txt");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, stderr) = convert_archive(
+        &archive,
+        &output,
+        &[&format!("--log-file={}", log_file.display())],
+    );
+    let log = std::fs::read_to_string(&log_file).unwrap();
+    assert!(log.contains("Texture TLUT not found for ci4"), "{}", log);
+    assert!(log.contains("textures/broken"));
+    assert!(!stdout.contains("Texture TLUT not found"));
+    assert!(stderr.contains("textures/broken"));
+    assert!(stdout.contains("Processing texture"));
+
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert_archive(&archive, &output, &["--log=progress=off,skip=console"]);
+    assert!(stdout.contains("Texture TLUT not found for ci4"));
+    assert!(!stdout.contains("Processing texture"));
+
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert_archive(&archive, &output, &["--quiet-skip"]);
+    assert!(!stdout.contains("Texture TLUT not found"));
+}
+
+#[test]
+fn checks_the_asset_path_of_the_config() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-asset-path");
+    std::fs::create_dir_all(&dir).unwrap();
+    let yaml = Path::new(FIXTURES).join("yaml");
+    let missing = dir.join("missing");
+    let config = |name: &str, games: &[&Path]| {
+        let path = dir.join(name);
+        let games = games
+            .iter()
+            .enumerate()
+            .map(|(i, path)| format!("game{}:\n  path: {}\n", i, path.display()))
+            .collect::<String>();
+        std::fs::write(&path, games).unwrap();
+        format!("--config={}", path.display())
+    };
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-asset-path-output");
+
+    // The first game whose asset path exists is used
+    let _ = std::fs::remove_dir_all(&output);
+    let both = config("both.yml", &[&missing, &yaml]);
+    let (stdout, _) = convert(&output, &["--types=texture", &both]);
+    assert!(stdout.contains("YAML files found under"));
+    assert_eq!(rgba(&output, "textures/ci4.png"), RGBA);
+
+    let _ = std::fs::remove_dir_all(&output);
+    let missing_only = config("missing.yml", &[&missing]);
+    let (_, stderr) = convert(&output, &["--types=texture", &missing_only]);
+    assert!(stderr.contains(&format!(
+        "Asset path '{}' does not exist",
+        missing.display()
+    )));
+    assert!(stderr.contains("CI textures will be skipped"));
+    assert!(!output.join("textures/ci4.png").exists());
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg(missing_only)
+        .arg(format!("--output={}", output.display()))
+        .arg("--strict")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+}
+
+#[test]
+fn profiles_stages_and_the_slowest_entries() {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-profile");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert(
+        &output,
+        &["--types=texture", "--compressed-texels", "--profile=3"],
+    );
+    let milliseconds = |line: &str| {
+        line.split_whitespace()
+            .next()
+            .unwrap()
+            .parse::<f64>()
+            .unwrap()
+    };
+
+    let report = stdout
+        .split_once("Converted 15 entries in ")
+        .unwrap_or_else(|| panic!("No profile in {}", stdout))
+        .1;
+    let mut lines = report.lines();
+    let total = milliseconds(lines.next().unwrap());
+    let stages = lines.by_ref().take(3).collect::<Vec<_>>();
+    for (line, stage) in stages.iter().zip(["decode", "encode", "write"]) {
+        assert!(line.trim_start().starts_with(stage), "{}", line);
+    }
+    let staged = stages
+        .iter()
+        .map(|line| milliseconds(line.trim_start().trim_start_matches(char::is_alphabetic)))
+        .sum::<f64>();
+    assert!((staged - total).abs() < 0.01, "{} vs {}", staged, total);
+
+    assert_eq!(lines.next(), Some("Slowest 3 entries:"));
+    let slowest = lines.take(3).map(milliseconds).collect::<Vec<_>>();
+    assert_eq!(slowest.len(), 3);
+    assert!(slowest.is_sorted_by(|a, b| a >= b), "{:?}", slowest);
+    assert!(slowest[0] <= total);
+}
+
+#[test]
+fn emits_textures_as_decomp_c_arrays() {
+    let emit = |data: &str| {
+        let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("mini-emit-c-{}", data));
+        let _ = std::fs::remove_dir_all(&output);
+        convert(&output, &["--types=texture", &format!("--emit-c={}", data)]);
+        output
+    };
+
+    let raw = emit("raw");
+    assert_eq!(
+        std::fs::read_to_string(raw.join("textures/rgba16.rgba16.inc.c")).unwrap(),
+        "0xF80107C1003F0000,\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(raw.join("textures/rgba16.h")).unwrap(),
+        "#ifndef RGBA16_H\n#define RGBA16_H\n\n#define rgba16_WIDTH 2\n\
+         #define rgba16_HEIGHT 2\nextern u64 rgba16[1];\n\n#endif\n"
+    );
+    assert!(raw.join("textures/tlut.tlut.rgba16.inc.c").exists());
+    // The row padding of the stride is kept as stored
+    assert_eq!(
+        std::fs::read_to_string(raw.join("textures/rgba32_stride.rgba32.inc.c")).unwrap(),
+        "0xFF0000FF00FF00FF, 0x090909090000FFFF, 0x0000000000000000,\n"
+    );
+
+    let encoded = emit("encoded");
+    assert_eq!(
+        std::fs::read_to_string(encoded.join("textures/rgba32_stride.rgba32.inc.c")).unwrap(),
+        "0xFF0000FF00FF00FF, 0x0000FFFF00000000,\n"
+    );
+    assert!(
+        std::fs::read_to_string(encoded.join("textures/rgba32_stride.h"))
+            .unwrap()
+            .contains("extern u64 rgba32_stride[2];\n")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn post_processes_converted_textures() {
+    use convert_texture_o2r::json::Json;
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-post-process");
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert(
+        &output,
+        &[
+            "--types=texture",
+            "--compressed-texels",
+            "--post-process=cp {in} {out}",
+            "--post-process-jobs=2",
+        ],
+    );
+    assert!(stdout.contains("15 of 15 textures post-processed"));
+    assert_eq!(rgba(&output, "processed/textures/ci4.png"), RGBA);
+
+    // cat reading the output it should write fails on every texture
+    let _ = std::fs::remove_dir_all(&output);
+    let (stdout, _) = convert(
+        &output,
+        &[
+            "--types=texture",
+            "--compressed-texels",
+            "--post-process=cat {in} {out}",
+        ],
+    );
+    assert!(
+        stdout.contains("0 of 15 textures post-processed"),
+        "{}",
+        stdout
+    );
+    let report = std::fs::read_to_string(output.join("post_process.json")).unwrap();
+    let report = Json::parse(&report).unwrap();
+    assert_eq!(
+        report.get("command").and_then(Json::as_str),
+        Some("cat {in} {out}")
+    );
+    let Some(Json::Array(failures)) = report.get("failures") else {
+        panic!("No failures in {}", report.pretty());
+    };
+    assert_eq!(failures.len(), 15);
+    let error = failures[0].get("error").and_then(Json::as_str).unwrap();
+    assert!(error.contains("No such file"), "{}", error);
+}
+
+#[test]
+fn selects_entries_by_their_header_fields() {
+    let mut custom = archive_entry("textures/rgba32");
+    custom[1] = 1;
+    let archive = write_archive(
+        "mini-header-filter.o2r",
+        &[
+            ("textures/plain", archive_entry("textures/rgba32")),
+            ("textures/custom", custom),
+            ("textures/v2", archive_entry("textures/rgba32_stride")),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-header-filter");
+    let converted = |args: &[&str]| {
+        let _ = std::fs::remove_dir_all(&output);
+        convert_archive(&archive, &output, args);
+        ["plain", "custom", "v2"]
+            .into_iter()
+            .filter(|name| output.join(format!("textures/{}.png", name)).exists())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(converted(&[]), ["plain", "custom", "v2"]);
+    assert_eq!(converted(&["--only-custom"]), ["custom"]);
+    assert_eq!(converted(&["--header-version=2"]), ["v2"]);
+    assert_eq!(converted(&["--byte-order=le"]), ["plain", "custom", "v2"]);
+    assert!(converted(&["--byte-order=be"]).is_empty());
+}
+
+#[test]
+fn extracts_the_localized_variants_of_one_language() {
+    use convert_texture_o2r::json::Json;
+
+    let texture = archive_entry("textures/rgba32");
+    let archive = write_archive(
+        "mini-language.o2r",
+        &[
+            ("textures/title_eng", texture.clone()),
+            ("textures/title_ger", texture.clone()),
+            ("textures/logo_eng", texture.clone()),
+            ("textures/plain", texture),
+        ],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-language");
+    let _ = std::fs::remove_dir_all(&output);
+    convert_archive(&archive, &output, &["--language=ger"]);
+
+    assert_eq!(rgba(&output, "textures/title_ger.png"), RGBA);
+    for name in ["title_eng", "logo_eng", "plain"] {
+        assert!(!output.join(format!("textures/{}.png", name)).exists());
+    }
+    let report = std::fs::read_to_string(output.join("languages.json")).unwrap();
+    let report = Json::parse(&report).unwrap();
+    assert_eq!(report.get("language").and_then(Json::as_str), Some("ger"));
+    assert_eq!(
+        report.get("groups").unwrap().pretty(),
+        Json::object()
+            .with("textures/logo", vec![Json::from("eng")])
+            .with("textures/title", vec![Json::from("eng"), Json::from("ger")])
+            .pretty()
+    );
+    assert_eq!(
+        report.get("missing").unwrap().pretty(),
+        Json::Array(vec![Json::from("textures/logo")]).pretty()
+    );
+}
+
+#[test]
+fn flags_textures_just_over_the_tmem_size() {
+    use convert_texture_o2r::{TextureFormat, TextureType};
+
+    let overflow = |type_id: TextureType, width: u32, height: u32| {
+        TextureFormat::new(type_id, width, height, 0, Vec::new()).tmem_overflow()
+    };
+    // 64 bytes a row, filling the 4 KiB of TMEM with 64 rows
+    assert_eq!(overflow(TextureType::RGBA16bpp, 32, 64), None);
+    assert_eq!(
+        overflow(TextureType::RGBA16bpp, 32, 65).as_deref(),
+        Some("32x65 RGBA16bpp takes 4160 bytes of TMEM but only 4096 are available")
+    );
+    // Rows are loaded in whole 64-bit lines, 57 bytes taking 64
+    assert!(overflow(TextureType::Grayscale8bpp, 57, 65).is_some());
+    // RGBA32 and CI textures only get half of TMEM
+    assert_eq!(overflow(TextureType::RGBA32bpp, 32, 32), None);
+    assert!(overflow(TextureType::RGBA32bpp, 32, 33).is_some());
+    assert_eq!(overflow(TextureType::Palette8bpp, 32, 64), None);
+    assert!(overflow(TextureType::Palette8bpp, 32, 65).is_some());
+    assert_eq!(overflow(TextureType::Palette4bpp, 64, 64), None);
+    assert!(overflow(TextureType::Palette4bpp, 64, 65).is_some());
+
+    // Converted anyway, but logged as an error
+    let mut payload = Vec::new();
+    for field in [2u32, 32, 65, 32 * 65 * 2] {
+        payload.extend(field.to_le_bytes());
+    }
+    payload.resize(16 + 32 * 65 * 2, 0xFF);
+    let archive = write_archive(
+        "mini-tmem.o2r",
+        &[("textures/large", resource(0x4F544558, &payload))],
+    );
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("mini-tmem");
+    let _ = std::fs::remove_dir_all(&output);
+    let (_, stderr) = convert_archive(&archive, &output, &[]);
+    assert!(
+        stderr.contains("Texture textures/large can't fit in TMEM: 32x65 RGBA16bpp takes 4160")
+    );
+    assert!(output.join("textures/large.png").exists());
+}
+
+#[test]
+fn io_profiles_dont_change_the_output() {
+    let sums = ["nvme", "hdd", "network"].map(|profile| {
+        let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("mini-io-{}", profile));
+        let _ = std::fs::remove_dir_all(&output);
+        convert(
+            &output,
+            &["--reproducible", &format!("--io-profile={}", profile)],
+        );
+        assert_eq!(rgba(&output, "textures/ci4.png"), RGBA);
+        std::fs::read_to_string(output.join("SHA256SUMS")).unwrap()
+    });
+    assert_eq!(sums[0], sums[1]);
+    assert_eq!(sums[0], sums[2]);
+
+    let result = Command::new(env!("CARGO_BIN_EXE_convert-texture-o2r"))
+        .arg(format!("{}/mini.o2r", FIXTURES))
+        .arg("--io-profile=ssd")
+        .output()
+        .expect("Failed to run the converter");
+    assert!(!result.status.success());
+    assert!(
+        String::from_utf8_lossy(&result.stderr)
+            .contains("Unknown IO profile 'ssd', expected nvme, hdd or network")
+    );
+}
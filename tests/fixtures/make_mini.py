@@ -0,0 +1,241 @@
+#!/usr/bin/env python3
+"""Builds mini.o2r, the archive the end-to-end test converts, hostile.o2r,
+whose entry names try to leave the output folder, and legacy.o2r, holding
+only what the option-less tool converted. legacy/ holds the images that tool
+wrote for it, which `legacy` mode must reproduce byte for byte.
+
+Every texture is 2x2 and decodes to the same four pixels where its format
+allows: red, green, blue and transparent black.
+"""
+
+import struct
+import zipfile
+from pathlib import Path
+
+OTR_HEADER_MAGIC = 0xDEADBEEFDEADBEEF
+
+
+def header(type_id, version=0):
+    return struct.pack("<BBxxIIQ", 0, 0, type_id, version, OTR_HEADER_MAGIC).ljust(64, b"\0")
+
+
+def texture(texture_type, width, height, data):
+    return header(0x4F544558) + struct.pack("<IIII", texture_type, width, height, len(data)) + data
+
+
+def strided_texture(texture_type, width, height, stride, data):
+    """Version 2 texture, rows starting every `stride` bytes."""
+    fields = struct.pack("<IIIII", texture_type, width, height, stride, len(data))
+    return header(0x4F544558, 2) + fields + data
+
+
+def set_texture_image(path, fmt, siz):
+    """G_SETTIMG_OTR_FILEPATH, the path in the next command slots."""
+    path = b"__OTR__" + path + b"\0"
+    path = path.ljust((len(path) + 7) // 8 * 8, b"\0")
+    return struct.pack("<II", 0x25 << 24 | fmt << 21 | siz << 19, 0) + path
+
+
+def display_list():
+    # The CI 4b texture at the start of TMEM, its TLUT at 0x800
+    commands = set_texture_image(b"textures/ci4", 2, 0)
+    # G_SETTILE, mirrored on s and clamped on t
+    commands += struct.pack("<II", 0xF5 << 24, 7 << 24 | 2 << 18 | 1 << 8)
+    commands += struct.pack("<II", 0xF3 << 24, 7 << 24 | 3 << 12)  # G_LOADBLOCK
+    commands += set_texture_image(b"textures/tlut", 0, 2)
+    commands += struct.pack("<II", 0xF5 << 24 | 256, 7 << 24)  # G_SETTILE
+    commands += struct.pack("<II", 0xF0 << 24, 7 << 24 | 15 << 14)  # G_LOADTLUT
+    # A TLUT and a CI 4b texture embedded in the display list, at 0xA0 and 0x110
+    commands += inline(texture(11, 16, 1, TLUT))
+    commands += inline(texture(3, 2, 2, bytes([0x01, 0x23])))
+    # G_SETOTHERMODE_H, point filtering
+    commands += struct.pack("<II", 0xE3 << 24 | (32 - 12 - 2) << 8 | 1, 0)
+    commands += struct.pack("<II", 0xDF << 24, 0)  # G_ENDDL
+    return header(0x4F444C54) + commands
+
+
+def inline(resource):
+    """A resource embedded in a display list, padded to the next command."""
+    return resource.ljust((len(resource) + 7) // 8 * 8, b"\0")
+
+
+def lz_tokens(data, max_length):
+    """Literal bytes and (length, distance) back-references of 3 or more
+    bytes up to 4096 bytes back, found greedily."""
+    position = 0
+    while position < len(data):
+        best = (0, 0)
+        for start in range(max(0, position - 4096), position):
+            length = 0
+            while (
+                length < max_length
+                and position + length < len(data)
+                and data[start + length] == data[position + length]
+            ):
+                length += 1
+            if length > best[0]:
+                best = (length, position - start)
+        if best[0] >= 3:
+            yield best
+            position += best[0]
+        else:
+            yield data[position]
+            position += 1
+
+
+def layout_bits(layout):
+    layout = layout + [0] * (-len(layout) % 32)
+    return bytes(int("".join(map(str, layout[i : i + 8])), 2) for i in range(0, len(layout), 8))
+
+
+def mio0(data):
+    layout, references, literals = [], b"", b""
+    for token in lz_tokens(data, 18):
+        if isinstance(token, int):
+            layout.append(1)
+            literals += bytes([token])
+        else:
+            layout.append(0)
+            references += struct.pack(">H", (token[0] - 3) << 12 | (token[1] - 1))
+    bits = layout_bits(layout)
+    references_offset = 16 + len(bits)
+    literals_offset = references_offset + len(references)
+    return b"MIO0" + struct.pack(">III", len(data), references_offset, literals_offset) + bits + references + literals
+
+
+def reference(length, distance):
+    """Yay0 and Yaz0 back-reference, the length byte following the pair for
+    lengths past 17."""
+    if length < 18:
+        return struct.pack(">H", (length - 2) << 12 | (distance - 1)), b""
+    return struct.pack(">H", distance - 1), bytes([length - 0x12])
+
+
+def yay0(data):
+    layout, references, literals = [], b"", b""
+    for token in lz_tokens(data, 0x111):
+        if isinstance(token, int):
+            layout.append(1)
+            literals += bytes([token])
+        else:
+            layout.append(0)
+            pair, length = reference(*token)
+            references += pair
+            literals += length
+    bits = layout_bits(layout)
+    references_offset = 16 + len(bits)
+    literals_offset = references_offset + len(references)
+    return b"Yay0" + struct.pack(">III", len(data), references_offset, literals_offset) + bits + references + literals
+
+
+def yaz0(data):
+    tokens = list(lz_tokens(data, 0x111))
+    compressed = b""
+    for group in range(0, len(tokens), 8):
+        layout, body = 0, b""
+        for step, token in enumerate(tokens[group : group + 8]):
+            if isinstance(token, int):
+                layout |= 0x80 >> step
+                body += bytes([token])
+            else:
+                pair, length = reference(*token)
+                body += pair + length
+        compressed += bytes([layout]) + body
+    return b"Yaz0" + struct.pack(">I", len(data)) + bytes(8) + compressed
+
+
+# Red, green, blue and transparent black as RGBA5551
+TLUT = struct.pack(">4H", 0xF801, 0x07C1, 0x003F, 0x0000) + struct.pack(">H", 0x0001) * 12
+# The same colors in a full palette, as CI8 textures use
+TLUT256 = TLUT + struct.pack(">H", 0x0001) * 240
+
+ENTRIES = {
+    "textures/rgba32": texture(1, 2, 2, bytes([255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 0, 0, 0, 0])),
+    "textures/rgba32_stride": strided_texture(
+        1, 2, 2, 12, bytes([255, 0, 0, 255, 0, 255, 0, 255, 9, 9, 9, 9, 0, 0, 255, 255, 0, 0, 0, 0])
+    ),
+    "textures/rgba16": texture(2, 2, 2, TLUT[:8]),
+    # Texels and a whole resource compressed as some community archives store them
+    "textures/rgba16_yay0": texture(2, 2, 2, yay0(TLUT[:8])),
+    "textures/rgba32_yaz0": yaz0(texture(1, 2, 2, bytes([255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 0, 0, 0, 0]))),
+    "textures/ci4": texture(3, 2, 2, bytes([0x01, 0x23])),
+    "textures/ci8": texture(4, 2, 2, bytes([0, 1, 2, 3])),
+    "textures/i4": texture(5, 2, 2, bytes([0x0F, 0x84])),
+    "textures/i8": texture(6, 2, 2, bytes([0x00, 0xFF, 0x80, 0x40])),
+    "textures/ia4": texture(7, 2, 2, bytes([0xF0, 0x9E])),
+    "textures/ia8": texture(8, 2, 2, bytes([0xFF, 0x0F, 0xF0, 0x88])),
+    "textures/ia16": texture(9, 2, 2, bytes([0xFF, 0xFF, 0x00, 0xFF, 0x80, 0x00, 0x40, 0x80])),
+    "textures/tlut": texture(11, 16, 1, TLUT),
+    "textures/tlut256": texture(11, 16, 16, TLUT256),
+    "textures/broken": texture(2, 4, 4, bytes(8)),
+    # Alternating 4-bit texels, the same byte repeated
+    "textures/i4_stripes": texture(5, 4, 2, bytes([0x0F] * 4)),
+    # Passes the size check but has more texels than the image takes
+    "textures/oversized": texture(1, 2, 2, bytes(20)),
+    "models/model": display_list(),
+    "misc/unknown": header(0x4F585858) + bytes(8),
+    # Spaghetti Kart courses with palettes of the same name, the texture
+    # MIO0 compressed as in the ROM
+    "courses/luigi_raceway/course_tlut": texture(11, 16, 1, TLUT[2:] + TLUT[:2]),
+    "courses/mario_raceway/course_tlut": texture(11, 16, 1, TLUT),
+    "courses/mario_raceway/road": texture(3, 8, 8, mio0(bytes([0x01, 0x23]) * 16)),
+}
+
+HOSTILE_ENTRIES = {
+    "../../evil": ENTRIES["textures/rgba32"],
+    "C:\\x": ENTRIES["textures/rgba32"],
+    "/abs": ENTRIES["textures/rgba32"],
+}
+
+# A CI8 texture reading indices 16 and 255 of a 16-color TLUT
+OVERFLOW_ENTRIES = {
+    "textures/ci8": texture(4, 2, 2, bytes([0, 1, 16, 255])),
+    "textures/tlut256": texture(11, 16, 1, TLUT),
+}
+
+# Extended CI textures with 12-bit indices of a 300-color TLUT, the top bits
+# of the second texel set, the last texel past the end of the TLUT
+TLUT300 = bytearray(TLUT + struct.pack(">H", 0x0001) * 284)
+TLUT300[257 * 2 : 258 * 2] = TLUT[2:4]
+TLUT300[299 * 2 : 300 * 2] = TLUT[4:6]
+CI16_TEXELS = struct.pack(">4H", 0x0000, 0xF101, 0x012B, 0x0FFF)
+CI16_ENTRIES = {
+    "textures/ci16": texture(2, 2, 2, CI16_TEXELS),
+    "textures/ci16_stride": strided_texture(2, 2, 2, 6, CI16_TEXELS[:4] + bytes(2) + CI16_TEXELS[4:]),
+    "textures/tlut300": texture(11, 300, 1, bytes(TLUT300)),
+}
+
+# The textures the option-less tool read, and a CI8 texel whose color has
+# only the second lowest bit set, opaque to that tool
+LEGACY_ENTRIES = {
+    name: ENTRIES[name]
+    for name in [
+        "textures/rgba32",
+        "textures/rgba16",
+        "textures/ci4",
+        "textures/i4",
+        "textures/i8",
+        "textures/ia4",
+        "textures/ia8",
+        "textures/ia16",
+        "textures/tlut",
+        "textures/broken",
+        "textures/i4_stripes",
+        "models/model",
+        "misc/unknown",
+    ]
+}
+LEGACY_ENTRIES["textures/ci8"] = texture(4, 2, 2, bytes([0, 1, 2, 16]))
+LEGACY_ENTRIES["textures/tlut256"] = texture(11, 16, 16, TLUT + struct.pack(">H", 0x0002) * 240)
+
+for file_name, entries in [
+    ("mini.o2r", ENTRIES),
+    ("hostile.o2r", HOSTILE_ENTRIES),
+    ("overflow.o2r", OVERFLOW_ENTRIES),
+    ("ci16.o2r", CI16_ENTRIES),
+    ("legacy.o2r", LEGACY_ENTRIES),
+]:
+    with zipfile.ZipFile(Path(__file__).with_name(file_name), "w") as archive:
+        for name, data in entries.items():
+            info = zipfile.ZipInfo(name, date_time=(2024, 1, 1, 0, 0, 0))
+            archive.writestr(info, data)